@@ -3,6 +3,14 @@ use voprf_rs::oprf::*;
 use voprf_rs::oprf::Server;
 use voprf_rs::oprf::groups::PrimeOrderGroup;
 use voprf_rs::oprf::ciphersuite::{Ciphersuite,Supported};
+use voprf_rs::oprf::dleq::{self,Domain};
+
+// the batch benchmarks are labelled with the mode, so that running them with
+// and without `--features parallel` compares the two
+#[cfg(feature = "parallel")]
+const MODE: &str = "parallel";
+#[cfg(not(feature = "parallel"))]
+const MODE: &str = "sequential";
 
 fn server_oprf_setup_ristretto() {
     let pog = PrimeOrderGroup::ristretto_255();
@@ -33,7 +41,7 @@ fn server_eval<T,H>(srv: Server<T,H>, elems: &[T]) -> Evaluation<T>
         + digest::BlockInput + digest::FixedOutput + digest::Input
         + digest::Reset + std::default::Default,
         PrimeOrderGroup<T, H>: Supported, Server<T,H>: Clone {
-    srv.eval(elems)
+    srv.eval(elems).unwrap()
 }
 
 fn client_oprf_setup_ristretto() {
@@ -45,7 +53,7 @@ fn client_oprf_setup_ristretto() {
 fn client_voprf_setup_ristretto(pub_key: String) {
     let pog = PrimeOrderGroup::ristretto_255();
     let ciph = Ciphersuite::new(pog.clone(), true);
-    Client::setup(ciph, Some(PublicKey::from_hex(pub_key, &pog))).unwrap();
+    Client::setup(ciph, Some(PublicKey::from_hex(pub_key, &pog).unwrap())).unwrap();
 }
 
 fn client_oprf_setup_p384() {
@@ -57,7 +65,7 @@ fn client_oprf_setup_p384() {
 fn client_voprf_setup_p384(pub_key: String) {
     let pog = PrimeOrderGroup::p384();
     let ciph = Ciphersuite::new(pog.clone(), true);
-    Client::setup(ciph, Some(PublicKey::from_hex(pub_key, &pog))).unwrap();
+    Client::setup(ciph, Some(PublicKey::from_hex(pub_key, &pog).unwrap())).unwrap();
 }
 
 fn client_blind<T,H>(cli: Client<T,H>, x: Vec<u8>) -> Vec<Input<T>>
@@ -96,7 +104,48 @@ fn create_unblinding_values<T,H>(ciph: Ciphersuite<T,H>, inputs: &[Vec<u8>]) ->
     for bi in &blinded_inps {
         elems.push(bi.elem.clone());
     }
-    (cli, blinded_inps, srv.eval(&elems))
+    (cli, blinded_inps, srv.eval(&elems).unwrap())
+}
+
+fn create_dleq_statement<T,H>(pog: &PrimeOrderGroup<T,H>, n: usize) -> (Vec<u8>, T, T, Vec<T>, Vec<T>)
+        where T: Clone {
+    let mut k = Vec::new();
+    (pog.uniform_bytes)(&mut k);
+    let a = pog.generator.clone();
+    let b = (pog.scalar_mult)(&a, &k);
+    let mut cs = Vec::new();
+    let mut ds = Vec::new();
+    for _ in 0..n {
+        let c = (pog.random_element)();
+        ds.push((pog.scalar_mult)(&c, &k));
+        cs.push(c);
+    }
+    (k, a, b, cs, ds)
+}
+
+fn create_scalars<T,H>(pog: &PrimeOrderGroup<T,H>, n: usize) -> Vec<Vec<u8>> {
+    let mut rs = Vec::new();
+    for _ in 0..n {
+        let mut r = Vec::new();
+        (pog.uniform_bytes)(&mut r);
+        rs.push(r);
+    }
+    rs
+}
+
+// unblinds each element with its own scalar inversion
+fn unblind_separately<T,H>(pog: &PrimeOrderGroup<T,H>, inputs: &[Input<T>], evals: &Evaluation<T>) -> Vec<T> {
+    inputs.iter().zip(&evals.elems).map(|(i, z)| (pog.inverse_mult)(z, &i.blind)).collect()
+}
+
+// computes `Σ rs[i]*ps[i]` with a scalar multiplication per element, as
+// before multi-scalar multiplication
+fn multi_scalar_mult_separately<T,H>(pog: &PrimeOrderGroup<T,H>, ps: &[T], rs: &[Vec<u8>]) -> T {
+    let mut acc = (pog.scalar_mult)(&ps[0], &rs[0]);
+    for i in 1..ps.len() {
+        acc = (pog.add)(&acc, &(pog.scalar_mult)(&ps[i], &rs[i]));
+    }
+    acc
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -181,7 +230,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=10
-    c.bench_function("srv eval oprf ristretto n=10", |b| {
+    c.bench_function(&format!("srv eval oprf ristretto n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..10 {
@@ -191,7 +240,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf ristretto n=10", |b| {
+    c.bench_function(&format!("srv eval voprf ristretto n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..10 {
@@ -201,7 +250,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval oprf p384 n=10", |b| {
+    c.bench_function(&format!("srv eval oprf p384 n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..10 {
@@ -211,7 +260,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf p384 n=10", |b| {
+    c.bench_function(&format!("srv eval voprf p384 n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..10 {
@@ -223,7 +272,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=25
-    c.bench_function("srv eval oprf ristretto n=25", |b| {
+    c.bench_function(&format!("srv eval oprf ristretto n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..25 {
@@ -233,7 +282,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf ristretto n=25", |b| {
+    c.bench_function(&format!("srv eval voprf ristretto n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..25 {
@@ -243,7 +292,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval oprf p384 n=25", |b| {
+    c.bench_function(&format!("srv eval oprf p384 n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..25 {
@@ -253,7 +302,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf p384 n=25", |b| {
+    c.bench_function(&format!("srv eval voprf p384 n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..25 {
@@ -265,7 +314,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=50
-    c.bench_function("srv eval oprf ristretto n=50", |b| {
+    c.bench_function(&format!("srv eval oprf ristretto n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..50 {
@@ -275,7 +324,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf ristretto n=50", |b| {
+    c.bench_function(&format!("srv eval voprf ristretto n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..50 {
@@ -285,7 +334,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval oprf p384 n=50", |b| {
+    c.bench_function(&format!("srv eval oprf p384 n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..50 {
@@ -295,7 +344,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf p384 n=50", |b| {
+    c.bench_function(&format!("srv eval voprf p384 n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..50 {
@@ -307,7 +356,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=100
-    c.bench_function("srv eval oprf ristretto n=100", |b| {
+    c.bench_function(&format!("srv eval oprf ristretto n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..100 {
@@ -317,7 +366,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf ristretto n=100", |b| {
+    c.bench_function(&format!("srv eval voprf ristretto n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let mut elems = Vec::new();
         for _ in 0..100 {
@@ -327,7 +376,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval oprf p384 n=100", |b| {
+    c.bench_function(&format!("srv eval oprf p384 n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..100 {
@@ -337,7 +386,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let srv = Server::setup(ciph);
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
-    c.bench_function("srv eval voprf p384 n=100", |b| {
+    c.bench_function(&format!("srv eval voprf p384 n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let mut elems = Vec::new();
         for _ in 0..100 {
@@ -348,6 +397,159 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
     });
 
+    // n=1000
+    c.bench_function("srv eval voprf ristretto n=1000", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut elems = Vec::new();
+        for _ in 0..1000 {
+            elems.push((pog.clone().random_element)());
+        }
+        let ciph = Ciphersuite::new(pog, true);
+        let srv = Server::setup(ciph);
+        b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
+    });
+    c.bench_function("srv eval voprf p384 n=1000", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let mut elems = Vec::new();
+        for _ in 0..1000 {
+            elems.push((pog.clone().random_element)());
+        }
+        let ciph = Ciphersuite::new(pog, true);
+        let srv = Server::setup(ciph);
+        b.iter(|| server_eval(black_box(srv.clone()), black_box(&elems)))
+    });
+
+    // DLEQ composites, as computed by the verifier and by the server
+    c.bench_function("dleq composites ristretto n=100", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (_, a, y, cs, ds) = create_dleq_statement(&pog, 100);
+        b.iter(|| dleq::composites(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+    c.bench_function("dleq composites fast ristretto n=100", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 100);
+        b.iter(|| dleq::composites_fast(black_box(&pog), black_box(&domain), black_box(&k), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+    c.bench_function("dleq composites p384 n=100", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (_, a, y, cs, ds) = create_dleq_statement(&pog, 100);
+        b.iter(|| dleq::composites(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+    c.bench_function("dleq composites fast p384 n=100", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 100);
+        b.iter(|| dleq::composites_fast(black_box(&pog), black_box(&domain), black_box(&k), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+    c.bench_function("dleq composites ristretto n=1000", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (_, a, y, cs, ds) = create_dleq_statement(&pog, 1000);
+        b.iter(|| dleq::composites(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+    c.bench_function("dleq composites fast ristretto n=1000", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 1000);
+        b.iter(|| dleq::composites_fast(black_box(&pog), black_box(&domain), black_box(&k), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+    c.bench_function("dleq composites p384 n=1000", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (_, a, y, cs, ds) = create_dleq_statement(&pog, 1000);
+        b.iter(|| dleq::composites(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+    c.bench_function("dleq composites fast p384 n=1000", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 1000);
+        b.iter(|| dleq::composites_fast(black_box(&pog), black_box(&domain), black_box(&k), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds)).unwrap())
+    });
+
+    // multi-scalar multiplication, against independent multiplications
+    for &n in &[10, 100, 1000] {
+        c.bench_function(&format!("msm ristretto n={}", n), |b| {
+            let pog = PrimeOrderGroup::ristretto_255();
+            let (_, _, _, ps, _) = create_dleq_statement(&pog, n);
+            let rs = create_scalars(&pog, n);
+            b.iter(|| (pog.multi_scalar_mult)(black_box(&ps), black_box(&rs)))
+        });
+        c.bench_function(&format!("naive msm ristretto n={}", n), |b| {
+            let pog = PrimeOrderGroup::ristretto_255();
+            let (_, _, _, ps, _) = create_dleq_statement(&pog, n);
+            let rs = create_scalars(&pog, n);
+            b.iter(|| multi_scalar_mult_separately(black_box(&pog), black_box(&ps), black_box(&rs)))
+        });
+        c.bench_function(&format!("msm p384 n={}", n), |b| {
+            let pog = PrimeOrderGroup::p384();
+            let (_, _, _, ps, _) = create_dleq_statement(&pog, n);
+            let rs = create_scalars(&pog, n);
+            b.iter(|| (pog.multi_scalar_mult)(black_box(&ps), black_box(&rs)))
+        });
+        c.bench_function(&format!("naive msm p384 n={}", n), |b| {
+            let pog = PrimeOrderGroup::p384();
+            let (_, _, _, ps, _) = create_dleq_statement(&pog, n);
+            let rs = create_scalars(&pog, n);
+            b.iter(|| multi_scalar_mult_separately(black_box(&pog), black_box(&ps), black_box(&rs)))
+        });
+    }
+
+    // generator multiplication, with the fixed-base tables (as used by key
+    // generation and proofs in "srv eval voprf") and without
+    c.bench_function("base mult ristretto", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let rs = create_scalars(&pog, 1);
+        b.iter(|| (pog.base_mult)(black_box(&rs[0])))
+    });
+    c.bench_function("variable-base mult ristretto", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let rs = create_scalars(&pog, 1);
+        b.iter(|| (pog.scalar_mult)(black_box(&pog.generator), black_box(&rs[0])))
+    });
+    c.bench_function("base mult p384", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let rs = create_scalars(&pog, 1);
+        b.iter(|| (pog.base_mult)(black_box(&rs[0])))
+    });
+    c.bench_function("variable-base mult p384", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let rs = create_scalars(&pog, 1);
+        b.iter(|| (pog.scalar_mult)(black_box(&pog.generator), black_box(&rs[0])))
+    });
+
+    // DLEQ batch verification
+    c.bench_function("dleq batch verify ristretto n=100", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 100);
+        let proof = dleq::batch_prove(&pog, &domain, &k, &a, &y, &cs, &ds).unwrap();
+        b.iter(|| dleq::batch_verify(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds), black_box(&proof)))
+    });
+    c.bench_function("dleq batch verify p384 n=100", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 100);
+        let proof = dleq::batch_prove(&pog, &domain, &k, &a, &y, &cs, &ds).unwrap();
+        b.iter(|| dleq::batch_verify(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds), black_box(&proof)))
+    });
+    c.bench_function("dleq batch verify ristretto n=1000", |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 1000);
+        let proof = dleq::batch_prove(&pog, &domain, &k, &a, &y, &cs, &ds).unwrap();
+        b.iter(|| dleq::batch_verify(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds), black_box(&proof)))
+    });
+    c.bench_function("dleq batch verify p384 n=1000", |b| {
+        let pog = PrimeOrderGroup::p384();
+        let domain = Domain::new(b"bench", b"").unwrap();
+        let (k, a, y, cs, ds) = create_dleq_statement(&pog, 1000);
+        let proof = dleq::batch_prove(&pog, &domain, &k, &a, &y, &cs, &ds).unwrap();
+        b.iter(|| dleq::batch_verify(black_box(&pog), black_box(&domain), black_box(&a), black_box(&y), black_box(&cs), black_box(&ds), black_box(&proof)))
+    });
+
     /******************** CLIENT BENCHMARKS ********************/
 
     // setup
@@ -374,6 +576,63 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| client_blind(black_box(cli.clone()), black_box(buf.clone())))
     });
 
+    c.bench_function(&format!("client blind ristretto n=10 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let inputs = create_scalars(&pog, 10);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+    c.bench_function(&format!("client blind p384 n=10 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::p384();
+        let inputs = create_scalars(&pog, 10);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+    c.bench_function(&format!("client blind ristretto n=25 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let inputs = create_scalars(&pog, 25);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+    c.bench_function(&format!("client blind p384 n=25 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::p384();
+        let inputs = create_scalars(&pog, 25);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+    c.bench_function(&format!("client blind ristretto n=50 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let inputs = create_scalars(&pog, 50);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+    c.bench_function(&format!("client blind p384 n=50 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::p384();
+        let inputs = create_scalars(&pog, 50);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+    c.bench_function(&format!("client blind ristretto n=100 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let inputs = create_scalars(&pog, 100);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+    c.bench_function(&format!("client blind p384 n=100 ({})", MODE), |b| {
+        let pog = PrimeOrderGroup::p384();
+        let inputs = create_scalars(&pog, 100);
+        let ciph = Ciphersuite::new(pog, false);
+        let cli = Client::setup(ciph, None).unwrap();
+        b.iter(|| cli.blind(black_box(&inputs)))
+    });
+
     // unblinding
     // n=1
     c.bench_function("client unblind oprf ristretto n=1", |b| {
@@ -476,7 +735,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=10
-    c.bench_function("client unblind oprf ristretto n=10", |b| {
+    c.bench_function(&format!("client unblind oprf ristretto n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -488,7 +747,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind oprf p384 n=10", |b| {
+    c.bench_function(&format!("client unblind oprf p384 n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -500,7 +759,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf ristretto n=10", |b| {
+    c.bench_function(&format!("client unblind voprf ristretto n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -512,7 +771,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf p384 n=10", |b| {
+    c.bench_function(&format!("client unblind voprf p384 n=10 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -526,7 +785,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=25
-    c.bench_function("client unblind oprf ristretto n=25", |b| {
+    c.bench_function(&format!("client unblind oprf ristretto n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -538,7 +797,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind oprf p384 n=25", |b| {
+    c.bench_function(&format!("client unblind oprf p384 n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -550,7 +809,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf ristretto n=25", |b| {
+    c.bench_function(&format!("client unblind voprf ristretto n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -562,7 +821,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf p384 n=25", |b| {
+    c.bench_function(&format!("client unblind voprf p384 n=25 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -576,7 +835,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=50
-    c.bench_function("client unblind oprf ristretto n=50", |b| {
+    c.bench_function(&format!("client unblind oprf ristretto n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -588,7 +847,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind oprf p384 n=50", |b| {
+    c.bench_function(&format!("client unblind oprf p384 n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -600,7 +859,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf ristretto n=50", |b| {
+    c.bench_function(&format!("client unblind voprf ristretto n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -612,7 +871,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf p384 n=50", |b| {
+    c.bench_function(&format!("client unblind voprf p384 n=50 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -626,7 +885,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     // n=100
-    c.bench_function("client unblind oprf ristretto n=100", |b| {
+    c.bench_function(&format!("client unblind oprf ristretto n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -638,7 +897,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind oprf p384 n=100", |b| {
+    c.bench_function(&format!("client unblind oprf p384 n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), false);
         let mut inputs = Vec::new();
@@ -650,7 +909,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf ristretto n=100", |b| {
+    c.bench_function(&format!("client unblind voprf ristretto n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::ristretto_255();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -662,7 +921,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         let (cli, blinded_inps, evals) = create_unblinding_values(ciph, &inputs);
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
-    c.bench_function("client unblind voprf p384 n=100", |b| {
+    c.bench_function(&format!("client unblind voprf p384 n=100 ({})", MODE), |b| {
         let pog = PrimeOrderGroup::p384();
         let ciph = Ciphersuite::new(pog.clone(), true);
         let mut inputs = Vec::new();
@@ -675,6 +934,42 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| client_unblind(black_box(cli.clone()), black_box(blinded_inps.clone()), black_box(evals.clone())))
     });
 
+    // unblinding with separate inversions (as before batch inversion), and
+    // with the inverses that are computed at blinding time, along with the
+    // batch inversion itself
+    for &n in &[1, 5, 10, 25, 50, 100] {
+        c.bench_function(&format!("client unblind separate inversions ristretto n={}", n), |b| {
+            let pog = PrimeOrderGroup::ristretto_255();
+            let (_, blinded_inps, evals) = create_unblinding_values(Ciphersuite::new(pog.clone(), false), &create_scalars(&pog, n));
+            b.iter(|| unblind_separately(black_box(&pog), black_box(&blinded_inps), black_box(&evals)))
+        });
+        c.bench_function(&format!("client unblind precomputed inverses ristretto n={}", n), |b| {
+            let pog = PrimeOrderGroup::ristretto_255();
+            let (cli, blinded_inps, evals) = create_unblinding_values(Ciphersuite::new(pog.clone(), false), &create_scalars(&pog, n));
+            b.iter(|| cli.unblind(black_box(&blinded_inps), black_box(&evals)).unwrap())
+        });
+        c.bench_function(&format!("client batch inversion ristretto n={}", n), |b| {
+            let pog = PrimeOrderGroup::ristretto_255();
+            let scalars = create_scalars(&pog, n);
+            b.iter(|| pog.batch_invert_scalars(black_box(&scalars)).unwrap())
+        });
+        c.bench_function(&format!("client unblind separate inversions p384 n={}", n), |b| {
+            let pog = PrimeOrderGroup::p384();
+            let (_, blinded_inps, evals) = create_unblinding_values(Ciphersuite::new(pog.clone(), false), &create_scalars(&pog, n));
+            b.iter(|| unblind_separately(black_box(&pog), black_box(&blinded_inps), black_box(&evals)))
+        });
+        c.bench_function(&format!("client unblind precomputed inverses p384 n={}", n), |b| {
+            let pog = PrimeOrderGroup::p384();
+            let (cli, blinded_inps, evals) = create_unblinding_values(Ciphersuite::new(pog.clone(), false), &create_scalars(&pog, n));
+            b.iter(|| cli.unblind(black_box(&blinded_inps), black_box(&evals)).unwrap())
+        });
+        c.bench_function(&format!("client batch inversion p384 n={}", n), |b| {
+            let pog = PrimeOrderGroup::p384();
+            let scalars = create_scalars(&pog, n);
+            b.iter(|| pog.batch_invert_scalars(black_box(&scalars)).unwrap())
+        });
+    }
+
     // finalize
     c.bench_function("client finalize ristretto", |b| {
         let pog = PrimeOrderGroup::ristretto_255();
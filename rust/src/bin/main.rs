@@ -1,9 +1,14 @@
 extern crate voprf_rs;
 
-use voprf_rs::http::client::start_client;
-use voprf_rs::http::server::start_server;
+use voprf_rs::http::client::{ClientOptions,start_client,start_token_client,start_psi_client,start_credential_client,start_password_client,discover_pub_key,discover_proofs,OldKey};
+use voprf_rs::http::server::{ServerOptions,start_server,start_tenant_server,start_mirror};
+use voprf_rs::http::directory::key_consistency;
+use voprf_rs::oprf::any::AnyCiphersuite;
+use voprf_rs::oprf::ciphersuite::ProofVersion;
+use voprf_rs::oprf::consistency::KeyHistory;
 
 use clap::{Arg,App};
+use std::sync::Arc;
 
 fn main() {
     let matches = App::new("voprf-rs")
@@ -12,14 +17,14 @@ fn main() {
                         .about("Proof-of-concept implementation of draft-irtf-cfrg-voprf-02 in rust.")
                         .arg(Arg::with_name("group")
                             .long("group")
-                            .required(true)
+                            .required_unless_one(&["tenants", "ciphersuites", "upstream"])
                             .takes_value(true)
-                            .help("Sets the group to use, currently supported groups: P384, ristretto255 [EXPERIMENTAL]"))
+                            .help("Sets the group to use, currently supported groups: P384, P384-SHA384 (RFC 9497), ristretto255 [EXPERIMENTAL]. A server can host several groups at once with a comma-separated list (e.g. P384,ristretto255)"))
                         .arg(Arg::with_name("mode")
                             .long("mode")
-                            .required(true)
+                            .required_unless("ciphersuites")
                             .takes_value(true)
-                            .help("Determines the running mode, supported: server, client"))
+                            .help("Determines the running mode, supported: server, client, mirror"))
                         .arg(Arg::with_name("port")
                             .long("port")
                             .default_value("3001")
@@ -47,9 +52,117 @@ fn main() {
                             .long("max_evals")
                             .default_value("10")
                             .help("Specifies the maximum number evaluations permitted on the server-side (default: 10)"))
+                        .arg(Arg::with_name("tenants")
+                            .long("tenants")
+                            .takes_value(true)
+                            .help("Runs the server for each of the tenants configured in the provided JSON file"))
+                        .arg(Arg::with_name("tenant")
+                            .long("tenant")
+                            .takes_value(true)
+                            .help("Sets the tenant that the client sends requests to (for use with a multi-tenant server)"))
+                        .arg(Arg::with_name("token")
+                            .long("token")
+                            .help("Requests a Privacy Pass token from the issuance endpoint of the server (client mode only, requires --pk and --group=P384-SHA384)"))
+                        .arg(Arg::with_name("discover")
+                            .long("discover")
+                            .help("Fetches the public key from the key directory of the server and pins it, instead of using --pk (client mode only)"))
+                        .arg(Arg::with_name("key_cache")
+                            .long("key_cache")
+                            .default_value("voprf-keys.json")
+                            .help("Sets the file that discovered keys are pinned in (default: voprf-keys.json)"))
+                        .arg(Arg::with_name("key_sources")
+                            .long("key_sources")
+                            .takes_value(true)
+                            .help("Checks the public key against the key directories at the comma-separated list of mirror URLs or local files (client mode only)"))
+                        .arg(Arg::with_name("quorum")
+                            .long("quorum")
+                            .takes_value(true)
+                            .help("Sets the number of key sources that must publish the public key (default: all)"))
+                        .arg(Arg::with_name("key_history")
+                            .long("key_history")
+                            .default_value("voprf-key-history.json")
+                            .help("Sets the file that the history of public keys is stored in (default: voprf-key-history.json)"))
+                        .arg(Arg::with_name("announce_key")
+                            .long("announce_key")
+                            .takes_value(true)
+                            .help("Announces that the public key of the verifiable ciphersuite of --group is rotated to the provided (hex-encoded) key in --key_history, and exits"))
+                        .arg(Arg::with_name("upstream")
+                            .long("upstream")
+                            .takes_value(true)
+                            .help("Sets the URL or file of the key directory that is mirrored (mirror mode only)"))
+                        .arg(Arg::with_name("spend_store")
+                            .long("spend_store")
+                            .takes_value(true)
+                            .help("Records redeemed Privacy Pass tokens in the provided directory, rather than in memory (server mode only)"))
+                        .arg(Arg::with_name("psi")
+                            .long("psi")
+                            .takes_value(true)
+                            .help("Runs private set intersection with the items in the provided file (one per line): the server publishes the set, and the client intersects with it in batches of --n items"))
+                        .arg(Arg::with_name("psi_cardinality")
+                            .long("psi_cardinality")
+                            .requires("psi")
+                            .conflicts_with("verifiable")
+                            .help("Runs private set intersection in the cardinality-only mode, where the client only learns the size of the intersection (OPRF ciphersuites only)"))
+                        .arg(Arg::with_name("credentials")
+                            .long("credentials")
+                            .takes_value(true)
+                            .help("Serves the dataset of compromised credentials in the provided file, with one username:password (or password) on each line (server mode only)"))
+                        .arg(Arg::with_name("credential")
+                            .long("credential")
+                            .takes_value(true)
+                            .help("Checks whether the provided username:password (or password) is in the dataset of compromised credentials of the server (client mode only)"))
+                        .arg(Arg::with_name("enroll")
+                            .long("enroll")
+                            .takes_value(true)
+                            .conflicts_with_all(&["verify_password", "reharden"])
+                            .help("Hardens the provided password with the server, and writes the password record to --record (client mode only)"))
+                        .arg(Arg::with_name("verify_password")
+                            .long("verify_password")
+                            .takes_value(true)
+                            .help("Verifies the provided password against the password record in --record with the server (client mode only)"))
+                        .arg(Arg::with_name("reharden")
+                            .long("reharden")
+                            .takes_value(true)
+                            .conflicts_with("verify_password")
+                            .requires("old_pk")
+                            .help("Re-hardens the password record in --record for the provided password after a key rotation: the password is verified with the old key (--old_pk) and hardened again with the current key (client mode only)"))
+                        .arg(Arg::with_name("old_pk")
+                            .long("old_pk")
+                            .takes_value(true)
+                            .help("Sets the old public key that password records are re-hardened from"))
+                        .arg(Arg::with_name("old_port")
+                            .long("old_port")
+                            .takes_value(true)
+                            .help("Sets the port of the server that still evaluates with the old key (default: --port)"))
+                        .arg(Arg::with_name("old_tenant")
+                            .long("old_tenant")
+                            .takes_value(true)
+                            .help("Sets the tenant that the old key is served for (default: --tenant)"))
+                        .arg(Arg::with_name("record")
+                            .long("record")
+                            .default_value("voprf-password.rec")
+                            .help("Sets the file that the password record is stored in (default: voprf-password.rec)"))
+                        .arg(Arg::with_name("proof_version")
+                            .long("proof_version")
+                            .possible_values(&["legacy", "v1"])
+                            .default_value("v1")
+                            .help("Sets the version of the DLEQ proofs: v1 (bound to the ciphersuite and --context) or legacy (draft-irtf-cfrg-voprf-02). Clients using --discover take the version published by the server (default: v1)"))
+                        .arg(Arg::with_name("context")
+                            .long("context")
+                            .takes_value(true)
+                            .help("Binds v1 DLEQ proofs to the provided context string, which must be the same for the server and client. Clients using --discover take the context published by the server"))
+                        .arg(Arg::with_name("ciphersuites")
+                            .long("ciphersuites")
+                            .help("Lists the names of all supported ciphersuites"))
                         .get_matches();
 
-    let gp_name = matches.value_of("group").unwrap_or_else(|| panic!("no group selected")).to_string();
+    if matches.is_present("ciphersuites") {
+        for name in AnyCiphersuite::supported_names() {
+            println!("{}", name);
+        }
+        return;
+    }
+
     let port = matches.value_of("port").unwrap_or("3001").to_string();
     let host = matches.value_of("host").unwrap_or("127.0.0.1").to_string();
     let n_evals = matches.value_of("n").unwrap().parse::<u16>().unwrap();
@@ -57,15 +170,127 @@ fn main() {
     let verifiable = matches.is_present("verifiable");
     let test_index = matches.value_of("test").unwrap().parse::<i16>().unwrap();
     let mode = matches.value_of("mode").unwrap_or_else(|| panic!("no mode selected"));
+    let mut proof_version = ProofVersion::from_name(matches.value_of("proof_version").unwrap()).unwrap();
+    let mut context = matches.value_of("context").map(|c| c.as_bytes().to_vec()).unwrap_or_default();
+    if !context.is_empty() && proof_version != ProofVersion::V1 {
+        panic!("--context is only bound by v1 proofs, set --proof_version=v1");
+    }
+    if let Some(path) = matches.value_of("tenants") {
+        if mode != "server" {
+            panic!("tenants can only be configured in server mode");
+        }
+        return start_tenant_server(path.to_string(), host, port);
+    }
+    if mode == "mirror" {
+        let upstream = matches.value_of("upstream").unwrap_or_else(|| panic!("an upstream directory must be set in mirror mode"));
+        return start_mirror(upstream.to_string(), host, port);
+    }
+    let gp_name = matches.value_of("group").unwrap_or_else(|| panic!("no group selected")).to_string();
+    if let Some(key) = matches.value_of("announce_key") {
+        let ciph = AnyCiphersuite::from_group(&gp_name, true)
+            .unwrap_or_else(|e| panic!("Unsupported group requested: {}", e));
+        let key = hex::decode(key).unwrap_or_else(|e| panic!("Failed to decode announced key: {}", e));
+        let path = matches.value_of("key_history").unwrap();
+        match KeyHistory::load(path).and_then(|mut h| h.announce(ciph.name(), &key)) {
+            Ok(()) => println!("Announced key rotation for {} in {}", ciph.name(), path),
+            Err(e) => panic!("Failed to announce key rotation: {}", e),
+        }
+        return;
+    }
+    let mut pk = matches.value_of("pk").map(|pk| pk.to_string());
+    if mode == "client" && matches.is_present("discover") {
+        let ciph = AnyCiphersuite::from_group(&gp_name, verifiable || matches.is_present("token"))
+            .unwrap_or_else(|e| panic!("Unsupported group requested: {}", e));
+        let cache = matches.value_of("key_cache").unwrap();
+        match discover_pub_key(&host, &port, ciph.name(), cache) {
+            Ok(key) => pk = Some(key),
+            Err(e) => panic!("Failed to discover public key: {}", e),
+        }
+        match discover_proofs(&host, &port, ciph.name()) {
+            Ok((v, c)) => {
+                proof_version = v;
+                context = c;
+            },
+            Err(_) => println!("Failed to discover DLEQ proof version, using --proof_version and --context"),
+        }
+    }
+    let tenant = matches.value_of("tenant").map(|t| t.to_string());
+    let client_opts = |pub_key: Option<String>| ClientOptions{
+        group_name: gp_name.clone(),
+        host: host.clone(),
+        port: port.clone(),
+        pub_key: pub_key,
+        verifiable: verifiable,
+        tenant: tenant.clone(),
+        proof_version: proof_version,
+        context: context.clone(),
+    };
     match mode {
+        "client" if matches.is_present("token") => {
+            let pk = pk.unwrap_or_else(|| panic!("Public key must be provided (or discovered) for token issuance"));
+            start_token_client(client_opts(Some(pk)))
+        },
+        "client" if matches.is_present("credential") => {
+            let credential = matches.value_of("credential").unwrap().to_string();
+            start_credential_client(client_opts(verifiable_pub_key(pk, verifiable)), credential)
+        },
+        "client" if matches.is_present("enroll") || matches.is_present("verify_password") || matches.is_present("reharden") => {
+            let pk = pk.unwrap_or_else(|| panic!("Public key must be provided (or discovered) for password hardening"));
+            let enroll = matches.is_present("enroll");
+            let mode = match (enroll, matches.is_present("reharden")) {
+                (true, _) => "enroll",
+                (false, true) => "reharden",
+                _ => "verify_password",
+            };
+            let password = matches.value_of(mode).unwrap().to_string();
+            let record = matches.value_of("record").unwrap().to_string();
+            let rotation = matches.value_of("old_pk").filter(|_| mode == "reharden").map(|old_pk| OldKey{
+                port: matches.value_of("old_port").unwrap_or(&port).to_string(),
+                pub_key: old_pk.to_string(),
+                tenant: matches.value_of("old_tenant").map(|t| t.to_string()).or_else(|| tenant.clone()),
+            });
+            start_password_client(client_opts(Some(pk)), password, record, enroll, rotation)
+        },
+        "client" if matches.is_present("psi") => {
+            let items = matches.value_of("psi").unwrap().to_string();
+            let cardinality = matches.is_present("psi_cardinality");
+            start_psi_client(client_opts(verifiable_pub_key(pk, verifiable)), items, n_evals, cardinality)
+        },
         "client" => {
-            let mut pk = None;
-            if verifiable && test_index == -1 {
-                pk = Some(matches.value_of("pk").unwrap_or_else(|| panic!("Public key must be provided in verifiable mode")).to_string());
-            }
-            start_client(gp_name, host, port, None, pk, n_evals, verifiable, test_index)
+            let pk = verifiable_pub_key(pk, verifiable && test_index == -1);
+            let consistency = matches.value_of("key_sources").map(|sources| {
+                let sources: Vec<String> = sources.split(',').map(|s| s.trim().to_string()).collect();
+                let quorum = matches.value_of("quorum").map(|q| q.parse::<usize>().unwrap());
+                match key_consistency(&sources, quorum, matches.value_of("key_history").unwrap()) {
+                    Ok(c) => Arc::new(c),
+                    Err(e) => panic!("Failed to set up key consistency checks: {}", e),
+                }
+            });
+            start_client(client_opts(pk), None, n_evals, test_index, consistency)
         },
-        "server" => start_server(gp_name, host, port, max_evals, verifiable, test_index),
+        "server" => start_server(ServerOptions{
+            group_names: gp_name,
+            host: host,
+            port: port,
+            max_evals: max_evals,
+            verifiable: verifiable,
+            test_index: test_index,
+            spend_dir: matches.value_of("spend_store").map(|d| d.to_string()),
+            psi_path: matches.value_of("psi").map(|p| p.to_string()),
+            psi_cardinality: matches.is_present("psi_cardinality"),
+            credentials_path: matches.value_of("credentials").map(|c| c.to_string()),
+            proof_version: proof_version,
+            context: context,
+        }),
         _ => panic!("unsupported mode specified {}", mode)
     }
+}
+
+// returns the public key if it is required, which it must be in verifiable
+// mode
+fn verifiable_pub_key(pk: Option<String>, required: bool) -> Option<String> {
+    match required {
+        true => Some(pk.unwrap_or_else(|| panic!("Public key must be provided (or discovered) in verifiable mode"))),
+        false => None,
+    }
 }
\ No newline at end of file
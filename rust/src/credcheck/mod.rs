@@ -0,0 +1,252 @@
+//! The credcheck module implements a compromised-credential checking service
+//! on top of the (V)OPRF, in the style of the k-anonymity leaked-password
+//! checks used in browsers.
+//!
+//! The server evaluates the full (V)OPRF over the hash of each leaked
+//! credential (see `credential_hash`), and stores the outputs in a `Dataset`,
+//! bucketed by a short prefix of the credential hash. To check a credential,
+//! the client blinds its hash, has the server evaluate it, and downloads the
+//! bucket for the prefix of the hash. The credential is compromised if its
+//! output is in the bucket. The server only learns the prefix, which is shared
+//! by many credentials, and the outputs in the bucket cannot be checked
+//! against other credentials without the key of the server.
+//!
+//! Each bucket is encoded as a `psi::ServerSet`.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,Client};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::credcheck::{Dataset,credential_hash,bucket_prefix,is_compromised,DEFAULT_PREFIX_LEN};
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use sha2::Sha512;
+//!
+//! let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let srv = Server::setup(ciph.clone());
+//! let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+//!
+//! // server builds the dataset of leaked credentials
+//! let leaked = vec![credential_hash(b"alice", b"hunter2")];
+//! let dataset = Dataset::build(&srv, &leaked, DEFAULT_PREFIX_LEN).unwrap();
+//!
+//! // client checks its credential
+//! let hash = credential_hash(b"alice", b"hunter2");
+//! let inputs = cli.blind(&[hash.clone()]);
+//! let eval = srv.eval(&[inputs[0].elem]).unwrap();
+//! let bucket = dataset.bucket(&bucket_prefix(&hash, DEFAULT_PREFIX_LEN)).unwrap();
+//! assert!(is_compromised(&cli, &inputs[0], &eval, &bucket).unwrap());
+//! ```
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Error;
+
+use byteorder::{BigEndian,WriteBytesExt};
+use sha2::{Sha256,Digest};
+
+use crate::oprf::{Server,Client,Input,Evaluation};
+use crate::oprf::ciphersuite::Supported;
+use crate::oprf::groups::PrimeOrderGroup;
+use crate::psi::{ServerSet,DEFAULT_TRUNCATION};
+use crate::errors::{err_bucket_prefix,err_finalization};
+
+/// The auxiliary data that credential outputs are finalized with
+pub const CREDENTIAL_INFO: &[u8] = b"voprf_credcheck";
+/// The default number of bytes of the credential hash that buckets are
+/// indexed by
+pub const DEFAULT_PREFIX_LEN: usize = 2;
+/// The maximum number of bytes of the credential hash that buckets may be
+/// indexed by
+pub const MAX_PREFIX_LEN: usize = 8;
+
+/// Returns the hash of a credential, which the (V)OPRF is evaluated over. An
+/// empty `username` can be used for checking passwords alone.
+pub fn credential_hash(username: &[u8], password: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.write_u32::<BigEndian>(username.len() as u32).unwrap();
+    buf.extend_from_slice(username);
+    buf.extend_from_slice(password);
+    Sha256::digest(&buf).to_vec()
+}
+
+/// Returns the prefix of the credential hash that indexes its bucket
+pub fn bucket_prefix(hash: &[u8], prefix_len: usize) -> Vec<u8> {
+    hash[..prefix_len].to_vec()
+}
+
+/// Parses a credential of the form `username:password` (or a password alone,
+/// if there is no `:`), and returns its hash
+pub fn parse_credential(line: &str) -> Vec<u8> {
+    let buf = line.as_bytes();
+    match line.find(':') {
+        Some(i) => credential_hash(&buf[..i], &buf[i+1..]),
+        None => credential_hash(&[], buf),
+    }
+}
+
+/// Reads the hashes of the credentials in a file, with one credential (see
+/// `parse_credential`) on each non-empty line
+pub fn read_credentials(path: &str) -> Result<Vec<Vec<u8>>, Error> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(parse_credential)
+        .collect())
+}
+
+/// The `Dataset` struct holds the (V)OPRF outputs for the hashes of leaked
+/// credentials, bucketed by the prefix of the hashes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dataset {
+    prefix_len: usize,
+    buckets: BTreeMap<Vec<u8>, ServerSet>,
+}
+
+impl Dataset {
+    /// Evaluates the outputs for the credential `hashes` with the key of `srv`,
+    /// and buckets them by their first `prefix_len` bytes
+    pub fn build<T,H>(srv: &Server<T,H>, hashes: &[Vec<u8>], prefix_len: usize) -> Result<Self, Error>
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        if prefix_len == 0 || prefix_len > MAX_PREFIX_LEN {
+            return Err(err_bucket_prefix());
+        }
+        let mut outputs: BTreeMap<Vec<u8>, Vec<Vec<u8>>> = BTreeMap::new();
+        for hash in hashes {
+            if hash.len() < prefix_len {
+                return Err(err_bucket_prefix());
+            }
+            let out = srv.full_evaluate(hash, CREDENTIAL_INFO)?;
+            outputs.entry(bucket_prefix(hash, prefix_len)).or_default().push(out);
+        }
+        let mut buckets = BTreeMap::new();
+        for (prefix, outs) in outputs {
+            buckets.insert(prefix, ServerSet::from_outputs(&outs, DEFAULT_TRUNCATION)?);
+        }
+        Ok(Dataset{
+            prefix_len: prefix_len,
+            buckets: buckets,
+        })
+    }
+
+    /// The number of bytes of the credential hash that buckets are indexed by
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    /// The number of non-empty buckets
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Returns `true` if the dataset holds no credentials
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Returns the bucket for `prefix`, which is empty if no credential hash
+    /// has that prefix
+    pub fn bucket(&self, prefix: &[u8]) -> Result<ServerSet, Error> {
+        if prefix.len() != self.prefix_len {
+            return Err(err_bucket_prefix());
+        }
+        match self.buckets.get(prefix) {
+            Some(b) => Ok(b.clone()),
+            None => ServerSet::from_outputs(&[], DEFAULT_TRUNCATION),
+        }
+    }
+}
+
+/// Unblinds and finalizes the server evaluation of the blinded credential hash
+/// in `input`, and returns `true` if the output is in `bucket`
+pub fn is_compromised<T,H>(cli: &Client<T,H>, input: &Input<T>, eval: &Evaluation<T>, bucket: &ServerSet) -> Result<bool, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    if eval.elems.len() != 1 {
+        return Err(err_finalization());
+    }
+    let unblinded = cli.unblind(std::slice::from_ref(input), eval)?;
+    Ok(bucket.contains(&cli.finalize(&input.data, &unblinded[0], CREDENTIAL_INFO)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::oprf::groups::p384::NistPoint;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+
+    fn check<T,H>(pog: PrimeOrderGroup<T,H>, verifiable: bool)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let ciph = Ciphersuite::new(pog.clone(), verifiable);
+        let srv = Server::setup(ciph.clone());
+        let pk = match verifiable {
+            true => Some(srv.key.pub_key(&pog)),
+            false => None,
+        };
+        let cli = Client::setup(ciph, pk).unwrap();
+        let leaked: Vec<Vec<u8>> = ["alice:hunter2", "bob:password", "letmein"].iter()
+            .map(|l| parse_credential(l)).collect();
+        let dataset = Dataset::build(&srv, &leaked, 1).unwrap();
+        let compromised = |hash: Vec<u8>| {
+            let inputs = cli.blind(std::slice::from_ref(&hash));
+            let eval = srv.eval(&[inputs[0].elem.clone()]).unwrap();
+            let bucket = dataset.bucket(&bucket_prefix(&hash, 1)).unwrap();
+            is_compromised(&cli, &inputs[0], &eval, &bucket).unwrap()
+        };
+        assert!(compromised(credential_hash(b"alice", b"hunter2")));
+        assert!(compromised(credential_hash(b"", b"letmein")));
+        assert!(!compromised(credential_hash(b"alice", b"password")));
+        assert!(!compromised(credential_hash(b"carol", b"hunter2")));
+    }
+
+    #[test]
+    fn check_ristretto() {
+        check(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        check(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+    }
+
+    #[test]
+    fn check_p384() {
+        check(PrimeOrderGroup::<NistPoint,Sha512>::p384(), true);
+    }
+
+    #[test]
+    fn buckets() {
+        let ciph = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+        let srv = Server::setup(ciph);
+        let leaked: Vec<Vec<u8>> = (0..50u8).map(|i| credential_hash(b"user", &[i])).collect();
+        let dataset = Dataset::build(&srv, &leaked, 1).unwrap();
+        let total: usize = (0..=255u8).map(|p| dataset.bucket(&[p]).unwrap().len()).sum();
+        assert_eq!(total, 50);
+        assert!(dataset.len() <= 50);
+        // the bucket holds the outputs of all credentials with its prefix
+        let hash = &leaked[0];
+        let bucket = dataset.bucket(&bucket_prefix(hash, 1)).unwrap();
+        assert!(bucket.contains(&srv.full_evaluate(hash, CREDENTIAL_INFO).unwrap()));
+        // prefixes of the wrong length are rejected
+        assert!(dataset.bucket(&[0, 0]).is_err());
+        assert!(Dataset::build(&srv, &leaked, 0).is_err());
+        assert!(Dataset::build(&srv, &leaked, MAX_PREFIX_LEN + 1).is_err());
+    }
+
+    #[test]
+    fn credentials_from_file() {
+        let path = std::env::temp_dir().join(format!("voprf-rs-credentials-{}", std::process::id()));
+        fs::write(&path, "alice:hunter2\n\nbob:pass:word\nletmein \n").unwrap();
+        let hashes = read_credentials(path.to_str().unwrap()).unwrap();
+        assert_eq!(hashes, vec![
+            credential_hash(b"alice", b"hunter2"),
+            credential_hash(b"bob", b"pass:word"),
+            credential_hash(b"", b"letmein "),
+        ]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -5,6 +5,9 @@ use std::io::{Error, ErrorKind};
 /// Error deserializing bytes into a valid group element object
 pub fn err_deserialization() -> Error { Error::new(ErrorKind::Other, "Failed to deserialize") }
 
+/// Indicates that a value is too long for the length prefix of its encoding
+pub fn err_serialization() -> Error { Error::new(ErrorKind::InvalidInput, "Value is too long to serialize") }
+
 /// Indicates that the client has no valid public key set
 pub fn err_public_key_not_found() -> Error { Error::new(ErrorKind::Other, "No public key found for verification") }
 /// Indicates that the server response does not contain a proof object, when one
@@ -12,10 +15,84 @@ pub fn err_public_key_not_found() -> Error { Error::new(ErrorKind::Other, "No pu
 pub fn err_proof_not_found() -> Error { Error::new(ErrorKind::Other, "No proof object sent for verification") }
 /// Indicates that client proof verification failed based on the server response
 pub fn err_proof_verification() -> Error { Error::new(ErrorKind::Other, "Proof verification failed") }
+/// Indicates that a scalar that must be inverted is zero
+pub fn err_scalar_inversion() -> Error { Error::new(ErrorKind::InvalidInput, "Cannot invert the zero scalar") }
+/// Indicates that a batched DLEQ statement is empty, or has different numbers
+/// of elements on each side
+pub fn err_dleq_statement() -> Error { Error::new(ErrorKind::InvalidInput, "Invalid DLEQ statement") }
+/// Indicates that a DLEQ protocol label is longer than 200 bytes
+pub fn err_dleq_label() -> Error { Error::new(ErrorKind::InvalidInput, "DLEQ label is too long") }
 
 /// Indicates that the client failed to process finalization of the (V)OPRF
 /// output
 pub fn err_finalization() -> Error { Error::new(ErrorKind::Other, "Finalization failed") }
+/// Indicates that the server failed to evaluate the (V)OPRF for the client, or
+/// returned a malformed evaluation
+pub fn err_evaluation() -> Error { Error::new(ErrorKind::Other, "Server evaluation failed") }
+/// Indicates that a shuffled evaluation was requested for a verifiable
+/// ciphersuite, whose DLEQ proofs cannot bind the shuffled elements
+pub fn err_shuffled_verifiable() -> Error { Error::new(ErrorKind::InvalidInput, "Shuffled evaluations are not supported for verifiable ciphersuites") }
+
+/// Indicates that a stored client state does not match the ciphersuite or
+/// public key that it is restored with
+pub fn err_invalid_state() -> Error { Error::new(ErrorKind::InvalidData, "Invalid client state") }
+/// Indicates that the integrity check on a stored client state failed
+pub fn err_state_integrity() -> Error { Error::new(ErrorKind::InvalidData, "Client state integrity check failed") }
+
+/// Indicates that a key update token is invalid, or does not apply to the
+/// public key of the client
+pub fn err_update_token() -> Error { Error::new(ErrorKind::InvalidData, "Invalid key update token") }
+
+/// Indicates that a Privacy Pass message is for an unsupported token type
+pub fn err_unsupported_token_type() -> Error { Error::new(ErrorKind::InvalidInput, "Unsupported token type") }
+/// Indicates that a token request is for a different issuer key
+pub fn err_token_key_id() -> Error { Error::new(ErrorKind::InvalidInput, "Token key ID does not match issuer key") }
+/// Indicates that the issuer failed to issue a token
+pub fn err_token_issuance() -> Error { Error::new(ErrorKind::Other, "Token issuance failed") }
+/// Indicates that a redeemed token is invalid
+pub fn err_token_verification() -> Error { Error::new(ErrorKind::InvalidData, "Token verification failed") }
+
+/// Indicates that the keys published by a server do not match the keys that
+/// the client has pinned
+pub fn err_key_pinning() -> Error { Error::new(ErrorKind::InvalidData, "Server keys do not match pinned keys") }
+/// Indicates that the client failed to discover the keys of a server
+pub fn err_discovery() -> Error { Error::new(ErrorKind::Other, "Key discovery failed") }
+
+/// Indicates that the public key of a server is not agreed upon by the
+/// independent sources of keys
+pub fn err_key_consistency() -> Error { Error::new(ErrorKind::InvalidData, "Public key is not consistent across sources") }
+/// Indicates that the public key of a server changed without the rotation
+/// being announced
+pub fn err_key_rotation() -> Error { Error::new(ErrorKind::InvalidData, "Public key changed without a rotation announcement") }
+
+/// Indicates that the prefix of a credential hash is not valid for the
+/// dataset of compromised credentials
+pub fn err_bucket_prefix() -> Error { Error::new(ErrorKind::InvalidInput, "Invalid bucket prefix") }
+
+/// Indicates that the client failed to fetch a bucket of compromised
+/// credentials from the server
+pub fn err_bucket_fetch() -> Error { Error::new(ErrorKind::Other, "Failed to fetch credential bucket") }
+
+/// Indicates that the client failed to recover its credentials from an
+/// OPAQUE envelope, e.g. because the password is wrong
+pub fn err_envelope_recovery() -> Error { Error::new(ErrorKind::InvalidData, "Envelope recovery failed") }
+/// Indicates that the peer failed to authenticate in the OPAQUE key exchange
+pub fn err_key_exchange() -> Error { Error::new(ErrorKind::InvalidData, "Key exchange authentication failed") }
+
+/// Indicates that the cost parameters of the memory-hard function are invalid
+pub fn err_pwhash_params() -> Error { Error::new(ErrorKind::InvalidInput, "Invalid password hashing parameters") }
+/// Indicates that a password record was hardened with a different ciphersuite
+/// or server key
+pub fn err_record_key_id() -> Error { Error::new(ErrorKind::InvalidInput, "Password record does not match server key") }
+
+/// Indicates that a VRF proof is invalid for the public key and input
+pub fn err_vrf_verification() -> Error { Error::new(ErrorKind::InvalidData, "VRF proof verification failed") }
+
+/// Indicates that the requested ciphersuite is not supported
+pub fn err_unsupported_ciphersuite() -> Error { Error::new(ErrorKind::InvalidInput, "Unsupported ciphersuite") }
+
+/// Indicates that a server configuration (e.g. a tenant file) is invalid
+pub fn err_invalid_config() -> Error { Error::new(ErrorKind::InvalidData, "Invalid server configuration") }
 
 /// Indicates that an internal error occurred
 pub fn err_internal() -> Error { Error::new(ErrorKind::Other, "Internal error occurred") }
\ No newline at end of file
@@ -1,15 +1,27 @@
 //! client module
 use std::fs;
+use std::io::Error;
+use std::sync::Arc;
 
 use reqwest;
 
 use super::jsonrpc;
 use crate::oprf;
-use oprf::ciphersuite::{Ciphersuite,Supported};
+use oprf::ciphersuite::{Ciphersuite,ProofVersion,Supported};
+use oprf::any::{AnyCiphersuite,CiphersuiteVisitor,SUPPORTED_GROUPS};
 use oprf::groups::PrimeOrderGroup;
-use oprf::groups::p384::NistPoint;
-use curve25519_dalek::ristretto::RistrettoPoint;
-use sha2::Sha512;
+use oprf::proof::Proof;
+use oprf::consistency::KeyConsistency;
+use crate::privacypass::{TokenClient,TokenChallenge,TokenResponse,Token,REQUEST_CONTENT_TYPE};
+use crate::psi;
+use psi::ServerSet;
+use crate::credcheck;
+use crate::pwhash;
+use pwhash::{Params,PasswordRecord};
+use crate::utils::{rand_bytes,unix_time};
+use crate::errors::{err_token_issuance,err_discovery,err_unsupported_ciphersuite,err_evaluation,err_bucket_fetch,err_record_key_id,err_deserialization};
+use super::server::{ISSUANCE_PATH,PSI_SET_PATH};
+use super::directory::{Directory,KeyCache,DIRECTORY_PATH};
 
 use serde::Deserialize;
 
@@ -26,6 +38,7 @@ pub struct Config<T,H>
     verifiable: bool,
     out_path: Option<String>,
     tv: Option<TestVector>,
+    tenant: Option<String>,
 }
 
 impl<T,H> Config<T,H>
@@ -33,16 +46,19 @@ impl<T,H> Config<T,H>
         + digest::Input + digest::BlockInput + digest::FixedOutput
         + digest::Reset + Clone {
     /// initialises the client config
-    fn init(pog: PrimeOrderGroup<T,H>, host: String, port: String,
+    fn init(mut ciph: Ciphersuite<T,H>, host: String, port: String,
             out_path: Option<String>, pub_key: Option<String>, n_evals: u16,
-            verifiable: bool, test_idx: i16) -> Self {
-        let ciph = Ciphersuite::new(pog.clone(), verifiable);
+            test_idx: i16) -> Self {
+        let pog = ciph.pog.clone();
+        let verifiable = ciph.verifiable;
 
         let mut tv: Option<TestVector> = None;
         let mut pk_to_use = pub_key;
         // indicates that the test mode is activated
         if test_idx != -1 {
             println!("***** Testing mode activated *****");
+            // test vectors use the unbound proofs of draft-irtf-cfrg-voprf-02
+            ciph = ciph.with_proof_version(ProofVersion::Legacy);
             // deserialize test vectors
             let tvs: Vec<TestVector> = serde_json::from_str(
                             &fs::read_to_string(
@@ -54,7 +70,10 @@ impl<T,H> Config<T,H>
         }
 
         let pk = match pk_to_use {
-            Some(s) => Some(oprf::PublicKey::from_hex(s, &pog)),
+            Some(s) => match oprf::PublicKey::from_hex(s, &pog) {
+                Ok(pk) => Some(pk),
+                Err(e) => panic!("Invalid public key: {}", e),
+            },
             None => None,
         };
 
@@ -76,40 +95,663 @@ impl<T,H> Config<T,H>
             verifiable: verifiable,
             out_path: out_path,
             tv: tv,
+            tenant: None,
+        }
+    }
+}
+
+/// The `ClientOptions` struct holds the options that are shared by the HTTP
+/// clients (see `start_client` and the other `start_*` functions)
+#[derive(Clone, Debug)]
+pub struct ClientOptions {
+    /// The group of the ciphersuite (see `SUPPORTED_GROUPS`)
+    pub group_name: String,
+    /// The host of the server
+    pub host: String,
+    /// The port of the server
+    pub port: String,
+    /// The hex-encoded public key of the server, which is required in
+    /// verifiable mode
+    pub pub_key: Option<String>,
+    /// Whether the ciphersuite is verifiable
+    pub verifiable: bool,
+    /// The tenant of a multi-tenant server that requests are addressed to
+    pub tenant: Option<String>,
+    /// The version of the DLEQ proofs that are verified
+    pub proof_version: ProofVersion,
+    /// The context that the DLEQ proofs are bound to
+    pub context: Vec<u8>,
+}
+
+impl ClientOptions {
+    // returns the ciphersuite of the options, which is verifiable if
+    // `verifiable` is set
+    fn ciphersuite(&self, verifiable: bool) -> AnyCiphersuite {
+        match AnyCiphersuite::from_group(&self.group_name, verifiable) {
+            Ok(c) => c.with_proofs(self.proof_version, &self.context),
+            Err(_) => panic!("Unsupported group requested, supported groups are: {:?}", SUPPORTED_GROUPS)
         }
     }
 }
 
-impl Config<NistPoint,Sha512> {
-    fn p384(host: String, port: String, out_path: Option<String>,
-            pub_key: Option<String>, n_evals: u16, verifiable: bool, test_idx: i16) -> Self {
-        let pog = PrimeOrderGroup::p384();
-        Config::init(pog, host, port, out_path, pub_key, n_evals, verifiable, test_idx)
+// runs the client for a ciphersuite that is chosen at runtime
+struct Runner {
+    host: String,
+    port: String,
+    out_path: Option<String>,
+    pub_key: Option<String>,
+    n_evals: u16,
+    test_idx: i16,
+    tenant: Option<String>,
+    consistency: Option<Arc<KeyConsistency>>,
+}
+
+impl CiphersuiteVisitor for Runner {
+    type Output = ();
+
+    fn visit<T,H>(self, ciph: Ciphersuite<T,H>)
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static {
+        let mut cfg = Config::init(ciph, self.host, self.port, self.out_path,
+                self.pub_key, self.n_evals, self.test_idx);
+        cfg.tenant = self.tenant;
+        if let Some(checker) = self.consistency {
+            // the keys are fetched (once) here, to report the sources that
+            // are unavailable
+            if let Ok(agreement) = checker.agreed_keys(&cfg.oprf_cli.ciph.name) {
+                for f in agreement.failures {
+                    println!("Failed to fetch keys from {}: {}", f.source, f.error);
+                }
+            }
+            cfg.oprf_cli.set_consistency(checker);
+        }
+        run(cfg);
+    }
+}
+
+/// Starts the HTTP client for sending VOPRF messages with the options in
+/// `opts`, for `n_evals` inputs (or the test vector `test_idx`, if it is not
+/// -1). If `consistency` is set, then the public key is checked with it before
+/// the server response is unblinded.
+pub fn start_client(opts: ClientOptions, out_path: Option<String>, n_evals: u16,
+        test_idx: i16, consistency: Option<Arc<KeyConsistency>>) {
+    opts.ciphersuite(opts.verifiable).visit(Runner{
+        host: opts.host,
+        port: opts.port,
+        out_path: out_path,
+        pub_key: opts.pub_key,
+        n_evals: n_evals,
+        test_idx: test_idx,
+        tenant: opts.tenant,
+        consistency: consistency,
+    });
+}
+
+// requests a Privacy Pass token for a ciphersuite that is chosen at runtime
+struct TokenRunner {
+    host: String,
+    port: String,
+    pub_key: String,
+}
+
+impl CiphersuiteVisitor for TokenRunner {
+    type Output = Result<Token, Error>;
+
+    fn visit<T,H>(self, ciph: Ciphersuite<T,H>) -> Result<Token, Error>
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static {
+        let pk = oprf::PublicKey::from_hex(self.pub_key, &ciph.pog)?;
+        let cli = TokenClient::new(ciph, pk)?;
+        let url = format!("http://{}:{}{}", self.host, self.port, ISSUANCE_PATH);
+        let challenge = TokenChallenge::new(&self.host, &[], &[])?;
+        println!("Client requesting token from {} with ciphersuite {}", url, cli.ciph().name);
+        request_token(&url, &cli, &challenge)
+    }
+}
+
+/// Requests a Privacy Pass token from the issuance endpoint of the server, for
+/// a challenge issued by the server itself. The ciphersuite is always
+/// verifiable, and the options must hold the public key of the server.
+pub fn start_token_client(opts: ClientOptions) {
+    let pub_key = match opts.pub_key.clone() {
+        Some(pk) => pk,
+        None => panic!("Public key must be provided for token issuance"),
+    };
+    let token = opts.ciphersuite(true).visit(TokenRunner{
+        host: opts.host,
+        port: opts.port,
+        pub_key: pub_key,
+    });
+    match token {
+        Ok(token) => println!("Token: {}", base64::encode_config(token.to_bytes(), base64::URL_SAFE_NO_PAD)),
+        Err(e) => panic!("Failed to obtain token: {}", e),
     }
 }
 
-impl Config<RistrettoPoint,Sha512> {
-    fn ristretto_255(host: String, port: String, out_path: Option<String>,
-            pub_key: Option<String>, n_evals: u16, verifiable: bool, test_idx: i16) -> Self {
-        let pog = PrimeOrderGroup::ristretto_255();
-        Config::init(pog, host, port, out_path, pub_key, n_evals, verifiable, test_idx)
+/// Requests a Privacy Pass token for `challenge` from the issuance endpoint at
+/// `url`, and finalizes the token from the issuer response
+pub fn request_token<T,H>(url: &str, cli: &TokenClient<T,H>, challenge: &TokenChallenge) -> Result<Token, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    let (req, state) = cli.request(challenge);
+    let client = reqwest::blocking::Client::new();
+    let resp = match client.post(url).header("Content-Type", REQUEST_CONTENT_TYPE).body(req.to_bytes()).send() {
+        Ok(r) => r,
+        Err(_) => return Err(err_token_issuance()),
+    };
+    if !resp.status().is_success() {
+        return Err(err_token_issuance());
     }
+    let body = match resp.bytes() {
+        Ok(b) => b,
+        Err(_) => return Err(err_token_issuance()),
+    };
+    let token_resp = TokenResponse::from_bytes(&cli.ciph().pog, &body)?;
+    cli.finalize(&state, &token_resp)
 }
 
-/// Starts the HTTP client for sending VOPRF messages
-pub fn start_client(group_name: String, host: String, port: String,
-        out_path: Option<String>, pub_key: Option<String>, n_evals: u16,
-        verifiable: bool, test_idx: i16) {
-    match group_name.as_str() {
-        "P384" => {
-            let cfg = Config::p384(host, port, out_path, pub_key, n_evals, verifiable, test_idx);
-            run(cfg);
+// runs private set intersection for a ciphersuite that is chosen at runtime
+struct PsiRunner {
+    host: String,
+    port: String,
+    pub_key: Option<String>,
+    items_path: String,
+    batch: u16,
+    tenant: Option<String>,
+    cardinality: bool,
+}
+
+impl CiphersuiteVisitor for PsiRunner {
+    type Output = ();
+
+    fn visit<T,H>(self, ciph: Ciphersuite<T,H>)
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static {
+        let pk = match self.pub_key.map(|s| oprf::PublicKey::from_hex(s, &ciph.pog)).transpose() {
+            Ok(pk) => pk,
+            Err(e) => panic!("Invalid public key: {}", e),
+        };
+        let cli = match oprf::Client::setup(ciph, pk) {
+            Ok(c) => c,
+            Err(e) => panic!("Failed to set up client: {}", e),
+        };
+        let items = match psi::read_items(&self.items_path) {
+            Ok(i) => i,
+            Err(e) => panic!("Failed to read PSI items from {}: {}", self.items_path, e),
+        };
+        let target = format!("http://{}:{}", self.host, self.port);
+        println!("Client running PSI with {} and ciphersuite {}", target, cli.ciph.name);
+        let set = match fetch_psi_set(&format!("{}{}{}", target, PSI_SET_PATH, cli.ciph.name)) {
+            Ok(s) => s,
+            Err(e) => panic!("Failed to fetch PSI set: {}", e),
+        };
+        if self.cardinality {
+            let mut count = 0;
+            for chunk in items.chunks(self.batch as usize) {
+                let inputs = cli.blind_shared(chunk);
+                let eval = match evaluate_remote_shuffled(&target, &cli, &inputs, self.tenant.clone()) {
+                    Ok(ev) => ev,
+                    Err(e) => panic!("Failed to evaluate PSI items: {}", e),
+                };
+                match psi::cardinality(&cli, &inputs, &eval, &set) {
+                    Ok(c) => count += c,
+                    Err(e) => panic!("Failed to count PSI items: {}", e),
+                }
+            }
+            println!("Intersection size: {} (of {} items)", count, items.len());
+            return;
+        }
+        let mut matches = Vec::new();
+        for chunk in items.chunks(self.batch as usize) {
+            let inputs = cli.blind(chunk);
+            let eval = match evaluate_remote(&target, &cli, &inputs, self.tenant.clone()) {
+                Ok(ev) => ev,
+                Err(e) => panic!("Failed to evaluate PSI items: {}", e),
+            };
+            match psi::intersect(&cli, &inputs, &eval, &set) {
+                Ok(m) => matches.extend(m),
+                Err(e) => panic!("Failed to intersect PSI items: {}", e),
+            }
+        }
+        for item in &matches {
+            println!("{}", String::from_utf8_lossy(item));
+        }
+        println!("Intersection size: {} (of {} items)", matches.len(), items.len());
+    }
+}
+
+// checks a credential for a ciphersuite that is chosen at runtime
+struct CredentialRunner {
+    host: String,
+    port: String,
+    pub_key: Option<String>,
+    credential: String,
+    tenant: Option<String>,
+}
+
+impl CiphersuiteVisitor for CredentialRunner {
+    type Output = ();
+
+    fn visit<T,H>(self, ciph: Ciphersuite<T,H>)
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static {
+        let pk = match self.pub_key.map(|s| oprf::PublicKey::from_hex(s, &ciph.pog)).transpose() {
+            Ok(pk) => pk,
+            Err(e) => panic!("Invalid public key: {}", e),
+        };
+        let cli = match oprf::Client::setup(ciph, pk) {
+            Ok(c) => c,
+            Err(e) => panic!("Failed to set up client: {}", e),
+        };
+        let (username, password) = match self.credential.find(':') {
+            Some(i) => (&self.credential[..i], &self.credential[i+1..]),
+            None => ("", self.credential.as_str()),
+        };
+        let target = format!("http://{}:{}", self.host, self.port);
+        println!("Client checking credential with {} and ciphersuite {}", target, cli.ciph.name);
+        match check_credential(&target, &cli, username.as_bytes(), password.as_bytes(), self.tenant) {
+            Ok(true) => println!("Credential is compromised"),
+            Ok(false) => println!("Credential was not found"),
+            Err(e) => panic!("Failed to check credential: {}", e),
+        }
+    }
+}
+
+/// Checks whether `credential` (of the form `username:password`, or a password
+/// alone) is in the dataset of compromised credentials of the server, with the
+/// options in `opts`.
+pub fn start_credential_client(opts: ClientOptions, credential: String) {
+    opts.ciphersuite(opts.verifiable).visit(CredentialRunner{
+        host: opts.host,
+        port: opts.port,
+        pub_key: opts.pub_key,
+        credential: credential,
+        tenant: opts.tenant,
+    });
+}
+
+// enrolls or verifies a password for a ciphersuite that is chosen at runtime
+struct PasswordRunner {
+    host: String,
+    port: String,
+    pub_key: Option<String>,
+    password: String,
+    record_path: String,
+    enroll: bool,
+    tenant: Option<String>,
+    rotation: Option<OldKey>,
+}
+
+/// The `OldKey` struct locates the server key that password records are
+/// re-hardened from after a key rotation
+pub struct OldKey {
+    /// The port of the server that still evaluates with the old key (on the
+    /// same host)
+    pub port: String,
+    /// The hex-encoded old public key
+    pub pub_key: String,
+    /// The tenant that the old key is served for
+    pub tenant: Option<String>,
+}
+
+impl CiphersuiteVisitor for PasswordRunner {
+    type Output = ();
+
+    fn visit<T,H>(self, ciph: Ciphersuite<T,H>)
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static {
+        let pk = match self.pub_key.map(|s| oprf::PublicKey::from_hex(s, &ciph.pog)).transpose() {
+            Ok(pk) => pk,
+            Err(e) => panic!("Invalid public key: {}", e),
+        };
+        let cli = match oprf::Client::setup(ciph, pk) {
+            Ok(c) => c,
+            Err(e) => panic!("Failed to set up client: {}", e),
+        };
+        let target = format!("http://{}:{}", self.host, self.port);
+        let password = self.password.as_bytes();
+        if self.enroll {
+            println!("Client enrolling password with {} and ciphersuite {}", target, cli.ciph.name);
+            let record = match enroll_password(&target, &cli, password, Params::default(), self.tenant) {
+                Ok(r) => r,
+                Err(e) => panic!("Failed to enroll password: {}", e),
+            };
+            write_record(&self.record_path, &record);
+            return;
+        }
+        let record = fs::read_to_string(&self.record_path)
+            .and_then(|buf| hex::decode(buf.trim()).map_err(|_| err_deserialization()))
+            .and_then(|buf| PasswordRecord::from_bytes(&buf));
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => panic!("Failed to read password record: {}", e),
+        };
+        if let Some(old) = self.rotation {
+            if !record.needs_rehardening(&pwhash::key_id(&cli).unwrap()) {
+                println!("Password record is already hardened with the current key");
+                return;
+            }
+            let old_pk = match oprf::PublicKey::from_hex(old.pub_key, &cli.ciph.pog) {
+                Ok(pk) => pk,
+                Err(e) => panic!("Invalid public key for the old key: {}", e),
+            };
+            let old_cli = match oprf::Client::setup(cli.ciph.clone(), Some(old_pk)) {
+                Ok(c) => c,
+                Err(e) => panic!("Failed to set up client for the old key: {}", e),
+            };
+            let old_target = format!("http://{}:{}", self.host, old.port);
+            println!("Client re-hardening password from {} with {} and ciphersuite {}", old_target, target, cli.ciph.name);
+            match reharden_password(&old_target, &old_cli, old.tenant, &target, &cli, self.tenant, password, &record) {
+                Ok(Some(r)) => write_record(&self.record_path, &r),
+                Ok(None) => println!("Password is incorrect, the record was not re-hardened"),
+                Err(e) => panic!("Failed to re-harden password: {}", e),
+            }
+            return;
+        }
+        println!("Client verifying password with {} and ciphersuite {}", target, cli.ciph.name);
+        match verify_password(&target, &cli, password, &record, self.tenant) {
+            Ok(true) => println!("Password is correct"),
+            Ok(false) => println!("Password is incorrect"),
+            Err(e) => panic!("Failed to verify password: {}", e),
+        }
+    }
+}
+
+// writes the encoded password record to `path`
+fn write_record(path: &str, record: &PasswordRecord) {
+    let buf = match record.to_bytes() {
+        Ok(b) => b,
+        Err(e) => panic!("Failed to encode password record: {}", e),
+    };
+    fs::write(path, hex::encode(buf)).unwrap();
+    println!("Password record written to {}", path);
+}
+
+/// Enrolls `password` with the (V)OPRF server, and writes the password record
+/// to `record_path`, or verifies `password` against the record in
+/// `record_path` if `enroll` is not set. If `rotation` is set, the record is
+/// instead re-hardened from the old key to the key of the server, and
+/// overwritten. The options in `opts` must hold the public key of the server.
+pub fn start_password_client(opts: ClientOptions, password: String, record_path: String,
+        enroll: bool, rotation: Option<OldKey>) {
+    if opts.pub_key.is_none() {
+        panic!("Public key must be provided for password hardening");
+    }
+    opts.ciphersuite(opts.verifiable).visit(PasswordRunner{
+        host: opts.host,
+        port: opts.port,
+        pub_key: opts.pub_key,
+        password: password,
+        record_path: record_path,
+        enroll: enroll,
+        tenant: opts.tenant,
+        rotation: rotation,
+    });
+}
+
+/// Runs private set intersection between the items in the file at
+/// `items_path` (one per line) and the PSI set published by the server. The
+/// items are evaluated in batches of `batch` items. If `cardinality` is set,
+/// then only the size of the intersection is computed, with a server that
+/// publishes its set for the cardinality-only mode. The other options are
+/// taken from `opts`.
+pub fn start_psi_client(opts: ClientOptions, items_path: String, batch: u16, cardinality: bool) {
+    if batch == 0 {
+        panic!("PSI batch size must be positive");
+    }
+    opts.ciphersuite(opts.verifiable).visit(PsiRunner{
+        host: opts.host,
+        port: opts.port,
+        pub_key: opts.pub_key,
+        items_path: items_path,
+        batch: batch,
+        tenant: opts.tenant,
+        cardinality: cardinality,
+    });
+}
+
+/// Sends the blinded `inputs` of `cli` to the (V)OPRF server at `url` in an
+/// `eval` request, and returns the parsed evaluation. The proof in the
+/// evaluation is verified when it is unblinded.
+pub fn evaluate_remote<T,H>(url: &str, cli: &oprf::Client<T,H>, inputs: &[oprf::Input<T>], tenant: Option<String>) -> Result<oprf::Evaluation<T>, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    send_eval(url, "eval", cli, inputs, tenant)
+}
+
+/// Sends the blinded `inputs` of `cli` to the (V)OPRF server at `url` in an
+/// `eval_cardinality` request, and returns the parsed evaluation, where the
+/// evaluated elements are in a random order (see `oprf::Server::eval_shuffled`)
+pub fn evaluate_remote_shuffled<T,H>(url: &str, cli: &oprf::Client<T,H>, inputs: &[oprf::Input<T>], tenant: Option<String>) -> Result<oprf::Evaluation<T>, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    send_eval(url, "eval_cardinality", cli, inputs, tenant)
+}
+
+// sends an evaluation request with the JSON-RPC method `method`
+fn send_eval<T,H>(url: &str, method: &str, cli: &oprf::Client<T,H>, inputs: &[oprf::Input<T>], tenant: Option<String>) -> Result<oprf::Evaluation<T>, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    let pog = &cli.ciph.pog;
+    let mut enc_elems = Vec::new();
+    for inp in inputs {
+        let mut buf = Vec::new();
+        (pog.serialize)(&inp.elem, true, &mut buf);
+        enc_elems.push(hex::encode(buf));
+    }
+    let result = match post_request(url, method, enc_elems, &cli.ciph.name, tenant) {
+        Some(r) => r,
+        None => return Err(err_evaluation()),
+    };
+    if result.data.len() != inputs.len() {
+        return Err(err_evaluation());
+    }
+    let mut elems = Vec::new();
+    for z in &result.data {
+        let buf = hex::decode(z).map_err(|_| err_evaluation())?;
+        elems.push((pog.deserialize)(&buf)?);
+    }
+    let mut proof = None;
+    if cli.ciph.verifiable {
+        if result.proof.len() != 2 {
+            return Err(err_evaluation());
+        }
+        let c = hex::decode(&result.proof[0]).map_err(|_| err_evaluation())?;
+        let s = hex::decode(&result.proof[1]).map_err(|_| err_evaluation())?;
+        proof = Some(Proof::new(pog, c, s)?);
+    }
+    Ok(oprf::Evaluation {
+        elems: elems,
+        proof: proof,
+    })
+}
+
+// sends a JSON-RPC request to `url`, and returns the result if the request
+// succeeds
+fn post_request(url: &str, method: &str, data: Vec<String>, ciph: &str, tenant: Option<String>) -> Option<jsonrpc::SuccessResult> {
+    let req = jsonrpc::Request {
+        jsonrpc: String::from("2.0"),
+        method: method.to_string(),
+        params: jsonrpc::RequestParams {
+            data: data,
+            ciph: ciph.to_string(),
+            tenant: tenant,
         },
-        "ristretto255" => {
-            let cfg = Config::ristretto_255(host, port, out_path, pub_key, n_evals, verifiable, test_idx);
-            run(cfg);
+        id: 1
+    };
+    let req_data = serde_json::to_string(&req).unwrap();
+    let client = reqwest::blocking::Client::new();
+    let resp = client.post(url).body(req_data).send().ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    resp.json::<jsonrpc::ResponseSuccess>().ok().map(|out| out.result)
+}
+
+/// Fetches the bucket of compromised credentials for the credential hash
+/// `prefix` from the (V)OPRF server at `url`, for the ciphersuite `ciph_name`
+pub fn fetch_bucket(url: &str, ciph_name: &str, prefix: &[u8], tenant: Option<String>) -> Result<ServerSet, Error> {
+    let result = match post_request(url, "bucket", vec![hex::encode(prefix)], ciph_name, tenant) {
+        Some(r) => r,
+        None => return Err(err_bucket_fetch()),
+    };
+    if result.data.len() != 1 {
+        return Err(err_bucket_fetch());
+    }
+    match hex::decode(&result.data[0]) {
+        Ok(buf) => ServerSet::from_bytes(&buf),
+        Err(_) => Err(err_bucket_fetch()),
+    }
+}
+
+/// Checks whether the credential of `username` and `password` is in the
+/// dataset of compromised credentials of the (V)OPRF server at `url`. The
+/// server only learns the prefix of the credential hash (see the `credcheck`
+/// module).
+pub fn check_credential<T,H>(url: &str, cli: &oprf::Client<T,H>, username: &[u8], password: &[u8], tenant: Option<String>) -> Result<bool, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    let hash = credcheck::credential_hash(username, password);
+    let inputs = cli.blind(std::slice::from_ref(&hash));
+    let eval = evaluate_remote(url, cli, &inputs, tenant.clone())?;
+    let prefix = credcheck::bucket_prefix(&hash, credcheck::DEFAULT_PREFIX_LEN);
+    let bucket = fetch_bucket(url, &cli.ciph.name, &prefix, tenant)?;
+    credcheck::is_compromised(cli, &inputs[0], &eval, &bucket)
+}
+
+/// Hardens `password` with the (V)OPRF server at `url` under a new random
+/// salt, and returns the password record (see the `pwhash` module)
+pub fn enroll_password<T,H>(url: &str, cli: &oprf::Client<T,H>, password: &[u8], params: Params, tenant: Option<String>) -> Result<PasswordRecord, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    let mut salt = Vec::new();
+    rand_bytes(pwhash::SALT_LENGTH, &mut salt);
+    let input = pwhash::blind_password(cli, password, &salt);
+    let eval = evaluate_remote(url, cli, std::slice::from_ref(&input), tenant)?;
+    let hash = pwhash::harden(cli, &input, &eval, &salt, &params)?;
+    PasswordRecord::new(cli, &salt, params, hash)
+}
+
+/// Verifies `password` against `record` with the (V)OPRF server at `url`.
+/// Returns an error if the record was hardened with another ciphersuite or
+/// server key.
+pub fn verify_password<T,H>(url: &str, cli: &oprf::Client<T,H>, password: &[u8], record: &PasswordRecord, tenant: Option<String>) -> Result<bool, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    if record.ciphersuite != cli.ciph.name || record.needs_rehardening(&pwhash::key_id(cli)?) {
+        return Err(err_record_key_id());
+    }
+    let input = pwhash::blind_password(cli, password, &record.salt);
+    let eval = evaluate_remote(url, cli, std::slice::from_ref(&input), tenant)?;
+    Ok(record.matches(&pwhash::harden(cli, &input, &eval, &record.salt, &record.params)?))
+}
+
+/// Re-hardens `record` after a rotation of the server key. The password is
+/// verified with `old_cli` against the (V)OPRF server at `old_url`, which
+/// still evaluates with the key of the record, and hardened again with `cli`
+/// against the server at `url` under a new random salt. Returns the record for
+/// the new key, or `None` if the password is incorrect.
+pub fn reharden_password<T,H>(old_url: &str, old_cli: &oprf::Client<T,H>, old_tenant: Option<String>,
+        url: &str, cli: &oprf::Client<T,H>, tenant: Option<String>, password: &[u8], record: &PasswordRecord) -> Result<Option<PasswordRecord>, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    if record.ciphersuite != old_cli.ciph.name || record.needs_rehardening(&pwhash::key_id(old_cli)?) {
+        return Err(err_record_key_id());
+    }
+    let input = pwhash::blind_password(old_cli, password, &record.salt);
+    let eval = evaluate_remote(old_url, old_cli, std::slice::from_ref(&input), old_tenant)?;
+    let old_hash = pwhash::harden(old_cli, &input, &eval, &record.salt, &record.params)?;
+    if !record.matches(&old_hash) {
+        return Ok(None);
+    }
+    let mut salt = Vec::new();
+    rand_bytes(pwhash::SALT_LENGTH, &mut salt);
+    let input = pwhash::blind_password(cli, password, &salt);
+    let eval = evaluate_remote(url, cli, std::slice::from_ref(&input), tenant)?;
+    let new_hash = pwhash::harden(cli, &input, &eval, &salt, &record.params)?;
+    record.reharden(cli, &old_hash, &salt, new_hash)
+}
+
+/// Fetches the PSI set that the server publishes at `url`
+pub fn fetch_psi_set(url: &str) -> Result<ServerSet, Error> {
+    let resp = match reqwest::blocking::get(url) {
+        Ok(r) => r,
+        Err(_) => return Err(err_evaluation()),
+    };
+    if !resp.status().is_success() {
+        return Err(err_evaluation());
+    }
+    match resp.bytes() {
+        Ok(b) => ServerSet::from_bytes(&b),
+        Err(_) => Err(err_evaluation()),
+    }
+}
+
+/// Fetches the key directory of the server at `url`
+pub fn fetch_directory(url: &str) -> Result<Directory, Error> {
+    let resp = match reqwest::blocking::get(url) {
+        Ok(r) => r,
+        Err(_) => return Err(err_discovery()),
+    };
+    if !resp.status().is_success() {
+        return Err(err_discovery());
+    }
+    match resp.bytes() {
+        Ok(b) => Directory::from_json(&b),
+        Err(_) => Err(err_discovery()),
+    }
+}
+
+/// Discovers the hex-encoded public key that the server at `host:port` uses
+/// for the ciphersuite `ciph_name`, by fetching its key directory. The keys
+/// are pinned in the `KeyCache` stored at `cache_path`, and the pinned key is
+/// used if the directory cannot be fetched.
+pub fn discover_pub_key(host: &str, port: &str, ciph_name: &str, cache_path: &str) -> Result<String, Error> {
+    let origin = format!("{}:{}", host, port);
+    let url = format!("http://{}{}", origin, DIRECTORY_PATH);
+    let mut cache = KeyCache::load(cache_path)?;
+    let now = unix_time();
+    let key = match fetch_directory(&url) {
+        Ok(dir) => {
+            let suite = match dir.get(ciph_name) {
+                Some(s) => s,
+                None => return Err(err_unsupported_ciphersuite()),
+            };
+            let key = cache.pin(&origin, suite, now)?;
+            cache.save()?;
+            key
         },
-        _ => panic!("Unsupported group requested, supported groups are: 'P384', 'ristretto255'")
+        Err(_) => {
+            println!("Failed to fetch key directory from {}, using cached keys", url);
+            match cache.current(&origin, ciph_name, now) {
+                Some(k) => k,
+                None => return Err(err_discovery()),
+            }
+        }
+    };
+    Ok(key.pub_key)
+}
+
+/// Discovers the version and context of the DLEQ proofs that the server at
+/// `host:port` uses for the ciphersuite `ciph_name`, from its key directory
+pub fn discover_proofs(host: &str, port: &str, ciph_name: &str) -> Result<(ProofVersion, Vec<u8>), Error> {
+    let url = format!("http://{}:{}{}", host, port, DIRECTORY_PATH);
+    match fetch_directory(&url)?.get(ciph_name) {
+        Some(suite) => suite.proofs(),
+        None => Err(err_unsupported_ciphersuite()),
     }
 }
 
@@ -141,6 +783,7 @@ fn run<T,H>(cfg: Config<T,H>)
         params: jsonrpc::RequestParams {
             data: enc_elems,
             ciph: ciph.name.clone(),
+            tenant: cfg.tenant.clone(),
         },
         id: 1
     };
@@ -167,15 +810,11 @@ fn generate_inputs<T,H>(cfg: &Config<T,H>) -> Vec<oprf::Input<T>>
             // if not in test mode, then generate bytes uniformly
             let x = hex::decode(&inputs[i]).unwrap();
             let r = hex::decode(&blinds[i]).unwrap();
-            let ele = cfg.oprf_cli.blind_fixed(&x, &r);
             // generate Input object
-            out.push(
-                oprf::Input {
-                    data: x,
-                    elem: ele,
-                    blind: r
-                }
-            )
+            match cfg.oprf_cli.blind_with(&x, &r) {
+                Ok(input) => out.push(input),
+                Err(e) => panic!("Invalid test vector blind: {}", e),
+            }
         }
     } else {
         // generate inputs randomly
@@ -216,12 +855,17 @@ fn process_resp<T,H>(cfg: &Config<T,H>, resp: reqwest::blocking::Response,
     }
 
     // parse proof
-    let mut proof: Option<[Vec<u8>; 2]> = None;
+    let mut proof: Option<Proof<T>> = None;
     if cfg.verifiable {
         if result.proof.len() != 2 {
             panic!("Invalid proof object returned by server");
         }
-        proof = Some([hex::decode(&result.proof[0]).unwrap(), hex::decode(&result.proof[1]).unwrap()]);
+        let c = hex::decode(&result.proof[0]).unwrap();
+        let s = hex::decode(&result.proof[1]).unwrap();
+        proof = match Proof::new(pog, c, s) {
+            Ok(p) => Some(p),
+            Err(e) => panic!("Invalid proof object returned by server: {}", e),
+        };
     }
 
     // create Evaluation object & unblind
@@ -298,13 +942,108 @@ struct TestVector {
 
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{Config,check_credential,fetch_bucket,enroll_password,verify_password,reharden_password};
     use crate::oprf::groups::PrimeOrderGroup;
-    use crate::oprf::{Client,Input,Evaluation};
-    use crate::oprf::ciphersuite::Supported;
+    use crate::oprf::{Client,Server,Input,Evaluation};
+    use crate::oprf::ciphersuite::{Ciphersuite,Supported};
+    use crate::http::registry::SuiteRegistry;
+    use crate::http::server::handle;
+    use crate::credcheck::{parse_credential,DEFAULT_PREFIX_LEN};
+    use crate::pwhash::{Params,PasswordRecord};
     use curve25519_dalek::ristretto::RistrettoPoint;
     use crate::oprf::groups::p384::NistPoint;
     use sha2::Sha512;
+    use std::thread;
+
+    // starts a local HTTP server for the ciphersuites in `registry`, and
+    // returns its URL
+    fn local_server(registry: SuiteRegistry) -> String {
+        let server = rouille::Server::new("127.0.0.1:0", move |request| handle(&registry, request)).unwrap();
+        let url = format!("http://{}", server.server_addr());
+        thread::spawn(move || server.run());
+        url
+    }
+
+    #[test]
+    fn check_credential_end_to_end() {
+        for verifiable in &[true, false] {
+            let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+            let ciph = Ciphersuite::new(pog.clone(), *verifiable);
+            let srv = Server::setup(ciph.clone());
+            let mut registry = SuiteRegistry::new(5);
+            registry.insert(srv.clone()).unwrap();
+            let leaked: Vec<Vec<u8>> = ["alice:hunter2", "bob:password", "letmein"].iter()
+                .map(|c| parse_credential(c)).collect();
+            registry.publish_credentials(&leaked, DEFAULT_PREFIX_LEN).unwrap();
+            let url = local_server(registry);
+
+            let pk = match verifiable {
+                true => Some(srv.key.pub_key(&pog)),
+                false => None,
+            };
+            let cli = Client::setup(ciph.clone(), pk).unwrap();
+            assert!(check_credential(&url, &cli, b"alice", b"hunter2", None).unwrap());
+            assert!(check_credential(&url, &cli, b"", b"letmein", None).unwrap());
+            assert!(!check_credential(&url, &cli, b"alice", b"password", None).unwrap());
+            assert!(!check_credential(&url, &cli, b"carol", b"hunter2", None).unwrap());
+            // prefixes of the wrong length are refused
+            assert!(fetch_bucket(&url, &ciph.name, &[0], None).is_err());
+        }
+    }
+
+    #[test]
+    fn check_credential_without_dataset() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let mut registry = SuiteRegistry::new(5);
+        registry.insert(srv.clone()).unwrap();
+        let url = local_server(registry);
+        let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        assert!(check_credential(&url, &cli, b"alice", b"hunter2", None).is_err());
+
+        // a client with another key fails to verify the evaluation
+        let other = Server::setup(cli.ciph.clone());
+        let cli = Client::setup(cli.ciph.clone(), Some(other.key.pub_key(&pog))).unwrap();
+        assert!(check_credential(&url, &cli, b"alice", b"hunter2", None).is_err());
+    }
+
+    #[test]
+    fn password_end_to_end() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let mut registry = SuiteRegistry::new(5);
+        registry.insert(srv.clone()).unwrap();
+        let url = local_server(registry);
+        let cli = Client::setup(ciph.clone(), Some(srv.key.pub_key(&pog))).unwrap();
+
+        let params = Params{ space_cost: 64, time_cost: 1 };
+        let record = enroll_password(&url, &cli, b"hunter2", params, None).unwrap();
+        let record = PasswordRecord::from_bytes(&record.to_bytes().unwrap()).unwrap();
+        assert!(verify_password(&url, &cli, b"hunter2", &record, None).unwrap());
+        assert!(!verify_password(&url, &cli, b"hunter3", &record, None).unwrap());
+        // a second enrollment uses a new salt
+        let other = enroll_password(&url, &cli, b"hunter2", params, None).unwrap();
+        assert_ne!(other.salt, record.salt);
+        assert_ne!(other.hash, record.hash);
+
+        // records of another key are refused
+        let rotated = Server::setup(ciph.clone());
+        let mut registry = SuiteRegistry::new(5);
+        registry.insert(rotated.clone()).unwrap();
+        let new_url = local_server(registry);
+        let new_cli = Client::setup(ciph, Some(rotated.key.pub_key(&pog))).unwrap();
+        assert!(verify_password(&new_url, &new_cli, b"hunter2", &record, None).is_err());
+
+        // until they are re-hardened with the new key
+        assert!(reharden_password(&url, &cli, None, &new_url, &new_cli, None, b"hunter3", &record).unwrap().is_none());
+        assert!(reharden_password(&new_url, &new_cli, None, &url, &cli, None, b"hunter2", &record).is_err());
+        let rehardened = reharden_password(&url, &cli, None, &new_url, &new_cli, None, b"hunter2", &record).unwrap().unwrap();
+        assert_ne!(rehardened.salt, record.salt);
+        assert!(verify_password(&new_url, &new_cli, b"hunter2", &rehardened, None).unwrap());
+        assert!(!verify_password(&new_url, &new_cli, b"hunter3", &rehardened, None).unwrap());
+    }
 
     #[test]
     fn init_oprf_ristretto() {
@@ -380,7 +1119,7 @@ mod tests {
         let port = "1234".to_string();
         let out_path = Some("some_file_path".to_string());
         let n_evals = 5;
-        let cfg = Config::init(pog.clone(), host.clone(), port.clone(), out_path.clone(), pub_key.clone(), n_evals, verifiable, test_idx);
+        let cfg = Config::init(Ciphersuite::new(pog.clone(), verifiable), host.clone(), port.clone(), out_path.clone(), pub_key.clone(), n_evals, test_idx);
         assert_eq!(cfg.oprf_cli.ciph.verifiable, verifiable);
         assert_eq!(cfg.oprf_cli.ciph.name, expected_name);
         assert_eq!(cfg.host, host);
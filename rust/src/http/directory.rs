@@ -0,0 +1,496 @@
+//! The directory module provides the key directory that the server publishes
+//! via GET requests to `/.well-known/voprf-directory`, so that clients can
+//! discover the public keys of the server rather than having them configured
+//! by hand. The directory lists every hosted ciphersuite, along with the
+//! version of its DLEQ proofs, the (hex-encoded) context that they are bound
+//! to, and the IDs, (hex-encoded) public keys and validity windows of its
+//! keys:
+//!
+//! ```json
+//! {
+//!     "ciphersuites": [
+//!         {
+//!             "ciphersuite": "VOPRF-P384-HKDF-SHA512-SSWU-RO",
+//!             "verifiable": true,
+//!             "proof_version": "legacy",
+//!             "context": "",
+//!             "keys": [
+//!                 { "key_id": "...", "pub_key": "...", "not_before": 1700000000, "not_after": null }
+//!             ]
+//!         },
+//!         { "ciphersuite": "OPRF-ristretto255-HKDF-SHA512-ELL2-RO", "verifiable": false, "proof_version": "legacy", "context": "", "keys": [] }
+//!     ]
+//! }
+//! ```
+//!
+//! The key ID is the SHA-256 fingerprint of the public key (the Privacy Pass
+//! token key ID). Validity windows are in seconds since the UNIX epoch, and
+//! keys without `not_after` do not expire. Directories that do not publish a
+//! proof version (or context) use legacy proofs (and an empty context), and
+//! discovering clients use the published values (see
+//! `client::discover_proofs`).
+//!
+//! Clients pin the keys that they discover in a `KeyCache`. The first keys
+//! that are seen for a ciphersuite are trusted, and are then used until they
+//! expire: if the server stops offering a pinned key before it has expired,
+//! then discovery fails. The cache also allows clients to run without
+//! fetching the directory each time.
+//!
+//! Directories that are stored in local files (`DirectoryFile`) or served by
+//! mirrors (`Mirror`) are also sources of keys for the key consistency checks
+//! of the client (see the `oprf::consistency` module). A mirror can be run
+//! locally with `server::start_mirror`.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error,ErrorKind};
+use std::path::{Path,PathBuf};
+
+use serde::{Serialize,Deserialize};
+use sha2::{Sha256,Digest};
+
+use super::client::fetch_directory;
+use crate::oprf::ciphersuite::ProofVersion;
+use crate::oprf::consistency::{KeySource,KeyConsistency,KeyHistory};
+use crate::errors::{err_deserialization,err_key_pinning};
+use crate::utils::unix_time;
+
+/// The URL path of the key directory
+pub const DIRECTORY_PATH: &str = "/.well-known/voprf-directory";
+
+/// The `KeyValidity` struct holds the window in which a key may be used
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct KeyValidity {
+    /// The time (in seconds since the UNIX epoch) from which the key is valid
+    pub not_before: u64,
+    /// The time from which the key is no longer valid, if it expires
+    pub not_after: Option<u64>,
+}
+
+impl KeyValidity {
+    /// A window that starts now, and lasts for `lifetime` seconds if it is set
+    pub fn from_now(lifetime: Option<u64>) -> Self {
+        let now = unix_time();
+        KeyValidity{
+            not_before: now,
+            not_after: lifetime.map(|l| now + l),
+        }
+    }
+
+    /// Returns `true` if the window contains the time `t`
+    pub fn contains(&self, t: u64) -> bool {
+        match self.not_after {
+            Some(end) => t >= self.not_before && t < end,
+            None => t >= self.not_before,
+        }
+    }
+}
+
+/// The `KeyEntry` struct describes a public key in the directory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeyEntry {
+    /// The hex-encoded ID of the key
+    pub key_id: String,
+    /// The hex-encoded (compressed) public key
+    pub pub_key: String,
+    /// The window in which the key may be used
+    #[serde(flatten)]
+    pub validity: KeyValidity,
+}
+
+impl KeyEntry {
+    /// Creates the entry for the hex-encoded public key `pub_key`
+    pub fn new(pub_key: String, validity: KeyValidity) -> Result<Self, Error> {
+        let key_id = fingerprint(&pub_key)?;
+        Ok(KeyEntry{
+            key_id: key_id,
+            pub_key: pub_key,
+            validity: validity,
+        })
+    }
+
+    /// Returns `true` if the key ID is the fingerprint of the public key
+    pub fn is_consistent(&self) -> bool {
+        match fingerprint(&self.pub_key) {
+            Ok(id) => id == self.key_id,
+            Err(_) => false,
+        }
+    }
+}
+
+/// The `SuiteEntry` struct describes a ciphersuite in the directory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SuiteEntry {
+    /// The name of the ciphersuite
+    pub ciphersuite: String,
+    /// Whether the ciphersuite is verifiable
+    pub verifiable: bool,
+    /// The name of the version of the DLEQ proofs (see `ProofVersion::name`)
+    #[serde(default = "legacy_proof_version")]
+    pub proof_version: String,
+    /// The hex-encoded context that the DLEQ proofs are bound to
+    #[serde(default)]
+    pub context: String,
+    /// The public keys of the ciphersuite, which are only published if the
+    /// ciphersuite is verifiable
+    pub keys: Vec<KeyEntry>,
+}
+
+impl SuiteEntry {
+    /// Returns the version and context of the DLEQ proofs of the ciphersuite
+    pub fn proofs(&self) -> Result<(ProofVersion, Vec<u8>), Error> {
+        let version = ProofVersion::from_name(&self.proof_version)?;
+        match hex::decode(&self.context) {
+            Ok(context) => Ok((version, context)),
+            Err(_) => Err(err_deserialization()),
+        }
+    }
+}
+
+/// The `Directory` struct is the key directory that is published by a server
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Directory {
+    /// The hosted ciphersuites, in sorted order
+    pub ciphersuites: Vec<SuiteEntry>,
+}
+
+impl Directory {
+    /// Returns the entry for the ciphersuite `name`
+    pub fn get(&self, name: &str) -> Option<&SuiteEntry> {
+        self.ciphersuites.iter().find(|s| s.ciphersuite == name)
+    }
+
+    /// Returns the JSON encoding of the directory
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Parses a directory from its JSON encoding
+    pub fn from_json(buf: &[u8]) -> Result<Self, Error> {
+        match serde_json::from_slice(buf) {
+            Ok(d) => Ok(d),
+            Err(_) => Err(err_deserialization()),
+        }
+    }
+
+    /// Loads a directory from a URL, or from a local file otherwise
+    pub fn load(source: &str) -> Result<Self, Error> {
+        match source.starts_with("http://") || source.starts_with("https://") {
+            true => fetch_directory(source),
+            false => Directory::from_json(&fs::read(source)?),
+        }
+    }
+
+    /// Returns the (compressed) public keys of the ciphersuite `name` that are
+    /// valid at the time `now`
+    pub fn valid_keys(&self, name: &str, now: u64) -> Result<Vec<Vec<u8>>, Error> {
+        let mut keys = Vec::new();
+        if let Some(suite) = self.get(name) {
+            for k in suite.keys.iter().filter(|k| k.validity.contains(now)) {
+                match hex::decode(&k.pub_key) {
+                    Ok(buf) => keys.push(buf),
+                    Err(_) => return Err(err_deserialization()),
+                }
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// The `DirectoryFile` struct is a source of keys that reads a key directory
+/// from a local file
+pub struct DirectoryFile {
+    path: PathBuf,
+}
+
+impl DirectoryFile {
+    /// Creates a source that reads the directory at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        DirectoryFile{
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl KeySource for DirectoryFile {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn keys(&self, ciph_name: &str) -> Result<Vec<Vec<u8>>, Error> {
+        Directory::from_json(&fs::read(&self.path)?)?.valid_keys(ciph_name, unix_time())
+    }
+}
+
+/// The `Mirror` struct is a source of keys that fetches the key directory of
+/// a server from a mirror at `url`
+pub struct Mirror {
+    url: String,
+}
+
+impl Mirror {
+    /// Creates a source that fetches the directory from `url`
+    pub fn new(url: &str) -> Self {
+        Mirror{
+            url: url.to_string(),
+        }
+    }
+}
+
+impl KeySource for Mirror {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    fn keys(&self, ciph_name: &str) -> Result<Vec<Vec<u8>>, Error> {
+        fetch_directory(&self.url)?.valid_keys(ciph_name, unix_time())
+    }
+}
+
+/// Creates a key consistency checker over the directories at `sources`
+/// (URLs of mirrors, or local files), where keys must be published by at
+/// least `quorum` sources (all of them if `quorum` is not set). The history
+/// of keys is stored at `history_path`.
+pub fn key_consistency(sources: &[String], quorum: Option<usize>, history_path: &str) -> Result<KeyConsistency, Error> {
+    let mut boxed: Vec<Box<dyn KeySource>> = Vec::new();
+    for source in sources {
+        match source.starts_with("http://") || source.starts_with("https://") {
+            true => boxed.push(Box::new(Mirror::new(source))),
+            false => boxed.push(Box::new(DirectoryFile::new(source))),
+        }
+    }
+    let quorum = quorum.unwrap_or(boxed.len());
+    Ok(KeyConsistency::new(boxed, quorum, KeyHistory::load(history_path)?))
+}
+
+/// The `KeyCache` struct holds the keys that a client has pinned for each
+/// server (identified by its origin, e.g. `127.0.0.1:3001`) and ciphersuite,
+/// and is stored as a JSON file.
+#[derive(Debug)]
+pub struct KeyCache {
+    path: PathBuf,
+    pins: BTreeMap<String, BTreeMap<String, Vec<KeyEntry>>>,
+}
+
+impl KeyCache {
+    /// Loads the cache from the file at `path`, the cache is empty if the file
+    /// does not exist
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let pins = match fs::read(&path) {
+            Ok(buf) => match serde_json::from_slice(&buf) {
+                Ok(p) => p,
+                Err(_) => return Err(err_deserialization()),
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(KeyCache{
+            path: path,
+            pins: pins,
+        })
+    }
+
+    /// Writes the cache to its file
+    pub fn save(&self) -> Result<(), Error> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.pins).unwrap())
+    }
+
+    /// Returns the keys that are pinned for the ciphersuite `name` of the
+    /// server at `origin`
+    pub fn pinned(&self, origin: &str, name: &str) -> &[KeyEntry] {
+        match self.pins.get(origin).and_then(|p| p.get(name)) {
+            Some(keys) => keys,
+            None => &[],
+        }
+    }
+
+    /// Returns the pinned key that was most recently made valid, for the
+    /// ciphersuite `name` of the server at `origin`, at the time `now`
+    pub fn current(&self, origin: &str, name: &str, now: u64) -> Option<KeyEntry> {
+        latest(self.pinned(origin, name).iter().filter(|k| k.validity.contains(now)))
+    }
+
+    /// Pins the keys of `suite`, fetched from the directory of the server at
+    /// `origin`, and returns the key that should be used at the time `now`.
+    ///
+    /// Expired pins are dropped. If no pins remain, then the valid keys of
+    /// `suite` are pinned. Otherwise, the returned key must be pinned, and an
+    /// error is returned if the server no longer offers any of the pinned keys.
+    pub fn pin(&mut self, origin: &str, suite: &SuiteEntry, now: u64) -> Result<KeyEntry, Error> {
+        if suite.keys.iter().any(|k| !k.is_consistent()) {
+            return Err(err_key_pinning());
+        }
+        let offered: Vec<&KeyEntry> = suite.keys.iter().filter(|k| k.validity.contains(now)).collect();
+        let pins = self.pins.entry(origin.to_string()).or_default()
+            .entry(suite.ciphersuite.clone()).or_default();
+        pins.retain(|k| k.validity.contains(now));
+        if pins.is_empty() {
+            pins.extend(offered.iter().map(|k| (*k).clone()));
+        }
+        let pinned = pins.clone();
+        let key = latest(offered.into_iter().filter(|k| pinned.contains(k)));
+        match key {
+            Some(k) => Ok(k),
+            None => Err(err_key_pinning()),
+        }
+    }
+}
+
+// returns the hex-encoded SHA-256 fingerprint of a hex-encoded public key
+fn fingerprint(pub_key: &str) -> Result<String, Error> {
+    match hex::decode(pub_key) {
+        Ok(buf) => Ok(hex::encode(Sha256::digest(&buf))),
+        Err(_) => Err(err_deserialization()),
+    }
+}
+
+fn legacy_proof_version() -> String {
+    ProofVersion::Legacy.name().to_string()
+}
+
+// returns the key that was most recently made valid
+fn latest<'a, I: Iterator<Item = &'a KeyEntry>>(keys: I) -> Option<KeyEntry> {
+    keys.max_by_key(|k| k.validity.not_before).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8, not_before: u64, not_after: Option<u64>) -> KeyEntry {
+        KeyEntry::new(hex::encode(vec![seed; 32]), KeyValidity{
+            not_before: not_before,
+            not_after: not_after,
+        }).unwrap()
+    }
+
+    fn suite(keys: Vec<KeyEntry>) -> SuiteEntry {
+        SuiteEntry{
+            ciphersuite: "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO".to_string(),
+            verifiable: true,
+            proof_version: "v1".to_string(),
+            context: hex::encode(b"context"),
+            keys: keys,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("voprf-rs-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn directory_json() {
+        let dir = Directory{
+            ciphersuites: vec![suite(vec![key(1, 10, None), key(2, 20, Some(30))])],
+        };
+        let json = dir.to_json();
+        assert!(json.contains(r#""not_before":10,"not_after":null"#));
+        assert_eq!(Directory::from_json(json.as_bytes()).unwrap(), dir);
+        assert!(dir.get("VOPRF-ristretto255-HKDF-SHA512-ELL2-RO").is_some());
+        assert!(dir.get("VOPRF-P384-HKDF-SHA512-SSWU-RO").is_none());
+        assert!(Directory::from_json(b"{").is_err());
+    }
+
+    #[test]
+    fn suite_proofs() {
+        assert_eq!(suite(vec![]).proofs().unwrap(), (ProofVersion::V1, b"context".to_vec()));
+        // directories that predate proof versions use legacy proofs
+        let json = r#"{ "ciphersuites": [{ "ciphersuite": "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", "verifiable": true, "keys": [] }] }"#;
+        let dir = Directory::from_json(json.as_bytes()).unwrap();
+        assert_eq!(dir.ciphersuites[0].proofs().unwrap(), (ProofVersion::Legacy, Vec::new()));
+        let mut bad = suite(vec![]);
+        bad.proof_version = "v2".to_string();
+        assert!(bad.proofs().is_err());
+        let mut bad = suite(vec![]);
+        bad.context = "zz".to_string();
+        assert!(bad.proofs().is_err());
+    }
+
+    #[test]
+    fn validity_window() {
+        let v = KeyValidity{ not_before: 10, not_after: Some(20) };
+        assert!(!v.contains(9));
+        assert!(v.contains(10));
+        assert!(!v.contains(20));
+        assert!(KeyValidity{ not_before: 10, not_after: None }.contains(u64::MAX));
+    }
+
+    #[test]
+    fn pin_on_first_use() {
+        let mut cache = KeyCache::load(temp_path("pin-first-use")).unwrap();
+        let k1 = key(1, 10, Some(100));
+        let k2 = key(2, 20, Some(100));
+        let expired = key(3, 0, Some(5));
+        assert_eq!(cache.pin("a", &suite(vec![k1.clone(), k2.clone(), expired]), 50).unwrap(), k2);
+        assert_eq!(cache.pinned("a", "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO"), &[k1.clone(), k2.clone()]);
+        // pins are per origin
+        assert!(cache.pinned("b", "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO").is_empty());
+        assert_eq!(cache.pin("a", &suite(vec![k1.clone()]), 60).unwrap(), k1);
+    }
+
+    #[test]
+    fn reject_unpinned_keys() {
+        let mut cache = KeyCache::load(temp_path("reject-unpinned")).unwrap();
+        let k1 = key(1, 10, Some(100));
+        let k2 = key(2, 20, None);
+        cache.pin("a", &suite(vec![k1.clone()]), 50).unwrap();
+        // the pinned key is still valid, so a new key is not trusted
+        assert!(cache.pin("a", &suite(vec![k2.clone()]), 60).is_err());
+        assert_eq!(cache.pin("a", &suite(vec![k1.clone(), k2.clone()]), 60).unwrap(), k1);
+        // once the pin has expired, the new key is pinned
+        assert_eq!(cache.pin("a", &suite(vec![k2.clone()]), 100).unwrap(), k2);
+        assert_eq!(cache.pinned("a", "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO"), &[k2]);
+    }
+
+    #[test]
+    fn reject_inconsistent_keys() {
+        let mut cache = KeyCache::load(temp_path("reject-inconsistent")).unwrap();
+        let mut k = key(1, 10, None);
+        k.key_id = key(2, 10, None).key_id;
+        assert!(cache.pin("a", &suite(vec![k]), 50).is_err());
+        assert!(cache.pin("a", &suite(vec![]), 50).is_err());
+    }
+
+    #[test]
+    fn directory_sources() {
+        let path = temp_path("directory-source");
+        let dir = Directory{
+            ciphersuites: vec![suite(vec![key(1, 0, None), key(2, 0, Some(5)), key(3, u64::MAX, None)])],
+        };
+        fs::write(&path, dir.to_json()).unwrap();
+        let source = DirectoryFile::new(&path);
+        // only keys that are currently valid are published
+        assert_eq!(source.keys("VOPRF-ristretto255-HKDF-SHA512-ELL2-RO").unwrap(), vec![vec![1; 32]]);
+        assert!(source.keys("VOPRF-P384-HKDF-SHA512-SSWU-RO").unwrap().is_empty());
+        assert_eq!(Directory::load(path.to_str().unwrap()).unwrap(), dir);
+
+        let history = temp_path("directory-source-history");
+        let _ = fs::remove_file(&history);
+        let sources = vec![path.to_str().unwrap().to_string(), path.to_str().unwrap().to_string()];
+        let checker = key_consistency(&sources, None, history.to_str().unwrap()).unwrap();
+        assert!(checker.check("VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", &[1; 32]).is_ok());
+        assert!(checker.check("VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", &[2; 32]).is_err());
+        assert!(!history.exists());
+        checker.confirm("VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", &[1; 32]).unwrap();
+        assert!(history.exists());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&history).unwrap();
+    }
+
+    #[test]
+    fn cache_persists() {
+        let path = temp_path("cache-persists");
+        let _ = fs::remove_file(&path);
+        let mut cache = KeyCache::load(&path).unwrap();
+        let k = key(1, 10, Some(100));
+        cache.pin("a", &suite(vec![k.clone()]), 50).unwrap();
+        cache.save().unwrap();
+
+        let cache = KeyCache::load(&path).unwrap();
+        assert_eq!(cache.current("a", "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", 50), Some(k));
+        assert_eq!(cache.current("a", "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", 100), None);
+        fs::write(&path, "[").unwrap();
+        assert!(KeyCache::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -19,15 +19,33 @@ impl Request {
         }
     }
 
+    // requests for the cardinality-only mode are evaluated with shuffled
+    // responses
+    pub fn is_cardinality(&self) -> bool {
+        self.method == "eval_cardinality"
+    }
+
+    // requests for a bucket of compromised credentials carry the hex-encoded
+    // prefix of the credential hash
+    pub fn is_bucket(&self) -> bool {
+        self.method == "bucket"
+    }
+
     fn validate(&self) -> Result<(), ErrorType> {
         match self.jsonrpc.as_str() {
             "2.0" => match self.method.as_str() {
-                "eval" => {
+                "eval" | "eval_cardinality" => {
                     if self.params.data.len() < 1 {
                         return Err(ErrorType::InvalidParams);
                     }
                     Ok(())
                 },
+                "bucket" => {
+                    if self.params.data.len() != 1 {
+                        return Err(ErrorType::InvalidParams);
+                    }
+                    Ok(())
+                },
                 _ => Err(ErrorType::MethodNotFound),
             },
             _ => Err(ErrorType::InvalidRequest)
@@ -38,7 +56,9 @@ impl Request {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RequestParams {
     pub data: Vec<String>,
-    pub ciph: String
+    pub ciph: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -73,6 +93,7 @@ pub struct ErrorResult {
     pub code: i16,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ErrorType {
     ParseError,
     InvalidRequest,
@@ -82,6 +103,8 @@ pub enum ErrorType {
     // custom JSON-RPC errors
     IncompatibleCiphersuite,
     Deserialization,
+    UnknownTenant,
+    QuotaExceeded,
 }
 
 pub fn error(err: ErrorType, id: i16) -> String {
@@ -92,6 +115,8 @@ pub fn error(err: ErrorType, id: i16) -> String {
         ErrorType::InvalidParams => ErrorResult{ message: "Invalid method parameter(s).".to_string(), code: -32602 },
         ErrorType::IncompatibleCiphersuite => ErrorResult{ message: "Specified ciphersuite is incompatible with server.".to_string(), code: -32000 },
         ErrorType::Deserialization => ErrorResult{ message: "Failed to deserialize client input.".to_string(), code: -32001 },
+        ErrorType::UnknownTenant => ErrorResult{ message: "Requested tenant is not available.".to_string(), code: -32002 },
+        ErrorType::QuotaExceeded => ErrorResult{ message: "Evaluation quota exceeded.".to_string(), code: -32003 },
         _ => ErrorResult{ message: "Internal JSON-RPC error.".to_string(), code: -32603 },
     };
     let resp_err = ResponseError{ jsonrpc: "2.0".to_string(), error: result, id: id };
@@ -2,4 +2,7 @@
 
 pub mod server;
 pub mod client;
+pub mod registry;
+pub mod tenant;
+pub mod directory;
 mod jsonrpc;
\ No newline at end of file
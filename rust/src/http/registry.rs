@@ -0,0 +1,809 @@
+//! The registry module provides the `SuiteRegistry` struct, a type-erased
+//! collection of (V)OPRF servers keyed by the name of the ciphersuite that
+//! they run. This allows a single HTTP server to host several ciphersuites
+//! (each with its own key) at the same time, and to route each request on the
+//! ciphersuite that it specifies, without the HTTP layer being monomorphized
+//! over a single `T,H` pair.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::http::registry::SuiteRegistry;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::Server;
+//!
+//! let mut registry = SuiteRegistry::new(10);
+//! let p384 = Ciphersuite::new(PrimeOrderGroup::p384(), true);
+//! let ristretto = Ciphersuite::new(PrimeOrderGroup::ristretto_255(), true);
+//! registry.insert(Server::setup(p384)).unwrap();
+//! registry.insert(Server::setup(ristretto)).unwrap();
+//! assert_eq!(registry.names().len(), 2);
+//! ```
+//!
+//! The keys of the hosted ciphersuites are published in a `Directory` (see the
+//! `directory` module), with a validity window for each key.
+//!
+//! A registry may also publish the `psi::ServerSet` of each ciphersuite for a
+//! set of items, for private set intersection with clients. If the sets are
+//! published for the cardinality-only mode, then the registry only accepts
+//! `eval_cardinality` requests, which are evaluated with shuffled responses.
+//!
+//! A registry may also hold a `credcheck::Dataset` of compromised credentials
+//! for each ciphersuite, whose buckets are served with `bucket` requests.
+//!
+//! Redeemed Privacy Pass tokens are recorded in the `EpochStores` of the
+//! registry (in memory by default), with a store for each token key ID, so
+//! that each token is accepted at most once.
+use std::collections::HashMap;
+use std::io::Error;
+
+use rouille::Response;
+
+use super::jsonrpc;
+use jsonrpc::{ErrorType,RequestParams};
+use super::server::error_response;
+use crate::oprf;
+use oprf::ciphersuite::{ProofVersion,Supported};
+use oprf::any::AnyServer;
+use oprf::groups::PrimeOrderGroup;
+use oprf::Evaluation;
+use crate::privacypass;
+use crate::psi::ServerSet;
+use crate::credcheck::Dataset;
+use privacypass::{TokenRequest,Token,RESPONSE_CONTENT_TYPE};
+use privacypass::store::{SpendStore,EpochStores};
+use super::directory::{Directory,SuiteEntry,KeyEntry,KeyValidity};
+use crate::errors::{err_invalid_config,err_token_issuance,err_token_key_id,err_unsupported_ciphersuite};
+
+/// The `Evaluator` trait erases the `T,H` parameters of an `oprf::Server`, so
+/// that servers running different ciphersuites can be held side-by-side and
+/// driven with the hex-encoded data carried in JSON-RPC requests.
+pub(crate) trait Evaluator: Send + Sync {
+    /// The name of the ciphersuite that the evaluator runs
+    fn ciph_name(&self) -> String;
+    /// The hex-encoded public key, if the ciphersuite is verifiable
+    fn pub_key_hex(&self) -> Option<String>;
+    /// The version and context of the DLEQ proofs of the ciphersuite
+    fn proofs(&self) -> (ProofVersion, Vec<u8>);
+    /// Evaluates the (V)OPRF over hex-encoded blinded group elements and
+    /// returns the hex-encoded evaluations and proof values
+    fn eval_hex(&self, data: Vec<String>) -> Result<(Vec<String>, Vec<String>), ErrorType>;
+    /// Same as `eval_hex`, except that the evaluations are returned in a
+    /// random order without a proof (see `oprf::Server::eval_shuffled`)
+    fn eval_hex_shuffled(&self, data: Vec<String>) -> Result<(Vec<String>, Vec<String>), ErrorType>;
+    /// The Privacy Pass token key ID, if tokens can be issued with the
+    /// ciphersuite (see `privacypass::TOKEN_CIPHERSUITE`)
+    fn token_key_id(&self) -> Option<Vec<u8>>;
+    /// Issues a Privacy Pass token for an encoded `TokenRequest`, and returns
+    /// the encoded `TokenResponse`
+    fn issue_token(&self, req: &[u8]) -> Result<Vec<u8>, Error>;
+    /// Verifies a redeemed Privacy Pass token and marks it as spent in
+    /// `store`, returns `false` if it has already been spent
+    fn redeem_token(&self, token: &Token, store: &dyn SpendStore) -> Result<bool, Error>;
+    /// Returns the encoded `psi::ServerSet` for `items`, for the
+    /// cardinality-only mode if `cardinality` is set
+    fn psi_set(&self, items: &[Vec<u8>], truncation: usize, cardinality: bool) -> Result<Vec<u8>, Error>;
+    /// Builds the `credcheck::Dataset` for the compromised credential `hashes`
+    fn credential_dataset(&self, hashes: &[Vec<u8>], prefix_len: usize) -> Result<Dataset, Error>;
+}
+
+impl<T,H> Evaluator for oprf::Server<T,H>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync,
+        H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    fn ciph_name(&self) -> String {
+        self.ciph.name.clone()
+    }
+
+    fn pub_key_hex(&self) -> Option<String> {
+        let pog = &self.ciph.pog;
+        match self.ciph.verifiable {
+            true => Some(self.key.pub_key(pog).as_hex(pog)),
+            false => None,
+        }
+    }
+
+    fn proofs(&self) -> (ProofVersion, Vec<u8>) {
+        (self.ciph.proof_version, self.ciph.context.clone())
+    }
+
+    fn eval_hex(&self, data: Vec<String>) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+        let pog = &self.ciph.pog;
+        let eles = decode_elems(pog, data)?;
+        let ev = self.eval(&eles).map_err(|_| ErrorType::InternalError)?;
+        check_proof(self.ciph.verifiable, encode_evaluation(pog, &ev))
+    }
+
+    fn eval_hex_shuffled(&self, data: Vec<String>) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+        let pog = &self.ciph.pog;
+        let eles = decode_elems(pog, data)?;
+        let ev = self.eval_shuffled(&eles).map_err(|_| ErrorType::IncompatibleCiphersuite)?;
+        Ok(encode_evaluation(pog, &ev))
+    }
+
+    fn token_key_id(&self) -> Option<Vec<u8>> {
+        let pog = &self.ciph.pog;
+        match privacypass::is_token_ciphersuite(&self.ciph) {
+            true => Some(privacypass::token_key_id(pog, &self.key.pub_key(pog))),
+            false => None,
+        }
+    }
+
+    fn issue_token(&self, req: &[u8]) -> Result<Vec<u8>, Error> {
+        let key_id = match self.token_key_id() {
+            Some(id) => id,
+            None => return Err(err_token_issuance()),
+        };
+        let req = TokenRequest::from_bytes(&self.ciph.pog, req)?;
+        Ok(privacypass::issue(self, &key_id, &req)?.to_bytes())
+    }
+
+    fn redeem_token(&self, token: &Token, store: &dyn SpendStore) -> Result<bool, Error> {
+        let key_id = match self.token_key_id() {
+            Some(id) => id,
+            None => return Err(err_token_key_id()),
+        };
+        privacypass::redeem(self, &key_id, token, store)
+    }
+
+    fn psi_set(&self, items: &[Vec<u8>], truncation: usize, cardinality: bool) -> Result<Vec<u8>, Error> {
+        let set = match cardinality {
+            true => ServerSet::build_cardinality(self, items, truncation)?,
+            false => ServerSet::build(self, items, truncation)?,
+        };
+        Ok(set.to_bytes())
+    }
+
+    fn credential_dataset(&self, hashes: &[Vec<u8>], prefix_len: usize) -> Result<Dataset, Error> {
+        Dataset::build(self, hashes, prefix_len)
+    }
+}
+
+/// Deserializes hex-encoded group elements sent by the client
+pub(crate) fn decode_elems<T,H>(pog: &PrimeOrderGroup<T,H>, data: Vec<String>) -> Result<Vec<T>, ErrorType> {
+    let decoded: Result<Vec<Vec<u8>>, hex::FromHexError> = data.into_iter()
+        .map(|s| hex::decode(s))
+        .collect();
+    match decoded {
+        Ok(v) => {
+            let deser_eles: Result<Vec<T>, Error> = v.into_iter()
+                                    .map(|bytes| (pog.deserialize)(&bytes))
+                                    .collect();
+            match deser_eles {
+                Ok(eles) => Ok(eles),
+                Err(_) => Err(ErrorType::Deserialization)
+            }
+        },
+        Err(_) => Err(ErrorType::InvalidParams)
+    }
+}
+
+/// Hex-encodes the evaluated group elements and proof values of a server
+/// evaluation
+pub(crate) fn encode_evaluation<T,H>(pog: &PrimeOrderGroup<T,H>, ev: &Evaluation<T>) -> (Vec<String>, Vec<String>) {
+    let mut buf = Vec::new();
+    let mut eles_hex = Vec::new();
+    for p in &ev.elems {
+        (pog.serialize)(p, true, &mut buf);
+        eles_hex.push(hex::encode(&buf));
+    }
+    let mut proof_hex = Vec::new();
+    if let Some(proof) = &ev.proof {
+        proof_hex.push(hex::encode(proof.c()));
+        proof_hex.push(hex::encode(proof.s()));
+    }
+    (eles_hex, proof_hex)
+}
+
+/// Checks that an encoded evaluation contains proof values if the
+/// ciphersuite is verifiable
+pub(crate) fn check_proof(verifiable: bool, encoded: (Vec<String>, Vec<String>)) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+    if encoded.1.len() != 2 && verifiable {
+        println!("ciphersuite should be verifiable");
+        return Err(ErrorType::InternalError);
+    }
+    Ok(encoded)
+}
+
+/// The `SuiteRegistry` struct holds a type-erased (V)OPRF server for each
+/// ciphersuite that is hosted, keyed by the name of the ciphersuite.
+pub struct SuiteRegistry {
+    suites: HashMap<String, Box<dyn Evaluator>>,
+    max_evals: u16,
+    validity: HashMap<String, KeyValidity>,
+    psi_sets: HashMap<String, Vec<u8>>,
+    cardinality_only: bool,
+    datasets: HashMap<String, Dataset>,
+    spent: EpochStores,
+}
+
+impl SuiteRegistry {
+    /// Creates an empty registry, where each request may ask for at most
+    /// `max_evals` evaluations.
+    pub fn new(max_evals: u16) -> Self {
+        SuiteRegistry{
+            suites: HashMap::new(),
+            max_evals: max_evals,
+            validity: HashMap::new(),
+            psi_sets: HashMap::new(),
+            cardinality_only: false,
+            datasets: HashMap::new(),
+            spent: EpochStores::in_memory(),
+        }
+    }
+
+    /// Sets the stores that redeemed tokens are recorded in, and purges the
+    /// stores of epochs whose keys are no longer hosted
+    pub fn set_spend_stores(&mut self, stores: EpochStores) -> Result<(), Error> {
+        self.spent = stores;
+        self.rotate_spend_stores()
+    }
+
+    /// Purges the stores of redeemed tokens for all epochs whose keys are no
+    /// longer hosted, this should be called when keys are rotated
+    pub fn rotate_spend_stores(&self) -> Result<(), Error> {
+        self.spent.rotate(&self.token_key_ids())
+    }
+
+    /// Returns the Privacy Pass token key IDs of all hosted verifiable
+    /// ciphersuites
+    pub fn token_key_ids(&self) -> Vec<Vec<u8>> {
+        self.names().iter().filter_map(|n| self.suites[n].token_key_id()).collect()
+    }
+
+    /// Adds a (V)OPRF server, for a ciphersuite chosen at runtime, to the
+    /// registry. Returns an error if a server for the same ciphersuite has
+    /// already been added.
+    pub fn insert_any(&mut self, srv: AnyServer) -> Result<(), Error> {
+        match srv {
+            AnyServer::P384(s) => self.insert(s),
+            AnyServer::P384Sha384(s) => self.insert(s),
+            AnyServer::Ristretto255(s) => self.insert(s),
+        }
+    }
+
+    /// Adds a (V)OPRF server to the registry. Returns an error if a server
+    /// for the same ciphersuite has already been added.
+    pub fn insert<T,H>(&mut self, srv: oprf::Server<T,H>) -> Result<(), Error>
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static {
+        self.insert_evaluator(Box::new(srv))
+    }
+
+    /// Adds a type-erased evaluator to the registry
+    pub(crate) fn insert_evaluator(&mut self, evaluator: Box<dyn Evaluator>) -> Result<(), Error> {
+        let name = evaluator.ciph_name();
+        if self.suites.contains_key(&name) {
+            return Err(err_invalid_config());
+        }
+        self.validity.insert(name.clone(), KeyValidity::from_now(None));
+        self.suites.insert(name, evaluator);
+        Ok(())
+    }
+
+    /// Sets the validity window of the key used for the ciphersuite `name`,
+    /// keys are valid from the time they are added (without expiry) by default
+    pub fn set_validity(&mut self, name: &str, validity: KeyValidity) -> Result<(), Error> {
+        match self.validity.get_mut(name) {
+            Some(v) => {
+                *v = validity;
+                Ok(())
+            },
+            None => Err(err_unsupported_ciphersuite()),
+        }
+    }
+
+    /// Returns the key directory, which holds an entry for each hosted
+    /// ciphersuite with its public key (if it is verifiable)
+    pub fn directory(&self) -> Directory {
+        let mut ciphersuites = Vec::new();
+        for name in self.names() {
+            let keys = match self.suites[&name].pub_key_hex() {
+                Some(pk) => vec![KeyEntry::new(pk, self.validity[&name]).unwrap()],
+                None => Vec::new(),
+            };
+            let (version, context) = self.suites[&name].proofs();
+            ciphersuites.push(SuiteEntry{
+                ciphersuite: name,
+                verifiable: !keys.is_empty(),
+                proof_version: version.name().to_string(),
+                context: hex::encode(context),
+                keys: keys,
+            });
+        }
+        Directory{
+            ciphersuites: ciphersuites,
+        }
+    }
+
+    /// Returns the names of all hosted ciphersuites, in sorted order
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.suites.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the hex-encoded public key used for the ciphersuite `name`, if
+    /// it is hosted and verifiable
+    pub fn pub_key_hex(&self, name: &str) -> Option<String> {
+        match self.suites.get(name) {
+            Some(ev) => ev.pub_key_hex(),
+            None => None,
+        }
+    }
+
+    /// Evaluates the request params with the server for the ciphersuite that
+    /// they specify, with shuffled responses if `shuffled` is set
+    pub(crate) fn evaluate(&self, params: RequestParams, shuffled: bool) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+        let evaluator = match self.suites.get(&params.ciph) {
+            Some(ev) => ev,
+            None => {
+                println!("chosen ciphersuite ({}) is not supported", params.ciph);
+                return Err(ErrorType::IncompatibleCiphersuite);
+            }
+        };
+        if params.data.len() > self.max_evals as usize {
+            return Err(ErrorType::InvalidParams);
+        }
+        if shuffled {
+            return evaluator.eval_hex_shuffled(params.data);
+        }
+        if self.cardinality_only {
+            // ordered evaluations would reveal the intersection
+            println!("only cardinality evaluations are permitted");
+            return Err(ErrorType::MethodNotFound);
+        }
+        evaluator.eval_hex(params.data)
+    }
+
+    /// Processes the body of a Privacy Pass token request, which is issued by
+    /// the verifiable ciphersuite with the matching (truncated) token key ID,
+    /// and returns the HTTP response
+    pub fn process_token_request(&self, buf: &[u8]) -> Response {
+        if buf.len() > 2 {
+            for name in self.names() {
+                let evaluator = &self.suites[&name];
+                let key_id = match evaluator.token_key_id() {
+                    Some(id) => id,
+                    None => continue,
+                };
+                if key_id[key_id.len()-1] != buf[2] {
+                    continue;
+                }
+                // several keys may share a truncated ID
+                if let Ok(resp) = evaluator.issue_token(buf) {
+                    return Response::from_data(RESPONSE_CONTENT_TYPE, resp);
+                }
+            }
+        }
+        println!("failed to issue token");
+        Response::empty_400()
+    }
+
+    /// Builds the `psi::ServerSet` for `items` with the key of each hosted
+    /// ciphersuite, which is then published to clients
+    pub fn publish_psi_sets(&mut self, items: &[Vec<u8>], truncation: usize) -> Result<(), Error> {
+        self.publish_sets(items, truncation, false)
+    }
+
+    /// Builds the `psi::ServerSet` for `items` for the cardinality-only mode
+    /// with the key of each hosted ciphersuite. From then on, evaluations are
+    /// only processed with shuffled responses, so that clients only learn the
+    /// size of the intersection.
+    pub fn publish_cardinality_sets(&mut self, items: &[Vec<u8>], truncation: usize) -> Result<(), Error> {
+        self.publish_sets(items, truncation, true)
+    }
+
+    fn publish_sets(&mut self, items: &[Vec<u8>], truncation: usize, cardinality: bool) -> Result<(), Error> {
+        let mut sets = HashMap::new();
+        for name in self.names() {
+            let set = self.suites[&name].psi_set(items, truncation, cardinality)?;
+            sets.insert(name, set);
+        }
+        self.psi_sets = sets;
+        self.cardinality_only = cardinality;
+        Ok(())
+    }
+
+    /// Returns the HTTP response carrying the encoded `psi::ServerSet` of the
+    /// ciphersuite `name`, or a 404 if no set is published for it
+    pub fn process_psi_set_request(&self, name: &str) -> Response {
+        match self.psi_sets.get(name) {
+            Some(set) => Response::from_data("application/octet-stream", set.clone()),
+            None => Response::empty_404(),
+        }
+    }
+
+    /// Builds the `credcheck::Dataset` for the compromised credential `hashes`
+    /// with the key of each hosted ciphersuite, whose buckets are then served
+    /// to clients
+    pub fn publish_credentials(&mut self, hashes: &[Vec<u8>], prefix_len: usize) -> Result<(), Error> {
+        let mut datasets = HashMap::new();
+        for name in self.names() {
+            datasets.insert(name.clone(), self.suites[&name].credential_dataset(hashes, prefix_len)?);
+        }
+        self.datasets = datasets;
+        Ok(())
+    }
+
+    /// Returns the hex-encoded bucket of compromised credentials for the
+    /// hex-encoded prefix in the request params, from the dataset of the
+    /// ciphersuite that they specify
+    pub(crate) fn fetch_bucket(&self, params: RequestParams) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+        if !self.suites.contains_key(&params.ciph) {
+            println!("chosen ciphersuite ({}) is not supported", params.ciph);
+            return Err(ErrorType::IncompatibleCiphersuite);
+        }
+        let dataset = match self.datasets.get(&params.ciph) {
+            Some(d) => d,
+            None => return Err(ErrorType::MethodNotFound),
+        };
+        let prefix = match params.data.first().map(hex::decode) {
+            Some(Ok(p)) => p,
+            _ => return Err(ErrorType::InvalidParams),
+        };
+        match dataset.bucket(&prefix) {
+            Ok(bucket) => Ok((vec![hex::encode(bucket.to_bytes())], Vec::new())),
+            Err(_) => Err(ErrorType::InvalidParams),
+        }
+    }
+
+    /// Verifies a redeemed Privacy Pass token with the ciphersuite that holds
+    /// its key, and marks it as spent in the store for that key. Returns
+    /// `false` if the token has already been spent.
+    pub fn redeem_token(&self, token: &Token) -> Result<bool, Error> {
+        for name in self.names() {
+            let evaluator = &self.suites[&name];
+            if evaluator.token_key_id().as_ref() == Some(&token.token_key_id) {
+                let store = self.spent.store(&token.token_key_id)?;
+                return evaluator.redeem_token(token, &*store);
+            }
+        }
+        Err(err_token_key_id())
+    }
+
+    /// Processes an encoded Privacy Pass token that is redeemed, and returns
+    /// the HTTP response. Tokens that are invalid or have already been spent
+    /// are rejected with a 401.
+    pub fn process_token_redemption(&self, buf: &[u8]) -> Response {
+        let accepted = match Token::from_bytes(buf) {
+            Ok(token) => self.redeem_token(&token),
+            Err(e) => Err(e),
+        };
+        match accepted {
+            Ok(true) => Response::text(""),
+            Ok(false) => {
+                println!("token has already been spent");
+                Response::text("").with_status_code(401)
+            },
+            Err(_) => {
+                println!("failed to redeem token");
+                Response::text("").with_status_code(401)
+            },
+        }
+    }
+
+    /// Processes a JSON-RPC request body and returns the HTTP response
+    pub fn process_request(&self, buf: &[u8]) -> Response {
+        match jsonrpc::Request::read(buf) {
+            Ok(req) => {
+                let id = req.id;
+                let res = match req.is_bucket() {
+                    true => self.fetch_bucket(req.params),
+                    false => {
+                        let shuffled = req.is_cardinality();
+                        self.evaluate(req.params, shuffled)
+                    },
+                };
+                match res {
+                    Ok((eles_hex, proof_hex)) => Response::text(jsonrpc::success(eles_hex, proof_hex, id)),
+                    Err(e) => {
+                        println!("failed to process evaluation results");
+                        error_response(e, id)
+                    }
+                }
+            },
+            Err(e) => {
+                println!("failed to read request into buffer");
+                error_response(e, -1)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuiteRegistry;
+    use crate::http::jsonrpc::{ResponseSuccess,ResponseError};
+    use crate::oprf::groups::PrimeOrderGroup;
+    use crate::oprf::ciphersuite::{Ciphersuite,ProofVersion,Supported};
+    use crate::oprf::{Client,Server,Input,Evaluation,PublicKey};
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use crate::oprf::groups::p384::NistPoint;
+    use crate::oprf::proof::Proof;
+    use crate::privacypass::{Issuer,TokenClient,TokenChallenge,TokenResponse,RESPONSE_CONTENT_TYPE};
+    use crate::privacypass::store::EpochStores;
+    use crate::http::directory::KeyValidity;
+    use crate::psi::{ServerSet,DEFAULT_TRUNCATION,cardinality};
+    use sha2::Sha512;
+    use rouille::Response;
+    use std::io::Read;
+
+    fn request<T,H>(ciph: &Ciphersuite<T,H>, n: usize) -> (Vec<u8>, Vec<Input<T>>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let pog = &ciph.pog;
+        let cli = Client::setup(Ciphersuite::new(pog.clone(), false), None).unwrap();
+        let data: Vec<Vec<u8>> = (0..n).map(|i| vec![i as u8; 32]).collect();
+        let inputs = cli.blind(&data);
+        let mut data_hex = Vec::new();
+        for input in &inputs {
+            let mut buf = Vec::new();
+            (pog.serialize)(&input.elem, true, &mut buf);
+            data_hex.push(format!("\"{}\"", hex::encode(buf)));
+        }
+        let req = format!(r#"{{ "jsonrpc": "2.0", "method": "eval", "params": {{ "data": [{}], "ciph": "{}" }}, "id": 1 }}"#,
+                data_hex.join(","), ciph.name).into_bytes();
+        (req, inputs)
+    }
+
+    fn read_body(resp: Response) -> String {
+        let (mut reader, _) = resp.data.into_reader_and_size();
+        let mut body = String::new();
+        reader.read_to_string(&mut body).unwrap();
+        body
+    }
+
+    // checks that the registry response verifies under the registered public
+    // key of the ciphersuite
+    fn check_response<T,H>(registry: &SuiteRegistry, ciph: &Ciphersuite<T,H>, n: usize)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let pog = &ciph.pog;
+        let (req, inputs) = request(ciph, n);
+        let resp = registry.process_request(&req);
+        assert_eq!(resp.status_code, 200);
+        let res: ResponseSuccess = serde_json::from_str(&read_body(resp)).unwrap();
+        assert_eq!(res.result.data.len(), n);
+        let evals: Vec<T> = res.result.data.iter()
+            .map(|z| (pog.deserialize)(&hex::decode(z).unwrap()).unwrap())
+            .collect();
+        let proof = Proof::new(pog, hex::decode(&res.result.proof[0]).unwrap(), hex::decode(&res.result.proof[1]).unwrap()).unwrap();
+        let pk = PublicKey::from_hex(registry.pub_key_hex(&ciph.name).unwrap(), pog).unwrap();
+        let cli = Client::setup(ciph.clone(), Some(pk)).unwrap();
+        let ev = Evaluation{ elems: evals, proof: Some(proof) };
+        assert!(cli.unblind(&inputs, &ev).is_ok());
+    }
+
+    #[test]
+    fn route_on_ciphersuite() {
+        let mut registry = SuiteRegistry::new(5);
+        let oprf = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+        let voprf = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        registry.insert(Server::setup(oprf.clone())).unwrap();
+        registry.insert(Server::setup(voprf.clone())).unwrap();
+        assert_eq!(registry.names(), vec![oprf.name.clone(), voprf.name.clone()]);
+        assert!(registry.pub_key_hex(&oprf.name).is_none());
+        assert!(registry.pub_key_hex(&voprf.name).is_some());
+
+        // OPRF requests are answered without a proof
+        let (req, _) = request(&oprf, 2);
+        let resp = registry.process_request(&req);
+        assert_eq!(resp.status_code, 200);
+        let res: ResponseSuccess = serde_json::from_str(&read_body(resp)).unwrap();
+        assert_eq!(res.result.data.len(), 2);
+        assert_eq!(res.result.proof.len(), 0);
+
+        // VOPRF requests are answered with a valid proof
+        check_response(&registry, &voprf, 3);
+    }
+
+    #[test]
+    fn p384_and_ristretto() {
+        let mut registry = SuiteRegistry::new(5);
+        let p384 = Ciphersuite::new(PrimeOrderGroup::<NistPoint,Sha512>::p384(), true);
+        let ristretto = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        registry.insert(Server::setup(p384.clone())).unwrap();
+        registry.insert(Server::setup(ristretto.clone())).unwrap();
+        check_response(&registry, &p384, 2);
+        check_response(&registry, &ristretto, 2);
+    }
+
+    #[test]
+    fn reject_duplicate_ciphersuite() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        registry.insert(Server::setup(ciph.clone())).unwrap();
+        assert!(registry.insert(Server::setup(ciph)).is_err());
+    }
+
+    #[test]
+    fn reject_unknown_ciphersuite() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        registry.insert(Server::setup(ciph)).unwrap();
+        let other = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+        let (req, _) = request(&other, 1);
+        let resp = registry.process_request(&req);
+        assert_eq!(resp.status_code, 400);
+        let err: ResponseError = serde_json::from_str(&read_body(resp)).unwrap();
+        assert_eq!(err.error.code, -32000);
+    }
+
+    #[test]
+    fn reject_too_many_evals() {
+        let mut registry = SuiteRegistry::new(2);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+        registry.insert(Server::setup(ciph.clone())).unwrap();
+        let (req, _) = request(&ciph, 3);
+        let resp = registry.process_request(&req);
+        let err: ResponseError = serde_json::from_str(&read_body(resp)).unwrap();
+        assert_eq!(err.error.code, -32602);
+    }
+
+    #[test]
+    fn issue_token() {
+        let mut registry = SuiteRegistry::new(5);
+        // tokens are not issued with the draft-02 ciphersuites
+        let draft = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        let voprf = Ciphersuite::new(PrimeOrderGroup::p384_sha384(), true);
+        let srv = Server::setup(voprf.clone());
+        registry.insert(Server::setup(draft)).unwrap();
+        registry.insert(srv.clone()).unwrap();
+        assert_eq!(registry.token_key_ids().len(), 1);
+
+        let issuer = Issuer::new(srv).unwrap();
+        let cli = TokenClient::new(voprf.clone(), issuer.pub_key()).unwrap();
+        let challenge = TokenChallenge::new("issuer.example", &[], &[]).unwrap();
+        let (req, state) = cli.request(&challenge);
+        let resp = registry.process_token_request(&req.to_bytes());
+        assert_eq!(resp.status_code, 200);
+        assert_eq!(resp.headers[0].1, RESPONSE_CONTENT_TYPE);
+        let (mut reader, _) = resp.data.into_reader_and_size();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        let resp = TokenResponse::from_bytes(&voprf.pog, &body).unwrap();
+        let token = cli.finalize(&state, &resp).unwrap();
+        assert!(issuer.verify(&token));
+
+        // requests for unknown keys or malformed requests are rejected
+        let mut unknown = req.clone();
+        unknown.truncated_token_key_id ^= 1;
+        assert_eq!(registry.process_token_request(&unknown.to_bytes()).status_code, 400);
+        assert_eq!(registry.process_token_request(&req.to_bytes()[1..]).status_code, 400);
+        assert_eq!(registry.process_token_request(&[]).status_code, 400);
+    }
+
+    #[test]
+    fn directory() {
+        let mut registry = SuiteRegistry::new(5);
+        let oprf = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+        let voprf = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        let srv = Server::setup(voprf.clone());
+        registry.insert(Server::setup(oprf.clone())).unwrap();
+        registry.insert(srv.clone()).unwrap();
+        let validity = KeyValidity{ not_before: 10, not_after: Some(20) };
+        registry.set_validity(&voprf.name, validity).unwrap();
+        assert!(registry.set_validity("unknown", validity).is_err());
+
+        let dir = registry.directory();
+        assert_eq!(dir.ciphersuites.len(), 2);
+        let entry = dir.get(&oprf.name).unwrap();
+        assert!(!entry.verifiable && entry.keys.is_empty());
+        let entry = dir.get(&voprf.name).unwrap();
+        assert!(entry.verifiable);
+        assert_eq!(entry.keys.len(), 1);
+        let pk = srv.key.pub_key(&voprf.pog);
+        assert_eq!(entry.keys[0].pub_key, pk.as_hex(&voprf.pog));
+        assert_eq!(entry.keys[0].key_id, hex::encode(crate::privacypass::token_key_id(&voprf.pog, &pk)));
+        assert_eq!(entry.keys[0].validity, validity);
+        assert_eq!(entry.proofs().unwrap(), (ProofVersion::V1, Vec::new()));
+
+        // the proof version and context are published
+        let mut registry = SuiteRegistry::new(5);
+        registry.insert(Server::setup(voprf.with_proof_version(ProofVersion::V1).with_context(b"context"))).unwrap();
+        let dir = registry.directory();
+        assert_eq!(dir.ciphersuites[0].proofs().unwrap(), (ProofVersion::V1, b"context".to_vec()));
+    }
+
+    #[test]
+    fn publish_psi_sets() {
+        let mut registry = SuiteRegistry::new(5);
+        let voprf = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        let srv = Server::setup(voprf.clone());
+        registry.insert(srv.clone()).unwrap();
+        let items = vec![b"alice".to_vec(), b"bob".to_vec()];
+        assert_eq!(registry.process_psi_set_request(&voprf.name).status_code, 404);
+        registry.publish_psi_sets(&items, DEFAULT_TRUNCATION).unwrap();
+
+        let resp = registry.process_psi_set_request(&voprf.name);
+        assert_eq!(resp.status_code, 200);
+        let (mut reader, _) = resp.data.into_reader_and_size();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        assert_eq!(ServerSet::from_bytes(&body).unwrap(), ServerSet::build(&srv, &items, DEFAULT_TRUNCATION).unwrap());
+        assert_eq!(registry.process_psi_set_request("unknown").status_code, 404);
+    }
+
+    #[test]
+    fn cardinality_only() {
+        let mut registry = SuiteRegistry::new(5);
+        let oprf = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+        let srv = Server::setup(oprf.clone());
+        registry.insert(srv.clone()).unwrap();
+        let items: Vec<Vec<u8>> = (0..3).map(|i| vec![i as u8; 32]).collect();
+        registry.publish_cardinality_sets(&items, DEFAULT_TRUNCATION).unwrap();
+
+        // ordered evaluations are refused
+        let (req, _) = request(&oprf, 2);
+        let resp = registry.process_request(&req);
+        assert_eq!(resp.status_code, 400);
+        let res: ResponseError = serde_json::from_str(&read_body(resp)).unwrap();
+        assert_eq!(res.error.code, -32601);
+
+        // shuffled evaluations give the size of the intersection
+        let cli = Client::setup(oprf.clone(), None).unwrap();
+        let data: Vec<Vec<u8>> = (1..5).map(|i| vec![i as u8; 32]).collect();
+        let inputs = cli.blind_shared(&data);
+        let data_hex: Vec<String> = inputs.iter().map(|input| {
+            let mut buf = Vec::new();
+            (oprf.pog.serialize)(&input.elem, true, &mut buf);
+            format!("\"{}\"", hex::encode(buf))
+        }).collect();
+        let req = format!(r#"{{ "jsonrpc": "2.0", "method": "eval_cardinality", "params": {{ "data": [{}], "ciph": "{}" }}, "id": 1 }}"#,
+                data_hex.join(","), oprf.name).into_bytes();
+        let resp = registry.process_request(&req);
+        assert_eq!(resp.status_code, 200);
+        let res: ResponseSuccess = serde_json::from_str(&read_body(resp)).unwrap();
+        assert!(res.result.proof.is_empty());
+        let elems = res.result.data.iter().map(|z| (oprf.pog.deserialize)(&hex::decode(z).unwrap()).unwrap()).collect();
+        let eval = Evaluation{ elems: elems, proof: None };
+        let (mut reader, _) = registry.process_psi_set_request(&oprf.name).data.into_reader_and_size();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        let set = ServerSet::from_bytes(&body).unwrap();
+        assert_eq!(cardinality(&cli, &inputs, &eval, &set).unwrap(), 2);
+
+        // verifiable ciphersuites cannot be used in the cardinality-only mode
+        let mut registry = SuiteRegistry::new(5);
+        registry.insert(Server::setup(Ciphersuite::new(oprf.pog.clone(), true))).unwrap();
+        assert!(registry.publish_cardinality_sets(&items, DEFAULT_TRUNCATION).is_err());
+    }
+
+    #[test]
+    fn redeem_token() {
+        let mut registry = SuiteRegistry::new(5);
+        let voprf = Ciphersuite::new(PrimeOrderGroup::p384_sha384(), true);
+        let srv = Server::setup(voprf.clone());
+        registry.insert(srv.clone()).unwrap();
+
+        let issuer = Issuer::new(srv).unwrap();
+        let cli = TokenClient::new(voprf.clone(), issuer.pub_key()).unwrap();
+        let challenge = TokenChallenge::new("issuer.example", &[], &[]).unwrap();
+        let (req, state) = cli.request(&challenge);
+        let token = cli.finalize(&state, &issuer.issue(&req).unwrap()).unwrap();
+        let buf = token.to_bytes();
+        assert_eq!(registry.process_token_redemption(&buf).status_code, 200);
+        assert_eq!(registry.process_token_redemption(&buf).status_code, 401);
+        assert_eq!(registry.process_token_redemption(&buf[1..]).status_code, 401);
+
+        // tokens for unknown keys are rejected
+        let other = Issuer::new(Server::setup(voprf.clone())).unwrap();
+        let other_cli = TokenClient::new(voprf, other.pub_key()).unwrap();
+        let (other_req, other_state) = other_cli.request(&challenge);
+        let other_token = other_cli.finalize(&other_state, &other.issue(&other_req).unwrap()).unwrap();
+        assert!(registry.redeem_token(&other_token).is_err());
+
+        // stores of epochs whose keys are not hosted are purged
+        let stores = EpochStores::in_memory();
+        stores.spend(other.token_key_id(), b"token").unwrap();
+        registry.set_spend_stores(stores).unwrap();
+        assert_eq!(registry.spent.epochs().unwrap(), Vec::<Vec<u8>>::new());
+        assert_eq!(registry.process_token_redemption(&buf).status_code, 200);
+        assert_eq!(registry.spent.epochs().unwrap(), registry.token_key_ids());
+    }
+}
@@ -1,8 +1,10 @@
 //! The server module wraps the functionality required to run a HTTP server for
 //! terminating (V)OPRF connections. The server is compatible with the go client
 //! at https://github.com/alxdavids/voprf-poc/go. The supported ciphersuites
-//! are: VOPRF-ristretto255-HKDF-SHA512-ELL2-RO (experimental) and
-//! VOPRF-P384-HKDF-SHA512-SSWU-RO
+//! are: VOPRF-ristretto255-HKDF-SHA512-ELL2-RO (experimental),
+//! VOPRF-P384-HKDF-SHA512-SSWU-RO and VOPRF-P384-SHA384 (RFC 9497). A single
+//! server can host several ciphersuites at once, requests are routed on the
+//! ciphersuite that they specify.
 //!
 //! # Example commands
 //!
@@ -15,7 +17,39 @@
 //!     `cargo run -- --group=P384 --mode=server --max_evals=10 --test=1`
 //! * running with the ristretto255 ciphersuite just requires changing `group`
 //!   to `ristretto255`
-use std::io::{Read,Error};
+//! * run P384 and ristretto255 VOPRFs side-by-side (each with its own key):
+//!     `cargo run -- --group=P384,ristretto255 --mode=server --verifiable`
+//! * the server also issues Privacy Pass tokens (see the `privacypass` module)
+//!   with the VOPRF-P384-SHA384 ciphersuite, via POST requests to
+//!   `/token-request`:
+//!     `cargo run -- --group=P384-SHA384 --mode=server --verifiable`
+//! * tokens are redeemed via requests to `/token-redeem`, carrying the token
+//!   in an `Authorization: PrivateToken token="..."` header. Each token is
+//!   accepted at most once: spent tokens are recorded in memory, or in a
+//!   directory with `--spend_store`:
+//!     `cargo run -- --group=P384-SHA384 --mode=server --verifiable --spend_store=spent`
+//! * the keys of all hosted ciphersuites are published (see the `directory`
+//!   module) via GET requests to `/.well-known/voprf-directory`
+//! * publish the outputs for the items of a local file (one per line) for
+//!   private set intersection (see the `psi` module), via GET requests to
+//!   `/psi-set/<ciphersuite>`:
+//!     `cargo run -- --group=ristretto255 --mode=server --psi=server-set.txt`
+//! * with `--psi_cardinality`, the sets are published for the cardinality-only
+//!   mode, and the server only answers `eval_cardinality` requests, with the
+//!   evaluations in a random order and without proofs, so this requires an
+//!   OPRF ciphersuite (see the `psi` module)
+//! * serve the buckets of a dataset of compromised credentials (see the
+//!   `credcheck` module) built from a local file, with one `username:password`
+//!   (or password) on each line, via `bucket` requests:
+//!     `cargo run -- --group=ristretto255 --mode=server --verifiable --credentials=leaked.txt`
+//! * run a mirror that republishes the key directory of a server (or a
+//!   directory stored in a file), which clients can use as an independent
+//!   source of keys (see the `oprf::consistency` module):
+//!     `cargo run -- --mode=mirror --port=3002 --upstream=http://127.0.0.1:3001/.well-known/voprf-directory`
+//! * run a multi-tenant server with the tenants configured in `tenants.json`
+//!   (see the `tenant` module for the file format):
+//!     `cargo run -- --mode=server --tenants=tenants.json`
+use std::io::{Error,Read};
 use std::marker::{Send,Sync};
 use std::fs;
 
@@ -24,26 +58,41 @@ use rouille::{Response,ResponseBody};
 
 use super::jsonrpc;
 use jsonrpc::ErrorType;
+use super::tenant::Tenants;
+use super::directory::{Directory,DIRECTORY_PATH};
+use super::registry::{SuiteRegistry,Evaluator,decode_elems,encode_evaluation,check_proof};
 use crate::oprf;
-use oprf::ciphersuite::{Ciphersuite,Supported};
+use oprf::ciphersuite::{Ciphersuite,ProofVersion,Supported};
+use oprf::any::{AnyCiphersuite,CiphersuiteVisitor,SUPPORTED_GROUPS};
 use oprf::groups::PrimeOrderGroup;
-use oprf::Evaluation;
-use oprf::groups::p384::NistPoint;
-use curve25519_dalek::ristretto::RistrettoPoint;
+use crate::privacypass::REQUEST_CONTENT_TYPE;
+use crate::privacypass::Token;
+use crate::privacypass::store::{EpochStores,SpendStore};
+use crate::psi;
+use crate::credcheck;
+use credcheck::Dataset;
 
-use sha2::Sha512;
 use num::BigInt;
 use num_bigint::Sign;
 use serde::Deserialize;
 
+/// The URL path of the Privacy Pass token issuance endpoint
+pub const ISSUANCE_PATH: &str = "/token-request";
+/// The URL path of the Privacy Pass token redemption endpoint
+pub const REDEMPTION_PATH: &str = "/token-redeem";
+/// The authentication scheme of the `Authorization` header that carries a
+/// redeemed token
+pub const AUTH_SCHEME: &str = "PrivateToken";
+/// The URL path prefix of the PSI set endpoint, which is followed by the name
+/// of the ciphersuite
+pub const PSI_SET_PATH: &str = "/psi-set/";
+
 /// The `Config` struct holds the necessary information for running the
-/// (V)OPRF functionality as a HTTP server.
+/// (V)OPRF functionality for a single ciphersuite as part of a HTTP server.
 #[derive(Clone)]
 pub struct Config<T,H>
         where T: Clone, H: Clone, T: Send + Sync {
     oprf_srv: oprf::Server<T,H>,
-    host: String,
-    port: String,
     max_evals: u16,
     tv: Option<TestVector>
 }
@@ -53,8 +102,11 @@ impl<T,H> Config<T,H>
         + digest::Input + digest::BlockInput + digest::FixedOutput
         + digest::Reset + Clone {
     /// initialises the server config
-    fn init(pog: PrimeOrderGroup<T,H>, host: String, port: String, max_evals: u16, verifiable: bool, test_idx: i16) -> Self {
-        let ciph = Ciphersuite::new(pog, verifiable);
+    fn init(mut ciph: Ciphersuite<T,H>, max_evals: u16, test_idx: i16) -> Self {
+        if test_idx != -1 {
+            // test vectors use the unbound proofs of draft-irtf-cfrg-voprf-02
+            ciph = ciph.with_proof_version(ProofVersion::Legacy);
+        }
         let name = &ciph.name;
         let mut oprf_srv = oprf::Server::setup(ciph.clone());
         if max_evals > 100 {
@@ -79,183 +131,375 @@ impl<T,H> Config<T,H>
 
         Self {
             oprf_srv: oprf_srv,
-            host: host,
-            port: port,
             max_evals: max_evals,
             tv: tv,
         }
     }
 }
 
-impl Config<NistPoint,Sha512> {
-    fn p384(host: String, port: String, max_evals: u16, verifiable: bool, test_idx: i16) -> Self {
-        let pog = PrimeOrderGroup::p384();
-        Config::init(pog, host, port, max_evals, verifiable, test_idx)
+impl<T,H> Config<T,H>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+        H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone + 'static {
+    // converts the config into a type-erased evaluator. If test vectors are
+    // used, then the evaluator generates DLEQ proofs with the fixed scalar
+    // from the test vector.
+    fn into_evaluator(self) -> Box<dyn Evaluator> {
+        match self.tv {
+            None => Box::new(self.oprf_srv),
+            Some(tv) => Box::new(FixedEvaluator{
+                oprf_srv: self.oprf_srv,
+                dleq_scalar: hex::decode(&tv.dleq_scalar).unwrap(),
+            }),
+        }
     }
 }
 
-impl Config<RistrettoPoint,Sha512> {
-    fn ristretto_255(host: String, port: String, max_evals: u16, verifiable: bool, test_idx: i16) -> Self {
-        let pog = PrimeOrderGroup::ristretto_255();
-        Config::init(pog, host, port, max_evals, verifiable, test_idx)
+// builds the evaluator for a ciphersuite that is chosen at runtime
+struct ConfigBuilder {
+    max_evals: u16,
+    test_idx: i16,
+}
+
+impl CiphersuiteVisitor for ConfigBuilder {
+    type Output = Box<dyn Evaluator>;
+
+    fn visit<T,H>(self, ciph: Ciphersuite<T,H>) -> Box<dyn Evaluator>
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static {
+        Config::init(ciph, self.max_evals, self.test_idx).into_evaluator()
     }
 }
 
-/// Starts the HTTP server for processing VOPRF requests
-pub fn start_server(group_name: String, host: String, port: String, max_evals: u16, verifiable: bool, test_index: i16) {
-    match group_name.as_str() {
-        "P384" => {
-            let cfg = Config::p384(host, port, max_evals, verifiable, test_index);
-            run(cfg);
-        },
-        "ristretto255" => {
-            let cfg = Config::ristretto_255(host, port, max_evals, verifiable, test_index);
-            run(cfg);
-        },
-        _ => panic!("Unsupported group requested, supported groups are: 'P384', 'ristretto255'")
+/// The `ServerOptions` struct holds the options of the HTTP server (see
+/// `start_server`)
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    /// A comma-separated list of groups (e.g. `P384,ristretto255`): the server
+    /// hosts a ciphersuite with its own key for each of the groups, and routes
+    /// each request on the ciphersuite that it specifies
+    pub group_names: String,
+    /// The host that the server listens at
+    pub host: String,
+    /// The port that the server listens at
+    pub port: String,
+    /// The maximum number of inputs that are evaluated in a single request
+    pub max_evals: u16,
+    /// Whether the ciphersuites are verifiable
+    pub verifiable: bool,
+    /// The index of the test vector whose key is used, or -1 for a fresh key
+    pub test_index: i16,
+    /// The directory that redeemed tokens are recorded in, if they are not
+    /// recorded in memory
+    pub spend_dir: Option<String>,
+    /// The path of a file of items that PSI sets are published for
+    pub psi_path: Option<String>,
+    /// Whether the PSI sets are published for the cardinality-only mode
+    pub psi_cardinality: bool,
+    /// The path of a file of compromised credentials whose dataset is served
+    pub credentials_path: Option<String>,
+    /// The version of the DLEQ proofs, which is published in the key directory
+    pub proof_version: ProofVersion,
+    /// The context that the DLEQ proofs are bound to, which is published in
+    /// the key directory
+    pub context: Vec<u8>,
+}
+
+/// Starts the HTTP server for processing VOPRF requests with the options in
+/// `opts`
+pub fn start_server(opts: ServerOptions) {
+    let mut registry = SuiteRegistry::new(opts.max_evals);
+    for group_name in opts.group_names.split(',') {
+        let ciph = match AnyCiphersuite::from_group(group_name.trim(), opts.verifiable) {
+            Ok(c) => c.with_proofs(opts.proof_version, &opts.context),
+            Err(_) => panic!("Unsupported group requested, supported groups are: {:?}", SUPPORTED_GROUPS)
+        };
+        let evaluator = ciph.visit(ConfigBuilder{
+            max_evals: opts.max_evals,
+            test_idx: opts.test_index,
+        });
+        if let Err(e) = registry.insert_evaluator(evaluator) {
+            panic!("Failed to host group {}: {}", group_name, e);
+        }
     }
+    if let Some(dir) = opts.spend_dir {
+        let stores = match EpochStores::in_dir(&dir) {
+            Ok(s) => s,
+            Err(e) => panic!("Failed to open spend store at {}: {}", dir, e),
+        };
+        if let Err(e) = registry.set_spend_stores(stores) {
+            panic!("Failed to purge expired spend stores: {}", e);
+        }
+    }
+    if let Some(path) = opts.psi_path {
+        let items = match psi::read_items(&path) {
+            Ok(i) => i,
+            Err(e) => panic!("Failed to read PSI items from {}: {}", path, e),
+        };
+        let published = match opts.psi_cardinality {
+            true => registry.publish_cardinality_sets(&items, psi::DEFAULT_TRUNCATION),
+            false => registry.publish_psi_sets(&items, psi::DEFAULT_TRUNCATION),
+        };
+        if let Err(e) = published {
+            panic!("Failed to build PSI sets: {}", e);
+        }
+        println!("Publishing PSI sets for {} items", items.len());
+    }
+    if let Some(path) = opts.credentials_path {
+        let hashes = match credcheck::read_credentials(&path) {
+            Ok(h) => h,
+            Err(e) => panic!("Failed to read credentials from {}: {}", path, e),
+        };
+        if let Err(e) = registry.publish_credentials(&hashes, credcheck::DEFAULT_PREFIX_LEN) {
+            panic!("Failed to build credential datasets: {}", e);
+        }
+        println!("Serving {} compromised credentials", hashes.len());
+    }
+    run(registry, opts.host, opts.port);
 }
 
 /// Runs the `rouille` HTTP server for processing JSONRPC requests from (V)OPRF
-/// clients.
-///
-/// Tried to include this function as part of the implementation of
-/// `Config` but there were problems with lifetimes when trying to call
-/// functions inside of the callback.
-fn run<T,H>(cfg: Config<T,H>)
-        where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
-        H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
-        + digest::Reset + Clone + 'static {
-    let ciph = &cfg.oprf_srv.ciph;
-    println!("Server listening at {}:{} and running with ciphersuite {}", cfg.host, cfg.port, ciph.name);
-    if ciph.verifiable {
-        // output public key
-        println!("Public key: {}", cfg.oprf_srv.key.pub_key(&ciph.pog).as_hex(&ciph.pog));
-    }
-    rouille::start_server(format!("{}:{}", cfg.host, cfg.port), move |request| {
-        let data = request.data();
-        match data {
-            Some(mut body) => {
-                let mut buf = Vec::new();
-                match body.read_to_end(&mut buf) {
-                    Ok(_) => process_request(&cfg, &buf),
-                    Err(_) => {
-                        println!("failed to process request");
-                        let mut err_resp = Response::empty_400();
-                        err_resp.data = ResponseBody::from_string(jsonrpc::error(ErrorType::ParseError, -1));
-                        err_resp
-                    }
-                }
-            },
-            None => {
-                println!("request data could not be read");
-                let mut err_resp = Response::empty_400();
-                err_resp.data = ResponseBody::from_string(jsonrpc::error(ErrorType::ParseError, -1));
-                err_resp
-            }
+/// clients, for each of the ciphersuites hosted in `registry`.
+fn run(registry: SuiteRegistry, host: String, port: String) {
+    println!("Server listening at {}:{}", host, port);
+    for name in registry.names() {
+        println!("Running with ciphersuite {}", name);
+        if let Some(pk) = registry.pub_key_hex(&name) {
+            // output public key
+            println!("Public key: {}", pk);
         }
+    }
+    rouille::start_server(format!("{}:{}", host, port), move |request| {
+        handle(&registry, request)
     });
 }
 
-fn process_request<T,H>(cfg: &Config<T,H>, buf: &[u8]) -> Response
-        where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync,
-        H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
-        + digest::Reset + Clone {
-    match jsonrpc::Request::read(buf) {
-        Ok(req) => {
-            let chosen_ciph = req.params.ciph;
-            let srv = &cfg.oprf_srv;
-            let srv_ciph = &srv.ciph;
-            let id = req.id;
-            // check that ciphersuite name matches
-            if chosen_ciph != srv_ciph.name {
-                println!("chosen ciphersuite ({}) is not the same as expected ({})", chosen_ciph, srv_ciph.name);
-                let mut err_resp = Response::empty_400();
-                err_resp.data = ResponseBody::from_string(jsonrpc::error(ErrorType::IncompatibleCiphersuite, id));
-                return err_resp;
+/// Routes a HTTP request to the endpoint that it is sent to, and returns the
+/// response of the server hosting the ciphersuites in `registry`
+pub(crate) fn handle(registry: &SuiteRegistry, request: &rouille::Request) -> Response {
+    if request.url() == ISSUANCE_PATH {
+        return process_issuance(registry, request);
+    }
+    if request.url() == REDEMPTION_PATH {
+        return process_redemption(registry, request);
+    }
+    if request.url() == DIRECTORY_PATH {
+        return process_directory(registry, request);
+    }
+    if request.url().starts_with(PSI_SET_PATH) {
+        return process_psi_set(registry, request);
+    }
+    match read_body(request) {
+        Ok(buf) => registry.process_request(&buf),
+        Err(err_resp) => err_resp,
+    }
+}
+
+/// Processes a HTTP request sent to the Privacy Pass issuance endpoint, which
+/// must be a POST request carrying an encoded `TokenRequest`
+pub(crate) fn process_issuance(registry: &SuiteRegistry, request: &rouille::Request) -> Response {
+    if request.method() != "POST" {
+        return Response::text("").with_status_code(405);
+    }
+    if request.header("Content-Type") != Some(REQUEST_CONTENT_TYPE) {
+        return Response::text("").with_status_code(415);
+    }
+    match read_body(request) {
+        Ok(buf) => registry.process_token_request(&buf),
+        Err(_) => Response::empty_400(),
+    }
+}
+
+/// Starts a HTTP server that mirrors the key directory at `upstream` (a URL,
+/// or a local file), which is loaded again for each request.
+pub fn start_mirror(upstream: String, host: String, port: String) {
+    println!("Mirror listening at {}:{} for {}", host, port, upstream);
+    rouille::start_server(format!("{}:{}", host, port), move |request| {
+        process_mirror(&upstream, request)
+    });
+}
+
+// processes a request sent to a mirror of the key directory at `upstream`
+fn process_mirror(upstream: &str, request: &rouille::Request) -> Response {
+    if request.url() != DIRECTORY_PATH {
+        return Response::empty_404();
+    }
+    if request.method() != "GET" {
+        return Response::text("").with_status_code(405);
+    }
+    match Directory::load(upstream) {
+        Ok(dir) => Response::from_data("application/json", dir.to_json()),
+        Err(e) => {
+            println!("failed to load directory from {}: {}", upstream, e);
+            Response::text("").with_status_code(502)
+        }
+    }
+}
+
+/// Processes a HTTP request for the key directory, which must be a GET request
+pub(crate) fn process_directory(registry: &SuiteRegistry, request: &rouille::Request) -> Response {
+    if request.method() != "GET" {
+        return Response::text("").with_status_code(405);
+    }
+    Response::from_data("application/json", registry.directory().to_json())
+}
+
+/// Processes a HTTP request for the PSI set of the ciphersuite named in the URL
+/// path, which must be a GET request
+pub(crate) fn process_psi_set(registry: &SuiteRegistry, request: &rouille::Request) -> Response {
+    if request.method() != "GET" {
+        return Response::text("").with_status_code(405);
+    }
+    registry.process_psi_set_request(&request.url()[PSI_SET_PATH.len()..])
+}
+
+/// Processes a HTTP request sent to the Privacy Pass redemption endpoint, which
+/// must carry the token in the `Authorization` header
+pub(crate) fn process_redemption(registry: &SuiteRegistry, request: &rouille::Request) -> Response {
+    match request.header("Authorization").and_then(parse_authorization) {
+        Some(buf) => registry.process_token_redemption(&buf),
+        None => Response::text("").with_status_code(401),
+    }
+}
+
+// parses the base64url-encoded token from an authorization header of the form
+// `PrivateToken token="..."`
+fn parse_authorization(header: &str) -> Option<Vec<u8>> {
+    let mut parts = header.trim().splitn(2, char::is_whitespace);
+    if parts.next()? != AUTH_SCHEME {
+        return None;
+    }
+    for param in parts.next()?.split(',') {
+        let mut kv = param.splitn(2, '=');
+        if kv.next()?.trim() != "token" {
+            continue;
+        }
+        let value = kv.next()?.trim().trim_matches('"').trim_end_matches('=');
+        return base64::decode_config(value, base64::URL_SAFE_NO_PAD).ok();
+    }
+    None
+}
+
+/// Starts the HTTP server for processing VOPRF requests on behalf of each of
+/// the tenants configured in the file at `tenants_path`.
+pub fn start_tenant_server(tenants_path: String, host: String, port: String) {
+    let tenants = match Tenants::from_file(&tenants_path) {
+        Ok(t) => t,
+        Err(e) => panic!("Failed to load tenants from {}: {}", tenants_path, e),
+    };
+    println!("Server listening at {}:{} for tenants:", host, port);
+    for name in tenants.names() {
+        let suites = tenants.get(&name).unwrap().suites();
+        for ciph_name in suites.names() {
+            println!("* {} running with ciphersuite {}", name, ciph_name);
+            if let Some(pk) = suites.pub_key_hex(&ciph_name) {
+                println!("  Public key: {}", pk);
             }
+        }
+    }
+    rouille::start_server(format!("{}:{}", host, port), move |request| {
+        match read_body(request) {
+            Ok(buf) => tenants.process_request(&request.url(), &buf),
+            Err(err_resp) => err_resp,
+        }
+    });
+}
 
-            // attempt to deserialize group elements and process PRF evaluation
-            let data = req.params.data;
-            let pog = &srv_ciph.pog;
-            let decoded: Result<Vec<Vec<u8>>, hex::FromHexError> = data.into_iter()
-                .map(|s| hex::decode(s))
-                .collect();
-            let res: Result<Evaluation<T>, ErrorType> = match decoded {
-                Ok(v) => {
-                    let deser_eles: Result<Vec<T>, Error> = v.into_iter()
-                                            .map(|bytes| (pog.deserialize)(&bytes))
-                                            .collect();
-                    match deser_eles {
-                        // evaluate PRF
-                        Ok(eles) => match &cfg.tv {
-                            None => Ok(srv.eval(&eles)),
-                            // if we're testing then we should evaluate with a
-                            // fixed parameter for generating the DLEQ proof
-                            Some(tv) => Ok(srv.fixed_eval(&eles, &hex::decode(&tv.dleq_scalar).unwrap())),
-                        }
-                        Err(_) => Err(ErrorType::Deserialization)
-                    }
-                },
-                Err(_) => Err(ErrorType::InvalidParams)
-            };
-
-            // return evaluation results
-            match res {
-                Ok(ev) => {
-                    // encode group elements
-                    let mut buf = Vec::new();
-                    let mut eles_hex = Vec::new();
-                    for p in ev.elems {
-                        (pog.serialize)(&p, true, &mut buf);
-                        eles_hex.push(hex::encode(&buf));
-                    }
-
-                    // recover proof
-                    let mut proof_hex = Vec::new();
-                    if let Some(proof) = ev.proof {
-                        proof_hex.push(hex::encode(&proof[0]));
-                        proof_hex.push(hex::encode(&proof[1]));
-                        // if we're testing then we should output the DLEQ value
-                        // t that is used
-                        if let Some(_) = &cfg.tv {
-                            let c = BigInt::from_bytes_be(Sign::Plus, &proof[0]);
-                            let s = BigInt::from_bytes_be(Sign::Plus, &proof[1]);
-                            let k = BigInt::parse_bytes(&srv.key.as_hex().as_bytes(), 16).unwrap();
-                            let (sgn, t) = (s+(c*k)).to_bytes_be();
-                            let t_red = (pog.reduce_scalar)(&t, sgn == Sign::Plus);
-                            println!("dleq scalar: {}", hex::encode(&t_red))
-                        }
-                    }
-                    if proof_hex.len() != 2 && srv_ciph.verifiable {
-                        // if the ciphersuite is verifiable, then we should have
-                        // proof elements
-                        println!("ciphersuite should be verifiable");
-                        let mut err_resp = Response::empty_400();
-                        err_resp.data = ResponseBody::from_string(jsonrpc::error(ErrorType::InternalError, id));
-                        return err_resp;
-                    }
-
-                    // return successful evaluation
-                    Response::text(jsonrpc::success(eles_hex, proof_hex, id))
-                },
-                Err(e) => {
-                    println!("failed to process evaluation results");
-                    let mut err_resp = Response::empty_400();
-                    err_resp.data = ResponseBody::from_string(jsonrpc::error(e, id));
-                    err_resp
+// reads the body of a HTTP request, or returns the error response that should
+// be sent to the client
+fn read_body(request: &rouille::Request) -> Result<Vec<u8>, Response> {
+    match request.data() {
+        Some(mut body) => {
+            let mut buf = Vec::new();
+            match body.read_to_end(&mut buf) {
+                Ok(_) => Ok(buf),
+                Err(_) => {
+                    println!("failed to process request");
+                    Err(error_response(ErrorType::ParseError, -1))
                 }
             }
         },
-        Err(e) => {
-            println!("failed to read request into buffer");
-            let mut err_resp = Response::empty_400();
-            err_resp.data = ResponseBody::from_string(jsonrpc::error(e, -1));
-            err_resp
+        None => {
+            println!("request data could not be read");
+            Err(error_response(ErrorType::ParseError, -1))
         }
     }
 }
 
+/// Returns a HTTP 400 response wrapping the JSON-RPC error of type `err`
+pub(crate) fn error_response(err: ErrorType, id: i16) -> Response {
+    let mut err_resp = Response::empty_400();
+    err_resp.data = ResponseBody::from_string(jsonrpc::error(err, id));
+    err_resp
+}
+
+// evaluates the (V)OPRF with the fixed DLEQ scalar taken from a test vector
+struct FixedEvaluator<T,H>
+        where T: Clone, H: Clone {
+    oprf_srv: oprf::Server<T,H>,
+    dleq_scalar: Vec<u8>,
+}
+
+impl<T,H> Evaluator for FixedEvaluator<T,H>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync,
+        H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    fn ciph_name(&self) -> String {
+        self.oprf_srv.ciph_name()
+    }
+
+    fn pub_key_hex(&self) -> Option<String> {
+        self.oprf_srv.pub_key_hex()
+    }
+
+    fn proofs(&self) -> (ProofVersion, Vec<u8>) {
+        self.oprf_srv.proofs()
+    }
+
+    fn eval_hex(&self, data: Vec<String>) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+        let srv = &self.oprf_srv;
+        let pog = &srv.ciph.pog;
+        let eles = decode_elems(pog, data)?;
+        let ev = srv.fixed_eval(&eles, &self.dleq_scalar).map_err(|_| ErrorType::InternalError)?;
+
+        // output the DLEQ value t that is used
+        if let Some(proof) = &ev.proof {
+            let c = BigInt::from_bytes_be(Sign::Plus, proof.c());
+            let s = BigInt::from_bytes_be(Sign::Plus, proof.s());
+            let k = BigInt::parse_bytes(&srv.key.as_hex().as_bytes(), 16).unwrap();
+            let (sgn, t) = (s+(c*k)).to_bytes_be();
+            let t_red = (pog.reduce_scalar)(&t, sgn == Sign::Plus);
+            println!("dleq scalar: {}", hex::encode(&t_red))
+        }
+        check_proof(srv.ciph.verifiable, encode_evaluation(pog, &ev))
+    }
+
+    fn eval_hex_shuffled(&self, data: Vec<String>) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+        self.oprf_srv.eval_hex_shuffled(data)
+    }
+
+    fn token_key_id(&self) -> Option<Vec<u8>> {
+        self.oprf_srv.token_key_id()
+    }
+
+    fn issue_token(&self, req: &[u8]) -> Result<Vec<u8>, Error> {
+        self.oprf_srv.issue_token(req)
+    }
+
+    fn redeem_token(&self, token: &Token, store: &dyn SpendStore) -> Result<bool, Error> {
+        self.oprf_srv.redeem_token(token, store)
+    }
+
+    fn psi_set(&self, items: &[Vec<u8>], truncation: usize, cardinality: bool) -> Result<Vec<u8>, Error> {
+        self.oprf_srv.psi_set(items, truncation, cardinality)
+    }
+
+    fn credential_dataset(&self, hashes: &[Vec<u8>], prefix_len: usize) -> Result<Dataset, Error> {
+        self.oprf_srv.credential_dataset(hashes, prefix_len)
+    }
+}
+
 #[derive(Clone, Deserialize, Debug, PartialEq)]
 struct TestVector {
     key: String,
@@ -265,7 +509,15 @@ struct TestVector {
 
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{Config,SuiteRegistry,ISSUANCE_PATH,REDEMPTION_PATH,DIRECTORY_PATH,PSI_SET_PATH,REQUEST_CONTENT_TYPE,process_issuance,process_redemption,process_directory,process_mirror,process_psi_set};
+    use crate::psi::{ServerSet,DEFAULT_TRUNCATION};
+    use crate::http::directory::Directory;
+    use std::io::Read;
+    use crate::oprf;
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::privacypass::{Issuer,TokenClient,TokenChallenge};
+    use std::sync::Arc;
+    use std::thread;
     use crate::oprf::groups::PrimeOrderGroup;
     use crate::oprf::{Client,Input,Evaluation};
     use crate::oprf::ciphersuite::Supported;
@@ -329,14 +581,10 @@ mod tests {
             + digest::BlockInput + digest::FixedOutput + digest::Input
             + digest::Reset + std::default::Default,
             PrimeOrderGroup<T, H>: Supported, Client<T,H>: Clone {
-        let host = "some_host".to_string();
-        let port = "1234".to_string();
         let max_evals = 5;
-        let cfg = Config::init(pog, host.clone(), port.clone(), max_evals, verifiable, test_idx);
+        let cfg = Config::init(Ciphersuite::new(pog, verifiable), max_evals, test_idx);
         assert_eq!(cfg.oprf_srv.ciph.verifiable, verifiable);
         assert_eq!(cfg.oprf_srv.ciph.name, expected_name);
-        assert_eq!(cfg.host, host);
-        assert_eq!(cfg.port, port);
         assert_eq!(cfg.max_evals, max_evals);
         match test_idx {
             -1 => {
@@ -355,4 +603,138 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn issuance_endpoint() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::p384_sha384(), true);
+        let srv = oprf::Server::setup(ciph.clone());
+        registry.insert(srv.clone()).unwrap();
+        let cli = TokenClient::new(ciph, srv.key.pub_key(&srv.ciph.pog)).unwrap();
+        let (req, _) = cli.request(&TokenChallenge::new("issuer.example", &[], &[]).unwrap());
+        let content_type = vec![("Content-Type".to_string(), REQUEST_CONTENT_TYPE.to_string())];
+
+        let request = rouille::Request::fake_http("POST", ISSUANCE_PATH, content_type.clone(), req.to_bytes());
+        assert_eq!(process_issuance(&registry, &request).status_code, 200);
+        let request = rouille::Request::fake_http("GET", ISSUANCE_PATH, content_type, req.to_bytes());
+        assert_eq!(process_issuance(&registry, &request).status_code, 405);
+        let request = rouille::Request::fake_http("POST", ISSUANCE_PATH, vec![], req.to_bytes());
+        assert_eq!(process_issuance(&registry, &request).status_code, 415);
+    }
+
+    #[test]
+    fn directory_endpoint() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        let srv = oprf::Server::setup(ciph.clone());
+        registry.insert(srv.clone()).unwrap();
+
+        let request = rouille::Request::fake_http("GET", DIRECTORY_PATH, vec![], vec![]);
+        let resp = process_directory(&registry, &request);
+        assert_eq!(resp.status_code, 200);
+        let (mut reader, _) = resp.data.into_reader_and_size();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        let dir = Directory::from_json(&body).unwrap();
+        assert_eq!(dir, registry.directory());
+        assert_eq!(dir.get(&ciph.name).unwrap().keys[0].pub_key, srv.key.pub_key(&ciph.pog).as_hex(&ciph.pog));
+
+        let request = rouille::Request::fake_http("POST", DIRECTORY_PATH, vec![], vec![]);
+        assert_eq!(process_directory(&registry, &request).status_code, 405);
+    }
+
+    #[test]
+    fn psi_set_endpoint() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+        let srv = oprf::Server::setup(ciph.clone());
+        registry.insert(srv.clone()).unwrap();
+        let items = vec![b"alice".to_vec(), b"bob".to_vec()];
+        registry.publish_psi_sets(&items, DEFAULT_TRUNCATION).unwrap();
+
+        let path = format!("{}{}", PSI_SET_PATH, ciph.name);
+        let request = rouille::Request::fake_http("GET", &path, vec![], vec![]);
+        let resp = process_psi_set(&registry, &request);
+        assert_eq!(resp.status_code, 200);
+        let (mut reader, _) = resp.data.into_reader_and_size();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        assert_eq!(ServerSet::from_bytes(&body).unwrap(), ServerSet::build(&srv, &items, DEFAULT_TRUNCATION).unwrap());
+
+        let request = rouille::Request::fake_http("POST", &path, vec![], vec![]);
+        assert_eq!(process_psi_set(&registry, &request).status_code, 405);
+        let request = rouille::Request::fake_http("GET", PSI_SET_PATH, vec![], vec![]);
+        assert_eq!(process_psi_set(&registry, &request).status_code, 404);
+    }
+
+    #[test]
+    fn mirror_endpoint() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        registry.insert(oprf::Server::setup(ciph)).unwrap();
+        let path = std::env::temp_dir().join(format!("voprf-rs-mirror-{}.json", std::process::id()));
+        std::fs::write(&path, registry.directory().to_json()).unwrap();
+        let upstream = path.to_str().unwrap();
+
+        let request = rouille::Request::fake_http("GET", DIRECTORY_PATH, vec![], vec![]);
+        let resp = process_mirror(upstream, &request);
+        assert_eq!(resp.status_code, 200);
+        let (mut reader, _) = resp.data.into_reader_and_size();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        assert_eq!(Directory::from_json(&body).unwrap(), registry.directory());
+
+        let request = rouille::Request::fake_http("GET", "/", vec![], vec![]);
+        assert_eq!(process_mirror(upstream, &request).status_code, 404);
+        let request = rouille::Request::fake_http("GET", DIRECTORY_PATH, vec![], vec![]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(process_mirror(upstream, &request).status_code, 502);
+    }
+
+    #[test]
+    fn redemption_endpoint() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::p384_sha384(), true);
+        let srv = oprf::Server::setup(ciph.clone());
+        registry.insert(srv.clone()).unwrap();
+        let issuer = Issuer::new(srv).unwrap();
+        let cli = TokenClient::new(ciph, issuer.pub_key()).unwrap();
+        let (req, state) = cli.request(&TokenChallenge::new("issuer.example", &[], &[]).unwrap());
+        let token = cli.finalize(&state, &issuer.issue(&req).unwrap()).unwrap();
+        let encoded = base64::encode_config(token.to_bytes(), base64::URL_SAFE_NO_PAD);
+        let redeem = |value: String| {
+            let headers = vec![("Authorization".to_string(), value)];
+            let request = rouille::Request::fake_http("GET", REDEMPTION_PATH, headers, vec![]);
+            process_redemption(&registry, &request).status_code
+        };
+
+        assert_eq!(redeem(format!("Bearer token=\"{}\"", encoded)), 401);
+        assert_eq!(redeem(format!("PrivateTokenX token=\"{}\"", encoded)), 401);
+        assert_eq!(redeem(format!("PrivateToken,token=\"{}\"", encoded)), 401);
+        assert_eq!(redeem(format!("PrivateToken token=\"{}\"", &encoded[1..])), 401);
+        assert_eq!(redeem(format!("PrivateToken token=\"{}\"", encoded)), 200);
+        // the token has been spent
+        assert_eq!(redeem(format!("PrivateToken token={}", encoded)), 401);
+    }
+
+    #[test]
+    fn concurrent_redemption() {
+        let mut registry = SuiteRegistry::new(5);
+        let ciph = Ciphersuite::new(PrimeOrderGroup::p384_sha384(), true);
+        let srv = oprf::Server::setup(ciph.clone());
+        registry.insert(srv.clone()).unwrap();
+        let issuer = Issuer::new(srv).unwrap();
+        let cli = TokenClient::new(ciph, issuer.pub_key()).unwrap();
+        let (req, state) = cli.request(&TokenChallenge::new("issuer.example", &[], &[]).unwrap());
+        let token = cli.finalize(&state, &issuer.issue(&req).unwrap()).unwrap().to_bytes();
+
+        let registry = Arc::new(registry);
+        let handles: Vec<_> = (0..8).map(|_| {
+            let registry = registry.clone();
+            let token = token.clone();
+            thread::spawn(move || registry.process_token_redemption(&token).status_code)
+        }).collect();
+        let accepted = handles.into_iter().map(|h| h.join().unwrap()).filter(|c| *c == 200).count();
+        assert_eq!(accepted, 1);
+    }
 }
\ No newline at end of file
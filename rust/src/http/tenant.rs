@@ -0,0 +1,459 @@
+//! The tenant module allows a single HTTP server to host the (V)OPRF on behalf
+//! of several tenants (e.g. separate products), each with its own ciphersuite
+//! and secret key. Tenants are isolated from each other: errors, metrics and
+//! evaluation quotas are all tracked per tenant, and a request for one tenant
+//! never reveals anything about the others.
+//!
+//! Tenants are configured from a JSON file of the following form, where `key`
+//! is the hex-encoded secret key of the tenant, and where `max_evals` and
+//! `quota` (the total number of evaluations that the tenant may perform) are
+//! optional. The ciphersuite of a tenant is set either with `group` and
+//! `verifiable`, or by its full name with `ciphersuite`:
+//!
+//! ```json
+//! {
+//!     "tenants": [
+//!         { "name": "alpha", "group": "P384", "verifiable": true, "key": "...", "max_evals": 10, "quota": 100000 },
+//!         { "name": "beta", "group": "ristretto255", "verifiable": false, "key": "..." },
+//!         { "name": "gamma", "ciphersuite": "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", "key": "..." }
+//!     ]
+//! }
+//! ```
+//!
+//! A request is routed to a tenant either by the URL path that it is sent to
+//! (e.g. `http://127.0.0.1:3001/alpha`), or by the `tenant` field of the
+//! JSON-RPC params. If both are set, then they must agree.
+use std::collections::HashMap;
+use std::fs;
+use std::io::Error;
+use std::sync::atomic::{AtomicU64,Ordering};
+
+use rouille::Response;
+use serde::Deserialize;
+
+use super::jsonrpc;
+use jsonrpc::{ErrorType,RequestParams};
+use super::server::error_response;
+use super::registry::SuiteRegistry;
+use crate::oprf::any::{AnyCiphersuite,AnyServer};
+use crate::errors::err_invalid_config;
+
+const DEFAULT_MAX_EVALS: u16 = 10;
+
+#[derive(Deserialize, Debug)]
+struct TenantsFile {
+    tenants: Vec<TenantEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct TenantEntry {
+    name: String,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    verifiable: bool,
+    #[serde(default)]
+    ciphersuite: Option<String>,
+    key: String,
+    #[serde(default = "default_max_evals")]
+    max_evals: u16,
+    #[serde(default)]
+    quota: Option<u64>,
+}
+
+fn default_max_evals() -> u16 {
+    DEFAULT_MAX_EVALS
+}
+
+/// The `Metrics` struct holds the request counters for a single tenant
+#[derive(Default, Debug)]
+pub struct Metrics {
+    requests: AtomicU64,
+    evaluations: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl Metrics {
+    /// The number of requests that have been routed to the tenant
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// The number of group elements that the tenant has evaluated
+    pub fn evaluations(&self) -> u64 {
+        self.evaluations.load(Ordering::SeqCst)
+    }
+
+    /// The number of requests to the tenant that resulted in an error
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::SeqCst)
+    }
+}
+
+/// A `Tenant` wraps the (V)OPRF servers (with tenant-specific ciphersuites
+/// and keys) of a tenant, along with the limits and metrics that apply to it.
+pub struct Tenant {
+    name: String,
+    suites: SuiteRegistry,
+    quota: Option<u64>,
+    used: AtomicU64,
+    metrics: Metrics,
+}
+
+impl Tenant {
+    fn from_entry(entry: TenantEntry) -> Result<Self, Error> {
+        if entry.max_evals > 100 {
+            return Err(err_invalid_config());
+        }
+        let key = match hex::decode(&entry.key) {
+            Ok(k) => k,
+            Err(_) => return Err(err_invalid_config()),
+        };
+        // the ciphersuite is either named in full, or given by its group
+        let ciph = match (&entry.group, &entry.ciphersuite) {
+            (Some(g), None) => AnyCiphersuite::from_group(g, entry.verifiable),
+            (None, Some(c)) => AnyCiphersuite::from_name(c),
+            _ => return Err(err_invalid_config()),
+        };
+        let ciph = match ciph {
+            Ok(c) => c,
+            Err(_) => return Err(err_invalid_config()),
+        };
+        let mut suites = SuiteRegistry::new(entry.max_evals);
+        suites.insert_any(AnyServer::with_key(ciph, key)?)?;
+        Ok(Tenant{
+            name: entry.name,
+            suites: suites,
+            quota: entry.quota,
+            used: AtomicU64::new(0),
+            metrics: Metrics::default(),
+        })
+    }
+
+    /// The name of the tenant
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The ciphersuites hosted for the tenant
+    pub fn suites(&self) -> &SuiteRegistry {
+        &self.suites
+    }
+
+    /// The metrics recorded for the tenant
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// The number of evaluations that remain in the quota of the tenant, or
+    /// `None` if the tenant is not subject to a quota
+    pub fn remaining_quota(&self) -> Option<u64> {
+        match self.quota {
+            Some(q) => Some(q.saturating_sub(self.used.load(Ordering::SeqCst))),
+            None => None,
+        }
+    }
+
+    // processes an evaluation request that has been routed to the tenant
+    fn process(&self, params: RequestParams, shuffled: bool, id: i16) -> Response {
+        self.metrics.requests.fetch_add(1, Ordering::SeqCst);
+        match self.evaluate(params, shuffled) {
+            Ok((eles_hex, proof_hex)) => {
+                self.metrics.evaluations.fetch_add(eles_hex.len() as u64, Ordering::SeqCst);
+                Response::text(jsonrpc::success(eles_hex, proof_hex, id))
+            },
+            Err(e) => {
+                self.metrics.errors.fetch_add(1, Ordering::SeqCst);
+                println!("[{}] failed to process request: {:?}", self.name, e);
+                error_response(e, id)
+            }
+        }
+    }
+
+    fn evaluate(&self, params: RequestParams, shuffled: bool) -> Result<(Vec<String>, Vec<String>), ErrorType> {
+        let n = params.data.len() as u64;
+        if !self.reserve(n) {
+            return Err(ErrorType::QuotaExceeded);
+        }
+        let res = self.suites.evaluate(params, shuffled);
+        if res.is_err() {
+            // failed evaluations do not count towards the quota
+            self.release(n);
+        }
+        res
+    }
+
+    // reserves `n` evaluations from the quota of the tenant, returns false if
+    // there are not enough evaluations remaining
+    fn reserve(&self, n: u64) -> bool {
+        let quota = match self.quota {
+            Some(q) => q,
+            None => return true,
+        };
+        let mut used = self.used.load(Ordering::SeqCst);
+        loop {
+            if used + n > quota {
+                return false;
+            }
+            match self.used.compare_exchange(used, used + n, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return true,
+                Err(u) => used = u,
+            }
+        }
+    }
+
+    fn release(&self, n: u64) {
+        if self.quota.is_some() {
+            self.used.fetch_sub(n, Ordering::SeqCst);
+        }
+    }
+}
+
+/// The `Tenants` struct maps tenant names to the `Tenant` objects that serve
+/// them.
+pub struct Tenants {
+    tenants: HashMap<String, Tenant>,
+}
+
+impl Tenants {
+    /// Loads the tenant configuration from the JSON file at `path`
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        Tenants::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Loads the tenant configuration from a JSON string
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let file: TenantsFile = match serde_json::from_str(json) {
+            Ok(f) => f,
+            Err(_) => return Err(err_invalid_config()),
+        };
+        let mut tenants = HashMap::new();
+        for entry in file.tenants {
+            let name = entry.name.clone();
+            if name.is_empty() || name.contains('/') || tenants.contains_key(&name) {
+                return Err(err_invalid_config());
+            }
+            tenants.insert(name, Tenant::from_entry(entry)?);
+        }
+        Ok(Tenants{ tenants: tenants })
+    }
+
+    /// Returns the tenant with the given name, if it exists
+    pub fn get(&self, name: &str) -> Option<&Tenant> {
+        self.tenants.get(name)
+    }
+
+    /// Returns the names of all configured tenants, in sorted order
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tenants.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Routes a JSON-RPC request body, received at the URL path `path`, to the
+    /// tenant that it addresses and returns the response of that tenant.
+    pub fn process_request(&self, path: &str, buf: &[u8]) -> Response {
+        let req = match jsonrpc::Request::read(buf) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("failed to read request into buffer");
+                return error_response(e, -1);
+            }
+        };
+        let id = req.id;
+        if req.is_bucket() {
+            // tenants do not hold datasets of compromised credentials
+            return error_response(ErrorType::MethodNotFound, id);
+        }
+        let from_path = path.trim_matches('/');
+        let name = match (from_path.is_empty(), &req.params.tenant) {
+            (true, None) => return error_response(ErrorType::UnknownTenant, id),
+            (true, Some(t)) => t.clone(),
+            (false, None) => from_path.to_string(),
+            (false, Some(t)) => {
+                if t != from_path {
+                    return error_response(ErrorType::InvalidParams, id);
+                }
+                t.clone()
+            }
+        };
+        match self.tenants.get(&name) {
+            Some(tenant) => {
+                let shuffled = req.is_cardinality();
+                tenant.process(req.params, shuffled, id)
+            },
+            None => {
+                println!("request for unknown tenant");
+                error_response(ErrorType::UnknownTenant, id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tenants;
+    use crate::http::jsonrpc::{ResponseSuccess,ResponseError};
+    use crate::oprf::groups::PrimeOrderGroup;
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::oprf::Client;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+    use rouille::Response;
+    use std::io::Read;
+
+    const KEY_ALPHA: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+    const KEY_BETA: &str = "0202020202020202020202020202020202020202020202020202020202020202";
+
+    fn tenants() -> Tenants {
+        let json = format!(r#"{{ "tenants": [
+            {{ "name": "alpha", "group": "ristretto255", "verifiable": true, "key": "{}", "max_evals": 3, "quota": 4 }},
+            {{ "name": "beta", "group": "ristretto255", "verifiable": false, "key": "{}" }}
+        ] }}"#, KEY_ALPHA, KEY_BETA);
+        Tenants::from_json(&json).unwrap()
+    }
+
+    fn request(ciph: &str, n: usize, tenant: Option<&str>) -> Vec<u8> {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let cli = Client::setup(Ciphersuite::new(pog.clone(), false), None).unwrap();
+        let mut data = Vec::new();
+        for i in 0..n {
+            let input = cli.blind(&vec![vec![i as u8; 32]]);
+            let mut buf = Vec::new();
+            (pog.serialize)(&input[0].elem, true, &mut buf);
+            data.push(format!("\"{}\"", hex::encode(buf)));
+        }
+        let tenant_param = match tenant {
+            Some(t) => format!(", \"tenant\": \"{}\"", t),
+            None => String::new(),
+        };
+        format!(r#"{{ "jsonrpc": "2.0", "method": "eval", "params": {{ "data": [{}], "ciph": "{}"{} }}, "id": 1 }}"#,
+                data.join(","), ciph, tenant_param).into_bytes()
+    }
+
+    fn read_body(resp: Response) -> String {
+        let (mut reader, _) = resp.data.into_reader_and_size();
+        let mut body = String::new();
+        reader.read_to_string(&mut body).unwrap();
+        body
+    }
+
+    fn success(resp: Response) -> ResponseSuccess {
+        assert_eq!(resp.status_code, 200);
+        serde_json::from_str(&read_body(resp)).unwrap()
+    }
+
+    fn error_code(resp: Response) -> i16 {
+        assert_eq!(resp.status_code, 400);
+        let err: ResponseError = serde_json::from_str(&read_body(resp)).unwrap();
+        err.error.code
+    }
+
+    #[test]
+    fn load_tenants() {
+        let t = tenants();
+        assert_eq!(t.names(), vec!["alpha".to_string(), "beta".to_string()]);
+        let alpha = t.get("alpha").unwrap();
+        let alpha_ciph = "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        assert_eq!(alpha.suites().names(), vec![alpha_ciph.to_string()]);
+        assert!(alpha.suites().pub_key_hex(alpha_ciph).is_some());
+        assert_eq!(alpha.remaining_quota(), Some(4));
+        let beta = t.get("beta").unwrap();
+        let beta_ciph = "OPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        assert_eq!(beta.suites().names(), vec![beta_ciph.to_string()]);
+        assert!(beta.suites().pub_key_hex(beta_ciph).is_none());
+        assert_eq!(beta.remaining_quota(), None);
+    }
+
+    #[test]
+    fn load_tenants_invalid() {
+        let dup = format!(r#"{{ "tenants": [
+            {{ "name": "alpha", "group": "ristretto255", "key": "{}" }},
+            {{ "name": "alpha", "group": "ristretto255", "key": "{}" }}
+        ] }}"#, KEY_ALPHA, KEY_BETA);
+        assert!(Tenants::from_json(&dup).is_err());
+        let bad_group = format!(r#"{{ "tenants": [ {{ "name": "alpha", "group": "P521", "key": "{}" }} ] }}"#, KEY_ALPHA);
+        assert!(Tenants::from_json(&bad_group).is_err());
+        let bad_key = r#"{ "tenants": [ { "name": "alpha", "group": "ristretto255", "key": "0102" } ] }"#;
+        assert!(Tenants::from_json(bad_key).is_err());
+        let big_key = format!(r#"{{ "tenants": [ {{ "name": "alpha", "group": "ristretto255", "key": "{}" }} ] }}"#, "ff".repeat(32));
+        assert!(Tenants::from_json(&big_key).is_err());
+        assert!(Tenants::from_json("not json").is_err());
+        let no_ciph = format!(r#"{{ "tenants": [ {{ "name": "alpha", "key": "{}" }} ] }}"#, KEY_ALPHA);
+        assert!(Tenants::from_json(&no_ciph).is_err());
+        let both = format!(r#"{{ "tenants": [ {{ "name": "alpha", "group": "ristretto255",
+            "ciphersuite": "OPRF-ristretto255-HKDF-SHA512-ELL2-RO", "key": "{}" }} ] }}"#, KEY_ALPHA);
+        assert!(Tenants::from_json(&both).is_err());
+        let bad_ciph = format!(r#"{{ "tenants": [ {{ "name": "alpha", "ciphersuite": "VOPRF-P521-HKDF-SHA512-SSWU-RO", "key": "{}" }} ] }}"#, KEY_ALPHA);
+        assert!(Tenants::from_json(&bad_ciph).is_err());
+    }
+
+    #[test]
+    fn load_tenant_by_ciphersuite_name() {
+        let ciph = "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        let json = format!(r#"{{ "tenants": [ {{ "name": "alpha", "ciphersuite": "{}", "key": "{}" }} ] }}"#, ciph, KEY_ALPHA);
+        let t = Tenants::from_json(&json).unwrap();
+        let by_group = tenants();
+        assert_eq!(t.get("alpha").unwrap().suites().names(), vec![ciph.to_string()]);
+        assert_eq!(t.get("alpha").unwrap().suites().pub_key_hex(ciph), by_group.get("alpha").unwrap().suites().pub_key_hex(ciph));
+    }
+
+    #[test]
+    fn route_by_path_and_param() {
+        let t = tenants();
+        let ciph = "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        let res = success(t.process_request("/alpha", &request(ciph, 1, None)));
+        assert_eq!(res.result.data.len(), 1);
+        assert_eq!(res.result.proof.len(), 2);
+        let res = success(t.process_request("/", &request(ciph, 1, Some("alpha"))));
+        assert_eq!(res.result.data.len(), 1);
+        assert_eq!(t.get("alpha").unwrap().metrics().requests(), 2);
+        assert_eq!(t.get("beta").unwrap().metrics().requests(), 0);
+    }
+
+    #[test]
+    fn routing_errors() {
+        let t = tenants();
+        let ciph = "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        assert_eq!(error_code(t.process_request("/", &request(ciph, 1, None))), -32002);
+        assert_eq!(error_code(t.process_request("/gamma", &request(ciph, 1, None))), -32002);
+        assert_eq!(error_code(t.process_request("/alpha", &request(ciph, 1, Some("beta")))), -32602);
+    }
+
+    #[test]
+    fn tenants_are_isolated() {
+        let t = tenants();
+        // beta runs an OPRF, so the VOPRF ciphersuite of alpha is rejected
+        let voprf = "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        assert_eq!(error_code(t.process_request("/beta", &request(voprf, 1, None))), -32000);
+        let beta = t.get("beta").unwrap();
+        assert_eq!(beta.metrics().errors(), 1);
+        let alpha = t.get("alpha").unwrap();
+        assert_eq!(alpha.metrics().errors(), 0);
+
+        // the same input evaluates differently under each tenant key
+        let oprf = "OPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        let alpha_res = success(t.process_request("/alpha", &request(voprf, 1, None)));
+        let beta_res = success(t.process_request("/beta", &request(oprf, 1, None)));
+        assert_ne!(alpha_res.result.data, beta_res.result.data);
+        assert_eq!(alpha.metrics().evaluations(), 1);
+        assert_eq!(beta.metrics().evaluations(), 1);
+    }
+
+    #[test]
+    fn quotas() {
+        let t = tenants();
+        let ciph = "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        // more elements than max_evals
+        assert_eq!(error_code(t.process_request("/alpha", &request(ciph, 4, None))), -32602);
+        success(t.process_request("/alpha", &request(ciph, 3, None)));
+        assert_eq!(t.get("alpha").unwrap().remaining_quota(), Some(1));
+        assert_eq!(error_code(t.process_request("/alpha", &request(ciph, 2, None))), -32003);
+        success(t.process_request("/alpha", &request(ciph, 1, None)));
+        assert_eq!(t.get("alpha").unwrap().remaining_quota(), Some(0));
+
+        // beta is unaffected by the quota of alpha
+        let oprf = "OPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+        success(t.process_request("/beta", &request(oprf, 3, None)));
+    }
+}
@@ -5,7 +5,13 @@
     unstable_features, // Used by `internal_benches`
 )]
 
+pub mod credcheck;
 pub mod http;
 pub mod oprf;
+pub mod opaque;
+pub mod privacypass;
+pub mod psi;
+pub mod pwhash;
 pub mod utils;
+pub mod vrf;
 pub mod errors;
@@ -0,0 +1,808 @@
+//! The opaque module implements the
+//! [OPAQUE](https://datatracker.ietf.org/doc/draft-irtf-cfrg-opaque/)
+//! asymmetric password-authenticated key exchange on top of the (V)OPRF, so
+//! that the server never learns the password of the client.
+//!
+//! At registration, the client blinds its password, the server evaluates the
+//! (V)OPRF with `Server::eval`, and the client derives a randomized password
+//! from the output with `Client::unblind` and `Client::finalize`. The client
+//! then generates a key pair, and seals its secret key and the public key of
+//! the server in an `Envelope`, under keys derived from the randomized
+//! password. The server stores the resulting `RegistrationRecord`.
+//!
+//! At login, the client runs the (V)OPRF on its password again, alongside a
+//! 3DH key exchange. The server responds with the evaluation, the stored
+//! envelope and an ephemeral public key. The envelope is masked under the
+//! masking key in the record and a fresh nonce, so that it differs in each
+//! login. The client derives the masking key from the randomized password,
+//! recovers its secret key from the envelope, and both sides derive the
+//! session key from the three Diffie-Hellman values between their static and
+//! ephemeral keys. Each side authenticates the other with a MAC over the
+//! transcript of the exchange.
+//!
+//! This is a simplified version of the protocol in the draft, and is not
+//! interoperable with other implementations: the (V)OPRF key is shared by all
+//! users (the (V)OPRF input binds the password to the username instead), and
+//! keys are derived with HMAC over the hash function of the ciphersuite. If no
+//! record exists for a username, the server should run the login with
+//! `OpaqueServer::dummy_record`, which is derived from a secret seed of the
+//! server and the username. The same username always gets the same dummy
+//! record, so that the server does not reveal which usernames are registered.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,Client};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::opaque::{OpaqueServer,OpaqueClient};
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use sha2::Sha512;
+//!
+//! let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let srv = OpaqueServer::new(Server::setup(ciph.clone()));
+//! let cli = OpaqueClient::new(Client::setup(ciph, Some(srv.oprf_pub_key())).unwrap());
+//!
+//! // registration
+//! let (req, input) = cli.registration_start(b"alice", b"hunter2");
+//! let resp = srv.registration_response(&req).unwrap();
+//! let record = cli.registration_finish(&input, &resp).unwrap();
+//!
+//! // login
+//! let (req, state) = cli.login_start(b"alice", b"hunter2");
+//! let (resp, srv_state) = srv.login_start(b"alice", &record, &req).unwrap();
+//! let (fin, client_key) = cli.login_finish(&state, &resp).unwrap();
+//! let server_key = srv.login_finish(&srv_state, &fin).unwrap();
+//! assert_eq!(client_key, server_key);
+//! ```
+use std::io::Error;
+
+use byteorder::{BigEndian,WriteBytesExt};
+use digest::generic_array::typenum::Unsigned;
+use hmac::Mac;
+use subtle::ConstantTimeEq;
+
+use crate::oprf::{Server,Client,Input,Evaluation,PublicKey};
+use crate::oprf::ciphersuite::{Ciphersuite,Supported};
+use crate::oprf::groups::PrimeOrderGroup;
+use crate::utils::rand_bytes;
+use crate::errors::{err_deserialization,err_evaluation,err_envelope_recovery,err_key_exchange};
+
+/// The length of the nonces in envelopes and key exchange messages
+pub const NONCE_LENGTH: usize = 32;
+
+const RWD_INFO: &[u8] = b"OPAQUE-RandomizedPassword";
+const ENC_KEY_LABEL: &[u8] = b"OPAQUE-EncryptionKey";
+const AUTH_KEY_LABEL: &[u8] = b"OPAQUE-AuthKey";
+const KE_CONTEXT: &[u8] = b"OPAQUE-3DH";
+const SERVER_MAC_LABEL: &[u8] = b"ServerMAC";
+const CLIENT_MAC_LABEL: &[u8] = b"ClientMAC";
+const SESSION_KEY_LABEL: &[u8] = b"SessionKey";
+const MASKING_KEY_LABEL: &[u8] = b"OPAQUE-MaskingKey";
+const MASKING_PAD_LABEL: &[u8] = b"OPAQUE-CredentialResponsePad";
+const DUMMY_LABEL: &[u8] = b"OPAQUE-DummyRecord";
+
+/// The `Envelope` struct holds the secret key of the client and the public
+/// key of the server, encrypted and authenticated under keys derived from the
+/// randomized password
+#[derive(Clone, Debug, PartialEq)]
+pub struct Envelope {
+    /// The random nonce that the envelope keys are derived with
+    pub nonce: Vec<u8>,
+    /// The encrypted secret key of the client and public key of the server
+    pub ciphertext: Vec<u8>,
+    /// The HMAC tag over the nonce and ciphertext
+    pub tag: Vec<u8>,
+}
+
+impl Envelope {
+    /// Returns the encoding of the envelope: `nonce || ciphertext || tag`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.nonce.clone();
+        out.extend_from_slice(&self.ciphertext);
+        out.extend_from_slice(&self.tag);
+        out
+    }
+
+    /// Parses an envelope for the group `pog` from its encoding, the tag
+    /// takes up the remaining bytes after the ciphertext, and must have the
+    /// length of a MAC
+    pub fn from_bytes<T,H>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<Self, Error>
+            where H: digest::FixedOutput {
+        let ct_len = pog.byte_length + element_length(pog);
+        if buf.len() != envelope_length(pog) {
+            return Err(err_deserialization());
+        }
+        let (nonce, rest) = buf.split_at(NONCE_LENGTH);
+        let (ciphertext, tag) = rest.split_at(ct_len);
+        Ok(Envelope{
+            nonce: nonce.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+            tag: tag.to_vec(),
+        })
+    }
+}
+
+/// The `RegistrationRecord` struct is stored by the server for each
+/// registered client
+#[derive(Clone)]
+pub struct RegistrationRecord<T> {
+    /// The static public key of the client
+    pub client_public_key: T,
+    /// The key that the server masks the envelope with in each login
+    pub masking_key: Vec<u8>,
+    /// The envelope that the client recovers its secret key from
+    pub envelope: Envelope,
+}
+
+impl<T> RegistrationRecord<T> {
+    /// Returns the encoding of the record: `client_public_key || masking_key
+    /// || envelope`
+    pub fn to_bytes<H>(&self, pog: &PrimeOrderGroup<T,H>) -> Vec<u8> {
+        let mut out = Vec::new();
+        push_elem(pog, &mut out, &self.client_public_key);
+        out.extend_from_slice(&self.masking_key);
+        out.extend_from_slice(&self.envelope.to_bytes());
+        out
+    }
+
+    /// Parses a record for the group `pog` from its encoding
+    pub fn from_bytes<H>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<Self, Error>
+            where H: digest::FixedOutput {
+        let ne = element_length(pog);
+        let nm = mac_length::<H>();
+        if buf.len() < ne + nm {
+            return Err(err_deserialization());
+        }
+        let client_public_key = (pog.deserialize)(&buf[..ne])?;
+        if !(pog.is_valid)(&client_public_key) {
+            return Err(err_deserialization());
+        }
+        Ok(RegistrationRecord{
+            client_public_key: client_public_key,
+            masking_key: buf[ne..ne+nm].to_vec(),
+            envelope: Envelope::from_bytes(pog, &buf[ne+nm..])?,
+        })
+    }
+
+    /// Returns the dummy record for `username`, which the server uses for
+    /// logins with usernames that are not registered. The record is derived
+    /// from the secret `seed` of the server, so that each login for the same
+    /// username uses the same record. The login fails on the client side, as
+    /// it does for a wrong password.
+    pub fn dummy<H>(ciph: &Ciphersuite<T,H>, seed: &[u8], username: &[u8]) -> Result<Self, Error>
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let pog = &ciph.pog;
+        let mut info = Vec::new();
+        write_bytes(&mut info, username);
+        let prk = mac(ciph, seed, &[DUMMY_LABEL, &info])?;
+        let sk = (pog.hash_to_scalar)(&prk, DUMMY_LABEL);
+        let env = expand(ciph, &prk, MASKING_PAD_LABEL, envelope_length(pog))?;
+        Ok(RegistrationRecord{
+            client_public_key: (pog.base_mult)(&sk),
+            masking_key: mac(ciph, &prk, &[MASKING_KEY_LABEL])?,
+            envelope: Envelope::from_bytes(pog, &env)?,
+        })
+    }
+}
+
+/// The `RegistrationRequest` struct is sent by the client to start
+/// registration, and holds the blinded password
+#[derive(Clone)]
+pub struct RegistrationRequest<T> {
+    /// The blinded (V)OPRF input
+    pub blinded_elem: T,
+}
+
+/// The `RegistrationResponse` struct is returned by the server during
+/// registration
+#[derive(Clone)]
+pub struct RegistrationResponse<T> {
+    /// The (V)OPRF evaluation of the blinded password
+    pub eval: Evaluation<T>,
+    /// The static public key of the server
+    pub server_public_key: T,
+}
+
+/// The `LoginRequest` struct is sent by the client to start a login
+#[derive(Clone)]
+pub struct LoginRequest<T> {
+    /// The blinded (V)OPRF input
+    pub blinded_elem: T,
+    /// The random nonce of the client
+    pub client_nonce: Vec<u8>,
+    /// The ephemeral public key of the client
+    pub client_eph_pub: T,
+}
+
+/// The `LoginResponse` struct is returned by the server to a login request
+#[derive(Clone)]
+pub struct LoginResponse<T> {
+    /// The (V)OPRF evaluation of the blinded password
+    pub eval: Evaluation<T>,
+    /// The random nonce that the envelope is masked with
+    pub masking_nonce: Vec<u8>,
+    /// The envelope from the record of the client, masked under the masking
+    /// key of the record
+    pub masked_envelope: Vec<u8>,
+    /// The random nonce of the server
+    pub server_nonce: Vec<u8>,
+    /// The ephemeral public key of the server
+    pub server_eph_pub: T,
+    /// The MAC that authenticates the server over the transcript
+    pub server_mac: Vec<u8>,
+}
+
+/// The `LoginFinalization` struct is sent by the client to complete a login
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginFinalization {
+    /// The MAC that authenticates the client over the transcript
+    pub client_mac: Vec<u8>,
+}
+
+/// The `ClientLoginState` struct holds the client state that is required for
+/// completing a login
+#[derive(Clone)]
+pub struct ClientLoginState<T> {
+    username: Vec<u8>,
+    input: Input<T>,
+    eph_sk: Vec<u8>,
+    req: LoginRequest<T>,
+}
+
+/// The `ServerLoginState` struct holds the server state that is required for
+/// completing a login
+#[derive(Clone)]
+pub struct ServerLoginState {
+    client_mac: Vec<u8>,
+    session_key: Vec<u8>,
+}
+
+/// The `OpaqueServer` struct registers clients and runs logins with the
+/// (V)OPRF key of a `Server`, and a static key pair for the key exchange
+pub struct OpaqueServer<T,H>
+        where T: Clone, H: Clone {
+    srv: Server<T,H>,
+    sk: Vec<u8>,
+    pk: T,
+    dummy_seed: Vec<u8>,
+}
+
+impl<T,H> OpaqueServer<T,H>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    /// Creates a server with the (V)OPRF key of `srv`, and generates a new
+    /// static key pair for the key exchange, and a new seed for dummy records
+    pub fn new(srv: Server<T,H>) -> Self {
+        let (sk, pk) = key_pair(&srv.ciph.pog);
+        let mut dummy_seed = Vec::new();
+        rand_bytes(NONCE_LENGTH, &mut dummy_seed);
+        OpaqueServer{
+            srv: srv,
+            sk: sk,
+            pk: pk,
+            dummy_seed: dummy_seed,
+        }
+    }
+
+    /// The static public key of the server for the key exchange
+    pub fn pub_key(&self) -> T {
+        self.pk.clone()
+    }
+
+    /// The public key of the (V)OPRF key of the server
+    pub fn oprf_pub_key(&self) -> PublicKey<T> {
+        self.srv.key.pub_key(&self.srv.ciph.pog)
+    }
+
+    /// Returns the dummy record for `username`, to be used in `login_start`
+    /// when no record exists for it
+    pub fn dummy_record(&self, username: &[u8]) -> Result<RegistrationRecord<T>, Error> {
+        RegistrationRecord::dummy(&self.srv.ciph, &self.dummy_seed, username)
+    }
+
+    /// Evaluates the (V)OPRF on the blinded password in a registration request
+    pub fn registration_response(&self, req: &RegistrationRequest<T>) -> Result<RegistrationResponse<T>, Error> {
+        if !(self.srv.ciph.pog.is_valid)(&req.blinded_elem) {
+            return Err(err_deserialization());
+        }
+        Ok(RegistrationResponse{
+            eval: self.srv.eval(std::slice::from_ref(&req.blinded_elem))?,
+            server_public_key: self.pk.clone(),
+        })
+    }
+
+    /// Responds to the login request of the client `username` with the
+    /// stored `record`, and returns the state that is required for
+    /// verifying the client in `login_finish`
+    pub fn login_start(&self, username: &[u8], record: &RegistrationRecord<T>, req: &LoginRequest<T>) -> Result<(LoginResponse<T>, ServerLoginState), Error> {
+        let ciph = &self.srv.ciph;
+        let pog = &ciph.pog;
+        if !(pog.is_valid)(&req.blinded_elem) || !(pog.is_valid)(&req.client_eph_pub)
+                || req.client_nonce.len() != NONCE_LENGTH {
+            return Err(err_deserialization());
+        }
+        let (eph_sk, eph_pub) = key_pair(pog);
+        let mut masking_nonce = Vec::new();
+        rand_bytes(NONCE_LENGTH, &mut masking_nonce);
+        let env = record.envelope.to_bytes();
+        let pad = masking_pad(ciph, &record.masking_key, &masking_nonce, env.len())?;
+        let mut server_nonce = Vec::new();
+        rand_bytes(NONCE_LENGTH, &mut server_nonce);
+        let mut resp = LoginResponse{
+            eval: self.srv.eval(std::slice::from_ref(&req.blinded_elem))?,
+            masked_envelope: xor(&env, &pad),
+            masking_nonce: masking_nonce,
+            server_nonce: server_nonce,
+            server_eph_pub: eph_pub,
+            server_mac: Vec::new(),
+        };
+        let tr = transcript(pog, username, req, &resp, &record.client_public_key, &self.pk);
+        let dh = [
+            (pog.scalar_mult)(&req.client_eph_pub, &eph_sk),
+            (pog.scalar_mult)(&req.client_eph_pub, &self.sk),
+            (pog.scalar_mult)(&record.client_public_key, &eph_sk),
+        ];
+        let keys = key_schedule(ciph, &dh, &tr)?;
+        resp.server_mac = mac(ciph, &keys.server_mac_key, &[&tr])?;
+        let client_mac = mac(ciph, &keys.client_mac_key, &[&tr, &resp.server_mac])?;
+        Ok((resp, ServerLoginState{
+            client_mac: client_mac,
+            session_key: keys.session_key,
+        }))
+    }
+
+    /// Verifies the MAC of the client (in constant time), and returns the
+    /// session key
+    pub fn login_finish(&self, state: &ServerLoginState, fin: &LoginFinalization) -> Result<Vec<u8>, Error> {
+        match bool::from(state.client_mac.ct_eq(&fin.client_mac)) {
+            true => Ok(state.session_key.clone()),
+            false => Err(err_key_exchange()),
+        }
+    }
+}
+
+/// The `OpaqueClient` struct registers and logs in with a password, using the
+/// (V)OPRF of a `Client`
+pub struct OpaqueClient<T,H>
+        where T: Clone, H: Clone {
+    cli: Client<T,H>,
+}
+
+impl<T,H> OpaqueClient<T,H>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    /// Creates an OPAQUE client from a (V)OPRF client
+    pub fn new(cli: Client<T,H>) -> Self {
+        OpaqueClient{
+            cli: cli,
+        }
+    }
+
+    /// Blinds the password of `username`, and returns the registration
+    /// request along with the blinded input
+    pub fn registration_start(&self, username: &[u8], password: &[u8]) -> (RegistrationRequest<T>, Input<T>) {
+        let input = self.cli.blind(&[oprf_input(username, password)]).remove(0);
+        (RegistrationRequest{ blinded_elem: input.elem.clone() }, input)
+    }
+
+    /// Derives the randomized password from the server response, and returns
+    /// the record to be stored by the server, with a new key pair of the
+    /// client sealed in its envelope
+    pub fn registration_finish(&self, input: &Input<T>, resp: &RegistrationResponse<T>) -> Result<RegistrationRecord<T>, Error> {
+        let pog = &self.cli.ciph.pog;
+        if !(pog.is_valid)(&resp.server_public_key) {
+            return Err(err_deserialization());
+        }
+        let rwd = self.randomized_password(input, &resp.eval)?;
+        let (sk, pk) = key_pair(pog);
+        Ok(RegistrationRecord{
+            client_public_key: pk,
+            masking_key: mac(&self.cli.ciph, &rwd, &[MASKING_KEY_LABEL])?,
+            envelope: seal(&self.cli.ciph, &rwd, &sk, &resp.server_public_key)?,
+        })
+    }
+
+    /// Blinds the password of `username`, and returns the login request along
+    /// with the state that is required for `login_finish`
+    pub fn login_start(&self, username: &[u8], password: &[u8]) -> (LoginRequest<T>, ClientLoginState<T>) {
+        let input = self.cli.blind(&[oprf_input(username, password)]).remove(0);
+        let (eph_sk, eph_pub) = key_pair(&self.cli.ciph.pog);
+        let mut client_nonce = Vec::new();
+        rand_bytes(NONCE_LENGTH, &mut client_nonce);
+        let req = LoginRequest{
+            blinded_elem: input.elem.clone(),
+            client_nonce: client_nonce,
+            client_eph_pub: eph_pub,
+        };
+        let state = ClientLoginState{
+            username: username.to_vec(),
+            input: input,
+            eph_sk: eph_sk,
+            req: req.clone(),
+        };
+        (req, state)
+    }
+
+    /// Recovers the secret key of the client from the envelope in the server
+    /// response, and verifies the MAC of the server. Returns the message that
+    /// authenticates the client, along with the session key.
+    pub fn login_finish(&self, state: &ClientLoginState<T>, resp: &LoginResponse<T>) -> Result<(LoginFinalization, Vec<u8>), Error> {
+        let ciph = &self.cli.ciph;
+        let pog = &ciph.pog;
+        if !(pog.is_valid)(&resp.server_eph_pub) || resp.server_nonce.len() != NONCE_LENGTH
+                || resp.masking_nonce.len() != NONCE_LENGTH
+                || resp.masked_envelope.len() != envelope_length(pog) {
+            return Err(err_deserialization());
+        }
+        let rwd = self.randomized_password(&state.input, &resp.eval)?;
+        let masking_key = mac(ciph, &rwd, &[MASKING_KEY_LABEL])?;
+        let pad = masking_pad(ciph, &masking_key, &resp.masking_nonce, resp.masked_envelope.len())?;
+        let env = Envelope::from_bytes(pog, &xor(&resp.masked_envelope, &pad))?;
+        let (sk, server_pk) = open(ciph, &rwd, &env)?;
+        let client_pk = (pog.base_mult)(&sk);
+        let tr = transcript(pog, &state.username, &state.req, resp, &client_pk, &server_pk);
+        let dh = [
+            (pog.scalar_mult)(&resp.server_eph_pub, &state.eph_sk),
+            (pog.scalar_mult)(&server_pk, &state.eph_sk),
+            (pog.scalar_mult)(&resp.server_eph_pub, &sk),
+        ];
+        let keys = key_schedule(ciph, &dh, &tr)?;
+        if !bool::from(mac(ciph, &keys.server_mac_key, &[&tr])?.ct_eq(&resp.server_mac)) {
+            return Err(err_key_exchange());
+        }
+        let fin = LoginFinalization{
+            client_mac: mac(ciph, &keys.client_mac_key, &[&tr, &resp.server_mac])?,
+        };
+        Ok((fin, keys.session_key))
+    }
+
+    // unblinds and finalizes the evaluation of the password
+    fn randomized_password(&self, input: &Input<T>, eval: &Evaluation<T>) -> Result<Vec<u8>, Error> {
+        if eval.elems.len() != 1 {
+            return Err(err_evaluation());
+        }
+        let outs = self.cli.unblind(std::slice::from_ref(input), eval)?;
+        self.cli.finalize(&input.data, &outs[0], RWD_INFO)
+    }
+}
+
+// the (V)OPRF input, which binds the password to the username
+fn oprf_input(username: &[u8], password: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_bytes(&mut out, username);
+    out.extend_from_slice(password);
+    out
+}
+
+// seals the secret key of the client and the public key of the server under
+// keys derived from the randomized password
+fn seal<T,H>(ciph: &Ciphersuite<T,H>, rwd: &[u8], sk: &[u8], server_pk: &T) -> Result<Envelope, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    let mut nonce = Vec::new();
+    rand_bytes(NONCE_LENGTH, &mut nonce);
+    let mut pt = sk.to_vec();
+    push_elem(&ciph.pog, &mut pt, server_pk);
+    let ciphertext = xor(&pt, &expand(ciph, rwd, &[ENC_KEY_LABEL, &nonce].concat(), pt.len())?);
+    let tag = envelope_tag(ciph, rwd, &nonce, &ciphertext)?;
+    Ok(Envelope{
+        nonce: nonce,
+        ciphertext: ciphertext,
+        tag: tag,
+    })
+}
+
+// verifies the tag of the envelope, and returns the secret key of the client
+// and the public key of the server
+fn open<T,H>(ciph: &Ciphersuite<T,H>, rwd: &[u8], env: &Envelope) -> Result<(Vec<u8>, T), Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    let pog = &ciph.pog;
+    if env.ciphertext.len() != pog.byte_length + element_length(pog)
+            || !bool::from(envelope_tag(ciph, rwd, &env.nonce, &env.ciphertext)?.ct_eq(&env.tag)) {
+        return Err(err_envelope_recovery());
+    }
+    let key = expand(ciph, rwd, &[ENC_KEY_LABEL, &env.nonce].concat(), env.ciphertext.len())?;
+    let pt = xor(&env.ciphertext, &key);
+    let (sk, pk) = pt.split_at(pog.byte_length);
+    match (pog.deserialize)(pk) {
+        Ok(server_pk) if (pog.is_valid)(&server_pk) => Ok((sk.to_vec(), server_pk)),
+        _ => Err(err_envelope_recovery()),
+    }
+}
+
+// the pad that the envelope is masked with in a login response
+fn masking_pad<T,H>(ciph: &Ciphersuite<T,H>, masking_key: &[u8], nonce: &[u8], len: usize) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    expand(ciph, masking_key, &[MASKING_PAD_LABEL, nonce].concat(), len)
+}
+
+fn envelope_tag<T,H>(ciph: &Ciphersuite<T,H>, rwd: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    let auth_key = mac(ciph, rwd, &[AUTH_KEY_LABEL, nonce])?;
+    mac(ciph, &auth_key, &[nonce, ciphertext])
+}
+
+// the transcript of the key exchange, which the MACs and the session key are
+// bound to (the MAC of the server in `resp` is not included)
+fn transcript<T,H>(pog: &PrimeOrderGroup<T,H>, username: &[u8], req: &LoginRequest<T>,
+        resp: &LoginResponse<T>, client_pk: &T, server_pk: &T) -> Vec<u8> {
+    let mut out = KE_CONTEXT.to_vec();
+    write_bytes(&mut out, username);
+    push_elem(pog, &mut out, client_pk);
+    push_elem(pog, &mut out, server_pk);
+    push_elem(pog, &mut out, &req.blinded_elem);
+    out.extend_from_slice(&req.client_nonce);
+    push_elem(pog, &mut out, &req.client_eph_pub);
+    for elem in &resp.eval.elems {
+        push_elem(pog, &mut out, elem);
+    }
+    if let Some(proof) = &resp.eval.proof {
+        out.extend_from_slice(&proof.to_bytes());
+    }
+    out.extend_from_slice(&resp.masking_nonce);
+    write_bytes(&mut out, &resp.masked_envelope);
+    out.extend_from_slice(&resp.server_nonce);
+    push_elem(pog, &mut out, &resp.server_eph_pub);
+    out
+}
+
+// the keys derived in the key exchange
+struct SessionKeys {
+    server_mac_key: Vec<u8>,
+    client_mac_key: Vec<u8>,
+    session_key: Vec<u8>,
+}
+
+// derives the MAC keys of the server and client, and the session key, from
+// the Diffie-Hellman values and the transcript
+fn key_schedule<T,H>(ciph: &Ciphersuite<T,H>, dh: &[T], tr: &[u8]) -> Result<SessionKeys, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    let mut ikm = Vec::new();
+    for elem in dh {
+        push_elem(&ciph.pog, &mut ikm, elem);
+    }
+    let prk = mac(ciph, KE_CONTEXT, &[&ikm])?;
+    Ok(SessionKeys{
+        server_mac_key: mac(ciph, &prk, &[SERVER_MAC_LABEL, tr])?,
+        client_mac_key: mac(ciph, &prk, &[CLIENT_MAC_LABEL, tr])?,
+        session_key: mac(ciph, &prk, &[SESSION_KEY_LABEL, tr])?,
+    })
+}
+
+// computes the HMAC of the ciphersuite under `key` over the concatenation of
+// `parts`
+fn mac<T,H>(ciph: &Ciphersuite<T,H>, key: &[u8], parts: &[&[u8]]) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    let mut mac = ciph.h2(key)?;
+    for part in parts {
+        mac.input(part);
+    }
+    Ok(mac.result().code().to_vec())
+}
+
+// expands `key` into `len` bytes for `info`, in the style of HKDF-Expand
+fn expand<T,H>(ciph: &Ciphersuite<T,H>, key: &[u8], info: &[u8], len: usize) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    let mut out = Vec::new();
+    let mut block = Vec::new();
+    let mut ctr: u8 = 1;
+    while out.len() < len {
+        block = mac(ciph, key, &[&block, info, &[ctr]])?;
+        out.extend_from_slice(&block);
+        ctr += 1;
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(x, y)| x ^ y).collect()
+}
+
+// samples a scalar and computes the corresponding public key
+fn key_pair<T,H>(pog: &PrimeOrderGroup<T,H>) -> (Vec<u8>, T) {
+    let mut sk = Vec::new();
+    (pog.uniform_bytes)(&mut sk);
+    let pk = (pog.base_mult)(&sk);
+    (sk, pk)
+}
+
+// appends the (compressed) serialization of a group element
+fn push_elem<T,H>(pog: &PrimeOrderGroup<T,H>, out: &mut Vec<u8>, elem: &T) {
+    let mut buf = Vec::new();
+    (pog.serialize)(elem, true, &mut buf);
+    out.extend_from_slice(&buf);
+}
+
+fn write_bytes(out: &mut Vec<u8>, x: &[u8]) {
+    out.write_u32::<BigEndian>(x.len() as u32).unwrap();
+    out.extend_from_slice(x);
+}
+
+// the length of a MAC, which is the output length of the hash function
+fn mac_length<H>() -> usize
+        where H: digest::FixedOutput {
+    H::OutputSize::to_usize()
+}
+
+// the length of an encoded envelope
+fn envelope_length<T,H>(pog: &PrimeOrderGroup<T,H>) -> usize
+        where H: digest::FixedOutput {
+    NONCE_LENGTH + pog.byte_length + element_length(pog) + mac_length::<H>()
+}
+
+// the length of a serialized (compressed) group element
+fn element_length<T,H>(pog: &PrimeOrderGroup<T,H>) -> usize {
+    let mut buf = Vec::new();
+    (pog.serialize)(&pog.generator, true, &mut buf);
+    buf.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::groups::p384::NistPoint;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+
+    fn setup<T,H>(pog: PrimeOrderGroup<T,H>, verifiable: bool) -> (OpaqueServer<T,H>, OpaqueClient<T,H>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let ciph = Ciphersuite::new(pog, verifiable);
+        let srv = OpaqueServer::new(Server::setup(ciph.clone()));
+        let pk = match verifiable {
+            true => Some(srv.oprf_pub_key()),
+            false => None,
+        };
+        (srv, OpaqueClient::new(Client::setup(ciph, pk).unwrap()))
+    }
+
+    fn register<T,H>(srv: &OpaqueServer<T,H>, cli: &OpaqueClient<T,H>, username: &[u8], password: &[u8]) -> RegistrationRecord<T>
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let (req, input) = cli.registration_start(username, password);
+        let resp = srv.registration_response(&req).unwrap();
+        cli.registration_finish(&input, &resp).unwrap()
+    }
+
+    fn login<T,H>(pog: PrimeOrderGroup<T,H>, verifiable: bool)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let (srv, cli) = setup(pog.clone(), verifiable);
+        let record = register(&srv, &cli, b"alice", b"hunter2");
+        // the server stores the encoded record
+        let record = RegistrationRecord::from_bytes(&pog, &record.to_bytes(&pog)).unwrap();
+
+        let (req, state) = cli.login_start(b"alice", b"hunter2");
+        let (resp, srv_state) = srv.login_start(b"alice", &record, &req).unwrap();
+        let (fin, client_key) = cli.login_finish(&state, &resp).unwrap();
+        let server_key = srv.login_finish(&srv_state, &fin).unwrap();
+        assert_eq!(client_key, server_key);
+
+        // a new login derives a new session key
+        let (req, state) = cli.login_start(b"alice", b"hunter2");
+        let (resp, srv_state) = srv.login_start(b"alice", &record, &req).unwrap();
+        let (fin, next_key) = cli.login_finish(&state, &resp).unwrap();
+        assert_eq!(srv.login_finish(&srv_state, &fin).unwrap(), next_key);
+        assert_ne!(next_key, client_key);
+    }
+
+    #[test]
+    fn login_ristretto() {
+        login(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        login(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+    }
+
+    #[test]
+    fn login_p384() {
+        login(PrimeOrderGroup::<NistPoint,Sha512>::p384(), true);
+    }
+
+    #[test]
+    fn reject_wrong_password() {
+        let (srv, cli) = setup(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        let record = register(&srv, &cli, b"alice", b"hunter2");
+        for (username, password) in &[(&b"alice"[..], &b"hunter3"[..]), (&b"bob"[..], &b"hunter2"[..])] {
+            let (req, state) = cli.login_start(username, password);
+            let (resp, _) = srv.login_start(username, &record, &req).unwrap();
+            assert!(cli.login_finish(&state, &resp).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_unregistered_username() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let (srv, cli) = setup(pog.clone(), true);
+        let record = srv.dummy_record(b"carol").unwrap();
+        let (req, state) = cli.login_start(b"carol", b"hunter2");
+        let (resp, _) = srv.login_start(b"carol", &record, &req).unwrap();
+        assert!(cli.login_finish(&state, &resp).is_err());
+
+        // the dummy record is the same for each login with a username, and
+        // looks like a registered record
+        let again = srv.dummy_record(b"carol").unwrap();
+        assert_eq!(again.to_bytes(&pog), record.to_bytes(&pog));
+        assert_ne!(srv.dummy_record(b"dave").unwrap().to_bytes(&pog), record.to_bytes(&pog));
+        let real = register(&srv, &cli, b"alice", b"hunter2");
+        assert_eq!(record.to_bytes(&pog).len(), real.to_bytes(&pog).len());
+
+        // the envelope is masked differently in each login
+        let (req, _) = cli.login_start(b"carol", b"hunter2");
+        let (next, _) = srv.login_start(b"carol", &record, &req).unwrap();
+        assert_eq!(next.masked_envelope.len(), resp.masked_envelope.len());
+        assert_ne!(next.masked_envelope, resp.masked_envelope);
+    }
+
+    #[test]
+    fn reject_tampered_messages() {
+        let (srv, cli) = setup(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        let record = register(&srv, &cli, b"alice", b"hunter2");
+
+        // the client rejects a modified server response
+        let (req, state) = cli.login_start(b"alice", b"hunter2");
+        let (resp, srv_state) = srv.login_start(b"alice", &record, &req).unwrap();
+        let mut bad = resp.clone();
+        bad.server_mac[0] ^= 1;
+        assert!(cli.login_finish(&state, &bad).is_err());
+        let mut bad = resp.clone();
+        bad.server_nonce[0] ^= 1;
+        assert!(cli.login_finish(&state, &bad).is_err());
+        let mut bad = resp.clone();
+        bad.masked_envelope[NONCE_LENGTH] ^= 1;
+        assert!(cli.login_finish(&state, &bad).is_err());
+        let mut bad = resp.clone();
+        bad.masking_nonce[0] ^= 1;
+        assert!(cli.login_finish(&state, &bad).is_err());
+
+        // the server rejects a modified client finalization
+        let (mut fin, _) = cli.login_finish(&state, &resp).unwrap();
+        fin.client_mac[0] ^= 1;
+        assert!(srv.login_finish(&srv_state, &fin).is_err());
+
+        // the server rejects a login for a different username
+        let (req, state) = cli.login_start(b"alice", b"hunter2");
+        let (resp, _) = srv.login_start(b"bob", &record, &req).unwrap();
+        assert!(cli.login_finish(&state, &resp).is_err());
+    }
+
+    #[test]
+    fn record_encoding() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let (srv, cli) = setup(pog.clone(), false);
+        let record = register(&srv, &cli, b"alice", b"hunter2");
+        let buf = record.to_bytes(&pog);
+        let parsed = RegistrationRecord::from_bytes(&pog, &buf).unwrap();
+        assert_eq!(parsed.envelope, record.envelope);
+        assert_eq!(parsed.to_bytes(&pog), buf);
+        assert!(RegistrationRecord::from_bytes(&pog, &buf[..buf.len()-64]).is_err());
+        assert!(RegistrationRecord::from_bytes(&pog, &buf[..16]).is_err());
+
+        // the tag must have the length of a MAC
+        let env = record.envelope.to_bytes();
+        assert!(Envelope::from_bytes(&pog, &env[..env.len()-1]).is_err());
+        assert!(Envelope::from_bytes(&pog, &[&env[..], &[0]].concat()).is_err());
+        assert!(RegistrationRecord::from_bytes(&pog, &[&buf[..], &[0]].concat()).is_err());
+    }
+}
@@ -0,0 +1,508 @@
+//! The any module provides runtime counterparts of the generic (V)OPRF types,
+//! for when the ciphersuite is only known at runtime (e.g. when it is named in
+//! a configuration file). `AnyCiphersuite`, `AnyServer` and `AnyClient` are
+//! enums with a variant for each supported group, and each of their methods
+//! dispatches to the corresponding generic instance. Group elements are passed
+//! in and out in their serialized form.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::any::{AnyCiphersuite,AnyServer,AnyClient};
+//!
+//! // list the names of all supported ciphersuites
+//! let names = AnyCiphersuite::supported_names();
+//! assert!(names.contains(&"VOPRF-ristretto255-HKDF-SHA512-ELL2-RO".to_string()));
+//!
+//! // set up a server and client from the name of the ciphersuite
+//! let ciph = AnyCiphersuite::from_name("VOPRF-ristretto255-HKDF-SHA512-ELL2-RO").unwrap();
+//! let srv = AnyServer::setup(ciph.clone());
+//! let cli = AnyClient::setup(ciph, Some(srv.pub_key_hex())).unwrap();
+//!
+//! // run the (V)OPRF protocol
+//! let inputs = cli.blind(&[b"some_input".to_vec()]);
+//! let elems: Vec<Vec<u8>> = inputs.iter().map(|i| i.elem.clone()).collect();
+//! let eval = srv.eval(&elems).unwrap();
+//! let outs = cli.unblind(&inputs, &eval).unwrap();
+//! let _ = cli.finalize(&inputs[0].data, &outs[0], b"auxiliary_data").unwrap();
+//! ```
+use std::io::Error;
+
+use sha2::{Sha384,Sha512};
+use curve25519_dalek::ristretto::RistrettoPoint;
+
+use super::{Server,Client,Input,Evaluation,PublicKey};
+use super::ciphersuite::{Ciphersuite,ProofVersion,Supported};
+use super::groups::PrimeOrderGroup;
+use super::proof::Proof;
+use super::groups::p384::NistPoint;
+use super::groups::p384_sha384::P384Point;
+use crate::errors::{err_deserialization,err_invalid_config,err_unsupported_ciphersuite};
+
+/// The identifiers of the supported groups, as used for selecting a group on
+/// the command line or in configuration files
+pub const SUPPORTED_GROUPS: [&str; 3] = ["P384", "P384-SHA384", "ristretto255"];
+
+/// The `CiphersuiteVisitor` trait allows running generic code over a
+/// ciphersuite that is only chosen at runtime, see `AnyCiphersuite::visit`.
+pub trait CiphersuiteVisitor {
+    /// The value returned by the visitor
+    type Output;
+
+    /// Called with the generic instance of the ciphersuite
+    fn visit<T,H>(self, ciph: Ciphersuite<T,H>) -> Self::Output
+            where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+            H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+            + digest::Reset + Clone + 'static;
+}
+
+/// `AnyCiphersuite` holds one of the supported `Ciphersuite<T,H>` instances
+#[derive(Clone)]
+pub enum AnyCiphersuite {
+    /// A ciphersuite over the NIST P-384 curve
+    P384(Ciphersuite<NistPoint,Sha512>),
+    /// A ciphersuite of RFC 9497 over the NIST P-384 curve with SHA-384
+    P384Sha384(Ciphersuite<P384Point,Sha384>),
+    /// A ciphersuite over ristretto255 (EXPERIMENTAL)
+    Ristretto255(Ciphersuite<RistrettoPoint,Sha512>),
+}
+
+impl AnyCiphersuite {
+    /// Constructs the ciphersuite with the given name (e.g.
+    /// `VOPRF-P384-HKDF-SHA512-SSWU-RO`)
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        if let Ok(ciph) = Ciphersuite::from_name(name) {
+            return Ok(AnyCiphersuite::P384(ciph));
+        }
+        if let Ok(ciph) = Ciphersuite::from_name(name) {
+            return Ok(AnyCiphersuite::P384Sha384(ciph));
+        }
+        if let Ok(ciph) = Ciphersuite::from_name(name) {
+            return Ok(AnyCiphersuite::Ristretto255(ciph));
+        }
+        Err(err_unsupported_ciphersuite())
+    }
+
+    /// Constructs the ciphersuite over the group with the given identifier
+    /// (see `SUPPORTED_GROUPS`)
+    pub fn from_group(group: &str, verifiable: bool) -> Result<Self, Error> {
+        match group {
+            "P384" => Ok(AnyCiphersuite::P384(Ciphersuite::new(PrimeOrderGroup::p384(), verifiable))),
+            "P384-SHA384" => Ok(AnyCiphersuite::P384Sha384(Ciphersuite::new(PrimeOrderGroup::p384_sha384(), verifiable))),
+            "ristretto255" => Ok(AnyCiphersuite::Ristretto255(Ciphersuite::new(PrimeOrderGroup::ristretto_255(), verifiable))),
+            _ => Err(err_unsupported_ciphersuite()),
+        }
+    }
+
+    /// Returns the ciphersuite with DLEQ proofs of the given version, bound to
+    /// `context` (see `Ciphersuite::with_proof_version` and
+    /// `Ciphersuite::with_context`)
+    pub fn with_proofs(self, version: ProofVersion, context: &[u8]) -> Self {
+        match self {
+            AnyCiphersuite::P384(c) => AnyCiphersuite::P384(c.with_proof_version(version).with_context(context)),
+            AnyCiphersuite::P384Sha384(c) => AnyCiphersuite::P384Sha384(c.with_proof_version(version).with_context(context)),
+            AnyCiphersuite::Ristretto255(c) => AnyCiphersuite::Ristretto255(c.with_proof_version(version).with_context(context)),
+        }
+    }
+
+    /// Returns every supported ciphersuite, in both OPRF and VOPRF modes
+    pub fn supported() -> Vec<Self> {
+        let mut out = Vec::new();
+        for group in SUPPORTED_GROUPS.iter() {
+            for verifiable in &[false, true] {
+                out.push(AnyCiphersuite::from_group(group, *verifiable).unwrap());
+            }
+        }
+        out
+    }
+
+    /// Returns the names of every supported ciphersuite
+    pub fn supported_names() -> Vec<String> {
+        AnyCiphersuite::supported().iter().map(|c| c.name().to_string()).collect()
+    }
+
+    /// The name of the ciphersuite
+    pub fn name(&self) -> &str {
+        match self {
+            AnyCiphersuite::P384(c) => &c.name,
+            AnyCiphersuite::P384Sha384(c) => &c.name,
+            AnyCiphersuite::Ristretto255(c) => &c.name,
+        }
+    }
+
+    /// Whether the ciphersuite is verifiable (VOPRF) or not (OPRF)
+    pub fn verifiable(&self) -> bool {
+        match self {
+            AnyCiphersuite::P384(c) => c.verifiable,
+            AnyCiphersuite::P384Sha384(c) => c.verifiable,
+            AnyCiphersuite::Ristretto255(c) => c.verifiable,
+        }
+    }
+
+    /// The version of the DLEQ proofs of the ciphersuite
+    pub fn proof_version(&self) -> ProofVersion {
+        match self {
+            AnyCiphersuite::P384(c) => c.proof_version,
+            AnyCiphersuite::P384Sha384(c) => c.proof_version,
+            AnyCiphersuite::Ristretto255(c) => c.proof_version,
+        }
+    }
+
+    /// The context that the DLEQ proofs of the ciphersuite are bound to
+    pub fn context(&self) -> &[u8] {
+        match self {
+            AnyCiphersuite::P384(c) => &c.context,
+            AnyCiphersuite::P384Sha384(c) => &c.context,
+            AnyCiphersuite::Ristretto255(c) => &c.context,
+        }
+    }
+
+    /// The identifier of the group that the ciphersuite uses
+    pub fn group(&self) -> &'static str {
+        match self {
+            AnyCiphersuite::P384(_) => SUPPORTED_GROUPS[0],
+            AnyCiphersuite::P384Sha384(_) => SUPPORTED_GROUPS[1],
+            AnyCiphersuite::Ristretto255(_) => SUPPORTED_GROUPS[2],
+        }
+    }
+
+    /// Runs `visitor` over the generic instance of the ciphersuite
+    pub fn visit<V: CiphersuiteVisitor>(self, visitor: V) -> V::Output {
+        match self {
+            AnyCiphersuite::P384(c) => visitor.visit(c),
+            AnyCiphersuite::P384Sha384(c) => visitor.visit(c),
+            AnyCiphersuite::Ristretto255(c) => visitor.visit(c),
+        }
+    }
+}
+
+/// `AnyServer` holds a (V)OPRF `Server<T,H>` for one of the supported
+/// ciphersuites
+#[derive(Clone)]
+pub enum AnyServer {
+    /// A server over the NIST P-384 curve
+    P384(Server<NistPoint,Sha512>),
+    /// A server of RFC 9497 over the NIST P-384 curve with SHA-384
+    P384Sha384(Server<P384Point,Sha384>),
+    /// A server over ristretto255 (EXPERIMENTAL)
+    Ristretto255(Server<RistrettoPoint,Sha512>),
+}
+
+impl AnyServer {
+    /// Creates a server with a freshly sampled key
+    pub fn setup(ciph: AnyCiphersuite) -> Self {
+        match ciph {
+            AnyCiphersuite::P384(c) => AnyServer::P384(Server::setup(c)),
+            AnyCiphersuite::P384Sha384(c) => AnyServer::P384Sha384(Server::setup(c)),
+            AnyCiphersuite::Ristretto255(c) => AnyServer::Ristretto255(Server::setup(c)),
+        }
+    }
+
+    /// Creates a server with the provided secret key, returns an error if the
+    /// key is not the canonical encoding of a non-zero scalar
+    pub fn with_key(ciph: AnyCiphersuite, key: Vec<u8>) -> Result<Self, Error> {
+        let mut srv = AnyServer::setup(ciph);
+        match &mut srv {
+            AnyServer::P384(s) => set_key(s, key)?,
+            AnyServer::P384Sha384(s) => set_key(s, key)?,
+            AnyServer::Ristretto255(s) => set_key(s, key)?,
+        }
+        Ok(srv)
+    }
+
+    /// The name of the ciphersuite run by the server
+    pub fn name(&self) -> &str {
+        match self {
+            AnyServer::P384(s) => &s.ciph.name,
+            AnyServer::P384Sha384(s) => &s.ciph.name,
+            AnyServer::Ristretto255(s) => &s.ciph.name,
+        }
+    }
+
+    /// The hex-encoded public key of the server
+    pub fn pub_key_hex(&self) -> String {
+        match self {
+            AnyServer::P384(s) => s.key.pub_key(&s.ciph.pog).as_hex(&s.ciph.pog),
+            AnyServer::P384Sha384(s) => s.key.pub_key(&s.ciph.pog).as_hex(&s.ciph.pog),
+            AnyServer::Ristretto255(s) => s.key.pub_key(&s.ciph.pog).as_hex(&s.ciph.pog),
+        }
+    }
+
+    /// Evaluates the (V)OPRF over serialized blinded group elements, and
+    /// returns the serialized evaluations
+    pub fn eval(&self, elems: &[Vec<u8>]) -> Result<Evaluation<Vec<u8>>, Error> {
+        match self {
+            AnyServer::P384(s) => eval(s, elems),
+            AnyServer::P384Sha384(s) => eval(s, elems),
+            AnyServer::Ristretto255(s) => eval(s, elems),
+        }
+    }
+}
+
+/// `AnyClient` holds a (V)OPRF `Client<T,H>` for one of the supported
+/// ciphersuites
+#[derive(Clone)]
+pub enum AnyClient {
+    /// A client over the NIST P-384 curve
+    P384(Client<NistPoint,Sha512>),
+    /// A client of RFC 9497 over the NIST P-384 curve with SHA-384
+    P384Sha384(Client<P384Point,Sha384>),
+    /// A client over ristretto255 (EXPERIMENTAL)
+    Ristretto255(Client<RistrettoPoint,Sha512>),
+}
+
+impl AnyClient {
+    /// Creates a client, with the hex-encoded public key of the server if the
+    /// ciphersuite is verifiable
+    pub fn setup(ciph: AnyCiphersuite, pub_key: Option<String>) -> Result<Self, Error> {
+        match ciph {
+            AnyCiphersuite::P384(c) => Ok(AnyClient::P384(client_setup(c, pub_key)?)),
+            AnyCiphersuite::P384Sha384(c) => Ok(AnyClient::P384Sha384(client_setup(c, pub_key)?)),
+            AnyCiphersuite::Ristretto255(c) => Ok(AnyClient::Ristretto255(client_setup(c, pub_key)?)),
+        }
+    }
+
+    /// The name of the ciphersuite run by the client
+    pub fn name(&self) -> &str {
+        match self {
+            AnyClient::P384(c) => &c.ciph.name,
+            AnyClient::P384Sha384(c) => &c.ciph.name,
+            AnyClient::Ristretto255(c) => &c.ciph.name,
+        }
+    }
+
+    /// Blinds each of the inputs, the blinded group elements are returned in
+    /// serialized form
+    pub fn blind(&self, inputs: &[Vec<u8>]) -> Vec<Input<Vec<u8>>> {
+        match self {
+            AnyClient::P384(c) => blind(c, inputs),
+            AnyClient::P384Sha384(c) => blind(c, inputs),
+            AnyClient::Ristretto255(c) => blind(c, inputs),
+        }
+    }
+
+    /// Unblinds (and verifies, if the ciphersuite is verifiable) the
+    /// serialized server evaluation, and returns the serialized unblinded
+    /// group elements
+    pub fn unblind(&self, inputs: &[Input<Vec<u8>>], eval: &Evaluation<Vec<u8>>) -> Result<Vec<Vec<u8>>, Error> {
+        match self {
+            AnyClient::P384(c) => unblind(c, inputs, eval),
+            AnyClient::P384Sha384(c) => unblind(c, inputs, eval),
+            AnyClient::Ristretto255(c) => unblind(c, inputs, eval),
+        }
+    }
+
+    /// Computes the final (V)OPRF output for the input `input_data` from the
+    /// serialized unblinded group element `elem`
+    pub fn finalize(&self, input_data: &[u8], elem: &[u8], aux: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            AnyClient::P384(c) => c.finalize(input_data, &(c.ciph.pog.deserialize)(elem)?, aux),
+            AnyClient::P384Sha384(c) => c.finalize(input_data, &(c.ciph.pog.deserialize)(elem)?, aux),
+            AnyClient::Ristretto255(c) => c.finalize(input_data, &(c.ciph.pog.deserialize)(elem)?, aux),
+        }
+    }
+}
+
+fn set_key<T,H>(srv: &mut Server<T,H>, key: Vec<u8>) -> Result<(), Error>
+        where T: Clone, H: Clone {
+    // rejects keys of the wrong length, zero keys and keys that are not
+    // reduced modulo the group order
+    if !(srv.ciph.pog.is_valid_scalar)(&key) {
+        return Err(err_invalid_config());
+    }
+    srv.set_key(key);
+    Ok(())
+}
+
+fn eval<T,H>(srv: &Server<T,H>, elems: &[Vec<u8>]) -> Result<Evaluation<Vec<u8>>, Error>
+        where T: Clone, H: Clone {
+    let pog = &srv.ciph.pog;
+    let elems = deserialize_all(pog, elems)?;
+    let ev = srv.eval(&elems)?;
+    Ok(Evaluation{
+        elems: serialize_all(pog, &ev.elems),
+        proof: ev.proof.map(|p| p.cast()),
+    })
+}
+
+fn client_setup<T,H>(ciph: Ciphersuite<T,H>, pub_key: Option<String>) -> Result<Client<T,H>, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    let pk = match pub_key {
+        Some(s) => {
+            let buf = match hex::decode(s) {
+                Ok(b) => b,
+                Err(_) => return Err(err_deserialization()),
+            };
+            Some(PublicKey((ciph.pog.deserialize)(&buf)?))
+        },
+        None => None,
+    };
+    Client::setup(ciph, pk)
+}
+
+fn blind<T,H>(cli: &Client<T,H>, inputs: &[Vec<u8>]) -> Vec<Input<Vec<u8>>>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    let pog = &cli.ciph.pog;
+    cli.blind(inputs).into_iter().map(|inp| Input{
+        data: inp.data,
+        elem: serialize(pog, &inp.elem),
+        blind: inp.blind,
+        inv_blind: inp.inv_blind,
+    }).collect()
+}
+
+fn unblind<T,H>(cli: &Client<T,H>, inputs: &[Input<Vec<u8>>], eval: &Evaluation<Vec<u8>>) -> Result<Vec<Vec<u8>>, Error>
+        where PrimeOrderGroup<T,H>: Supported, T: Clone, H: Default
+        + digest::Input + digest::BlockInput + digest::FixedOutput
+        + digest::Reset + Clone {
+    let pog = &cli.ciph.pog;
+    let mut typed_inputs = Vec::new();
+    for inp in inputs {
+        typed_inputs.push(Input{
+            data: inp.data.clone(),
+            elem: (pog.deserialize)(&inp.elem)?,
+            blind: inp.blind.clone(),
+            inv_blind: inp.inv_blind.clone(),
+        });
+    }
+    let proof = match &eval.proof {
+        Some(p) => Some(Proof::from_bytes(pog, &p.to_bytes())?),
+        None => None,
+    };
+    let typed_eval = Evaluation{
+        elems: deserialize_all(pog, &eval.elems)?,
+        proof: proof,
+    };
+    let outs = cli.unblind(&typed_inputs, &typed_eval)?;
+    Ok(serialize_all(pog, &outs))
+}
+
+fn serialize<T,H>(pog: &PrimeOrderGroup<T,H>, elem: &T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    (pog.serialize)(elem, true, &mut buf);
+    buf
+}
+
+fn serialize_all<T,H>(pog: &PrimeOrderGroup<T,H>, elems: &[T]) -> Vec<Vec<u8>> {
+    elems.iter().map(|e| serialize(pog, e)).collect()
+}
+
+fn deserialize_all<T,H>(pog: &PrimeOrderGroup<T,H>, elems: &[Vec<u8>]) -> Result<Vec<T>, Error> {
+    elems.iter().map(|e| (pog.deserialize)(e)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnyCiphersuite,AnyServer,AnyClient,CiphersuiteVisitor,Ciphersuite,ProofVersion,Supported,PrimeOrderGroup};
+
+    #[test]
+    fn supported_names() {
+        let names = AnyCiphersuite::supported_names();
+        assert_eq!(names, vec![
+            "OPRF-P384-HKDF-SHA512-SSWU-RO",
+            "VOPRF-P384-HKDF-SHA512-SSWU-RO",
+            "OPRF-P384-SHA384",
+            "VOPRF-P384-SHA384",
+            "OPRF-ristretto255-HKDF-SHA512-ELL2-RO",
+            "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO",
+        ]);
+        for name in names {
+            let ciph = AnyCiphersuite::from_name(&name).unwrap();
+            assert_eq!(ciph.name(), name);
+            assert_eq!(ciph.verifiable(), name.starts_with("VOPRF-"));
+        }
+    }
+
+    #[test]
+    fn from_group() {
+        let ciph = AnyCiphersuite::from_group("ristretto255", true).unwrap();
+        assert_eq!(ciph.name(), "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO");
+        assert_eq!(ciph.group(), "ristretto255");
+        assert!(AnyCiphersuite::from_group("P256", true).is_err());
+        assert!(AnyCiphersuite::from_name("VOPRF-P256-HKDF-SHA256-SSWU-RO").is_err());
+        assert_eq!(ciph.proof_version(), ProofVersion::V1);
+        let ciph = ciph.with_proofs(ProofVersion::Legacy, &[]);
+        assert_eq!(ciph.proof_version(), ProofVersion::Legacy);
+        let ciph = ciph.with_proofs(ProofVersion::V1, b"context");
+        assert_eq!(ciph.proof_version(), ProofVersion::V1);
+        assert_eq!(ciph.context(), b"context");
+    }
+
+    #[test]
+    fn visit_ciphersuite() {
+        struct Name;
+        impl CiphersuiteVisitor for Name {
+            type Output = String;
+            fn visit<T,H>(self, ciph: Ciphersuite<T,H>) -> String
+                    where PrimeOrderGroup<T,H>: Supported, T: Clone + Send + Sync + 'static,
+                    H: Default + digest::Input + digest::BlockInput + digest::FixedOutput
+                    + digest::Reset + Clone + 'static {
+                ciph.name
+            }
+        }
+        let ciph = AnyCiphersuite::from_group("ristretto255", false).unwrap();
+        assert_eq!(ciph.visit(Name), "OPRF-ristretto255-HKDF-SHA512-ELL2-RO");
+    }
+
+    #[test]
+    fn end_to_end_ristretto() {
+        end_to_end("OPRF-ristretto255-HKDF-SHA512-ELL2-RO", b"aux");
+        end_to_end("VOPRF-ristretto255-HKDF-SHA512-ELL2-RO", b"aux");
+    }
+
+    #[test]
+    fn end_to_end_p384() {
+        end_to_end("OPRF-P384-HKDF-SHA512-SSWU-RO", b"aux");
+        end_to_end("VOPRF-P384-HKDF-SHA512-SSWU-RO", b"aux");
+    }
+
+    #[test]
+    fn end_to_end_p384_sha384() {
+        // RFC 9497 has no auxiliary data
+        end_to_end("OPRF-P384-SHA384", b"");
+        end_to_end("VOPRF-P384-SHA384", b"");
+    }
+
+    #[test]
+    fn server_with_key() {
+        let ciph = AnyCiphersuite::from_group("ristretto255", true).unwrap();
+        let srv = AnyServer::with_key(ciph.clone(), vec![1; 32]).unwrap();
+        let same = AnyServer::with_key(ciph.clone(), vec![1; 32]).unwrap();
+        assert_eq!(srv.pub_key_hex(), same.pub_key_hex());
+        assert!(AnyServer::with_key(ciph.clone(), vec![1; 31]).is_err());
+        assert!(AnyServer::with_key(ciph.clone(), vec![0; 32]).is_err());
+        assert!(AnyServer::with_key(ciph, vec![0xff; 32]).is_err());
+    }
+
+    #[test]
+    fn client_invalid_pub_key() {
+        let ciph = AnyCiphersuite::from_group("ristretto255", true).unwrap();
+        assert!(AnyClient::setup(ciph.clone(), None).is_err());
+        assert!(AnyClient::setup(ciph.clone(), Some("zz".to_string())).is_err());
+        assert!(AnyClient::setup(ciph, Some("ff".repeat(32))).is_err());
+    }
+
+    fn end_to_end(name: &str, aux: &[u8]) {
+        let ciph = AnyCiphersuite::from_name(name).unwrap();
+        let srv = AnyServer::setup(ciph.clone());
+        let cli = AnyClient::setup(ciph, Some(srv.pub_key_hex())).unwrap();
+        assert_eq!(srv.name(), name);
+        assert_eq!(cli.name(), name);
+        let inputs = cli.blind(&[vec![1; 32], vec![2; 32]]);
+        let elems: Vec<Vec<u8>> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let eval = srv.eval(&elems).unwrap();
+        let outs = cli.unblind(&inputs, &eval).unwrap();
+        assert_eq!(outs.len(), 2);
+        let out = cli.finalize(&inputs[0].data, &outs[0], aux).unwrap();
+
+        // the output does not depend on the blind
+        let again = cli.blind(&[vec![1; 32]]);
+        let eval = srv.eval(&[again[0].elem.clone()]).unwrap();
+        let outs = cli.unblind(&again, &eval).unwrap();
+        assert_eq!(cli.finalize(&again[0].data, &outs[0], aux).unwrap(), out);
+    }
+}
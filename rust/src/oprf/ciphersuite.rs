@@ -5,20 +5,115 @@
 //! with the functions `H1, ..., H5` required in the specification. See
 //! https://tools.ietf.org/html/draft-irtf-cfrg-voprf-02#section-6 for a full
 //! list of supported ciphersuites in the spec.
+//!
+//! By default, the DLEQ proofs of a verifiable ciphersuite are bound to the
+//! name of the ciphersuite, the protocol mode and an application-supplied
+//! context (`ProofVersion::V1`, see `Ciphersuite::dleq_domain`), so that a
+//! proof generated for one ciphersuite or deployment is not valid in another.
+//! The unbound proofs of draft-irtf-cfrg-voprf-02 (`ProofVersion::Legacy`)
+//! remain available with `Ciphersuite::with_proof_version`, for peers that
+//! predate proof versions and for the test vectors. Both peers must use the
+//! same proof version and context, which servers publish in their key
+//! directory (see `http::directory`).
+//!
+//! The `P384-SHA384` ciphersuites (over `PrimeOrderGroup::p384_sha384`)
+//! instead follow [RFC 9497](https://www.rfc-editor.org/rfc/rfc9497.html)
+//! (`Spec::Rfc9497`): elements are hashed to the group, outputs are finalized
+//! and DLEQ proofs are generated as specified there, under the context string
+//! of the ciphersuite. The proof version and the context are ignored for these
+//! ciphersuites.
 
 use hmac::{Hmac,Mac};
 use digest::Digest;
 
 // supported primitives
-use sha2::Sha512;
+use sha2::{Sha384,Sha512};
 use super::groups::PrimeOrderGroup;
+use super::dleq::Domain;
 use curve25519_dalek::ristretto::RistrettoPoint;
 use super::groups::p384::NistPoint;
+use super::groups::p384_sha384::P384Point;
 use hkdf_sha512::Hkdf;
 use super::super::utils::copy_into;
+use byteorder::{BigEndian,WriteBytesExt};
 
+use std::convert::TryFrom;
 use std::io::Error;
-use super::super::errors::err_finalization;
+use super::super::errors::{err_finalization,err_unsupported_ciphersuite,err_deserialization,err_serialization};
+
+const VOPRF_PREFIX: &str = "VOPRF-";
+const OPRF_PREFIX: &str = "OPRF-";
+const DLEQ_LABEL: &[u8] = b"voprf_dleq_v1";
+const MODE_OPRF: u8 = 0x00;
+const MODE_VOPRF: u8 = 0x01;
+const RFC9497_CONTEXT_PREFIX: &[u8] = b"OPRFV1-";
+const RFC9497_HASH_TO_GROUP_DST: &[u8] = b"HashToGroup-";
+
+/// The specification that a ciphersuite follows
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Spec {
+    /// draft-irtf-cfrg-voprf-02, with the DLEQ proofs of the `ProofVersion` of
+    /// the ciphersuite
+    Draft02,
+    /// RFC 9497
+    Rfc9497,
+}
+
+/// The version of the DLEQ proofs that are generated and accepted by a
+/// ciphersuite
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ProofVersion {
+    /// Proofs of draft-irtf-cfrg-voprf-02, which are not bound to the
+    /// ciphersuite or a context. Only intended for verifying old proofs and
+    /// test vectors.
+    Legacy,
+    /// Proofs bound to the name of the ciphersuite, the protocol mode and the
+    /// context
+    V1,
+}
+
+impl ProofVersion {
+    /// The name of the version, as used on the command line and in the key
+    /// directory
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProofVersion::Legacy => "legacy",
+            ProofVersion::V1 => "v1",
+        }
+    }
+
+    /// Returns the version with the given name (see `name`)
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "legacy" => Ok(ProofVersion::Legacy),
+            "v1" => Ok(ProofVersion::V1),
+            _ => Err(err_deserialization()),
+        }
+    }
+
+    /// The one-byte identifier of the version, as used in stored states
+    pub fn id(&self) -> u8 {
+        match self {
+            ProofVersion::Legacy => 0,
+            ProofVersion::V1 => 1,
+        }
+    }
+
+    /// Returns the version with the given identifier (see `id`)
+    pub fn from_id(id: u8) -> Result<Self, Error> {
+        match id {
+            0 => Ok(ProofVersion::Legacy),
+            1 => Ok(ProofVersion::V1),
+            _ => Err(err_deserialization()),
+        }
+    }
+}
+
+impl Default for ProofVersion {
+    fn default() -> Self {
+        ProofVersion::V1
+    }
+}
 
 /// The Supported trait defines the `PrimeOrderGroup<T,H>` instantiations that
 /// are currently supported by the VOPRF implementation. Currently, only
@@ -28,18 +123,46 @@ use super::super::errors::err_finalization;
 pub trait Supported {
     /// Returns the string identifier for the supported group
     fn name(&self) -> String;
+    /// Returns the instantiation of the supported group
+    fn instance() -> Self where Self: Sized;
+    /// Returns the specification that the ciphersuites over the group follow
+    fn spec(&self) -> Spec {
+        Spec::Draft02
+    }
 }
 
 impl Supported for PrimeOrderGroup<RistrettoPoint,Sha512> {
     fn name(&self) -> String {
         String::from("ristretto255-HKDF-SHA512-ELL2-RO")
     }
+
+    fn instance() -> Self {
+        PrimeOrderGroup::ristretto_255()
+    }
 }
 
 impl Supported for PrimeOrderGroup<NistPoint,Sha512> {
     fn name(&self) -> String {
         String::from("P384-HKDF-SHA512-SSWU-RO")
     }
+
+    fn instance() -> Self {
+        PrimeOrderGroup::p384()
+    }
+}
+
+impl Supported for PrimeOrderGroup<P384Point,Sha384> {
+    fn name(&self) -> String {
+        String::from("P384-SHA384")
+    }
+
+    fn instance() -> Self {
+        PrimeOrderGroup::p384_sha384()
+    }
+
+    fn spec(&self) -> Spec {
+        Spec::Rfc9497
+    }
 }
 
 // Returns the name of the primitive set if it is supported
@@ -94,7 +217,72 @@ pub struct Ciphersuite<T,H>
     /// not (OPRF only).
     pub verifiable: bool,
     /// The PrimeOrderGroup instantiation that the ciphersuite corresponds to
-    pub pog: PrimeOrderGroup<T,H>
+    pub pog: PrimeOrderGroup<T,H>,
+    /// Application-supplied context that DLEQ proofs are bound to
+    pub context: Vec<u8>,
+    /// The version of DLEQ proofs
+    pub proof_version: ProofVersion,
+    /// The specification that the ciphersuite follows
+    pub spec: Spec,
+    /// The hash function of the group over byte strings
+    pub digest: fn(&[u8]) -> Vec<u8>,
+}
+
+impl<T,H> Ciphersuite<T,H>
+        where PrimeOrderGroup<T,H>: Clone {
+    /// Returns the ciphersuite with DLEQ proofs bound to `context`. The context
+    /// is only bound by `ProofVersion::V1` proofs.
+    pub fn with_context(mut self, context: &[u8]) -> Self {
+        self.context = context.to_vec();
+        self
+    }
+
+    /// Returns the ciphersuite with DLEQ proofs of the given version
+    pub fn with_proof_version(mut self, version: ProofVersion) -> Self {
+        self.proof_version = version;
+        self
+    }
+
+    /// Returns the domain of the DLEQ proofs of the ciphersuite. For
+    /// `ProofVersion::V1`, the challenge and the batching coefficients are
+    /// bound to the transcript `name || mode || context`, where `name` and
+    /// `context` are prefixed with their two-byte lengths. Returns an error if
+    /// the context is too long.
+    pub fn dleq_domain(&self) -> Result<Domain, Error> {
+        if self.spec == Spec::Rfc9497 {
+            return Ok(Domain::Rfc9497{
+                context_string: self.context_string(),
+                hash: self.digest,
+            });
+        }
+        match self.proof_version {
+            ProofVersion::Legacy => Ok(Domain::Legacy),
+            ProofVersion::V1 => {
+                let name_len = u16::try_from(self.name.len()).map_err(|_| err_serialization())?;
+                let context_len = u16::try_from(self.context.len()).map_err(|_| err_serialization())?;
+                let mut transcript = Vec::new();
+                transcript.write_u16::<BigEndian>(name_len)?;
+                transcript.extend_from_slice(self.name.as_bytes());
+                transcript.push(if self.verifiable { MODE_VOPRF } else { MODE_OPRF });
+                transcript.write_u16::<BigEndian>(context_len)?;
+                transcript.extend_from_slice(&self.context);
+                Domain::new(DLEQ_LABEL, &transcript)
+            },
+        }
+    }
+
+    /// Returns the context string of RFC 9497, `"OPRFV1-" || mode || "-" ||
+    /// identifier`, where the identifier is the name of the ciphersuite
+    /// without its `VOPRF-` or `OPRF-` prefix. Only meaningful for
+    /// ciphersuites that follow `Spec::Rfc9497`.
+    pub fn context_string(&self) -> Vec<u8> {
+        let prefix = if self.verifiable { VOPRF_PREFIX } else { OPRF_PREFIX };
+        let mut out = RFC9497_CONTEXT_PREFIX.to_vec();
+        out.push(if self.verifiable { MODE_VOPRF } else { MODE_OPRF });
+        out.push(b'-');
+        out.extend_from_slice(self.name[prefix.len()..].as_bytes());
+        out
+    }
 }
 
 impl<T,H> Ciphersuite<T,H>
@@ -113,17 +301,44 @@ impl<T,H> Ciphersuite<T,H>
     pub fn new(pog: PrimeOrderGroup<T,H>, verifiable: bool) -> Ciphersuite<T,H> {
         let mut name = String::from("");
         match verifiable {
-            true => name.push_str("VOPRF-"),
-            false => name.push_str("OPRF-"),
+            true => name.push_str(VOPRF_PREFIX),
+            false => name.push_str(OPRF_PREFIX),
         }
         name.push_str(&get_name(&pog));
         Ciphersuite {
             name: name,
             verifiable: verifiable,
-            pog: pog
+            spec: pog.spec(),
+            pog: pog,
+            context: Vec::new(),
+            proof_version: ProofVersion::default(),
+            digest: |buf: &[u8]| H::digest(buf).to_vec(),
         }
     }
 
+    /// Constructs the Ciphersuite object from its name (e.g.
+    /// `VOPRF-P384-HKDF-SHA512-SSWU-RO`), as returned in the `name` field.
+    /// Returns an error if the name does not correspond to a ciphersuite over
+    /// `PrimeOrderGroup<T,H>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the ciphersuite
+    pub fn from_name(name: &str) -> Result<Ciphersuite<T,H>, Error> {
+        let pog = PrimeOrderGroup::<T,H>::instance();
+        let (verifiable, group_name) = if name.starts_with(VOPRF_PREFIX) {
+            (true, &name[VOPRF_PREFIX.len()..])
+        } else if name.starts_with(OPRF_PREFIX) {
+            (false, &name[OPRF_PREFIX.len()..])
+        } else {
+            return Err(err_unsupported_ciphersuite());
+        };
+        if group_name != get_name(&pog) {
+            return Err(err_unsupported_ciphersuite());
+        }
+        Ok(Ciphersuite::new(pog, verifiable))
+    }
+
     /// Provides access to the mechanism for deterministically mapping a
     /// sequence of bytes to an element of the group. This process should not
     /// reveal the discrete logarithm of the group element with respect to the
@@ -133,7 +348,14 @@ impl<T,H> Ciphersuite<T,H>
     ///
     /// * `buf`: the sequence of bytes to encode as a curve point
     pub fn h1(&self, buf: &[u8]) -> T {
-        (self.pog.encode_to_group)(buf)
+        match self.spec {
+            Spec::Draft02 => (self.pog.encode_to_group)(buf),
+            Spec::Rfc9497 => {
+                let mut dst = RFC9497_HASH_TO_GROUP_DST.to_vec();
+                dst.extend_from_slice(&self.context_string());
+                (self.pog.hash_to_group)(buf, &dst)
+            },
+        }
     }
 
     /// Provides access to the HMAC algorithm that is used in running
@@ -189,7 +411,10 @@ impl<T,H> Ciphersuite<T,H>
 
 #[cfg(test)]
 mod tests {
-    use super::{PrimeOrderGroup,Ciphersuite};
+    use super::{PrimeOrderGroup,Ciphersuite,ProofVersion,Domain};
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use super::super::groups::p384::NistPoint;
+    use sha2::Sha512;
 
     #[test]
     fn ristretto_oprf_ciphersuite() {
@@ -205,6 +430,31 @@ mod tests {
         assert_eq!(ciph.verifiable, true);
     }
 
+    #[test]
+    fn ristretto_from_name() {
+        for verifiable in &[false, true] {
+            let name = Ciphersuite::new(PrimeOrderGroup::ristretto_255(), *verifiable).name;
+            let ciph = Ciphersuite::<RistrettoPoint,Sha512>::from_name(&name).unwrap();
+            assert_eq!(ciph.name, name);
+            assert_eq!(ciph.verifiable, *verifiable);
+        }
+    }
+
+    #[test]
+    fn from_name_unsupported() {
+        let bad = vec![
+            "VOPRF-P384-HKDF-SHA512-SSWU-RO",
+            "VOPRF-ristretto255-HKDF-SHA512-ELL2",
+            "ristretto255-HKDF-SHA512-ELL2-RO",
+            "XOPRF-ristretto255-HKDF-SHA512-ELL2-RO",
+            "",
+        ];
+        for name in bad {
+            assert!(Ciphersuite::<RistrettoPoint,Sha512>::from_name(name).is_err());
+        }
+        assert!(Ciphersuite::<NistPoint,Sha512>::from_name("OPRF-ristretto255-HKDF-SHA512-ELL2-RO").is_err());
+    }
+
     #[test]
     fn ristretto_h1() {
         let pog = PrimeOrderGroup::ristretto_255();
@@ -238,6 +488,13 @@ mod tests {
         assert_eq!(ciph.verifiable, true);
     }
 
+    #[test]
+    fn p384_from_name() {
+        let ciph = Ciphersuite::<NistPoint,Sha512>::from_name("VOPRF-P384-HKDF-SHA512-SSWU-RO").unwrap();
+        assert_eq!(ciph.name, String::from("VOPRF-P384-HKDF-SHA512-SSWU-RO"));
+        assert_eq!(ciph.verifiable, true);
+    }
+
     #[test]
     fn p384_h1() {
         let pog = PrimeOrderGroup::p384();
@@ -257,5 +514,33 @@ mod tests {
         assert_eq!(h3_res, h4_res);
     }
 
+    #[test]
+    fn dleq_domains() {
+        let ciph = Ciphersuite::new(PrimeOrderGroup::ristretto_255(), true);
+        // proofs are bound to the ciphersuite by default
+        assert_eq!(ciph.proof_version, ProofVersion::V1);
+        let domain = ciph.dleq_domain().unwrap();
+        assert!(domain != Domain::Legacy);
+        assert!(domain != ciph.clone().with_context(b"context").dleq_domain().unwrap());
+        assert!(domain != Ciphersuite::new(PrimeOrderGroup::ristretto_255(), false).with_proof_version(ProofVersion::V1).dleq_domain().unwrap());
+        assert_eq!(ciph.clone().with_context(b"context").dleq_domain().unwrap(),
+            ciph.clone().with_context(b"context").dleq_domain().unwrap());
+        assert_eq!(ciph.clone().with_proof_version(ProofVersion::Legacy).dleq_domain().unwrap(), Domain::Legacy);
+        // the context and the transcript must fit their two-byte length
+        // prefixes
+        assert!(ciph.clone().with_context(&vec![0; 65535]).dleq_domain().is_err());
+        assert!(ciph.with_context(&vec![0; 65536]).dleq_domain().is_err());
+    }
+
+    #[test]
+    fn proof_version_names() {
+        for v in &[ProofVersion::Legacy, ProofVersion::V1] {
+            assert_eq!(ProofVersion::from_name(v.name()).unwrap(), *v);
+            assert_eq!(ProofVersion::from_id(v.id()).unwrap(), *v);
+        }
+        assert!(ProofVersion::from_name("v2").is_err());
+        assert!(ProofVersion::from_id(2).is_err());
+    }
+
     // TODO: test vectors for HMAC and HKDF?
 }
\ No newline at end of file
@@ -0,0 +1,440 @@
+//! The consistency module allows a client to check that the public key of a
+//! server is the same key that the server presents to everyone else. A server
+//! that gives each client a different key can link the evaluations of a
+//! client by key (a split-view attack).
+//!
+//! A `KeyConsistency` checker compares the key that the client uses with the
+//! keys that are published by several independent `KeySource`s (e.g. local
+//! files, or mirrors of the key directory of the server, see the
+//! `http::directory` module). A key is agreed upon if at least `quorum` of the
+//! sources publish it, and the sources that could not be queried are reported
+//! in the resulting `Agreement`. The checker also records the keys that have been used
+//! for each ciphersuite in a `KeyHistory`: the key may only change to a new key
+//! if the rotation has been announced with `KeyHistory::announce` (or with the
+//! `--announce_key` flag of the CLI).
+//!
+//! Once a checker is set with `Client::set_consistency`, `Client::unblind`
+//! refuses keys that fail either check. Keys are only recorded once the proof
+//! of an evaluation has been verified with them.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,Client};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::oprf::consistency::{KeySource,KeyConsistency,KeyHistory};
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use sha2::Sha512;
+//! use std::io::Error;
+//! use std::sync::Arc;
+//!
+//! // a source that always publishes the same key
+//! struct Fixed(Vec<u8>);
+//!
+//! impl KeySource for Fixed {
+//!     fn name(&self) -> String { "fixed".to_string() }
+//!     fn keys(&self, _: &str) -> Result<Vec<Vec<u8>>, Error> { Ok(vec![self.0.clone()]) }
+//! }
+//!
+//! let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let srv = Server::setup(ciph.clone());
+//! let pk = srv.key.pub_key(&pog);
+//! let pk_bytes = hex::decode(pk.as_hex(&pog)).unwrap();
+//!
+//! let sources: Vec<Box<dyn KeySource>> = vec![Box::new(Fixed(pk_bytes.clone())), Box::new(Fixed(pk_bytes))];
+//! let checker = KeyConsistency::new(sources, 2, KeyHistory::new());
+//! let mut cli = Client::setup(ciph, Some(pk)).unwrap();
+//! cli.set_consistency(Arc::new(checker));
+//!
+//! let inputs = cli.blind(&[b"some_input_data".to_vec()]);
+//! let eval = srv.eval(&[inputs[0].elem]).unwrap();
+//! assert!(cli.unblind(&inputs, &eval).is_ok());
+//! ```
+use std::collections::{BTreeMap,HashMap};
+use std::fs;
+use std::io::{Error,ErrorKind};
+use std::path::{Path,PathBuf};
+use std::sync::Mutex;
+
+use serde::{Serialize,Deserialize};
+
+use crate::utils::unix_time;
+use crate::errors::{err_deserialization,err_internal,err_key_consistency,err_key_rotation};
+
+/// The `KeySource` trait is implemented by sources that publish the public
+/// keys of a server, independently of the server itself
+pub trait KeySource: Send + Sync {
+    /// The name of the source, used for reporting
+    fn name(&self) -> String;
+    /// Returns the (compressed) public keys that are published for the
+    /// ciphersuite `ciph_name`
+    fn keys(&self, ciph_name: &str) -> Result<Vec<Vec<u8>>, Error>;
+}
+
+/// The `SourceFailure` struct records a source that the keys of a ciphersuite
+/// could not be fetched from
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceFailure {
+    /// The name of the source (see `KeySource::name`)
+    pub source: String,
+    /// The error that the source returned
+    pub error: String,
+}
+
+/// The `Agreement` struct holds the keys that the sources agree upon for a
+/// ciphersuite
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Agreement {
+    /// The (compressed) keys that are published by at least `quorum` sources
+    pub keys: Vec<Vec<u8>>,
+    /// The sources that could not be queried, which do not agree on any key
+    pub failures: Vec<SourceFailure>,
+}
+
+/// The `KeyRecord` struct records a key that has been used by a client
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeyRecord {
+    /// The hex-encoded (compressed) public key
+    pub key: String,
+    /// The time (in seconds since the UNIX epoch) at which the key was first
+    /// used
+    pub first_seen: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+struct SuiteHistory {
+    current: Option<String>,
+    seen: Vec<KeyRecord>,
+    announced: Vec<String>,
+}
+
+/// The `KeyHistory` struct records the keys that a client has used for each
+/// ciphersuite, along with the key rotations that have been announced. It may
+/// be stored as a JSON file.
+#[derive(Debug, Default)]
+pub struct KeyHistory {
+    path: Option<PathBuf>,
+    suites: BTreeMap<String, SuiteHistory>,
+}
+
+impl KeyHistory {
+    /// Creates an empty history that is held in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the history from the file at `path`, which is written each time
+    /// that the history changes. The history is empty if the file does not
+    /// exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let suites = match fs::read(&path) {
+            Ok(buf) => match serde_json::from_slice(&buf) {
+                Ok(s) => s,
+                Err(_) => return Err(err_deserialization()),
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(KeyHistory{
+            path: Some(path),
+            suites: suites,
+        })
+    }
+
+    /// The key that is currently used for the ciphersuite `ciph_name`
+    pub fn current(&self, ciph_name: &str) -> Result<Option<Vec<u8>>, Error> {
+        match self.suites.get(ciph_name).and_then(|s| s.current.as_ref()) {
+            Some(k) => hex::decode(k).map(Some).map_err(|_| err_deserialization()),
+            None => Ok(None),
+        }
+    }
+
+    /// All keys that have been used for the ciphersuite `ciph_name`, in the
+    /// order that they were first used
+    pub fn seen(&self, ciph_name: &str) -> &[KeyRecord] {
+        match self.suites.get(ciph_name) {
+            Some(s) => &s.seen,
+            None => &[],
+        }
+    }
+
+    /// Announces that the key for the ciphersuite `ciph_name` will be rotated
+    /// to `key`
+    pub fn announce(&mut self, ciph_name: &str, key: &[u8]) -> Result<(), Error> {
+        let suite = self.suites.entry(ciph_name.to_string()).or_default();
+        let key = hex::encode(key);
+        if !suite.announced.contains(&key) {
+            suite.announced.push(key);
+        }
+        self.save()
+    }
+
+    /// Returns an error if `key` differs from the current key for the
+    /// ciphersuite `ciph_name` and the rotation has not been announced
+    pub fn check(&self, ciph_name: &str, key: &[u8]) -> Result<(), Error> {
+        let suite = match self.suites.get(ciph_name) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+        let key = hex::encode(key);
+        match &suite.current {
+            Some(k) if k != &key && !suite.announced.contains(&key) => Err(err_key_rotation()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records that `key` is used for the ciphersuite `ciph_name` at the time
+    /// `now`. Returns an error if the key differs from the current key and
+    /// the rotation has not been announced.
+    pub fn record(&mut self, ciph_name: &str, key: &[u8], now: u64) -> Result<(), Error> {
+        self.check(ciph_name, key)?;
+        let suite = self.suites.entry(ciph_name.to_string()).or_default();
+        let key = hex::encode(key);
+        if suite.current.as_ref() == Some(&key) {
+            return Ok(());
+        }
+        suite.announced.retain(|k| k != &key);
+        if !suite.seen.iter().any(|r| r.key == key) {
+            suite.seen.push(KeyRecord{
+                key: key.clone(),
+                first_seen: now,
+            });
+        }
+        suite.current = Some(key);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        match &self.path {
+            Some(p) => fs::write(p, serde_json::to_string_pretty(&self.suites).unwrap()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The `KeyConsistency` struct checks the public key of a server against the
+/// keys that are published by independent sources, and against the history
+/// of keys that the client has used.
+pub struct KeyConsistency {
+    sources: Vec<Box<dyn KeySource>>,
+    quorum: usize,
+    agreed: Mutex<HashMap<String, Agreement>>,
+    history: Mutex<KeyHistory>,
+}
+
+impl KeyConsistency {
+    /// Creates a checker, where a key is agreed upon if at least `quorum` of
+    /// the `sources` publish it
+    pub fn new(sources: Vec<Box<dyn KeySource>>, quorum: usize, history: KeyHistory) -> Self {
+        KeyConsistency{
+            sources: sources,
+            quorum: quorum,
+            agreed: Mutex::new(HashMap::new()),
+            history: Mutex::new(history),
+        }
+    }
+
+    /// Returns the keys that are agreed upon for the ciphersuite `ciph_name`,
+    /// along with the sources that could not be queried. The sources are only
+    /// queried once for each ciphersuite, unless the checker is refreshed.
+    pub fn agreed_keys(&self, ciph_name: &str) -> Result<Agreement, Error> {
+        let mut agreed = self.agreed.lock().map_err(|_| err_internal())?;
+        if let Some(agreement) = agreed.get(ciph_name) {
+            return Ok(agreement.clone());
+        }
+        let mut counts: BTreeMap<Vec<u8>, usize> = BTreeMap::new();
+        let mut failures = Vec::new();
+        for source in &self.sources {
+            match source.keys(ciph_name) {
+                Ok(mut keys) => {
+                    keys.sort();
+                    keys.dedup();
+                    for k in keys {
+                        *counts.entry(k).or_insert(0) += 1;
+                    }
+                },
+                // a source that is unavailable does not agree on any key
+                Err(e) => failures.push(SourceFailure{
+                    source: source.name(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        let agreement = Agreement{
+            keys: counts.into_iter()
+                .filter(|(_, n)| *n >= self.quorum)
+                .map(|(k, _)| k)
+                .collect(),
+            failures: failures,
+        };
+        agreed.insert(ciph_name.to_string(), agreement.clone());
+        Ok(agreement)
+    }
+
+    /// Clears the keys that have been fetched from the sources
+    pub fn refresh(&self) -> Result<(), Error> {
+        self.agreed.lock().map_err(|_| err_internal())?.clear();
+        Ok(())
+    }
+
+    /// Announces a key rotation for the ciphersuite `ciph_name` (see
+    /// `KeyHistory::announce`)
+    pub fn announce(&self, ciph_name: &str, key: &[u8]) -> Result<(), Error> {
+        self.history.lock().map_err(|_| err_internal())?.announce(ciph_name, key)
+    }
+
+    /// Checks that the (compressed) public key `key` is agreed upon for the
+    /// ciphersuite `ciph_name`, and that it is either the key that is
+    /// currently used or an announced rotation. The key is not recorded until
+    /// it is confirmed.
+    pub fn check(&self, ciph_name: &str, key: &[u8]) -> Result<(), Error> {
+        if self.quorum == 0 || !self.agreed_keys(ciph_name)?.keys.iter().any(|k| k == key) {
+            return Err(err_key_consistency());
+        }
+        self.history.lock().map_err(|_| err_internal())?.check(ciph_name, key)
+    }
+
+    /// Records `key` as the current key for the ciphersuite `ciph_name`, once
+    /// a proof has been verified with it
+    pub fn confirm(&self, ciph_name: &str, key: &[u8]) -> Result<(), Error> {
+        self.history.lock().map_err(|_| err_internal())?.record(ciph_name, key, unix_time())
+    }
+
+    /// Returns a copy of the keys that have been used for the ciphersuite
+    /// `ciph_name`
+    pub fn seen(&self, ciph_name: &str) -> Result<Vec<KeyRecord>, Error> {
+        Ok(self.history.lock().map_err(|_| err_internal())?.seen(ciph_name).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::{Server,Client};
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::oprf::groups::PrimeOrderGroup;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+    use std::sync::Arc;
+
+    const CIPH: &str = "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO";
+
+    struct Fixed(Option<Vec<Vec<u8>>>);
+
+    impl KeySource for Fixed {
+        fn name(&self) -> String {
+            "fixed".to_string()
+        }
+
+        fn keys(&self, ciph_name: &str) -> Result<Vec<Vec<u8>>, Error> {
+            match (&self.0, ciph_name == CIPH) {
+                (Some(keys), true) => Ok(keys.clone()),
+                (Some(_), false) => Ok(Vec::new()),
+                (None, _) => Err(err_internal()),
+            }
+        }
+    }
+
+    fn sources(keys: Vec<Option<Vec<Vec<u8>>>>) -> Vec<Box<dyn KeySource>> {
+        keys.into_iter().map(|k| Box::new(Fixed(k)) as Box<dyn KeySource>).collect()
+    }
+
+    #[test]
+    fn agreed_keys() {
+        let (a, b) = (vec![1u8], vec![2u8]);
+        let checker = KeyConsistency::new(sources(vec![
+            Some(vec![a.clone(), b.clone()]),
+            Some(vec![a.clone(), a.clone()]),
+            None,
+        ]), 2, KeyHistory::new());
+        let agreement = checker.agreed_keys(CIPH).unwrap();
+        assert_eq!(agreement.keys, vec![a.clone()]);
+        // the unavailable source is reported
+        assert_eq!(agreement.failures, vec![SourceFailure{ source: "fixed".to_string(), error: err_internal().to_string() }]);
+        assert!(checker.agreed_keys("other").unwrap().keys.is_empty());
+        assert!(checker.check(CIPH, &a).is_ok());
+        // keys that only some sources publish are refused
+        assert!(checker.check(CIPH, &b).is_err());
+        assert!(checker.check("other", &a).is_err());
+    }
+
+    #[test]
+    fn rotation_must_be_announced() {
+        let (a, b) = (vec![1u8], vec![2u8]);
+        let checker = KeyConsistency::new(sources(vec![Some(vec![a.clone(), b.clone()])]), 1, KeyHistory::new());
+        // keys are only recorded once they are confirmed
+        checker.check(CIPH, &b).unwrap();
+        checker.check(CIPH, &a).unwrap();
+        checker.confirm(CIPH, &a).unwrap();
+        assert_eq!(checker.check(CIPH, &b).unwrap_err().to_string(), err_key_rotation().to_string());
+        assert!(checker.confirm(CIPH, &b).is_err());
+        checker.announce(CIPH, &b).unwrap();
+        checker.check(CIPH, &b).unwrap();
+        checker.confirm(CIPH, &b).unwrap();
+        // the announcement is consumed by the rotation
+        assert!(checker.check(CIPH, &a).is_err());
+        let seen: Vec<String> = checker.seen(CIPH).unwrap().into_iter().map(|r| r.key).collect();
+        assert_eq!(seen, vec![hex::encode(&a), hex::encode(&b)]);
+    }
+
+    #[test]
+    fn history_persists() {
+        let path = std::env::temp_dir().join(format!("voprf-rs-key-history-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut history = KeyHistory::load(&path).unwrap();
+        history.record(CIPH, &[1], 10).unwrap();
+        history.announce(CIPH, &[2]).unwrap();
+
+        let mut history = KeyHistory::load(&path).unwrap();
+        assert_eq!(history.current(CIPH).unwrap(), Some(vec![1]));
+        assert!(history.record(CIPH, &[3], 20).is_err());
+        history.record(CIPH, &[2], 20).unwrap();
+        assert_eq!(KeyHistory::load(&path).unwrap().seen(CIPH)[1], KeyRecord{ key: "02".to_string(), first_seen: 20 });
+        fs::write(&path, r#"{"VOPRF-ristretto255-HKDF-SHA512-ELL2-RO": {"current": "zz", "seen": [], "announced": []}}"#).unwrap();
+        assert!(KeyHistory::load(&path).unwrap().current(CIPH).is_err());
+        fs::write(&path, "{").unwrap();
+        assert!(KeyHistory::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unblind_refuses_inconsistent_keys() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let other = Server::setup(ciph.clone());
+        let encode = |s: &Server<RistrettoPoint,Sha512>| {
+            let mut buf = Vec::new();
+            (pog.serialize)(&s.key.pub_key(&pog).0, true, &mut buf);
+            buf
+        };
+        let pk = encode(&srv);
+        let mut cli = Client::setup(ciph.clone(), Some(srv.key.pub_key(&pog))).unwrap();
+        let inputs = cli.blind(&[b"some_input_data".to_vec()]);
+        let eval = srv.eval(&[inputs[0].elem]).unwrap();
+
+        // one of the sources sees a different key
+        cli.set_consistency(Arc::new(KeyConsistency::new(sources(vec![Some(vec![pk.clone()]), Some(vec![encode(&other)])]), 2, KeyHistory::new())));
+        assert_eq!(cli.unblind(&inputs, &eval).unwrap_err().to_string(), err_key_consistency().to_string());
+
+        let checker = Arc::new(KeyConsistency::new(sources(vec![Some(vec![pk.clone()]), Some(vec![pk.clone()])]), 2, KeyHistory::new()));
+        cli.set_consistency(checker.clone());
+        // a key is not recorded if the proof fails to verify
+        let mut bad = eval.clone();
+        bad.proof = other.eval(&[inputs[0].elem]).unwrap().proof;
+        assert!(cli.unblind(&inputs, &bad).is_err());
+        assert!(checker.seen(&ciph.name).unwrap().is_empty());
+        assert!(cli.unblind(&inputs, &eval).is_ok());
+        assert_eq!(checker.seen(&ciph.name).unwrap()[0].key, hex::encode(&pk));
+
+        // the OPRF mode does not use a key
+        let mut oprf = Client::setup(Ciphersuite::new(pog.clone(), false), None).unwrap();
+        oprf.set_consistency(Arc::new(KeyConsistency::new(Vec::new(), 1, KeyHistory::new())));
+        let srv = Server::setup(Ciphersuite::new(pog, false));
+        let eval = srv.eval(&[inputs[0].elem]).unwrap();
+        assert!(oprf.unblind(&inputs, &eval).is_ok());
+    }
+}
@@ -0,0 +1,515 @@
+//! The dleq module provides non-interactive zero-knowledge proofs that two
+//! pairs of group elements share the same discrete logarithm, i.e. that
+//! `log_A(B) == log_C(D)`, for any `PrimeOrderGroup`. The batched form proves
+//! that `log_A(B) == log_C_i(D_i)` for each `i`, by proving the statement for
+//! the random linear combinations `M = Σ d_i*C_i` and `Z = Σ d_i*D_i` (see
+//! `composites`). The prover knows `k`, and computes `Z = k*M` instead (see
+//! `composites_fast`), which halves its scalar multiplications.
+//!
+//! The (V)OPRF proves the statement `log_g(Y) == log_M(Z)`, where `Y` is the
+//! public key of the server, but other protocols (e.g. key updates, VRFs,
+//! credentials or threshold shares) can prove their own statements. The
+//! challenge and the batching coefficients are derived according to a
+//! `Domain`:
+//!
+//! - `Domain::Labelled` derives them with the `hash_to_scalar` function of
+//!   the group, under domain-separation tags built from a protocol label, and
+//!   binds them to an application-supplied transcript and to all elements of
+//!   the statement. Proofs for different labels or transcripts are not
+//!   interchangeable.
+//! - `Domain::Legacy` derives them with the group-specific functions of
+//!   draft-irtf-cfrg-voprf-02, for compatibility with existing proofs. It only
+//!   supports statements where `A` is the generator of the group.
+//! - `Domain::Rfc9497` derives them as in `GenerateProof` and
+//!   `ComputeComposites` of [RFC 9497](https://www.rfc-editor.org/rfc/rfc9497.html),
+//!   under the context string of a ciphersuite. The VOPRF of RFC 9497 always
+//!   proves the batched statement, even for a single element.
+//!
+//! Protocols with a fixed proof format can instead supply their own
+//! derivation of the challenge to `prove_with_challenge` and
+//! `verify_with_challenge`.
+//!
+//! A proof `(c, s)` is computed with a random nonce `t` as `c = H(A, B, C, D,
+//! t*A, t*C)` and `s = t - c*k`, and is verified by recomputing the challenge
+//! over `s*A + c*B` and `s*C + c*D`.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::dleq::{self,Domain};
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//!
+//! let pog = PrimeOrderGroup::ristretto_255();
+//! let domain = Domain::new(b"my_protocol", b"session_id").unwrap();
+//! let mut k: Vec<u8> = Vec::new();
+//! (pog.uniform_bytes)(&mut k);
+//! let a = (pog.random_element)();
+//! let c = (pog.random_element)();
+//! let b = (pog.scalar_mult)(&a, &k);
+//! let d = (pog.scalar_mult)(&c, &k);
+//!
+//! let proof = dleq::prove(&pog, &domain, &k, &a, &b, &c, &d).unwrap();
+//! assert!(dleq::verify(&pog, &domain, &a, &b, &c, &d, &proof));
+//! assert!(!dleq::verify(&pog, &Domain::new(b"my_protocol", b"other_session").unwrap(), &a, &b, &c, &d, &proof));
+//!
+//! // batched statements
+//! let cs: Vec<_> = (0..5).map(|_| (pog.random_element)()).collect();
+//! let ds: Vec<_> = cs.iter().map(|c| (pog.scalar_mult)(c, &k)).collect();
+//! let proof = dleq::batch_prove(&pog, &domain, &k, &a, &b, &cs, &ds).unwrap();
+//! assert!(dleq::batch_verify(&pog, &domain, &a, &b, &cs, &ds, &proof));
+//! ```
+use byteorder::{BigEndian,WriteBytesExt};
+use std::convert::TryFrom;
+use std::io::Error;
+
+use super::groups::PrimeOrderGroup;
+use super::proof::Proof;
+use crate::errors::{err_serialization,err_dleq_statement,err_dleq_label};
+use crate::utils::{expand_message_xmd,par_map};
+
+const CHALLENGE_DST: &[u8] = b"DLEQ-Challenge-";
+const SEED_DST: &[u8] = b"DLEQ-Seed-";
+const COMPOSITE_DST: &[u8] = b"DLEQ-Composite-";
+const SEED_LENGTH: usize = 64;
+const MAX_LABEL_LENGTH: usize = 200;
+const RFC9497_HASH_TO_SCALAR_DST: &[u8] = b"HashToScalar-";
+const RFC9497_SEED_DST: &[u8] = b"Seed-";
+
+/// The `Domain` of a DLEQ proof determines how its challenge and batching
+/// coefficients are derived
+#[derive(Clone,Debug)]
+pub enum Domain {
+    /// The group-specific derivation of draft-irtf-cfrg-voprf-02, without
+    /// domain separation
+    Legacy,
+    /// Derivation under the protocol `label`, bound to the `transcript`. The
+    /// label must be at most 200 bytes long, and the transcript at most
+    /// `u16::MAX` bytes long.
+    Labelled {
+        /// The protocol label
+        label: Vec<u8>,
+        /// Application data that the proof is bound to
+        transcript: Vec<u8>,
+    },
+    /// The derivation of RFC 9497
+    Rfc9497 {
+        /// The context string of the ciphersuite
+        context_string: Vec<u8>,
+        /// The hash function of the ciphersuite, which derives the seed of
+        /// the batching coefficients
+        hash: fn(&[u8]) -> Vec<u8>,
+    },
+}
+
+// the context string of RFC 9497 identifies the ciphersuite, and so its hash
+// function, which is not compared
+impl PartialEq for Domain {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Domain::Legacy, Domain::Legacy) => true,
+            (Domain::Labelled{ label: l1, transcript: t1 }, Domain::Labelled{ label: l2, transcript: t2 }) => l1 == l2 && t1 == t2,
+            (Domain::Rfc9497{ context_string: c1, .. }, Domain::Rfc9497{ context_string: c2, .. }) => c1 == c2,
+            _ => false,
+        }
+    }
+}
+
+impl Domain {
+    /// Returns the labelled domain for `label` and `transcript`, or an error
+    /// if either of them is too long
+    pub fn new(label: &[u8], transcript: &[u8]) -> Result<Self, Error> {
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(err_dleq_label());
+        }
+        if u16::try_from(transcript.len()).is_err() {
+            return Err(err_serialization());
+        }
+        Ok(Domain::Labelled{
+            label: label.to_vec(),
+            transcript: transcript.to_vec(),
+        })
+    }
+}
+
+/// Generates a proof that `log_a(b) == log_c(d)`, where `b = k*a` and `d =
+/// k*c`. Returns an error if the domain is invalid.
+pub fn prove<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, k: &[u8], a: &T, b: &T, c: &T, d: &T) -> Result<Proof<T>, Error>
+        where T: Clone {
+    let mut t: Vec<u8> = Vec::new();
+    (pog.uniform_bytes)(&mut t);
+    prove_with_nonce(pog, domain, k, a, b, c, d, &t)
+}
+
+/// Same as `prove`, with the fixed nonce `t`. Only intended for generating
+/// test vectors, as reusing `t` reveals `k`.
+pub fn prove_with_nonce<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, k: &[u8], a: &T, b: &T, c: &T, d: &T, t: &[u8]) -> Result<Proof<T>, Error>
+        where T: Clone {
+    prove_with_challenge(pog, k, a, b, c, d, t, |pts| challenge(pog, domain, pts))
+}
+
+/// Same as `prove_with_nonce`, where the challenge is derived by `ch` from the
+/// statement and commitments `[A, B, C, D, U, V]`, rather than by a `Domain`.
+/// This allows protocols that fix the derivation of the challenge (e.g. the
+/// VRFs of the `vrf` module) to use the same proofs. Errors from `ch` are
+/// returned as is.
+pub fn prove_with_challenge<T,H,F>(pog: &PrimeOrderGroup<T,H>, k: &[u8], a: &T, b: &T, c: &T, d: &T, t: &[u8], ch: F) -> Result<Proof<T>, Error>
+        where T: Clone, F: Fn(&[&T]) -> Result<Vec<u8>, Error> {
+    let u = if (pog.is_equal)(a, &pog.generator) {
+        (pog.base_mult)(t)
+    } else {
+        (pog.scalar_mult)(a, t)
+    };
+    let v = (pog.scalar_mult)(c, t);
+    let c_val = ch(&[a, b, c, d, &u, &v])?;
+    let s = (pog.sub_scalars)(t, &(pog.mul_scalars)(&c_val, k));
+    Ok(Proof::from_scalars(c_val, s))
+}
+
+/// Verifies a proof that `log_a(b) == log_c(d)`. Proofs for an invalid domain
+/// never verify.
+pub fn verify<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, a: &T, b: &T, c: &T, d: &T, proof: &Proof<T>) -> bool
+        where T: Clone {
+    if *domain == Domain::Legacy && !(pog.is_equal)(a, &pog.generator) {
+        return false;
+    }
+    verify_with_challenge(pog, a, b, c, d, proof, |pts| challenge(pog, domain, pts))
+}
+
+/// Verifies a proof from `prove_with_challenge`, where the challenge is
+/// derived by `ch`. The proof does not verify if `ch` returns an error.
+pub fn verify_with_challenge<T,H,F>(pog: &PrimeOrderGroup<T,H>, a: &T, b: &T, c: &T, d: &T, proof: &Proof<T>, ch: F) -> bool
+        where T: Clone, F: Fn(&[&T]) -> Result<Vec<u8>, Error> {
+    let scalars = [proof.s().to_vec(), proof.c().to_vec()];
+    let u = (pog.multi_scalar_mult)(&[a.clone(), b.clone()], &scalars);
+    let v = (pog.multi_scalar_mult)(&[c.clone(), d.clone()], &scalars);
+    match ch(&[a, b, c, d, &u, &v]) {
+        Ok(c_val) => c_val == proof.c(),
+        Err(_) => false,
+    }
+}
+
+/// Generates a batched proof that `log_a(b) == log_cs[i](ds[i])` for each `i`,
+/// where `b = k*a` and `ds[i] = k*cs[i]`. Returns an error if the statement
+/// or the domain is invalid (see `composites`).
+pub fn batch_prove<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, k: &[u8], a: &T, b: &T, cs: &[T], ds: &[T]) -> Result<Proof<T>, Error>
+        where T: Clone {
+    let (m, z) = composites_fast(pog, domain, k, a, b, cs, ds)?;
+    prove(pog, domain, k, a, b, &m, &z)
+}
+
+/// Same as `batch_prove`, with the fixed nonce `t`. Only intended for
+/// generating test vectors.
+pub fn batch_prove_with_nonce<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, k: &[u8], a: &T, b: &T, cs: &[T], ds: &[T], t: &[u8]) -> Result<Proof<T>, Error>
+        where T: Clone {
+    let (m, z) = composites_fast(pog, domain, k, a, b, cs, ds)?;
+    prove_with_nonce(pog, domain, k, a, b, &m, &z, t)
+}
+
+/// Verifies a batched proof that `log_a(b) == log_cs[i](ds[i])` for each `i`
+pub fn batch_verify<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, a: &T, b: &T, cs: &[T], ds: &[T], proof: &Proof<T>) -> bool
+        where T: Clone {
+    match composites(pog, domain, a, b, cs, ds) {
+        Ok((m, z)) => verify(pog, domain, a, b, &m, &z, proof),
+        Err(_) => false,
+    }
+}
+
+/// Computes the composite elements `M = Σ d_i*cs[i]` and `Z = Σ d_i*ds[i]` of
+/// a batched statement, where the coefficients `d_i` are derived from the
+/// whole statement. Returns an error if `cs` is empty, if `cs` and `ds` have
+/// different lengths, or if the domain is invalid.
+pub fn composites<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, a: &T, b: &T, cs: &[T], ds: &[T]) -> Result<(T, T), Error>
+        where T: Clone {
+    let coeffs = coefficients(pog, domain, a, b, cs, ds)?;
+    Ok(((pog.multi_scalar_mult)(cs, &coeffs), (pog.multi_scalar_mult)(ds, &coeffs)))
+}
+
+/// Same as `composites`, for the prover that knows the discrete logarithm
+/// `k`, such that `ds[i] = k*cs[i]`. Computes `Z = k*M` rather than `Z = Σ
+/// d_i*ds[i]`, so only one scalar multiplication per element is needed. The
+/// result is only correct if the statement holds.
+pub fn composites_fast<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, k: &[u8], a: &T, b: &T, cs: &[T], ds: &[T]) -> Result<(T, T), Error>
+        where T: Clone {
+    let coeffs = coefficients(pog, domain, a, b, cs, ds)?;
+    let m = (pog.multi_scalar_mult)(cs, &coeffs);
+    let z = (pog.scalar_mult)(&m, k);
+    Ok((m, z))
+}
+
+// derives the coefficients `d_i` of a batched statement
+fn coefficients<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, a: &T, b: &T, cs: &[T], ds: &[T]) -> Result<Vec<Vec<u8>>, Error>
+        where T: Clone {
+    // the index of each coefficient is encoded in four bytes (two bytes for
+    // RFC 9497)
+    if cs.is_empty() || cs.len() != ds.len() || u32::try_from(cs.len()).is_err() {
+        return Err(err_dleq_statement());
+    }
+    match domain {
+        Domain::Legacy => {
+            let mut pts = vec![b];
+            pts.extend(cs);
+            pts.extend(ds);
+            Ok((pog.legacy_dleq_coefficients)(&pts, cs.len()))
+        },
+        Domain::Labelled{ label, transcript } => {
+            let mut pts = vec![a, b];
+            pts.extend(cs);
+            pts.extend(ds);
+            let seed = expand_message_xmd(&frame(pog, transcript, &pts)?, &dst(SEED_DST, label), SEED_LENGTH);
+            let comp_dst = dst(COMPOSITE_DST, label);
+            // only the hash function is shared across threads, as the group
+            // elements are not necessarily `Sync`
+            let hash_to_scalar = pog.hash_to_scalar;
+            Ok(par_map(cs.len(), |i| {
+                let mut msg = seed.clone();
+                msg.write_u32::<BigEndian>(i as u32).unwrap();
+                hash_to_scalar(&msg, &comp_dst)
+            }))
+        },
+        Domain::Rfc9497{ context_string, hash } => {
+            if u16::try_from(cs.len()).is_err() {
+                return Err(err_dleq_statement());
+            }
+            let mut seed_transcript = Vec::new();
+            write_elements(pog, &mut seed_transcript, &[b])?;
+            write_bytes(&mut seed_transcript, &dst(RFC9497_SEED_DST, context_string))?;
+            let seed = hash(&seed_transcript);
+            let h2s_dst = dst(RFC9497_HASH_TO_SCALAR_DST, context_string);
+            let mut coeffs = Vec::with_capacity(cs.len());
+            for (i, (c, d)) in cs.iter().zip(ds).enumerate() {
+                let mut msg = Vec::new();
+                write_bytes(&mut msg, &seed)?;
+                msg.write_u16::<BigEndian>(i as u16)?;
+                write_elements(pog, &mut msg, &[c, d])?;
+                msg.extend_from_slice(b"Composite");
+                coeffs.push((pog.hash_to_scalar)(&msg, &h2s_dst));
+            }
+            Ok(coeffs)
+        },
+    }
+}
+
+// derives the challenge for the statement and commitments `pts` (in the order
+// `A, B, C, D, U, V`)
+fn challenge<T,H>(pog: &PrimeOrderGroup<T,H>, domain: &Domain, pts: &[&T]) -> Result<Vec<u8>, Error> {
+    match domain {
+        // the legacy challenge does not include the generator
+        Domain::Legacy => Ok((pog.legacy_dleq_challenge)(&pts[1..])),
+        Domain::Labelled{ label, transcript } => {
+            Ok((pog.hash_to_scalar)(&frame(pog, transcript, pts)?, &dst(CHALLENGE_DST, label)))
+        },
+        // the challenge of RFC 9497 does not include the generator either
+        Domain::Rfc9497{ context_string, .. } => {
+            let mut msg = Vec::new();
+            write_elements(pog, &mut msg, &pts[1..])?;
+            msg.extend_from_slice(b"Challenge");
+            Ok((pog.hash_to_scalar)(&msg, &dst(RFC9497_HASH_TO_SCALAR_DST, context_string)))
+        },
+    }
+}
+
+// encodes the transcript and the elements `pts`, each prefixed with its
+// two-byte length
+fn frame<T,H>(pog: &PrimeOrderGroup<T,H>, transcript: &[u8], pts: &[&T]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    write_bytes(&mut out, transcript)?;
+    write_elements(pog, &mut out, pts)?;
+    Ok(out)
+}
+
+// appends the (compressed) encodings of the elements `pts`, each prefixed with
+// its two-byte length
+fn write_elements<T,H>(pog: &PrimeOrderGroup<T,H>, out: &mut Vec<u8>, pts: &[&T]) -> Result<(), Error> {
+    let mut ser = Vec::new();
+    for p in pts {
+        (pog.serialize)(p, true, &mut ser);
+        write_bytes(out, &ser)?;
+    }
+    Ok(())
+}
+
+fn write_bytes(out: &mut Vec<u8>, buf: &[u8]) -> Result<(), Error> {
+    write_length(out, buf.len())?;
+    out.extend_from_slice(buf);
+    Ok(())
+}
+
+fn write_length(out: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+    let len = u16::try_from(len).map_err(|_| err_serialization())?;
+    out.write_u16::<BigEndian>(len)
+}
+
+fn dst(prefix: &[u8], label: &[u8]) -> Vec<u8> {
+    let mut out = prefix.to_vec();
+    out.extend_from_slice(label);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::groups::p384::NistPoint;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+
+    fn statement<T,H>(pog: &PrimeOrderGroup<T,H>, n: usize) -> (Vec<u8>, T, T, Vec<T>, Vec<T>)
+            where T: Clone {
+        let mut k: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut k);
+        let a = (pog.random_element)();
+        let b = (pog.scalar_mult)(&a, &k);
+        let cs: Vec<T> = (0..n).map(|_| (pog.random_element)()).collect();
+        let ds: Vec<T> = cs.iter().map(|c| (pog.scalar_mult)(c, &k)).collect();
+        (k, a, b, cs, ds)
+    }
+
+    fn labelled_proofs<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone {
+        let domain = Domain::new(b"test_label", b"test_transcript").unwrap();
+        let (k, a, b, cs, ds) = statement(&pog, 3);
+        let proof = prove(&pog, &domain, &k, &a, &b, &cs[0], &ds[0]).unwrap();
+        assert!(verify(&pog, &domain, &a, &b, &cs[0], &ds[0], &proof));
+        let proof = Proof::from_bytes(&pog, &proof.to_bytes()).unwrap();
+        assert!(verify(&pog, &domain, &a, &b, &cs[0], &ds[0], &proof));
+
+        // proofs are bound to the label, the transcript and the statement
+        assert!(!verify(&pog, &Domain::new(b"other_label", b"test_transcript").unwrap(), &a, &b, &cs[0], &ds[0], &proof));
+        assert!(!verify(&pog, &Domain::new(b"test_label", b"other_transcript").unwrap(), &a, &b, &cs[0], &ds[0], &proof));
+        assert!(!verify(&pog, &Domain::Legacy, &a, &b, &cs[0], &ds[0], &proof));
+        assert!(!verify(&pog, &domain, &a, &b, &cs[1], &ds[1], &proof));
+        assert!(!verify(&pog, &domain, &cs[0], &ds[0], &a, &b, &proof));
+
+        let proof = batch_prove(&pog, &domain, &k, &a, &b, &cs, &ds).unwrap();
+        assert!(batch_verify(&pog, &domain, &a, &b, &cs, &ds, &proof));
+        assert!(!batch_verify(&pog, &domain, &a, &b, &cs[1..], &ds[1..], &proof));
+        let mut swapped = ds.clone();
+        swapped.swap(0, 1);
+        assert!(!batch_verify(&pog, &domain, &a, &b, &cs, &swapped, &proof));
+        assert!(!batch_verify(&pog, &domain, &a, &b, &cs, &ds[1..], &proof));
+        assert!(!batch_verify(&pog, &domain, &a, &b, &[], &[], &proof));
+    }
+
+    fn legacy_proofs<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone {
+        let (k, _, _, cs, ds) = statement(&pog, 3);
+        let g = pog.generator.clone();
+        let y = (pog.scalar_mult)(&g, &k);
+        let proof = prove(&pog, &Domain::Legacy, &k, &g, &y, &cs[0], &ds[0]).unwrap();
+        assert!(verify(&pog, &Domain::Legacy, &g, &y, &cs[0], &ds[0], &proof));
+        let proof = batch_prove(&pog, &Domain::Legacy, &k, &g, &y, &cs, &ds).unwrap();
+        assert!(batch_verify(&pog, &Domain::Legacy, &g, &y, &cs, &ds, &proof));
+
+        // legacy proofs only support statements over the generator
+        let (k, a, b, cs, ds) = statement(&pog, 1);
+        let proof = prove(&pog, &Domain::Legacy, &k, &a, &b, &cs[0], &ds[0]).unwrap();
+        assert!(!verify(&pog, &Domain::Legacy, &a, &b, &cs[0], &ds[0], &proof));
+    }
+
+    #[test]
+    fn labelled_proofs_ristretto() {
+        labelled_proofs(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn labelled_proofs_p384() {
+        labelled_proofs(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    #[test]
+    fn legacy_proofs_ristretto() {
+        legacy_proofs(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn legacy_proofs_p384() {
+        legacy_proofs(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    fn fast_composites<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone {
+        let domains = [Domain::Legacy, Domain::new(b"test_label", b"").unwrap()];
+        let (k, a, b, cs, ds) = statement(&pog, 4);
+        for domain in &domains {
+            let (m, z) = composites(&pog, domain, &a, &b, &cs, &ds).unwrap();
+            let (m_fast, z_fast) = composites_fast(&pog, domain, &k, &a, &b, &cs, &ds).unwrap();
+            assert!((pog.is_equal)(&m, &m_fast));
+            assert!((pog.is_equal)(&z, &z_fast));
+        }
+    }
+
+    #[test]
+    fn fast_composites_ristretto() {
+        fast_composites(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn fast_composites_p384() {
+        fast_composites(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    #[test]
+    fn fixed_nonce_proofs() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let domain = Domain::new(b"test_label", b"").unwrap();
+        let (k, a, b, cs, ds) = statement(&pog, 2);
+        let mut t: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut t);
+        let p1 = batch_prove_with_nonce(&pog, &domain, &k, &a, &b, &cs, &ds, &t).unwrap();
+        let p2 = batch_prove_with_nonce(&pog, &domain, &k, &a, &b, &cs, &ds, &t).unwrap();
+        assert!(p1 == p2);
+        assert!(batch_verify(&pog, &domain, &a, &b, &cs, &ds, &p1));
+    }
+
+    #[test]
+    fn invalid_domains() {
+        assert!(Domain::new(&[0; 200], b"").is_ok());
+        assert!(Domain::new(&[0; 201], b"").is_err());
+        assert!(Domain::new(b"test_label", &vec![0; 65535]).is_ok());
+        assert!(Domain::new(b"test_label", &vec![0; 65536]).is_err());
+
+        // domains that are constructed directly are checked when used
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let domain = Domain::Labelled{ label: b"test_label".to_vec(), transcript: vec![0; 65536] };
+        let (k, a, b, cs, ds) = statement(&pog, 2);
+        assert!(prove(&pog, &domain, &k, &a, &b, &cs[0], &ds[0]).is_err());
+        assert!(batch_prove(&pog, &domain, &k, &a, &b, &cs, &ds).is_err());
+        let valid = Domain::new(b"test_label", b"").unwrap();
+        let proof = prove(&pog, &valid, &k, &a, &b, &cs[0], &ds[0]).unwrap();
+        assert!(!verify(&pog, &domain, &a, &b, &cs[0], &ds[0], &proof));
+    }
+
+    #[test]
+    fn invalid_statements() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let (k, a, b, cs, ds) = statement(&pog, 2);
+        for domain in &[Domain::Legacy, Domain::new(b"test_label", b"").unwrap()] {
+            assert!(composites(&pog, domain, &a, &b, &[], &[]).is_err());
+            assert!(composites(&pog, domain, &a, &b, &cs, &ds[1..]).is_err());
+            assert!(composites_fast(&pog, domain, &k, &a, &b, &cs[1..], &ds).is_err());
+            assert!(batch_prove(&pog, domain, &k, &a, &b, &[], &[]).is_err());
+        }
+    }
+
+    // legacy proofs for a fixed key, nonce and inputs, generated with the
+    // group-specific DLEQ functions that `Domain::Legacy` replaced
+    #[test]
+    fn legacy_known_answer_ristretto() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let scalar = |v: u8| {
+            let mut out = vec![0; 32];
+            out[0] = v;
+            out
+        };
+        let (k, t) = (scalar(7), scalar(11));
+        let g = pog.generator;
+        let y = (pog.scalar_mult)(&g, &k);
+        let ms: Vec<_> = (0..3).map(|i| (pog.scalar_mult)(&g, &scalar(2 + i))).collect();
+        let zs: Vec<_> = ms.iter().map(|m| (pog.scalar_mult)(m, &k)).collect();
+
+        let proof = prove_with_nonce(&pog, &Domain::Legacy, &k, &g, &y, &ms[0], &zs[0], &t).unwrap();
+        assert_eq!(hex::encode(proof.to_bytes()), "37d800306e42dc07a0748c183c2a422f917c759a05ccdee8f9fd85fc2c8714c630e29c5972d53f1bb40195c26a23047e48e7fdd45f281c80295288ebc049d9bd151c8547f2dc39b7771c4fb604c3f7cc0e98c9c6d86be8a12a0e5618c54d7005");
+        let proof = batch_prove_with_nonce(&pog, &Domain::Legacy, &k, &g, &y, &ms, &zs, &t).unwrap();
+        assert_eq!(hex::encode(proof.to_bytes()), "1cb3805b1b190d5191cdb4239386e0ebd9e656f2740468d73c29b56ec70c113119d539ab558448bd4a606509179e581e80efdac4eacc647783a236f737068c2aa5509b7c84d4385a72db55071ccb05580cb09f5fcde0271c56df0bf98ba68808");
+        assert!(batch_verify(&pog, &Domain::Legacy, &g, &y, &ms, &zs, &proof));
+    }
+}
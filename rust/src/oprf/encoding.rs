@@ -0,0 +1,603 @@
+//! The encoding module implements `Serialize` and `Deserialize` for the
+//! objects that are used in the (V)OPRF protocol: `SecretKey`, `PublicKey`,
+//! `Input`, `Evaluation` and `BlindState`. Group elements and scalars are encoded through
+//! the group that they belong to, and deserializing fails if they are not
+//! valid. Proofs are encoded as a single byte string (see `Proof`). Secret
+//! keys are not tied to a group, so they are deserialized with a `KeySeed`
+//! for their group.
+//!
+//! Byte strings are represented in one of the following formats:
+//!
+//! - `Hex`: a hex-encoded string
+//! - `Base64Url`: an unpadded base64url-encoded string
+//! - `Raw`: the raw bytes
+//!
+//! By default (`Auto`), objects are encoded as hex in human-readable formats
+//! (such as JSON) and as raw bytes otherwise. A specific format is selected by
+//! wrapping the object in `Encoded`.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,PublicKey};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::oprf::encoding::{Encoded,Base64Url};
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//!
+//! let pog = PrimeOrderGroup::ristretto_255();
+//! let srv = Server::setup(Ciphersuite::new(pog.clone(), true));
+//! let pk = srv.key.pub_key(&pog);
+//!
+//! // hex encoding (default)
+//! let json = serde_json::to_string(&pk).unwrap();
+//! assert_eq!(json, format!("\"{}\"", pk.as_hex(&pog)));
+//! let _: PublicKey<RistrettoPoint> = serde_json::from_str(&json).unwrap();
+//!
+//! // base64url encoding
+//! let json = serde_json::to_string(&Encoded::<Base64Url,_>::new(&pk)).unwrap();
+//! let dec: Encoded<Base64Url,PublicKey<RistrettoPoint>> = serde_json::from_str(&json).unwrap();
+//! assert_eq!(dec.into_inner().as_hex(&pog), pk.as_hex(&pog));
+//! ```
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::{Serialize,Serializer,Deserialize,Deserializer};
+use serde::ser::SerializeStruct;
+use serde::de::{self,Visitor,SeqAccess,DeserializeSeed};
+
+use super::{SecretKey,PublicKey,Input,Evaluation};
+use super::groups::{PrimeOrderGroup,GroupElement};
+use super::proof::Proof;
+use super::state::BlindState;
+
+/// The `Format` trait defines how byte strings are represented
+pub trait Format {
+    /// Serializes `bytes` in the format
+    fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>;
+    /// Deserializes bytes that are represented in the format
+    fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error>;
+}
+
+/// Hex in human-readable formats, raw bytes otherwise
+pub struct Auto;
+/// Hex-encoded strings
+pub struct Hex;
+/// Unpadded base64url-encoded strings
+pub struct Base64Url;
+/// Raw bytes
+pub struct Raw;
+
+impl Format for Auto {
+    fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => Hex::serialize_bytes(bytes, serializer),
+            false => Raw::serialize_bytes(bytes, serializer),
+        }
+    }
+
+    fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        match deserializer.is_human_readable() {
+            true => Hex::deserialize_bytes(deserializer),
+            false => Raw::deserialize_bytes(deserializer),
+        }
+    }
+}
+
+impl Format for Hex {
+    fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(&s).map_err(|_| de::Error::custom("invalid hex encoding"))
+    }
+}
+
+impl Format for Base64Url {
+    fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+    }
+
+    fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        base64::decode_config(&s, base64::URL_SAFE_NO_PAD).map_err(|_| de::Error::custom("invalid base64url encoding"))
+    }
+}
+
+impl Format for Raw {
+    fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+// accepts raw bytes, or a sequence of bytes for formats (such as JSON) that
+// have no native byte strings
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut out = Vec::new();
+        while let Some(b) = seq.next_element()? {
+            out.push(b);
+        }
+        Ok(out)
+    }
+}
+
+/// The `Encode` trait is implemented by objects that can be serialized in
+/// any `Format`
+pub trait Encode {
+    /// Serializes the object, with byte strings represented in format `F`
+    fn encode<F: Format, S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+/// The `Decode` trait is implemented by objects that can be deserialized from
+/// any `Format`
+pub trait Decode: Sized {
+    /// Deserializes the object, with byte strings represented in format `F`
+    fn decode<'de, F: Format, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+impl<'a, X: Encode> Encode for &'a X {
+    fn encode<F: Format, S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (*self).encode::<F,S>(serializer)
+    }
+}
+
+/// `Encoded` wraps an object so that it is (de)serialized with byte strings
+/// represented in format `F`
+pub struct Encoded<F,X> {
+    value: X,
+    format: PhantomData<F>,
+}
+
+impl<F,X> Encoded<F,X> {
+    /// Wraps `value`
+    pub fn new(value: X) -> Self {
+        Encoded{
+            value: value,
+            format: PhantomData,
+        }
+    }
+
+    /// Returns the wrapped object
+    pub fn into_inner(self) -> X {
+        self.value
+    }
+}
+
+impl<F: Format, X: Encode> Serialize for Encoded<F,X> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.encode::<F,S>(serializer)
+    }
+}
+
+impl<'de, F: Format, X: Decode> Deserialize<'de> for Encoded<F,X> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Encoded::new(X::decode::<F,D>(deserializer)?))
+    }
+}
+
+// serializes a byte string in format `F`
+struct AsFormat<'a, F>(&'a [u8], PhantomData<F>);
+
+impl<'a, F> AsFormat<'a, F> {
+    fn new(bytes: &'a [u8]) -> Self {
+        AsFormat(bytes, PhantomData)
+    }
+}
+
+impl<'a, F: Format> Serialize for AsFormat<'a, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        F::serialize_bytes(self.0, serializer)
+    }
+}
+
+// deserializes a byte string in format `F`
+struct FromFormat<F>(Vec<u8>, PhantomData<F>);
+
+impl<'de, F: Format> Deserialize<'de> for FromFormat<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(FromFormat(F::deserialize_bytes(deserializer)?, PhantomData))
+    }
+}
+
+fn serialize_elem<T,H>(pog: &PrimeOrderGroup<T,H>, elem: &T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    (pog.serialize)(elem, true, &mut buf);
+    buf
+}
+
+fn deserialize_elem<T,H,E: de::Error>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<T, E> {
+    match (pog.deserialize)(buf) {
+        Ok(elem) => {
+            if !(pog.is_valid)(&elem) {
+                return Err(E::custom("invalid group element"));
+            }
+            Ok(elem)
+        },
+        Err(_) => Err(E::custom("invalid group element")),
+    }
+}
+
+fn check_scalar<T,H,E: de::Error>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<(), E> {
+    match (pog.is_valid_scalar)(buf) {
+        true => Ok(()),
+        false => Err(E::custom("invalid scalar")),
+    }
+}
+
+impl Encode for SecretKey {
+    fn encode<F: Format, S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        F::serialize_bytes(&self.0, serializer)
+    }
+}
+
+/// `KeySeed` deserializes a `SecretKey` for the group `pog` (as a
+/// `DeserializeSeed`), with byte strings represented in format `F`. The
+/// `SecretKey` is not tied to a group, so the group must be supplied, and the
+/// key is rejected if it is not a valid scalar for the group.
+pub struct KeySeed<'a,T,H,F=Auto> {
+    pog: &'a PrimeOrderGroup<T,H>,
+    format: PhantomData<F>,
+}
+
+impl<'a,T,H,F> KeySeed<'a,T,H,F> {
+    /// Creates a seed for keys of the group `pog`
+    pub fn new(pog: &'a PrimeOrderGroup<T,H>) -> Self {
+        KeySeed{
+            pog: pog,
+            format: PhantomData,
+        }
+    }
+}
+
+impl<'de,'a,T,H,F: Format> DeserializeSeed<'de> for KeySeed<'a,T,H,F> {
+    type Value = SecretKey;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<SecretKey, D::Error> {
+        let buf = F::deserialize_bytes(deserializer)?;
+        check_scalar(self.pog, &buf)?;
+        Ok(SecretKey(buf))
+    }
+}
+
+impl<T: GroupElement> Encode for PublicKey<T> {
+    fn encode<F: Format, S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        F::serialize_bytes(&serialize_elem(&T::group(), &self.0), serializer)
+    }
+}
+
+impl<T: GroupElement> Decode for PublicKey<T> {
+    fn decode<'de, F: Format, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let buf = F::deserialize_bytes(deserializer)?;
+        Ok(PublicKey(deserialize_elem(&T::group(), &buf)?))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "", deny_unknown_fields)]
+struct InputRepr<F: Format> {
+    data: FromFormat<F>,
+    elem: FromFormat<F>,
+    blind: FromFormat<F>,
+}
+
+impl<T: GroupElement> Encode for Input<T> {
+    fn encode<F: Format, S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let elem = serialize_elem(&T::group(), &self.elem);
+        let mut st = serializer.serialize_struct("Input", 3)?;
+        st.serialize_field("data", &AsFormat::<F>::new(&self.data))?;
+        st.serialize_field("elem", &AsFormat::<F>::new(&elem))?;
+        st.serialize_field("blind", &AsFormat::<F>::new(&self.blind))?;
+        st.end()
+    }
+}
+
+impl<T: GroupElement> Decode for Input<T> {
+    fn decode<'de, F: Format, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = InputRepr::<F>::deserialize(deserializer)?;
+        let pog = T::group();
+        check_scalar(&pog, &repr.blind.0)?;
+        Ok(Input{
+            data: repr.data.0,
+            elem: deserialize_elem(&pog, &repr.elem.0)?,
+            inv_blind: (pog.invert_scalar)(&repr.blind.0),
+            blind: repr.blind.0,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "", deny_unknown_fields)]
+struct EvaluationRepr<F: Format> {
+    elems: Vec<FromFormat<F>>,
+    proof: Option<FromFormat<F>>,
+}
+
+impl<T: GroupElement> Encode for Evaluation<T> {
+    fn encode<F: Format, S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let pog = T::group();
+        let elems: Vec<Vec<u8>> = self.elems.iter().map(|e| serialize_elem(&pog, e)).collect();
+        let elems: Vec<AsFormat<F>> = elems.iter().map(|e| AsFormat::new(e)).collect();
+        let proof = self.proof.as_ref().map(|p| p.to_bytes());
+        let proof = proof.as_ref().map(|p| AsFormat::<F>::new(p));
+        let mut st = serializer.serialize_struct("Evaluation", 2)?;
+        st.serialize_field("elems", &elems)?;
+        st.serialize_field("proof", &proof)?;
+        st.end()
+    }
+}
+
+impl<T: GroupElement> Decode for Evaluation<T> {
+    fn decode<'de, F: Format, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = EvaluationRepr::<F>::deserialize(deserializer)?;
+        let pog = T::group();
+        let mut elems = Vec::new();
+        for e in repr.elems {
+            elems.push(deserialize_elem(&pog, &e.0)?);
+        }
+        let proof = match repr.proof {
+            Some(p) => match Proof::from_bytes(&pog, &p.0) {
+                Ok(proof) => Some(proof),
+                Err(_) => return Err(de::Error::custom("invalid proof")),
+            },
+            None => None,
+        };
+        Ok(Evaluation{
+            elems: elems,
+            proof: proof,
+        })
+    }
+}
+
+impl Encode for BlindState {
+    fn encode<F: Format, S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        F::serialize_bytes(&self.to_bytes(), serializer)
+    }
+}
+
+impl Decode for BlindState {
+    fn decode<'de, F: Format, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let buf = F::deserialize_bytes(deserializer)?;
+        BlindState::from_bytes(&buf).map_err(|_| de::Error::custom("invalid client state"))
+    }
+}
+
+impl Serialize for SecretKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.encode::<Auto,S>(serializer)
+    }
+}
+
+impl<T: GroupElement> Serialize for PublicKey<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.encode::<Auto,S>(serializer)
+    }
+}
+
+impl<'de, T: GroupElement> Deserialize<'de> for PublicKey<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PublicKey::decode::<Auto,D>(deserializer)
+    }
+}
+
+impl<T: GroupElement> Serialize for Input<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.encode::<Auto,S>(serializer)
+    }
+}
+
+impl<'de, T: GroupElement> Deserialize<'de> for Input<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Input::decode::<Auto,D>(deserializer)
+    }
+}
+
+impl<T: GroupElement> Serialize for Evaluation<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.encode::<Auto,S>(serializer)
+    }
+}
+
+impl<'de, T: GroupElement> Deserialize<'de> for Evaluation<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Evaluation::decode::<Auto,D>(deserializer)
+    }
+}
+
+impl Serialize for BlindState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.encode::<Auto,S>(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BlindState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BlindState::decode::<Auto,D>(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoded,Hex,Base64Url,Raw,KeySeed};
+    use serde::de::DeserializeSeed;
+    use crate::oprf::groups::p384::NistPoint;
+    use crate::oprf::{Server,Client,SecretKey,PublicKey,Input,Evaluation};
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::oprf::state::BlindState;
+    use crate::oprf::groups::PrimeOrderGroup;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+
+    fn setup() -> (Server<RistrettoPoint,Sha512>, Client<RistrettoPoint,Sha512>) {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        (srv, cli)
+    }
+
+    #[test]
+    fn secret_key_hex() {
+        let (srv, _) = setup();
+        let json = serde_json::to_string(&srv.key).unwrap();
+        assert_eq!(json, format!("\"{}\"", srv.key.as_hex()));
+        let sk = decode_key(&srv.ciph.pog, &json).unwrap();
+        assert_eq!(sk.as_hex(), srv.key.as_hex());
+
+        let json = serde_json::to_string(&Encoded::<Base64Url,_>::new(&srv.key)).unwrap();
+        let mut de = serde_json::Deserializer::from_str(&json);
+        let sk = KeySeed::<_,_,Base64Url>::new(&srv.ciph.pog).deserialize(&mut de).unwrap();
+        assert_eq!(sk.as_hex(), srv.key.as_hex());
+    }
+
+    fn decode_key<T,H>(pog: &PrimeOrderGroup<T,H>, json: &str) -> Result<SecretKey, serde_json::Error> {
+        KeySeed::<_,_>::new(pog).deserialize(&mut serde_json::Deserializer::from_str(json))
+    }
+
+    #[test]
+    fn secret_key_invalid() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        // zero, larger than the group order, too short
+        for bad in &[vec![0u8; 32], vec![0xffu8; 32], vec![1u8; 31]] {
+            let json = format!("\"{}\"", hex::encode(bad));
+            assert!(decode_key(&pog, &json).is_err());
+        }
+        assert!(decode_key(&pog, "\"not hex\"").is_err());
+    }
+
+    #[test]
+    fn secret_key_other_group() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let p384 = PrimeOrderGroup::<NistPoint,Sha512>::p384();
+        let json = serde_json::to_string(&SecretKey::new(&p384)).unwrap();
+        assert!(decode_key(&p384, &json).is_ok());
+        assert!(decode_key(&pog, &json).is_err());
+        let json = serde_json::to_string(&SecretKey::new(&pog)).unwrap();
+        assert!(decode_key(&p384, &json).is_err());
+    }
+
+    #[test]
+    fn public_key_formats() {
+        let (srv, _) = setup();
+        let pog = &srv.ciph.pog;
+        let pk = srv.key.pub_key(pog);
+        let expected = pk.as_hex(pog);
+
+        let json = serde_json::to_string(&Encoded::<Hex,_>::new(&pk)).unwrap();
+        let dec: Encoded<Hex,PublicKey<RistrettoPoint>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(dec.into_inner().as_hex(pog), expected);
+
+        let json = serde_json::to_string(&Encoded::<Base64Url,_>::new(&pk)).unwrap();
+        assert!(!json.contains('=') && !json.contains('+') && !json.contains('/'));
+        let dec: Encoded<Base64Url,PublicKey<RistrettoPoint>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(dec.into_inner().as_hex(pog), expected);
+
+        let json = serde_json::to_string(&Encoded::<Raw,_>::new(&pk)).unwrap();
+        assert!(json.starts_with('['));
+        let dec: Encoded<Raw,PublicKey<RistrettoPoint>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(dec.into_inner().as_hex(pog), expected);
+    }
+
+    #[test]
+    fn public_key_invalid() {
+        let bad = format!("\"{}\"", "ff".repeat(32));
+        assert!(serde_json::from_str::<PublicKey<RistrettoPoint>>(&bad).is_err());
+        let short = format!("\"{}\"", "01".repeat(31));
+        assert!(serde_json::from_str::<PublicKey<RistrettoPoint>>(&short).is_err());
+    }
+
+    #[test]
+    fn protocol_state_round_trip() {
+        let (srv, cli) = setup();
+        let inputs = cli.blind(&[vec![1; 32], vec![2; 32]]);
+        let eval = srv.eval(&[inputs[0].elem, inputs[1].elem]).unwrap();
+
+        // store the client state and server response, and restore them
+        let inputs_json = serde_json::to_string(&Encoded::<Base64Url,_>::new(&inputs[0])).unwrap();
+        let stored: Encoded<Base64Url,Input<RistrettoPoint>> = serde_json::from_str(&inputs_json).unwrap();
+        let mut restored = vec![stored.into_inner()];
+        restored.push(serde_json::from_str(&serde_json::to_string(&inputs[1]).unwrap()).unwrap());
+        let eval_json = serde_json::to_string(&eval).unwrap();
+        let restored_eval: Evaluation<RistrettoPoint> = serde_json::from_str(&eval_json).unwrap();
+
+        let outs = cli.unblind(&restored, &restored_eval).unwrap();
+        let expected = cli.unblind(&inputs, &eval).unwrap();
+        assert_eq!(outs, expected);
+    }
+
+    #[test]
+    fn input_invalid_blind() {
+        let (_, cli) = setup();
+        let inputs = cli.blind(&[vec![1; 32]]);
+        let mut json: serde_json::Value = serde_json::to_value(&inputs[0]).unwrap();
+        json["blind"] = serde_json::Value::String("00".repeat(32));
+        assert!(serde_json::from_value::<Input<RistrettoPoint>>(json.clone()).is_err());
+        json["blind"] = serde_json::Value::String(hex::encode(&inputs[0].blind));
+        json["elem"] = serde_json::Value::String("ff".repeat(32));
+        assert!(serde_json::from_value::<Input<RistrettoPoint>>(json).is_err());
+    }
+
+    #[test]
+    fn blind_state_formats() {
+        let (_, cli) = setup();
+        let inputs = cli.blind(&[vec![1; 32]]);
+        let state = BlindState::new(&cli, &inputs, Some(b"key"));
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(json, format!("\"{}\"", hex::encode(state.to_bytes())));
+        assert!(serde_json::from_str::<BlindState>(&json).unwrap() == state);
+        let json = serde_json::to_string(&Encoded::<Base64Url,_>::new(&state)).unwrap();
+        let dec: Encoded<Base64Url,BlindState> = serde_json::from_str(&json).unwrap();
+        assert!(dec.into_inner() == state);
+        assert!(serde_json::from_str::<BlindState>("\"0100\"").is_err());
+    }
+
+    #[test]
+    fn evaluation_invalid_proof() {
+        let (srv, cli) = setup();
+        let inputs = cli.blind(&[vec![1; 32]]);
+        let eval = srv.eval(&[inputs[0].elem]).unwrap();
+        let mut json: serde_json::Value = serde_json::to_value(&eval).unwrap();
+        let proof = eval.proof.unwrap().to_bytes();
+        assert_eq!(json["proof"], serde_json::Value::String(hex::encode(&proof)));
+        json["proof"] = serde_json::Value::String(hex::encode(&proof[1..]));
+        assert!(serde_json::from_value::<Evaluation<RistrettoPoint>>(json.clone()).is_err());
+        json["proof"] = serde_json::Value::String("ff".repeat(64));
+        assert!(serde_json::from_value::<Evaluation<RistrettoPoint>>(json).is_err());
+    }
+
+    #[test]
+    fn evaluation_without_proof() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let srv = Server::setup(Ciphersuite::new(pog.clone(), false));
+        let eval = srv.eval(&[(pog.random_element)()]).unwrap();
+        let json = serde_json::to_string(&eval).unwrap();
+        assert!(json.contains("\"proof\":null"));
+        let dec: Evaluation<RistrettoPoint> = serde_json::from_str(&json).unwrap();
+        assert!(dec.proof.is_none());
+        assert_eq!(dec.elems, eval.elems);
+        let unknown = json.replace("\"proof\"", "\"other\"");
+        assert!(serde_json::from_str::<Evaluation<RistrettoPoint>>(&unknown).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! The `edwards25519` module allows creating a `PrimeOrderGroup` object for
+//! the prime-order subgroup of
+//! [edwards25519](https://tools.ietf.org/html/rfc7748). It is not a (V)OPRF
+//! ciphersuite, but allows the ECVRF ciphersuites of the `vrf` module to use
+//! the same group operations and DLEQ proofs as the (V)OPRF.
+//!
+//! Elements are encoded as compressed Edwards points. Deserialization only
+//! accepts canonical encodings of points in the prime-order subgroup, and the
+//! scalar operations are those of ristretto255, which has the same order.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! let pog = PrimeOrderGroup::edwards_25519();
+//! ```
+
+use curve25519_dalek::edwards::{EdwardsPoint,CompressedEdwardsY};
+use curve25519_dalek::constants::{ED25519_BASEPOINT_POINT,ED25519_BASEPOINT_TABLE};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity,IsIdentity,VartimeMultiscalarMul};
+use num::BigUint;
+
+use super::{PrimeOrderGroup,GroupElement,MSM_CHUNK};
+use super::ristretto::ristretto_coefficients;
+use super::super::super::utils::{copy_into,expand_message_xmd,par_chunks,par_map};
+use super::super::super::errors::err_deserialization;
+
+use sha2::Sha512;
+use sha2::Digest;
+
+const EDWARDS_BYTE_LENGTH: usize = 32;
+// the domain-separation tag of `encode_to_group`
+const ENCODE_DST: &[u8] = b"voprf-rs-edwards25519_XMD:SHA-512_ELL2_NU_";
+
+/// Constructs an instance of `PrimeOrderGroup` for the prime-order subgroup
+/// of edwards25519, using the implementation found in curve25519_dalek
+/// (https://doc.dalek.rs/curve25519_dalek/edwards).
+impl PrimeOrderGroup<EdwardsPoint,Sha512> {
+    /// Returns an instance of PrimeOrderGroup for the prime-order subgroup of
+    /// edwards25519
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use voprf_rs::oprf::groups::PrimeOrderGroup;
+    /// let pog = PrimeOrderGroup::edwards_25519();
+    /// ```
+    pub fn edwards_25519() -> PrimeOrderGroup<EdwardsPoint,Sha512> {
+        // edwards25519 and ristretto255 share their scalars
+        let scalars = PrimeOrderGroup::ristretto_255();
+        PrimeOrderGroup{
+            generator: ED25519_BASEPOINT_POINT,
+            byte_length: EDWARDS_BYTE_LENGTH,
+            hash: || Sha512::new(),
+            deserialize: |buf: &[u8]| {
+                match edwards_decode(buf) {
+                    Some(p) if p.is_torsion_free() => Ok(p),
+                    _ => Err(err_deserialization()),
+                }
+            },
+            encode_to_group: |buf: &[u8]| edwards_encode_to_curve(buf, ENCODE_DST),
+            hash_to_group: |buf: &[u8], dst: &[u8]| edwards_encode_to_curve(buf, dst),
+            is_valid: |p: &EdwardsPoint| p.is_torsion_free() && !p.is_identity(),
+            is_equal: |p1: &EdwardsPoint, p2: &EdwardsPoint| p1 == p2,
+            add: |p1: &EdwardsPoint, p2: &EdwardsPoint| p1 + p2,
+            scalar_mult: |p: &EdwardsPoint, r: &[u8]| p * edwards_scalar_from_slice(r),
+            base_mult: |r: &[u8]| &edwards_scalar_from_slice(r) * &ED25519_BASEPOINT_TABLE,
+            batch_scalar_mult: |ps: &[EdwardsPoint], rs: &[Vec<u8>]| {
+                par_map(ps.len(), |i| ps[i] * edwards_scalar_from_slice(&rs[i]))
+            },
+            batch_mult_by: |ps: &[EdwardsPoint], r: &[u8]| {
+                let sc = edwards_scalar_from_slice(r);
+                par_map(ps.len(), |i| ps[i] * sc)
+            },
+            multi_scalar_mult: |ps: &[EdwardsPoint], rs: &[Vec<u8>]| edwards_multi_scalar_mult(ps, rs),
+            inverse_mult: |p: &EdwardsPoint, r: &[u8]| p * edwards_scalar_from_slice(r).invert(),
+            serialize: |p: &EdwardsPoint, _: bool, out: &mut Vec<u8>| {
+                copy_into(p.compress().as_bytes(), out)
+            },
+            random_element: || {
+                let mut r: Vec<u8> = Vec::new();
+                (PrimeOrderGroup::ristretto_255().uniform_bytes)(&mut r);
+                &edwards_scalar_from_slice(&r) * &ED25519_BASEPOINT_TABLE
+            },
+            uniform_bytes: scalars.uniform_bytes,
+            // reduces 32-byte or 64-byte little-endian values
+            reduce_scalar: |sc: &[u8], pve: bool| {
+                let mut wide = [0u8; 64];
+                wide[..sc.len()].copy_from_slice(sc);
+                let red = Scalar::from_bytes_mod_order_wide(&wide);
+                match pve {
+                    true => red.to_bytes().to_vec(),
+                    false => (-red).to_bytes().to_vec(),
+                }
+            },
+            is_valid_scalar: scalars.is_valid_scalar,
+            mul_scalars: scalars.mul_scalars,
+            invert_scalar: scalars.invert_scalar,
+            sub_scalars: scalars.sub_scalars,
+            hash_to_scalar: scalars.hash_to_scalar,
+            // draft-irtf-cfrg-voprf-02 does not define edwards25519, so the
+            // legacy DLEQ functions are derived as for ristretto255
+            legacy_challenge_length: EDWARDS_BYTE_LENGTH,
+            legacy_dleq_challenge: |pts: &[&EdwardsPoint]| {
+                Scalar::from_bytes_mod_order_wide(&edwards_dleq_hash(pts)).to_bytes().to_vec()
+            },
+            legacy_dleq_coefficients: |pts: &[&EdwardsPoint], n: usize| {
+                ristretto_coefficients(&edwards_dleq_hash(pts), n)
+            },
+        }
+    }
+}
+
+impl GroupElement for EdwardsPoint {
+    type Hash = Sha512;
+
+    fn group() -> PrimeOrderGroup<EdwardsPoint,Sha512> {
+        PrimeOrderGroup::edwards_25519()
+    }
+}
+
+/// Encodes `msg` to the prime-order subgroup with the
+/// `edwards25519_XMD:SHA-512_ELL2_NU_` suite of
+/// [RFC 9380](https://www.rfc-editor.org/rfc/rfc9380.html), under the
+/// domain-separation tag `dst`
+pub fn edwards_encode_to_curve(msg: &[u8], dst: &[u8]) -> EdwardsPoint {
+    let u = expand_message_xmd(msg, dst, 48);
+    map_to_curve_elligator2(&BigUint::from_bytes_be(&u)).mul_by_cofactor()
+}
+
+/// Decodes the canonical encoding of any point of edwards25519, and maps it
+/// to the prime-order subgroup by multiplying it with the cofactor
+pub fn edwards_clear_cofactor(buf: &[u8]) -> Option<EdwardsPoint> {
+    edwards_decode(buf).map(|p| p.mul_by_cofactor())
+}
+
+// decodes a point of edwards25519, rejecting non-canonical encodings
+fn edwards_decode(buf: &[u8]) -> Option<EdwardsPoint> {
+    if buf.len() != EDWARDS_BYTE_LENGTH {
+        return None;
+    }
+    let p = CompressedEdwardsY::from_slice(buf).decompress()?;
+    if p.compress().as_bytes() != buf {
+        return None;
+    }
+    Some(p)
+}
+
+// computes Σ rs[i]*ps[i], summing the results for each chunk of the inputs
+fn edwards_multi_scalar_mult(ps: &[EdwardsPoint], rs: &[Vec<u8>]) -> EdwardsPoint {
+    let chunks = par_chunks(ps.len(), MSM_CHUNK);
+    par_map(chunks.len(), |i| {
+        let scalars = rs[chunks[i].clone()].iter().map(|r| edwards_scalar_from_slice(r));
+        EdwardsPoint::vartime_multiscalar_mul(scalars, &ps[chunks[i].clone()])
+    }).iter().sum()
+}
+
+// hashes the generator and the points for legacy DLEQ proofs
+fn edwards_dleq_hash(pts: &[&EdwardsPoint]) -> [u8; 64] {
+    let mut hash = Sha512::new();
+    hash.input(ED25519_BASEPOINT_POINT.compress().as_bytes());
+    for p in pts {
+        hash.input(p.compress().as_bytes());
+    }
+    let mut out = [0u8; 64];
+    out.copy_from_slice(&hash.result());
+    out
+}
+
+// recovers a `Scalar` object from a slice
+fn edwards_scalar_from_slice(x: &[u8]) -> Scalar {
+    let mut buf = [0u8; EDWARDS_BYTE_LENGTH];
+    buf.copy_from_slice(&x[..EDWARDS_BYTE_LENGTH]);
+    Scalar::from_bytes_mod_order(buf)
+}
+
+/// Maps the field element `u` to edwards25519 with Elligator 2, as in
+/// `map_to_curve_elligator2` of RFC 9380 followed by the rational map to
+/// edwards25519. The result is not in the prime-order subgroup.
+fn map_to_curve_elligator2(u: &BigUint) -> EdwardsPoint {
+    let f = Field::new();
+    let a = BigUint::from(486_662u32);
+    let u = u % &f.p;
+
+    // map to curve25519
+    let den = f.add(&BigUint::from(1u8), &f.mul(&BigUint::from(2u8), &f.mul(&u, &u)));
+    let x1 = if den == BigUint::from(0u8) {
+        f.neg(&a)
+    } else {
+        f.mul(&f.neg(&a), &f.inv(&den))
+    };
+    let gx = |x: &BigUint| f.add(&f.mul(x, &f.add(&f.mul(x, x), &f.mul(&a, x))), x);
+    let x2 = f.neg(&f.add(&x1, &a));
+    let (s, t) = match f.sqrt(&gx(&x1)) {
+        Some(y) => (x1, if f.sgn0(&y) == 1 { y } else { f.neg(&y) }),
+        None => {
+            let y = f.sqrt(&gx(&x2)).expect("one of gx1 and gx2 is square");
+            (x2, if f.sgn0(&y) == 0 { y } else { f.neg(&y) })
+        },
+    };
+
+    // rational map to edwards25519
+    let s1 = f.add(&s, &BigUint::from(1u8));
+    if t == BigUint::from(0u8) || s1 == BigUint::from(0u8) {
+        return EdwardsPoint::identity();
+    }
+    let mut c1 = f.sqrt(&f.neg(&BigUint::from(486_664u32))).expect("-486664 is square");
+    if f.sgn0(&c1) == 1 {
+        c1 = f.neg(&c1);
+    }
+    let v = f.mul(&f.mul(&c1, &s), &f.inv(&t));
+    let w = f.mul(&f.sub(&s, &BigUint::from(1u8)), &f.inv(&s1));
+    let mut enc = [0u8; 32];
+    let w_bytes = w.to_bytes_le();
+    enc[..w_bytes.len()].copy_from_slice(&w_bytes);
+    enc[31] |= f.sgn0(&v) << 7;
+    CompressedEdwardsY(enc).decompress().expect("mapped point is on the curve")
+}
+
+/// Arithmetic in the field of integers modulo `2^255 - 19`, which is only
+/// needed by Elligator 2 (curve25519_dalek does not expose its own)
+struct Field {
+    p: BigUint,
+}
+
+impl Field {
+    fn new() -> Self {
+        Field{ p: (BigUint::from(1u8) << 255) - BigUint::from(19u8) }
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + b) % &self.p
+    }
+
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.add(a, &self.neg(b))
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a * b) % &self.p
+    }
+
+    fn neg(&self, a: &BigUint) -> BigUint {
+        (&self.p - (a % &self.p)) % &self.p
+    }
+
+    fn inv(&self, a: &BigUint) -> BigUint {
+        a.modpow(&(&self.p - BigUint::from(2u8)), &self.p)
+    }
+
+    fn sgn0(&self, a: &BigUint) -> u8 {
+        (a % BigUint::from(2u8) == BigUint::from(1u8)) as u8
+    }
+
+    /// Returns a square root of `a`, if `a` is square
+    fn sqrt(&self, a: &BigUint) -> Option<BigUint> {
+        let r = a.modpow(&((&self.p + BigUint::from(3u8)) >> 3), &self.p);
+        if self.mul(&r, &r) == *a {
+            return Some(r);
+        }
+        let sqrt_m1 = BigUint::from(2u8).modpow(&((&self.p - BigUint::from(1u8)) >> 2), &self.p);
+        let r = self.mul(&r, &sqrt_m1);
+        if self.mul(&r, &r) == *a {
+            return Some(r);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::constants::EIGHT_TORSION;
+
+    #[test]
+    fn edwards_serialization() {
+        let pog = PrimeOrderGroup::edwards_25519();
+        let p = (pog.random_element)();
+        let mut ser: Vec<u8> = Vec::new();
+        (pog.serialize)(&p, true, &mut ser);
+        assert_eq!((pog.deserialize)(&ser).unwrap(), p);
+        assert!((pog.deserialize)(&ser[1..]).is_err());
+
+        // points outside of the prime-order subgroup are rejected
+        let mixed = p + EIGHT_TORSION[1];
+        let mut ser: Vec<u8> = Vec::new();
+        (pog.serialize)(&mixed, true, &mut ser);
+        assert!((pog.deserialize)(&ser).is_err());
+        assert_eq!(edwards_clear_cofactor(&ser).unwrap(), p.mul_by_cofactor());
+        assert!(!(pog.is_valid)(&mixed));
+        assert!(!(pog.is_valid)(&EdwardsPoint::identity()));
+        assert!((pog.is_valid)(&p));
+    }
+
+    #[test]
+    fn edwards_point_mult() {
+        let pog = PrimeOrderGroup::edwards_25519();
+        let p = (pog.random_element)();
+        let mut r1: Vec<u8> = Vec::new();
+        let mut r2: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r1);
+        (pog.uniform_bytes)(&mut r2);
+        let add_p = (pog.add)(&(pog.scalar_mult)(&p, &r1), &(pog.scalar_mult)(&p, &r2));
+        let r1_r2 = (pog.sub_scalars)(&r1, &(pog.sub_scalars)(&[0; EDWARDS_BYTE_LENGTH], &r2));
+        assert!((pog.is_equal)(&add_p, &(pog.scalar_mult)(&p, &r1_r2)));
+        assert!((pog.is_equal)(&(pog.base_mult)(&r1), &(pog.scalar_mult)(&pog.generator, &r1)));
+        assert!((pog.is_equal)(&(pog.inverse_mult)(&(pog.scalar_mult)(&p, &r1), &r1), &p));
+
+        // reduction of wide and negated values
+        let neg = (pog.reduce_scalar)(&r1, false);
+        assert_eq!((pog.sub_scalars)(&[0; EDWARDS_BYTE_LENGTH], &r1), neg);
+        assert_eq!((pog.reduce_scalar)(&[&r1[..], &[0; 32]].concat(), true), r1);
+    }
+
+    #[test]
+    fn edwards_multi_scalar_mult() {
+        let pog = PrimeOrderGroup::edwards_25519();
+        for &n in &[1, 70] {
+            let mut ps = Vec::new();
+            let mut rs = Vec::new();
+            for _ in 0..n {
+                let mut r: Vec<u8> = Vec::new();
+                (pog.uniform_bytes)(&mut r);
+                ps.push((pog.random_element)());
+                rs.push(r);
+            }
+            let batch = (pog.batch_scalar_mult)(&ps, &rs);
+            let mut expected = batch[0];
+            for p in &batch[1..] {
+                expected = (pog.add)(&expected, p);
+            }
+            assert!((pog.is_equal)(&(pog.multi_scalar_mult)(&ps, &rs), &expected));
+        }
+    }
+
+    #[test]
+    fn edwards_encode_to_group() {
+        let pog = PrimeOrderGroup::edwards_25519();
+        let p = (pog.encode_to_group)(b"some_input");
+        assert!((pog.is_valid)(&p));
+        assert!((pog.is_equal)(&p, &(pog.encode_to_group)(b"some_input")));
+        assert!(!(pog.is_equal)(&p, &(pog.encode_to_group)(b"other_input")));
+        assert!(!(pog.is_equal)(&p, &edwards_encode_to_curve(b"some_input", b"other_dst")));
+    }
+}
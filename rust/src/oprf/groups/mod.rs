@@ -3,12 +3,27 @@
 //! insatntiations of the group settings. Currently supported groups:
 //!
 //! - ristretto255 (experimental, not specified in draft)
+//! - NIST P-384 with SHA-512 (draft-irtf-cfrg-voprf-02)
+//! - NIST P-384 with SHA-384 (RFC 9497)
+//!
+//! The prime-order subgroup of edwards25519 and the NIST P-256 group are also
+//! provided for the `vrf` module, but are not (V)OPRF ciphersuites.
 
 
 pub mod ristretto;
 pub mod p384;
+pub mod p384_sha384;
+pub mod p256;
+pub mod edwards25519;
+mod msm;
+mod weierstrass;
 
 use std::io::Error;
+use crate::errors::err_scalar_inversion;
+
+// minimum number of elements that each thread handles in a multi-scalar
+// multiplication, with the `parallel` feature
+const MSM_CHUNK: usize = 64;
 
 /// The `PrimeOrderGroup` struct defines the behaviour expected from an additive
 /// group with prime order instantiation. The template variable `T` corresponds
@@ -74,10 +89,11 @@ use std::io::Error;
 /// h.input(b"some_data");
 /// ```
 ///
-/// Each `PrimeOrderGroup` also defines methods for computing DLEQ proofs (see:
-/// <https://tools.ietf.org/html/draft-irtf-cfrg-voprf-02#section-5>). The
-/// proofs are dependent on scalar operations. These operations are implemeneted
-/// differently depending on the type of scalars used.
+/// Each `PrimeOrderGroup` also defines the scalar operations and hash functions
+/// that are required for computing DLEQ proofs (see:
+/// <https://tools.ietf.org/html/draft-irtf-cfrg-voprf-02#section-5>) with the
+/// generic `dleq` module. These operations are implemented differently
+/// depending on the type of scalars used.
 ///
 /// DLEQ proof objects allow someone to generate proof objects that attest to
 /// the fact that `y = k*g` and `z = k*m` share the same discrete logarithm `k`
@@ -85,6 +101,7 @@ use std::io::Error;
 /// revealing `k`). DLEQ proof generation and verification is as follows:
 ///
 /// ```
+/// use voprf_rs::oprf::dleq::{self,Domain};
 /// use voprf_rs::oprf::groups::PrimeOrderGroup;
 /// let pog = PrimeOrderGroup::ristretto_255();
 /// let m = (pog.random_element)();
@@ -92,36 +109,13 @@ use std::io::Error;
 /// // generate scalar value
 /// let mut k: Vec<u8> = Vec::new();
 /// (pog.uniform_bytes)(&mut k);
-/// let y = (pog.scalar_mult)(&pog.generator, &k);
+/// let y = (pog.base_mult)(&k);
 /// let z = (pog.scalar_mult)(&m, &k);
 ///
 /// // generate proof object
-/// let proof = (pog.dleq_generate)(&k, &y, &m, &z);
-/// let b = (pog.dleq_verify)(&y, &m, &z, &proof); // should return true
-/// ```
-///
-/// There are also "batch" methods that allow proving the same statement above
-/// where `m = vec![m_0, m_1, m_2, ...]`, `z = vec![z_0, z_1, z_2]` where `z_i =
-/// k*m_i` for each `i`:
-///
-/// ```
-/// use voprf_rs::oprf::groups::PrimeOrderGroup;
-/// let pog = PrimeOrderGroup::ristretto_255();
-/// let mut k: Vec<u8> = Vec::new();
-/// (pog.uniform_bytes)(&mut k);
-/// let y = (pog.scalar_mult)(&pog.generator, &k);
-///
-/// let mut inputs = Vec::new();
-/// let mut outs = Vec::new();
-/// for _ in 0..5 {
-///     let m = (pog.random_element)();
-///     inputs.push(m);
-///     outs.push((pog.scalar_mult)(&m, &k));
-/// }
-///
-/// // generate batched proof object
-/// let proof = (pog.batch_dleq_generate)(&k, &y, &inputs, &outs);
-/// let b = (pog.batch_dleq_verify)(&y, &inputs, &outs, &proof); // should return true
+/// let domain = Domain::new(b"some_protocol", b"").unwrap();
+/// let proof = dleq::prove(&pog, &domain, &k, &pog.generator, &y, &m, &z).unwrap();
+/// let b = dleq::verify(&pog, &domain, &pog.generator, &y, &m, &z, &proof); // should return true
 /// ```
 ///
 /// /// Examples above apply when using p384:
@@ -149,6 +143,9 @@ pub struct PrimeOrderGroup<T,H> {
     /// A function for deterministically mapping arbitrary bytes to uniformly
     /// distributed elements of the group.
     pub encode_to_group: fn(&[u8]) -> T,
+    /// A function that hashes arbitrary bytes to the group under the provided
+    /// domain-separation tag, used as the `HashToGroup` function of RFC 9497
+    pub hash_to_group: fn(&[u8], &[u8]) -> T,
     /// A function indicating whether the input is a valid group element
     pub is_valid: fn(&T) -> bool,
     /// A function for checking whether two points are equal, or not
@@ -158,6 +155,22 @@ pub struct PrimeOrderGroup<T,H> {
     /// A function that performs scalar multiplication of a group element with a
     /// provided scalar value
     pub scalar_mult: fn(&T, &[u8]) -> T,
+    /// A function that multiplies the fixed generator with a scalar, using
+    /// precomputed tables where available. Only the curve25519-dalek groups
+    /// (ristretto255 and edwards25519) run in constant time with respect to
+    /// the scalar. The NIST groups (P-384, P-384 with SHA-384 and P-256) do
+    /// not, as their arithmetic is carried out over `BigUint`.
+    pub base_mult: fn(&[u8]) -> T,
+    /// A function that computes `r_i*P_i` for each of the provided group
+    /// elements `P_i` and scalars `r_i`
+    pub batch_scalar_mult: fn(&[T], &[Vec<u8>]) -> Vec<T>,
+    /// A function that computes `r*P_i` for each of the provided group
+    /// elements `P_i` and a single scalar `r`
+    pub batch_mult_by: fn(&[T], &[u8]) -> Vec<T>,
+    /// A function that computes `Σ r_i*P_i` for the provided group elements
+    /// `P_i` and scalars `r_i`. It does not necessarily run in constant time,
+    /// and so must only be used over public inputs.
+    pub multi_scalar_mult: fn(&[T], &[Vec<u8>]) -> T,
     /// A function that computes (1/r) * P, where P is a group element and r is
     /// a scalar input
     pub inverse_mult: fn(&T, &[u8]) -> T,
@@ -174,25 +187,75 @@ pub struct PrimeOrderGroup<T,H> {
     pub deserialize: fn(&[u8]) -> Result<T, Error>,
     /// Reduces a scalar with respect to the order of the group
     pub reduce_scalar: fn(&[u8], bool) -> Vec<u8>,
+    /// A function indicating whether the input bytes are the canonical
+    /// encoding of a non-zero scalar
+    pub is_valid_scalar: fn(&[u8]) -> bool,
+    /// A function that multiplies two scalars modulo the order of the group
+    pub mul_scalars: fn(&[u8], &[u8]) -> Vec<u8>,
+    /// A function that computes the inverse of a non-zero scalar modulo the
+    /// order of the group
+    pub invert_scalar: fn(&[u8]) -> Vec<u8>,
+
+    /// A function that subtracts the second scalar from the first modulo the
+    /// order of the group
+    pub sub_scalars: fn(&[u8], &[u8]) -> Vec<u8>,
+    /// A function that deterministically maps a message to a uniformly
+    /// distributed scalar, under the provided domain-separation tag
+    pub hash_to_scalar: fn(&[u8], &[u8]) -> Vec<u8>,
 
-    // DLEQ operations have to be defined with respect to the prime-order group
-    // to allow for different scalar implementations
+    // The DLEQ challenges and batching coefficients of
+    // draft-irtf-cfrg-voprf-02 are derived differently for each group. They
+    // are used by `dleq::Domain::Legacy`.
 
-    /// A function that generates a DLEQ proof for the provided key and group
-    /// elements.
-    pub dleq_generate: fn(&[u8], &T, &T, &T) -> [Vec<u8>; 2],
-    /// A function for verifying a DLEQ proof based on the provided input
-    /// elements and the committed public key (as a byte slice).
-    pub dleq_verify: fn(&T, &T, &T, &[Vec<u8>; 2]) -> bool,
-    /// A function for computing batched DLEQ proofs.
-    pub batch_dleq_generate: fn(&[u8], &T, &[T], &[T]) -> [Vec<u8>; 2],
-    /// A function for verifying batched DLEQ proofs.
-    pub batch_dleq_verify: fn(&T, &[T], &[T], &[Vec<u8>; 2]) -> bool,
+    /// The byte length of the legacy DLEQ challenge, which is the raw hash
+    /// output (rather than a reduced scalar) for some groups
+    pub legacy_challenge_length: usize,
+    /// A function that derives the legacy DLEQ challenge from the points
+    /// `(Y, M, Z, A, B)`
+    pub legacy_dleq_challenge: fn(&[&T]) -> Vec<u8>,
+    /// A function that derives the given number of legacy batching
+    /// coefficients from the points `(Y, M_1..M_n, Z_1..Z_n)`
+    pub legacy_dleq_coefficients: fn(&[&T], usize) -> Vec<Vec<u8>>,
+}
 
-    // DLEQ functions used in testing only
+impl<T,H> PrimeOrderGroup<T,H> {
+    /// Inverts all of the scalars with a single call to `invert_scalar`,
+    /// using Montgomery's batch inversion trick. The result is the same as
+    /// inverting each scalar separately. Returns an error if any of the
+    /// scalars is zero.
+    pub fn batch_invert_scalars(&self, scalars: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        if scalars.is_empty() {
+            return Ok(Vec::new());
+        }
+        // prods[i] = scalars[0]*..*scalars[i]
+        let mut prods = vec![scalars[0].clone()];
+        for sc in &scalars[1..] {
+            let prod = (self.mul_scalars)(&prods[prods.len()-1], sc);
+            prods.push(prod);
+        }
+        // the product is zero if any of the scalars is zero
+        let mut acc = match prods.last() {
+            Some(prod) if (self.is_valid_scalar)(prod) => (self.invert_scalar)(prod),
+            _ => return Err(err_scalar_inversion()),
+        };
+        // acc = (scalars[0]*..*scalars[i])^-1, from the last i to the first
+        let mut out = vec![Vec::new(); scalars.len()];
+        for i in (1..scalars.len()).rev() {
+            out[i] = (self.mul_scalars)(&acc, &prods[i-1]);
+            acc = (self.mul_scalars)(&acc, &scalars[i]);
+        }
+        out[0] = acc;
+        Ok(out)
+    }
+}
 
-    /// generates DLEQ proof object with fixed values for testing
-    pub fixed_dleq_generate: fn(&[u8], &T, &T, &T, &[u8]) -> [Vec<u8>; 2],
-    /// batched DLEQ generation with fixed inputs for testing
-    pub fixed_batch_dleq_generate: fn(&[u8], &T, &[T], &[T], &[u8]) -> [Vec<u8>; 2],
-}
\ No newline at end of file
+/// The `GroupElement` trait is implemented by the element types of the
+/// supported groups, and gives access to the `PrimeOrderGroup` instantiation
+/// that the elements belong to. This allows objects holding group elements to
+/// be (de)serialized without an explicit group instance.
+pub trait GroupElement: Sized {
+    /// The hash function associated with the group
+    type Hash;
+    /// Returns the group that the element belongs to
+    fn group() -> PrimeOrderGroup<Self, Self::Hash>;
+}
@@ -0,0 +1,174 @@
+//! The `msm` module provides a generic multi-scalar multiplication, for
+//! groups whose backends do not implement one natively. Small batches use
+//! Straus' interleaved windowed method, and larger batches use Pippenger's
+//! bucket method.
+//!
+//! Neither method runs in constant time, so they must only be used over
+//! public inputs (e.g. for DLEQ batching and verification).
+
+// batch size from which Pippenger's method is used
+const PIPPENGER_THRESHOLD: usize = 64;
+// window size of Straus' method
+const STRAUS_WINDOW: usize = 4;
+
+/// Computes `Σ scalars[i]*points[i]`, where the scalars are little-endian
+/// byte strings and `add` adds two (possibly equal) points. Returns `None` if
+/// no term was accumulated, i.e. if there are no points or all scalars are
+/// zero. A sum that cancels out returns `Some` of the identity element, in
+/// whatever representation `add` produces.
+pub(crate) fn multiscalar_mul<P,F>(points: &[P], scalars: &[Vec<u8>], add: F) -> Option<P>
+        where P: Clone, F: Fn(&P, &P) -> P {
+    assert_eq!(points.len(), scalars.len());
+    if points.len() < PIPPENGER_THRESHOLD {
+        straus(points, scalars, &add)
+    } else {
+        pippenger(points, scalars, &add)
+    }
+}
+
+fn straus<P,F>(points: &[P], scalars: &[Vec<u8>], add: &F) -> Option<P>
+        where P: Clone, F: Fn(&P, &P) -> P {
+    // tables[i][j] = (j+1)*points[i]
+    let tables: Vec<Vec<P>> = points.iter().map(|p| {
+        let mut table = vec![p.clone()];
+        for j in 1..(1 << STRAUS_WINDOW) - 1 {
+            let next = add(&table[j-1], p);
+            table.push(next);
+        }
+        table
+    }).collect();
+    let windows = (max_bits(scalars) + STRAUS_WINDOW - 1) / STRAUS_WINDOW;
+    let mut acc: Option<P> = None;
+    for w in (0..windows).rev() {
+        acc = acc.map(|a| double_n(a, STRAUS_WINDOW, add));
+        for (table, sc) in tables.iter().zip(scalars) {
+            let d = digit(sc, w, STRAUS_WINDOW);
+            if d != 0 {
+                acc = Some(add_opt(acc, &table[d-1], add));
+            }
+        }
+    }
+    acc
+}
+
+fn pippenger<P,F>(points: &[P], scalars: &[Vec<u8>], add: &F) -> Option<P>
+        where P: Clone, F: Fn(&P, &P) -> P {
+    let c = pippenger_window(points.len());
+    let windows = (max_bits(scalars) + c - 1) / c;
+    let mut acc: Option<P> = None;
+    for w in (0..windows).rev() {
+        acc = acc.map(|a| double_n(a, c, add));
+        // buckets[j] accumulates the points with digit j+1
+        let mut buckets: Vec<Option<P>> = vec![None; (1 << c) - 1];
+        for (p, sc) in points.iter().zip(scalars) {
+            let d = digit(sc, w, c);
+            if d != 0 {
+                buckets[d-1] = Some(add_opt(buckets[d-1].take(), p, add));
+            }
+        }
+        // Σ (j+1)*buckets[j], as a sum of running sums
+        let mut running: Option<P> = None;
+        let mut sum: Option<P> = None;
+        for b in buckets.iter().rev() {
+            if let Some(b) = b {
+                running = Some(add_opt(running, b, add));
+            }
+            if let Some(r) = &running {
+                sum = Some(add_opt(sum, r, add));
+            }
+        }
+        if let Some(s) = sum {
+            acc = Some(add_opt(acc, &s, add));
+        }
+    }
+    acc
+}
+
+// chooses the window size that roughly minimises the number of additions
+fn pippenger_window(n: usize) -> usize {
+    match n {
+        0..=199 => 6,
+        200..=799 => 7,
+        _ => 8,
+    }
+}
+
+fn add_opt<P,F>(acc: Option<P>, p: &P, add: &F) -> P
+        where P: Clone, F: Fn(&P, &P) -> P {
+    match acc {
+        Some(a) => add(&a, p),
+        None => p.clone(),
+    }
+}
+
+fn double_n<P,F>(mut p: P, n: usize, add: &F) -> P
+        where F: Fn(&P, &P) -> P {
+    for _ in 0..n {
+        p = add(&p, &p);
+    }
+    p
+}
+
+// returns the number of bits of the longest scalar
+fn max_bits(scalars: &[Vec<u8>]) -> usize {
+    scalars.iter().map(|sc| 8*sc.len()).max().unwrap_or(0)
+}
+
+// returns bits `[w*c, (w+1)*c)` of the little-endian scalar `sc`
+fn digit(sc: &[u8], w: usize, c: usize) -> usize {
+    let mut d = 0;
+    for i in 0..c {
+        let bit = w*c + i;
+        if bit/8 < sc.len() {
+            d |= (((sc[bit/8] >> (bit%8)) & 1) as usize) << i;
+        }
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use curve25519_dalek::scalar::Scalar;
+    use curve25519_dalek::traits::Identity;
+    use rand_core::OsRng;
+
+    fn check(n: usize) {
+        let mut rng = OsRng;
+        let points: Vec<RistrettoPoint> = (0..n).map(|_| RistrettoPoint::random(&mut rng)).collect();
+        let scalars: Vec<Scalar> = (0..n).map(|_| Scalar::random(&mut rng)).collect();
+        let expected: RistrettoPoint = points.iter().zip(&scalars).map(|(p, s)| p * s).sum();
+        let bytes: Vec<Vec<u8>> = scalars.iter().map(|s| s.to_bytes().to_vec()).collect();
+        let result = multiscalar_mul(&points, &bytes, |a, b| a + b).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn straus_matches_naive() {
+        check(1);
+        check(PIPPENGER_THRESHOLD - 1);
+    }
+
+    #[test]
+    fn pippenger_matches_naive() {
+        check(PIPPENGER_THRESHOLD);
+        check(250);
+    }
+
+    #[test]
+    fn zero_scalars() {
+        let mut rng = OsRng;
+        let points = vec![RistrettoPoint::random(&mut rng); 3];
+        assert!(multiscalar_mul(&points, &[vec![0; 32], vec![0; 32], vec![0; 32]], |a, b| a + b).is_none());
+    }
+
+    #[test]
+    fn cancelling_sum() {
+        let mut rng = OsRng;
+        let points = vec![RistrettoPoint::random(&mut rng); 2];
+        let scalars = vec![Scalar::one().to_bytes().to_vec(), (-Scalar::one()).to_bytes().to_vec()];
+        let result = multiscalar_mul(&points, &scalars, |a, b| a + b);
+        assert_eq!(result, Some(RistrettoPoint::identity()));
+    }
+}
@@ -0,0 +1,295 @@
+//! The `p256` module allows creating a `PrimeOrderGroup` object using the NIST
+//! P-256 elliptic curve. It is not a (V)OPRF ciphersuite, but provides the
+//! group of the `ECVRF-P256-SHA256-SSWU` ciphersuite of the `vrf` module.
+//!
+//! Elements are encoded as SEC1 points, and scalars as 32-byte big-endian
+//! integers. The curve arithmetic is implemented in this crate over `BigUint`
+//! (there is no P-256 backend in ecc-rs), and does not run in constant time.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! let pog = PrimeOrderGroup::p256();
+//! ```
+
+use super::{PrimeOrderGroup,GroupElement};
+use super::weierstrass::{Affine,Curve};
+use super::super::super::utils::{copy_into,par_map,rand_bytes};
+use super::super::super::errors::err_deserialization;
+
+use sha2::Sha256;
+use sha2::Digest;
+use byteorder::{BigEndian, WriteBytesExt};
+use num::BigUint;
+use std::sync::OnceLock;
+
+const P256_BYTE_LENGTH: usize = 32;
+// the domain-separation tag of `encode_to_group`
+const ENCODE_DST: &[u8] = b"voprf-rs-P256_XMD:SHA-256_SSWU_RO_";
+
+// built on first use by `p256_curve`
+static P256_CURVE: OnceLock<Curve> = OnceLock::new();
+
+/// A point of the NIST P-256 curve, in affine coordinates
+#[derive(Clone, Debug, PartialEq)]
+pub struct P256Point(Affine);
+
+/// Instantiation of `PrimeOrderGroup` for NIST P-256 curve
+impl PrimeOrderGroup<P256Point,Sha256> {
+    /// Returns an instance of PrimeOrderGroup for the prime-order group
+    /// associated with the NIST P-256 curve
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use voprf_rs::oprf::groups::PrimeOrderGroup;
+    /// let pog = PrimeOrderGroup::p256();
+    /// ```
+    pub fn p256() -> PrimeOrderGroup<P256Point,Sha256> {
+        PrimeOrderGroup{
+            generator: P256Point(Some(p256_curve().g.clone())),
+            byte_length: P256_BYTE_LENGTH,
+            hash: || Sha256::new(),
+            deserialize: |buf: &[u8]| {
+                match p256_curve().decode(buf) {
+                    Some(p) => Ok(P256Point(Some(p))),
+                    None => Err(err_deserialization()),
+                }
+            },
+            encode_to_group: |buf: &[u8]| p256_hash_to_curve(buf, ENCODE_DST),
+            hash_to_group: |buf: &[u8], dst: &[u8]| p256_hash_to_curve(buf, dst),
+            is_valid: |p: &P256Point| match &p.0 {
+                Some((x, y)) => p256_curve().is_on_curve(x, y),
+                None => false,
+            },
+            is_equal: |p1: &P256Point, p2: &P256Point| p1 == p2,
+            add: |p1: &P256Point, p2: &P256Point| {
+                let curve = p256_curve();
+                P256Point(curve.to_affine(&curve.add(&curve.to_jacobian(&p1.0), &curve.to_jacobian(&p2.0))))
+            },
+            scalar_mult: |p: &P256Point, r: &[u8]| p256_scalar_mult(p, r),
+            base_mult: |r: &[u8]| p256_scalar_mult(&P256Point(Some(p256_curve().g.clone())), r),
+            batch_scalar_mult: |ps: &[P256Point], rs: &[Vec<u8>]| {
+                par_map(ps.len(), |i| p256_scalar_mult(&ps[i], &rs[i]))
+            },
+            batch_mult_by: |ps: &[P256Point], r: &[u8]| {
+                par_map(ps.len(), |i| p256_scalar_mult(&ps[i], r))
+            },
+            multi_scalar_mult: |ps: &[P256Point], rs: &[Vec<u8>]| p256_multi_scalar_mult(ps, rs),
+            inverse_mult: |p: &P256Point, r: &[u8]| p256_scalar_mult(p, &p256_curve().invert_scalar(r)),
+            serialize: |p: &P256Point, compress: bool, out: &mut Vec<u8>| {
+                copy_into(&p256_curve().encode(&p.0, compress), out)
+            },
+            random_element: || {
+                let mut alpha = Vec::new();
+                rand_bytes(P256_BYTE_LENGTH, &mut alpha);
+                p256_hash_to_curve(&alpha, ENCODE_DST)
+            },
+            uniform_bytes: |out: &mut Vec<u8>| {
+                // rejection sampling of scalars smaller than the order
+                let n = &p256_curve().n;
+                loop {
+                    rand_bytes(P256_BYTE_LENGTH, out);
+                    if &BigUint::from_bytes_be(out) < n {
+                        return;
+                    }
+                }
+            },
+            reduce_scalar: |sc: &[u8], pve: bool| p256_curve().reduce_scalar(sc, pve),
+            is_valid_scalar: |sc: &[u8]| p256_curve().is_valid_scalar(sc),
+            mul_scalars: |a: &[u8], b: &[u8]| p256_curve().mul_scalars(a, b),
+            invert_scalar: |sc: &[u8]| p256_curve().invert_scalar(sc),
+            sub_scalars: |a: &[u8], b: &[u8]| p256_curve().sub_scalars(a, b),
+            hash_to_scalar: |msg: &[u8], dst: &[u8]| p256_curve().hash_to_scalar::<Sha256>(msg, dst),
+            // draft-irtf-cfrg-voprf-02 does not define P-256, so the legacy
+            // DLEQ values are derived by hashing to scalars
+            legacy_challenge_length: P256_BYTE_LENGTH,
+            legacy_dleq_challenge: |pts: &[&P256Point]| {
+                p256_curve().hash_to_scalar::<Sha256>(&p256_dleq_hash(pts), b"voprf_dleq_challenge")
+            },
+            legacy_dleq_coefficients: |pts: &[&P256Point], n: usize| {
+                let seed = p256_dleq_hash(pts);
+                (0..n).map(|i| {
+                    let mut msg = seed.clone();
+                    msg.write_u32::<BigEndian>(i as u32).unwrap();
+                    p256_curve().hash_to_scalar::<Sha256>(&msg, b"voprf_batch_dleq")
+                }).collect()
+            },
+        }
+    }
+}
+
+impl GroupElement for P256Point {
+    type Hash = Sha256;
+
+    fn group() -> PrimeOrderGroup<P256Point,Sha256> {
+        PrimeOrderGroup::p256()
+    }
+}
+
+/// Encodes `msg` to the group with the `P256_XMD:SHA-256_SSWU_NU_` suite of
+/// [RFC 9380](https://www.rfc-editor.org/rfc/rfc9380.html), under the
+/// domain-separation tag `dst`
+pub fn p256_encode_to_curve(msg: &[u8], dst: &[u8]) -> P256Point {
+    P256Point(p256_curve().encode_to_curve::<Sha256>(msg, dst))
+}
+
+/// Hashes `msg` to the group with the `P256_XMD:SHA-256_SSWU_RO_` suite of
+/// [RFC 9380](https://www.rfc-editor.org/rfc/rfc9380.html), under the
+/// domain-separation tag `dst`
+pub fn p256_hash_to_curve(msg: &[u8], dst: &[u8]) -> P256Point {
+    P256Point(p256_curve().hash_to_curve::<Sha256>(msg, dst))
+}
+
+// returns the parameters of P-256
+fn p256_curve() -> &'static Curve {
+    P256_CURVE.get_or_init(|| Curve::new(
+        "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+        "5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b",
+        "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551",
+        "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296",
+        "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5",
+        10,
+    ))
+}
+
+fn p256_scalar_mult(p: &P256Point, r: &[u8]) -> P256Point {
+    let curve = p256_curve();
+    P256Point(curve.to_affine(&curve.mul(&curve.to_jacobian(&p.0), r)))
+}
+
+fn p256_multi_scalar_mult(ps: &[P256Point], rs: &[Vec<u8>]) -> P256Point {
+    let affine: Vec<Affine> = ps.iter().map(|p| p.0.clone()).collect();
+    P256Point(p256_curve().multi_mul(&affine, rs))
+}
+
+// hashes the generator and the points for legacy DLEQ proofs
+fn p256_dleq_hash(pts: &[&P256Point]) -> Vec<u8> {
+    let curve = p256_curve();
+    let mut hash = Sha256::new();
+    hash.input(curve.encode(&Some(curve.g.clone()), true));
+    for p in pts {
+        hash.input(curve.encode(&p.0, true));
+    }
+    hash.result().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p256_serialization() {
+        let pog = PrimeOrderGroup::p256();
+        let p = (pog.random_element)();
+        for &compress in &[true, false] {
+            let mut ser: Vec<u8> = Vec::new();
+            (pog.serialize)(&p, compress, &mut ser);
+            assert_eq!(ser.len(), if compress { 33 } else { 65 });
+            assert_eq!((pog.deserialize)(&ser).unwrap(), p);
+            assert!((pog.deserialize)(&ser[1..]).is_err());
+        }
+
+        // points that are not on the curve, and the identity
+        let mut ser: Vec<u8> = Vec::new();
+        (pog.serialize)(&p, false, &mut ser);
+        ser[64] ^= 1;
+        assert!((pog.deserialize)(&ser).is_err());
+        let identity = (pog.scalar_mult)(&p, &[0; P256_BYTE_LENGTH]);
+        (pog.serialize)(&identity, true, &mut ser);
+        assert_eq!(ser, vec![0]);
+        assert!((pog.deserialize)(&ser).is_err());
+        assert!(!(pog.is_valid)(&identity));
+        assert!((pog.is_valid)(&p));
+    }
+
+    #[test]
+    fn p256_point_mult() {
+        let pog = PrimeOrderGroup::p256();
+        let p = (pog.random_element)();
+        let mut r1: Vec<u8> = Vec::new();
+        let mut r2: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r1);
+        (pog.uniform_bytes)(&mut r2);
+        let add_p = (pog.add)(&(pog.scalar_mult)(&p, &r1), &(pog.scalar_mult)(&p, &r2));
+        let r1_r2 = (pog.sub_scalars)(&r1, &(pog.reduce_scalar)(&r2, false));
+        assert!((pog.is_equal)(&add_p, &(pog.scalar_mult)(&p, &r1_r2)));
+        assert!((pog.is_equal)(&(pog.add)(&p, &p), &(pog.scalar_mult)(&p, &p256_scalar(2))));
+        assert!((pog.is_equal)(&(pog.base_mult)(&r1), &(pog.scalar_mult)(&pog.generator, &r1)));
+        assert!((pog.is_equal)(&(pog.inverse_mult)(&(pog.scalar_mult)(&p, &r1), &r1), &p));
+
+        // the order of the group
+        let n = p256_curve().pad(&p256_curve().n);
+        assert_eq!((pog.scalar_mult)(&p, &n), P256Point(None));
+        assert_eq!((pog.add)(&p, &(pog.scalar_mult)(&p, &(pog.reduce_scalar)(&p256_scalar(1), false))), P256Point(None));
+    }
+
+    #[test]
+    fn p256_public_key() {
+        // the key pair of appendix A.2.5 of RFC 6979
+        let pog = PrimeOrderGroup::p256();
+        let sk = hex::decode("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721").unwrap();
+        let mut ser: Vec<u8> = Vec::new();
+        (pog.serialize)(&(pog.base_mult)(&sk), false, &mut ser);
+        assert_eq!(hex::encode(ser), "0460fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6\
+            7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299");
+    }
+
+    #[test]
+    fn p256_hash_to_curve_vectors() {
+        // appendix J.1 of RFC 9380, for the empty message
+        let mut ser: Vec<u8> = Vec::new();
+        let pog = PrimeOrderGroup::p256();
+        let p = p256_hash_to_curve(b"", b"QUUX-V01-CS02-with-P256_XMD:SHA-256_SSWU_RO_");
+        (pog.serialize)(&p, false, &mut ser);
+        assert_eq!(hex::encode(&ser[1..]), "2c15230b26dbc6fc9a37051158c95b79656e17a1a920b11394ca91c44247d3e4\
+            8a7a74985cc5c776cdfe4b1f19884970453912e9d31528c060be9ab5c43e8415");
+        let p = p256_encode_to_curve(b"", b"QUUX-V01-CS02-with-P256_XMD:SHA-256_SSWU_NU_");
+        (pog.serialize)(&p, false, &mut ser);
+        assert_eq!(hex::encode(&ser[1..]), "f871caad25ea3b59c16cf87c1894902f7e7b2c822c3d3f73596c5ace8ddd14d1\
+            87b9ae23335bee057b99bac1e68588b18b5691af476234b8971bc4f011ddc99b");
+        assert!((pog.is_valid)(&(pog.encode_to_group)(b"some_input")));
+    }
+
+    #[test]
+    fn p256_multi_scalar_mult() {
+        let pog = PrimeOrderGroup::p256();
+        for &n in &[1, 70] {
+            let mut ps = Vec::new();
+            let mut rs = Vec::new();
+            for _ in 0..n {
+                let mut r: Vec<u8> = Vec::new();
+                (pog.uniform_bytes)(&mut r);
+                ps.push((pog.random_element)());
+                rs.push(r);
+            }
+            let batch = (pog.batch_scalar_mult)(&ps, &rs);
+            let mut expected = batch[0].clone();
+            for p in &batch[1..] {
+                expected = (pog.add)(&expected, p);
+            }
+            assert!((pog.is_equal)(&(pog.multi_scalar_mult)(&ps, &rs), &expected));
+        }
+        assert_eq!((pog.multi_scalar_mult)(&[], &[]), P256Point(None));
+    }
+
+    #[test]
+    fn p256_scalar_arithmetic() {
+        let pog = PrimeOrderGroup::p256();
+        let mut r: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r);
+        assert!((pog.is_valid_scalar)(&r));
+        assert!(!(pog.is_valid_scalar)(&[0; P256_BYTE_LENGTH]));
+        assert!(!(pog.is_valid_scalar)(&[0xff; P256_BYTE_LENGTH]));
+        assert!(!(pog.is_valid_scalar)(&r[1..]));
+        assert_eq!((pog.mul_scalars)(&r, &(pog.invert_scalar)(&r)), p256_scalar(1));
+        assert_eq!((pog.reduce_scalar)(&[&[0; 32][..], &r[..]].concat(), true), r);
+    }
+
+    fn p256_scalar(x: u8) -> Vec<u8> {
+        let mut out = vec![0; P256_BYTE_LENGTH];
+        out[P256_BYTE_LENGTH-1] = x;
+        out
+    }
+}
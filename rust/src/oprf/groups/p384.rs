@@ -8,8 +8,10 @@
 //! let pog = PrimeOrderGroup::p384();
 //! ```
 
-use super::PrimeOrderGroup;
-use super::super::super::utils::copy_into;
+use super::{PrimeOrderGroup,GroupElement,MSM_CHUNK};
+use super::msm::multiscalar_mul;
+use super::super::super::utils::{copy_into,expand_message_xmd,par_chunks,par_map};
+use super::super::super::errors::err_deserialization;
 use hkdf_sha512::Hkdf;
 
 use ecc_rs::point::AffinePoint;
@@ -17,13 +19,24 @@ use ecc_rs::point::{P384,Encoded};
 
 use sha2::Sha512;
 use sha2::Digest;
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use rand_core::OsRng;
 use rand_core::RngCore;
-use num::{BigInt,BigUint};
-use num_bigint::Sign;
+use num::BigUint;
+use subtle::{ConditionallySelectable,ConstantTimeEq};
+use std::sync::OnceLock;
 
 const P384_BYTE_LENGTH: usize = 48;
+// the (big-endian) order of the P-384 group
+const P384_ORDER: &str = "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf581a0db248b0a77aecec196accc52973";
+// width (in bits) of the windows of the fixed-base table
+const P384_BASE_WINDOW: usize = 4;
+const P384_BASE_WINDOWS: usize = 8*P384_BYTE_LENGTH/P384_BASE_WINDOW;
+
+// built on first use by `p384_base_mult`
+static P384_BASE_TABLE: OnceLock<BaseTable> = OnceLock::new();
+// parsed on first use by `p384_order`
+static P384_ORDER_INT: OnceLock<BigUint> = OnceLock::new();
 
 /// Wraps the Montgomery encoded `AffinePoint` struct from ecc-rs.
 pub type NistPoint = AffinePoint<Encoded>;
@@ -44,12 +57,29 @@ impl PrimeOrderGroup<NistPoint,Sha512> {
             generator: NistPoint::get_generator(P384).unwrap(),
             byte_length: P384_BYTE_LENGTH,
             hash: || p384_hash(),
-            deserialize: |buf: &[u8]| NistPoint::new(P384).unwrap().deserialize(buf),
+            deserialize: |buf: &[u8]| {
+                // compressed or uncompressed SEC1 encodings only
+                if buf.len() != P384_BYTE_LENGTH+1 && buf.len() != 2*P384_BYTE_LENGTH+1 {
+                    return Err(err_deserialization());
+                }
+                NistPoint::new(P384).unwrap().deserialize(buf)
+            },
             encode_to_group: |buf: &[u8]| NistPoint::new(P384).unwrap().hash_to_curve(buf, "RFCXXXX-VOPRF".to_string()),
+            hash_to_group: |buf: &[u8], dst: &[u8]| {
+                NistPoint::new(P384).unwrap().hash_to_curve(buf, String::from_utf8_lossy(dst).into_owned())
+            },
             is_valid: |p: &NistPoint| p.is_valid(),
             is_equal: |p1: &NistPoint, p2: &NistPoint| p1.equals(p2),
             add: |p1: &NistPoint, p2: &NistPoint| p1.to_jacobian().add(&p2.to_jacobian()).to_affine(),
             scalar_mult: |p: &NistPoint, r: &[u8]| p.scalar_mul(r).to_affine(),
+            base_mult: |r: &[u8]| p384_base_mult(r),
+            batch_scalar_mult: |ps: &[NistPoint], rs: &[Vec<u8>]| {
+                par_map(ps.len(), |i| ps[i].scalar_mul(&rs[i]).to_affine())
+            },
+            batch_mult_by: |ps: &[NistPoint], r: &[u8]| {
+                par_map(ps.len(), |i| ps[i].scalar_mul(r).to_affine())
+            },
+            multi_scalar_mult: |ps: &[NistPoint], rs: &[Vec<u8>]| p384_multi_scalar_mult(ps, rs),
             inverse_mult: |p: &NistPoint, r: &[u8]| p.inv_scalar_mul(r).to_affine(),
             serialize: |p: &NistPoint, compress: bool, out: &mut Vec<u8>| nist_serialize(p, compress, out),
             random_element: || {
@@ -65,81 +95,170 @@ impl PrimeOrderGroup<NistPoint,Sha512> {
             },
             reduce_scalar: |sc: &[u8], pve: bool| NistPoint::new(P384).unwrap()
                                                         .reduce_scalar(sc, pve),
-            // DLEQ functions
-            dleq_generate: |key: &[u8], pub_key: &NistPoint, input: &NistPoint, eval: &NistPoint| -> [Vec<u8>; 2] {
-                p384_dleq_gen(key, pub_key, input, eval)
+            is_valid_scalar: |sc: &[u8]| p384_is_valid_scalar(sc),
+            mul_scalars: |a: &[u8], b: &[u8]| {
+                let n = p384_order();
+                let prod = (BigUint::from_bytes_be(a) * BigUint::from_bytes_be(b)) % n;
+                p384_pad_scalar(&prod.to_bytes_be())
             },
-            dleq_verify: |pub_key: &NistPoint, input: &NistPoint, eval: &NistPoint, proof: &[Vec<u8>; 2]| {
-                p384_dleq_vrf(pub_key, input, eval, proof)
+            invert_scalar: |sc: &[u8]| {
+                // the order is prime, so sc^-1 = sc^(n-2) mod n
+                let n = p384_order();
+                let exp = n - BigUint::from(2u8);
+                p384_pad_scalar(&BigUint::from_bytes_be(sc).modpow(&exp, n).to_bytes_be())
             },
-            batch_dleq_generate: |key: &[u8], pub_key: &NistPoint, inputs: &[NistPoint], evals: &[NistPoint]| -> [Vec<u8>; 2] {
-                let [comp_m, comp_z] = p384_batch_compute_composities(pub_key, inputs, evals);
-                p384_dleq_gen(key, pub_key, &comp_m, &comp_z)
+            sub_scalars: |a: &[u8], b: &[u8]| {
+                let n = p384_order();
+                let diff = (BigUint::from_bytes_be(a) % n + n - BigUint::from_bytes_be(b) % n) % n;
+                p384_pad_scalar(&diff.to_bytes_be())
             },
-            batch_dleq_verify: |pub_key: &NistPoint, inputs: &[NistPoint], evals: &[NistPoint], proof: &[Vec<u8>; 2]| {
-                let [comp_m, comp_z] = p384_batch_compute_composities(pub_key, inputs, evals);
-                p384_dleq_vrf(pub_key, &comp_m, &comp_z, proof)
+            hash_to_scalar: |msg: &[u8], dst: &[u8]| {
+                // 72 bytes leave a bias of at most 2^-192 after the reduction
+                let wide = expand_message_xmd(msg, dst, P384_BYTE_LENGTH + 24);
+                p384_pad_scalar(&(BigUint::from_bytes_be(&wide) % p384_order()).to_bytes_be())
             },
-            // DLEQ functions for testing
-            fixed_dleq_generate: |key: &[u8], pub_key: &NistPoint, input: &NistPoint, eval: &NistPoint, fixed_scalar: &[u8]| -> [Vec<u8>; 2] {
-                p384_fixed_dleq_gen(key, pub_key, input, eval, fixed_scalar)
+            // legacy DLEQ functions
+            legacy_challenge_length: P384_BYTE_LENGTH,
+            legacy_dleq_challenge: |pts: &[&NistPoint]| {
+                let g = NistPoint::get_generator(P384).unwrap();
+                let mut c = vec![0; P384_BYTE_LENGTH];
+                compute_expanded_dleq_challenge(pts, &mut c);
+                p384_pad_scalar(&g.reduce_scalar(&c, true))
             },
-            fixed_batch_dleq_generate: |key: &[u8], pub_key: &NistPoint, inputs: &[NistPoint], evals: &[NistPoint], fixed_scalar: &[u8]| -> [Vec<u8>; 2] {
-                let [comp_m, comp_z] = p384_batch_compute_composities(pub_key, inputs, evals);
-                p384_fixed_dleq_gen(key, pub_key, &comp_m, &comp_z, fixed_scalar)
+            legacy_dleq_coefficients: |pts: &[&NistPoint], n: usize| {
+                let mut seed: Vec<u8> = Vec::new();
+                p384_dleq_hash(pts, &mut seed);
+                p384_coefficients(&seed, n)
             },
         }
     }
 }
 
-// serialize the NIST curve point
-fn nist_serialize(p: &NistPoint, compress: bool, out: &mut Vec<u8>) {
-    let bytes = p.serialize(compress);
-    copy_into(&bytes, out);
+impl GroupElement for NistPoint {
+    type Hash = Sha512;
+
+    fn group() -> PrimeOrderGroup<NistPoint,Sha512> {
+        PrimeOrderGroup::p384()
+    }
 }
 
-// generates a DLEQ proof object for NistPoint objects
-fn p384_dleq_gen(key: &[u8], pub_key: &NistPoint, input: &NistPoint, eval: &NistPoint) -> [Vec<u8>; 2] {
-    let mut t: Vec<u8> = Vec::new();
-    p384_sample_uniform_bytes(&mut t);
-    p384_fixed_dleq_gen(key, pub_key, input, eval, &t)
+// computes Σ rs[i]*ps[i] over Jacobian coordinates, so that only the result
+// is converted back to affine coordinates. Each chunk of the inputs is summed
+// separately.
+fn p384_multi_scalar_mult(ps: &[NistPoint], rs: &[Vec<u8>]) -> NistPoint {
+    let jac: Vec<_> = ps.iter().map(|p| p.to_jacobian()).collect();
+    // the generic implementation expects little-endian scalars
+    let le: Vec<Vec<u8>> = rs.iter().map(|r| r.iter().rev().cloned().collect()).collect();
+    let chunks = par_chunks(ps.len(), MSM_CHUNK);
+    let sums = par_map(chunks.len(), |i| {
+        multiscalar_mul(&jac[chunks[i].clone()], &le[chunks[i].clone()], |p1, p2| p1.add(p2))
+    });
+    match sums.into_iter().flatten().reduce(|p1, p2| p1.add(&p2)) {
+        Some(p) => p.to_affine(),
+        // defer the representation of the identity to ecc-rs (this is also
+        // the result for empty inputs)
+        None => NistPoint::get_generator(P384).unwrap().scalar_mul(&[0; P384_BYTE_LENGTH]).to_affine(),
+    }
 }
 
-// generates a DLEQ proof object for NistPoint objects with fixed scalar input
-fn p384_fixed_dleq_gen(key: &[u8], pub_key: &NistPoint, input: &NistPoint, eval: &NistPoint, t: &[u8]) -> [Vec<u8>; 2] {
-    let gen = NistPoint::get_generator(P384).unwrap();
-    let a = gen.scalar_mul(t).to_affine();
-    let b = input.scalar_mul(t).to_affine();
-    let mut c = vec![0; P384_BYTE_LENGTH];
-    compute_expanded_dleq_challenge(&[pub_key, input, eval, &a, &b], &mut c);
-    let c_sc = BigInt::from_bytes_be(Sign::Plus, &c);
-    let t_sc = BigInt::from_bytes_be(Sign::Plus, t);
-    let k_sc = BigInt::from_bytes_be(Sign::Plus, &key);
-    let s_sc = t_sc - (c_sc * k_sc);
-    let (bi_sgn, bytes) = s_sc.to_bytes_be();
-    [gen.reduce_scalar(&c, true), gen.reduce_scalar(&bytes, bi_sgn == Sign::Plus)]
+// the number of 64-bit limbs of the affine coordinates `x||y` of a point
+const P384_COORD_LIMBS: usize = 2*P384_BYTE_LENGTH/8;
+
+// Fixed-base table for the generator `G`. Each digit `d_i` of a scalar is
+// looked up as `(d_i+1)*16^i*G`, so that no entry is the identity, and the sum
+// of these offsets is cancelled by adding `offset`.
+struct BaseTable {
+    // entries[i][j] holds the affine coordinates `x||y` of (j+1)*16^i*G, as
+    // big-endian limbs that are decoded once when the table is built
+    entries: Vec<Vec<[u64; P384_COORD_LIMBS]>>,
+    // the tag of uncompressed encodings
+    tag: u8,
+    // -Σ 16^i*G
+    offset: NistPoint,
 }
 
-fn p384_dleq_vrf(pub_key: &NistPoint, input: &NistPoint, eval: &NistPoint, proof: &[Vec<u8>; 2]) -> bool {
-    let g = NistPoint::get_generator(P384).unwrap();
-    let c_proof = &proof[0];
-    let s_proof = &proof[1];
-    let s_g = g.scalar_mul(s_proof);
-    let c_pk = pub_key.scalar_mul(c_proof);
-    let a = s_g.add(&c_pk).to_affine();
-    let s_m = input.scalar_mul(s_proof);
-    let c_z = eval.scalar_mul(c_proof);
-    let b = s_m.add(&c_z).to_affine();
-    let mut c_vrf = vec![0; P384_BYTE_LENGTH];
-    compute_expanded_dleq_challenge(&[pub_key, input, eval, &a, &b], &mut c_vrf);
-    return c_proof == &g.reduce_scalar(&c_vrf, true);
+impl BaseTable {
+    fn new() -> Self {
+        let g = NistPoint::get_generator(P384).unwrap();
+        let mut entries = Vec::with_capacity(P384_BASE_WINDOWS);
+        let mut base = g.to_jacobian();
+        for _ in 0..P384_BASE_WINDOWS {
+            let mut row = Vec::with_capacity(1 << P384_BASE_WINDOW);
+            let mut acc = base.clone();
+            for _ in 0..(1 << P384_BASE_WINDOW) {
+                let enc = acc.to_affine().serialize(false);
+                let mut coords = [0u64; P384_COORD_LIMBS];
+                for (c, chunk) in coords.iter_mut().zip(enc[1..].chunks(8)) {
+                    *c = BigEndian::read_u64(chunk);
+                }
+                row.push(coords);
+                acc = acc.add(&base);
+            }
+            base = (0..P384_BASE_WINDOW).fold(base, |b, _| b.add(&b));
+            entries.push(row);
+        }
+        // Σ 16^i = (2^384 - 1)/15
+        let n = p384_order();
+        let sum = ((BigUint::from(1u8) << (8*P384_BYTE_LENGTH)) - BigUint::from(1u8)) / BigUint::from(15u8);
+        let neg = (n - sum % n) % n;
+        BaseTable {
+            entries: entries,
+            tag: g.serialize(false)[0],
+            offset: g.scalar_mul(&p384_pad_scalar(&neg.to_bytes_be())).to_affine(),
+        }
+    }
+
+    // returns entries[i][d], selecting the coordinates of every entry of the
+    // row in constant time
+    fn lookup(&self, i: usize, d: u8) -> NistPoint {
+        let mut coords = [0u64; P384_COORD_LIMBS];
+        for (j, entry) in self.entries[i].iter().enumerate() {
+            let choice = (j as u8).ct_eq(&d);
+            for (c, e) in coords.iter_mut().zip(entry.iter()) {
+                c.conditional_assign(e, choice);
+            }
+        }
+        // ecc-rs only constructs points from their encodings
+        let mut buf = Vec::with_capacity(1 + 2*P384_BYTE_LENGTH);
+        buf.push(self.tag);
+        for c in coords.iter() {
+            buf.write_u64::<BigEndian>(*c).unwrap();
+        }
+        NistPoint::new(P384).unwrap().deserialize(&buf).unwrap()
+    }
 }
 
-fn p384_batch_compute_composities(pub_key: &NistPoint, inputs: &[NistPoint], evals: &[NistPoint]) -> [NistPoint; 2] {
-    assert_eq!(inputs.len(), evals.len());
-    let mut seed: Vec<u8> = Vec::new();
-    p384_batch_dleq_seed(pub_key, inputs, evals, &mut seed);
-    p384_compute_composites(&seed, inputs, evals)
+// computes r*G for the generator G using the fixed-base table. The table
+// lookups are constant time, but the point arithmetic of ecc-rs (over
+// `BigUint`) is not, and scalars longer than the order fall back to
+// `scalar_mul`
+fn p384_base_mult(r: &[u8]) -> NistPoint {
+    if r.len() > P384_BYTE_LENGTH {
+        return NistPoint::get_generator(P384).unwrap().scalar_mul(r).to_affine();
+    }
+    let table = P384_BASE_TABLE.get_or_init(BaseTable::new);
+    let sc = p384_pad_scalar(r);
+    let mut acc = table.offset.to_jacobian();
+    for i in 0..P384_BASE_WINDOWS {
+        // the i-th (little-endian) nibble of the big-endian scalar
+        let byte = sc[P384_BYTE_LENGTH - 1 - i/2];
+        let d = (byte >> (4*(i%2))) & 0x0f;
+        acc = acc.add(&table.lookup(i, d).to_jacobian());
+    }
+    acc.to_affine()
+}
+
+// serialize the NIST curve point
+fn nist_serialize(p: &NistPoint, compress: bool, out: &mut Vec<u8>) {
+    let bytes = p.serialize(compress);
+    copy_into(&bytes, out);
+}
+
+// left-pads a reduced (big-endian) scalar to the length of the group order
+fn p384_pad_scalar(x: &[u8]) -> Vec<u8> {
+    let mut out = vec![0; P384_BYTE_LENGTH - x.len()];
+    out.extend_from_slice(x);
+    out
 }
 
 // hash inputs points for DLEQ proofs into the output buffer `out`
@@ -155,19 +274,14 @@ fn p384_dleq_hash(to_hash: &[&NistPoint], out: &mut Vec<u8>) {
     copy_into(&hash.result(), out);
 }
 
-// computes composite ristretto255 points that are used in batch DLEQ proofs
-// TODO: add these to the impl of some utility struct?
-fn p384_compute_composites(seed: &[u8], inputs: &[NistPoint], evals: &[NistPoint]) -> [NistPoint; 2] {
-    // init these with dummy values
-    let p = NistPoint::new(P384).unwrap();
-    let mut comp_m = p.to_jacobian();
-    let mut comp_z = p.to_jacobian();
+// derives the coefficients that are used to construct the composite points in
+// batch DLEQ proofs from the seed, rejecting values that are not smaller than
+// the order of the group
+fn p384_coefficients(seed: &[u8], n: usize) -> Vec<Vec<u8>> {
     let label = "voprf_batch_dleq".as_bytes();
     let mut ctr = 0; // used for labelling hkdf implementation
-    let mut i = 0; // counts the number of items to process
-    while i < inputs.len() {
-        let m_i = &inputs[i];
-        let z_i = &evals[i];
+    let mut coeffs = Vec::new();
+    while coeffs.len() < n {
         let mut ctr_vec = Vec::new();
         ctr_vec.write_u32::<BigEndian>(ctr as u32).unwrap();
         ctr_vec.extend_from_slice(&label);
@@ -180,36 +294,9 @@ fn p384_compute_composites(seed: &[u8], inputs: &[NistPoint], evals: &[NistPoint
         if !verify_scalar_size(&d_i) {
             continue;
         }
-        let dm_i = m_i.scalar_mul(&d_i);
-        let dz_i = z_i.scalar_mul(&d_i);
-
-        let (m_i, z_i) = match i {
-            0 => (dm_i, dz_i), // overwrite dummy values
-            _ => (comp_m.add(&dm_i), comp_z.add(&dz_i)),
-        };
-        comp_m = m_i;
-        comp_z = z_i;
-        i = i+1;
+        coeffs.push(d_i);
     }
-    [comp_m.to_affine(), comp_z.to_affine()]
-}
-
-// generates a seed for deriving coefficients that are used to construct the
-// composite `RistrettoPoint` objects used in batch DLEQ proofs, moves the result
-// into the provided output buffer
-fn p384_batch_dleq_seed(y: &NistPoint, m: &[NistPoint], z: &[NistPoint], out: &mut Vec<u8>) {
-    let mut inputs: Vec<&NistPoint> = Vec::new();
-    inputs.push(y);
-    inputs.extend(m);
-    inputs.extend(z);
-    p384_dleq_hash(&inputs, out)
-}
-
-// Samples bytes uniformly corresponding to scalars in the base field associated
-// with P-384
-fn p384_sample_uniform_bytes(out: &mut Vec<u8>) {
-    let bytes = NistPoint::new(P384).unwrap().uniform_bytes_from_field().unwrap();
-    copy_into(&bytes, out)
+    coeffs
 }
 
 // Samples the random challenge value `c` used in the NI version of the DLEQ
@@ -240,6 +327,17 @@ fn verify_scalar_size(c: &[u8]) -> bool {
     BigUint::from_bytes_be(&reduced) == BigUint::from_bytes_be(&c)
 }
 
+// returns true if the bytes are the (big-endian) encoding of a non-zero scalar
+// that is smaller than the order of the group
+fn p384_is_valid_scalar(x: &[u8]) -> bool {
+    x.len() == P384_BYTE_LENGTH && x.iter().any(|b| *b != 0) && verify_scalar_size(x)
+}
+
+// returns the order of the P-384 group
+fn p384_order() -> &'static BigUint {
+    P384_ORDER_INT.get_or_init(|| BigUint::from_bytes_be(&hex::decode(P384_ORDER).unwrap()))
+}
+
 // returns the associated hash function (SHA512) for working with the p384
 // prime-order group
 fn p384_hash() -> Sha512 {
@@ -250,6 +348,7 @@ fn p384_hash() -> Sha512 {
 mod tests {
     use super::*;
     use num::BigUint;
+    use crate::oprf::dleq::{self,Domain};
 
     #[test]
     fn p384_serialization() {
@@ -298,6 +397,130 @@ mod tests {
         assert_eq!((pog.is_equal)(&add_p, &mult_p), true);
     }
 
+    #[test]
+    fn p384_base_mult() {
+        let pog = PrimeOrderGroup::p384();
+        let one = p384_pad_scalar(&[1]);
+        let mut scalars = vec![(pog.sub_scalars)(&[0; P384_BYTE_LENGTH], &one), one];
+        for _ in 0..5 {
+            let mut r: Vec<u8> = Vec::new();
+            (pog.uniform_bytes)(&mut r);
+            scalars.push(r);
+        }
+        for r in &scalars {
+            let expected = (pog.scalar_mult)(&pog.generator, r);
+            assert!((pog.is_equal)(&(pog.base_mult)(r), &expected));
+        }
+    }
+
+    #[test]
+    fn p384_batch_invert_scalars() {
+        let pog = PrimeOrderGroup::p384();
+        assert!(pog.batch_invert_scalars(&[]).unwrap().is_empty());
+        for &n in &[1, 2, 10] {
+            let mut scalars = Vec::new();
+            for _ in 0..n {
+                let mut r: Vec<u8> = Vec::new();
+                (pog.uniform_bytes)(&mut r);
+                scalars.push(r);
+            }
+            let invs = pog.batch_invert_scalars(&scalars).unwrap();
+            assert_eq!(invs.len(), n);
+            for i in 0..n {
+                assert_eq!(invs[i], (pog.invert_scalar)(&scalars[i]));
+            }
+            // zero has no inverse
+            scalars[n/2] = vec![0; P384_BYTE_LENGTH];
+            assert!(pog.batch_invert_scalars(&scalars).is_err());
+        }
+    }
+
+    #[test]
+    fn p384_batch_scalar_mult() {
+        let pog = PrimeOrderGroup::p384();
+        let mut ps = Vec::new();
+        let mut rs = Vec::new();
+        for _ in 0..10 {
+            let mut r: Vec<u8> = Vec::new();
+            (pog.uniform_bytes)(&mut r);
+            ps.push((pog.random_element)());
+            rs.push(r);
+        }
+        let batch = (pog.batch_scalar_mult)(&ps, &rs);
+        assert_eq!(batch.len(), ps.len());
+        for i in 0..ps.len() {
+            assert!((pog.is_equal)(&batch[i], &(pog.scalar_mult)(&ps[i], &rs[i])));
+        }
+        let batch = (pog.batch_mult_by)(&ps, &rs[0]);
+        assert_eq!(batch.len(), ps.len());
+        for i in 0..ps.len() {
+            assert!((pog.is_equal)(&batch[i], &(pog.scalar_mult)(&ps[i], &rs[0])));
+        }
+    }
+
+    #[test]
+    fn p384_multi_scalar_mult() {
+        let pog = PrimeOrderGroup::p384();
+        // covers both the small and large batch algorithms
+        for &n in &[1, 2, 70, 300] {
+            let mut ps = Vec::new();
+            let mut rs = Vec::new();
+            for _ in 0..n {
+                let mut r: Vec<u8> = Vec::new();
+                (pog.uniform_bytes)(&mut r);
+                ps.push((pog.random_element)());
+                rs.push(r);
+            }
+            let mut expected = (pog.scalar_mult)(&ps[0], &rs[0]);
+            for i in 1..n {
+                expected = (pog.add)(&expected, &(pog.scalar_mult)(&ps[i], &rs[i]));
+            }
+            assert!((pog.is_equal)(&(pog.multi_scalar_mult)(&ps, &rs), &expected));
+        }
+        // the empty sum is the identity
+        let identity = (pog.scalar_mult)(&pog.generator, &[0; P384_BYTE_LENGTH]);
+        assert!((pog.is_equal)(&(pog.multi_scalar_mult)(&[], &[]), &identity));
+    }
+
+    // a sum that cancels out is accumulated by the generic multi-scalar
+    // multiplication, so the addition of ecc-rs must produce the identity
+    #[test]
+    fn p384_multi_scalar_mult_cancels() {
+        let pog = PrimeOrderGroup::p384();
+        let p = (pog.random_element)();
+        let mut one = vec![0; P384_BYTE_LENGTH];
+        one[P384_BYTE_LENGTH-1] = 1;
+        let n_minus_one = p384_pad_scalar(&(p384_order() - BigUint::from(1u8)).to_bytes_be());
+        let identity = (pog.scalar_mult)(&pog.generator, &[0; P384_BYTE_LENGTH]);
+        assert!((pog.is_equal)(&(pog.multi_scalar_mult)(&[p.clone(), p], &[one, n_minus_one]), &identity));
+    }
+
+    // the generic multi-scalar multiplication doubles points by adding them to
+    // themselves, so the addition of ecc-rs must handle equal points
+    #[test]
+    fn p384_add_equal_points() {
+        let pog = PrimeOrderGroup::p384();
+        let mut two = vec![0; P384_BYTE_LENGTH];
+        two[P384_BYTE_LENGTH-1] = 2;
+        for _ in 0..10 {
+            let p = (pog.random_element)();
+            assert!((pog.is_equal)(&(pog.add)(&p, &p), &(pog.scalar_mult)(&p, &two)));
+        }
+        let g = pog.generator.clone();
+        assert!((pog.is_equal)(&(pog.add)(&g, &g), &(pog.base_mult)(&two)));
+        // every digit of the scalars is the same, so all of the points in the
+        // tables and buckets are doubled at some point
+        let r = vec![0x11; P384_BYTE_LENGTH];
+        for &n in &[3, 70] {
+            let ps = vec![g.clone(); n];
+            let rs = vec![r.clone(); n];
+            let mut n_sc = vec![0; P384_BYTE_LENGTH];
+            n_sc[P384_BYTE_LENGTH-1] = n as u8;
+            let expected = (pog.scalar_mult)(&g, &(pog.mul_scalars)(&r, &n_sc));
+            assert!((pog.is_equal)(&(pog.multi_scalar_mult)(&ps, &rs), &expected));
+        }
+    }
+
     #[test]
     fn p384_encode_to_group() {
         let pog = PrimeOrderGroup::p384();
@@ -328,6 +551,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn p384_valid_scalar() {
+        let pog = PrimeOrderGroup::p384();
+        let mut r: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r);
+        assert!((pog.is_valid_scalar)(&r));
+        assert!(!(pog.is_valid_scalar)(&vec![0; pog.byte_length]));
+        assert!(!(pog.is_valid_scalar)(&vec![0xff; pog.byte_length]));
+        assert!(!(pog.is_valid_scalar)(&r[1..]));
+    }
+
+    #[test]
+    fn p384_scalar_arithmetic() {
+        let pog = PrimeOrderGroup::p384();
+        let mut r: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r);
+        let inv = (pog.invert_scalar)(&r);
+        assert!((pog.is_valid_scalar)(&inv));
+        let mut one = vec![0; pog.byte_length];
+        one[pog.byte_length-1] = 1;
+        assert_eq!((pog.mul_scalars)(&r, &inv), one);
+        // (r*s)*g == s*(r*g)
+        let mut s: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut s);
+        let p = (pog.scalar_mult)(&pog.generator, &(pog.mul_scalars)(&r, &s));
+        let q = (pog.scalar_mult)(&(pog.scalar_mult)(&pog.generator, &r), &s);
+        assert!((pog.is_equal)(&p, &q));
+    }
+
+    #[test]
+    fn p384_err_deser_length() {
+        let pog = PrimeOrderGroup::p384();
+        let mut ser: Vec<u8> = Vec::new();
+        (pog.serialize)(&(pog.random_element)(), true, &mut ser);
+        assert!((pog.deserialize)(&ser[1..]).is_err());
+        ser.push(0);
+        assert!((pog.deserialize)(&ser).is_err());
+    }
+
     #[test]
     fn p384_inverse_mult() {
         let pog = PrimeOrderGroup::p384();
@@ -351,11 +613,11 @@ mod tests {
         let z = (pog.scalar_mult)(&m, &key);
 
         // generate proof
-        let proof = (pog.dleq_generate)(&key, &pub_key, &m, &z);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::prove(&pog, &Domain::Legacy, &key, &pog.generator, &pub_key, &m, &z).unwrap();
+        assert_eq!(proof.to_bytes().len(), 2*pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.dleq_verify)(&pub_key, &m, &z, &proof), true);
+        assert_eq!(dleq::verify(&pog, &Domain::Legacy, &pog.generator, &pub_key, &m, &z, &proof), true);
     }
 
     #[test]
@@ -376,11 +638,11 @@ mod tests {
         }
 
         // generate proof
-        let proof = (pog.batch_dleq_generate)(&key, &pub_key, &inputs, &evals);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::batch_prove(&pog, &Domain::Legacy, &key, &pog.generator, &pub_key, &inputs, &evals).unwrap();
+        assert_eq!(proof.to_bytes().len(), 2*pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.batch_dleq_verify)(&pub_key, &inputs, &evals, &proof), true);
+        assert_eq!(dleq::batch_verify(&pog, &Domain::Legacy, &pog.generator, &pub_key, &inputs, &evals, &proof), true);
     }
 
     #[test]
@@ -399,18 +661,18 @@ mod tests {
         let z_2 = (pog.scalar_mult)(&m, &key_2);
 
         // generate proof
-        let proof = (pog.dleq_generate)(&key_1, &pub_key_1, &m, &z_2);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::prove(&pog, &Domain::Legacy, &key_1, &pog.generator, &pub_key_1, &m, &z_2).unwrap();
+        assert_eq!(proof.to_bytes().len(), 2*pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.dleq_verify)(&pub_key_1, &m, &z_2, &proof), false);
+        assert_eq!(dleq::verify(&pog, &Domain::Legacy, &pog.generator, &pub_key_1, &m, &z_2, &proof), false);
 
         // generate proof
-        let proof = (pog.dleq_generate)(&key_1, &pub_key_2, &m, &z_1);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::prove(&pog, &Domain::Legacy, &key_1, &pog.generator, &pub_key_2, &m, &z_1).unwrap();
+        assert_eq!(proof.to_bytes().len(), 2*pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.dleq_verify)(&pub_key_2, &m, &z_1, &proof), false);
+        assert_eq!(dleq::verify(&pog, &Domain::Legacy, &pog.generator, &pub_key_2, &m, &z_1, &proof), false);
     }
 
     #[test]
@@ -436,11 +698,11 @@ mod tests {
         evals[2] = (pog.scalar_mult)(&inputs[2], &bad_key);
 
         // generate proof
-        let proof = (pog.batch_dleq_generate)(&key, &pub_key, &inputs, &evals);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::batch_prove(&pog, &Domain::Legacy, &key, &pog.generator, &pub_key, &inputs, &evals).unwrap();
+        assert_eq!(proof.to_bytes().len(), 2*pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.batch_dleq_verify)(&pub_key, &inputs, &evals, &proof), false);
+        assert_eq!(dleq::batch_verify(&pog, &Domain::Legacy, &pog.generator, &pub_key, &inputs, &evals, &proof), false);
     }
 
     // converts a slice into an array of size P384_BYTE_LENGTH
@@ -0,0 +1,222 @@
+//! The `p384_sha384` module allows creating a `PrimeOrderGroup` object using
+//! the NIST P-384 elliptic curve with SHA-384, as used by the `P384-SHA384`
+//! ciphersuite of [RFC 9497](https://www.rfc-editor.org/rfc/rfc9497.html).
+//! Elements are hashed to the curve with the `P384_XMD:SHA-384_SSWU_RO_`
+//! suite of RFC 9380.
+//!
+//! Elements are encoded as SEC1 points, and scalars as 48-byte big-endian
+//! integers. Unlike the `p384` module (which follows
+//! draft-irtf-cfrg-voprf-02 with SHA-512 on top of ecc-rs), the curve
+//! arithmetic is implemented in this crate over `BigUint`, and does not run in
+//! constant time.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! let pog = PrimeOrderGroup::p384_sha384();
+//! ```
+
+use super::{PrimeOrderGroup,GroupElement};
+use super::weierstrass::{Affine,Curve};
+use super::super::super::utils::{copy_into,par_map,rand_bytes};
+use super::super::super::errors::err_deserialization;
+
+use sha2::Sha384;
+use sha2::Digest;
+use byteorder::{BigEndian, WriteBytesExt};
+use num::BigUint;
+use std::sync::OnceLock;
+
+const P384_BYTE_LENGTH: usize = 48;
+// the domain-separation tag of `encode_to_group`
+const ENCODE_DST: &[u8] = b"voprf-rs-P384_XMD:SHA-384_SSWU_RO_";
+
+// built on first use by `p384_curve`
+static P384_CURVE: OnceLock<Curve> = OnceLock::new();
+
+/// A point of the NIST P-384 curve, in affine coordinates
+#[derive(Clone, Debug, PartialEq)]
+pub struct P384Point(Affine);
+
+/// Instantiation of `PrimeOrderGroup` for NIST P-384 curve with SHA-384
+impl PrimeOrderGroup<P384Point,Sha384> {
+    /// Returns an instance of PrimeOrderGroup for the prime-order group
+    /// associated with the NIST P-384 curve, with SHA-384 as the hash function
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use voprf_rs::oprf::groups::PrimeOrderGroup;
+    /// let pog = PrimeOrderGroup::p384_sha384();
+    /// ```
+    pub fn p384_sha384() -> PrimeOrderGroup<P384Point,Sha384> {
+        PrimeOrderGroup{
+            generator: P384Point(Some(p384_curve().g.clone())),
+            byte_length: P384_BYTE_LENGTH,
+            hash: || Sha384::new(),
+            deserialize: |buf: &[u8]| {
+                match p384_curve().decode(buf) {
+                    Some(p) => Ok(P384Point(Some(p))),
+                    None => Err(err_deserialization()),
+                }
+            },
+            encode_to_group: |buf: &[u8]| p384_hash_to_curve(buf, ENCODE_DST),
+            hash_to_group: |buf: &[u8], dst: &[u8]| p384_hash_to_curve(buf, dst),
+            is_valid: |p: &P384Point| match &p.0 {
+                Some((x, y)) => p384_curve().is_on_curve(x, y),
+                None => false,
+            },
+            is_equal: |p1: &P384Point, p2: &P384Point| p1 == p2,
+            add: |p1: &P384Point, p2: &P384Point| {
+                let curve = p384_curve();
+                P384Point(curve.to_affine(&curve.add(&curve.to_jacobian(&p1.0), &curve.to_jacobian(&p2.0))))
+            },
+            scalar_mult: |p: &P384Point, r: &[u8]| p384_scalar_mult(p, r),
+            base_mult: |r: &[u8]| p384_scalar_mult(&P384Point(Some(p384_curve().g.clone())), r),
+            batch_scalar_mult: |ps: &[P384Point], rs: &[Vec<u8>]| {
+                par_map(ps.len(), |i| p384_scalar_mult(&ps[i], &rs[i]))
+            },
+            batch_mult_by: |ps: &[P384Point], r: &[u8]| {
+                par_map(ps.len(), |i| p384_scalar_mult(&ps[i], r))
+            },
+            multi_scalar_mult: |ps: &[P384Point], rs: &[Vec<u8>]| {
+                let affine: Vec<Affine> = ps.iter().map(|p| p.0.clone()).collect();
+                P384Point(p384_curve().multi_mul(&affine, rs))
+            },
+            inverse_mult: |p: &P384Point, r: &[u8]| p384_scalar_mult(p, &p384_curve().invert_scalar(r)),
+            serialize: |p: &P384Point, compress: bool, out: &mut Vec<u8>| {
+                copy_into(&p384_curve().encode(&p.0, compress), out)
+            },
+            random_element: || {
+                let mut alpha = Vec::new();
+                rand_bytes(P384_BYTE_LENGTH, &mut alpha);
+                p384_hash_to_curve(&alpha, ENCODE_DST)
+            },
+            uniform_bytes: |out: &mut Vec<u8>| {
+                // rejection sampling of scalars smaller than the order
+                let n = &p384_curve().n;
+                loop {
+                    rand_bytes(P384_BYTE_LENGTH, out);
+                    if &BigUint::from_bytes_be(out) < n {
+                        return;
+                    }
+                }
+            },
+            reduce_scalar: |sc: &[u8], pve: bool| p384_curve().reduce_scalar(sc, pve),
+            is_valid_scalar: |sc: &[u8]| p384_curve().is_valid_scalar(sc),
+            mul_scalars: |a: &[u8], b: &[u8]| p384_curve().mul_scalars(a, b),
+            invert_scalar: |sc: &[u8]| p384_curve().invert_scalar(sc),
+            sub_scalars: |a: &[u8], b: &[u8]| p384_curve().sub_scalars(a, b),
+            hash_to_scalar: |msg: &[u8], dst: &[u8]| p384_curve().hash_to_scalar::<Sha384>(msg, dst),
+            // draft-irtf-cfrg-voprf-02 does not define this group, so the
+            // legacy DLEQ values are derived by hashing to scalars
+            legacy_challenge_length: P384_BYTE_LENGTH,
+            legacy_dleq_challenge: |pts: &[&P384Point]| {
+                p384_curve().hash_to_scalar::<Sha384>(&p384_dleq_hash(pts), b"voprf_dleq_challenge")
+            },
+            legacy_dleq_coefficients: |pts: &[&P384Point], n: usize| {
+                let seed = p384_dleq_hash(pts);
+                (0..n).map(|i| {
+                    let mut msg = seed.clone();
+                    msg.write_u32::<BigEndian>(i as u32).unwrap();
+                    p384_curve().hash_to_scalar::<Sha384>(&msg, b"voprf_batch_dleq")
+                }).collect()
+            },
+        }
+    }
+}
+
+impl GroupElement for P384Point {
+    type Hash = Sha384;
+
+    fn group() -> PrimeOrderGroup<P384Point,Sha384> {
+        PrimeOrderGroup::p384_sha384()
+    }
+}
+
+/// Hashes `msg` to the group with the `P384_XMD:SHA-384_SSWU_RO_` suite of
+/// [RFC 9380](https://www.rfc-editor.org/rfc/rfc9380.html), under the
+/// domain-separation tag `dst`
+pub fn p384_hash_to_curve(msg: &[u8], dst: &[u8]) -> P384Point {
+    P384Point(p384_curve().hash_to_curve::<Sha384>(msg, dst))
+}
+
+// returns the parameters of P-384
+fn p384_curve() -> &'static Curve {
+    P384_CURVE.get_or_init(|| Curve::new(
+        "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffeffffffff0000000000000000ffffffff",
+        "b3312fa7e23ee7e4988e056be3f82d19181d9c6efe8141120314088f5013875ac656398d8a2ed19d2a85c8edd3ec2aef",
+        "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf581a0db248b0a77aecec196accc52973",
+        "aa87ca22be8b05378eb1c71ef320ad746e1d3b628ba79b9859f741e082542a385502f25dbf55296c3a545e3872760ab7",
+        "3617de4a96262c6f5d9e98bf9292dc29f8f41dbd289a147ce9da3113b5f0b8c00a60b1ce1d7e819d7a431d7c90ea0e5f",
+        12,
+    ))
+}
+
+fn p384_scalar_mult(p: &P384Point, r: &[u8]) -> P384Point {
+    let curve = p384_curve();
+    P384Point(curve.to_affine(&curve.mul(&curve.to_jacobian(&p.0), r)))
+}
+
+// hashes the generator and the points for legacy DLEQ proofs
+fn p384_dleq_hash(pts: &[&P384Point]) -> Vec<u8> {
+    let curve = p384_curve();
+    let mut hash = Sha384::new();
+    hash.input(curve.encode(&Some(curve.g.clone()), true));
+    for p in pts {
+        hash.input(curve.encode(&p.0, true));
+    }
+    hash.result().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p384_sha384_serialization() {
+        let pog = PrimeOrderGroup::p384_sha384();
+        let p = (pog.random_element)();
+        for &compress in &[true, false] {
+            let mut ser: Vec<u8> = Vec::new();
+            (pog.serialize)(&p, compress, &mut ser);
+            assert_eq!(ser.len(), if compress { 49 } else { 97 });
+            assert_eq!((pog.deserialize)(&ser).unwrap(), p);
+            assert!((pog.deserialize)(&ser[1..]).is_err());
+        }
+
+        // the identity cannot be deserialized
+        let mut ser: Vec<u8> = Vec::new();
+        let identity = (pog.scalar_mult)(&p, &[0; P384_BYTE_LENGTH]);
+        (pog.serialize)(&identity, true, &mut ser);
+        assert!((pog.deserialize)(&ser).is_err());
+        assert!(!(pog.is_valid)(&identity));
+        assert!((pog.is_valid)(&p));
+    }
+
+    #[test]
+    fn p384_sha384_point_mult() {
+        let pog = PrimeOrderGroup::p384_sha384();
+        let p = (pog.random_element)();
+        let mut r: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r);
+        assert!((pog.is_equal)(&(pog.base_mult)(&r), &(pog.scalar_mult)(&pog.generator, &r)));
+        assert!((pog.is_equal)(&(pog.inverse_mult)(&(pog.scalar_mult)(&p, &r), &r), &p));
+        let n = p384_curve().pad(&p384_curve().n);
+        assert_eq!((pog.scalar_mult)(&p, &n), P384Point(None));
+        assert_eq!((pog.multi_scalar_mult)(&[p.clone(), p.clone()], &[r.clone(), (pog.reduce_scalar)(&r, false)]), P384Point(None));
+        assert_eq!((pog.mul_scalars)(&r, &(pog.invert_scalar)(&r)), [&[0; 47][..], &[1]].concat());
+    }
+
+    #[test]
+    fn p384_sha384_hash_to_curve_vector() {
+        // appendix J.3.1 of RFC 9380, for the empty message
+        let pog = PrimeOrderGroup::p384_sha384();
+        let p = p384_hash_to_curve(b"", b"QUUX-V01-CS02-with-P384_XMD:SHA-384_SSWU_RO_");
+        let mut ser: Vec<u8> = Vec::new();
+        (pog.serialize)(&p, false, &mut ser);
+        assert_eq!(hex::encode(&ser[1..]), "eb9fe1b4f4e14e7140803c1d99d0a93cd823d2b024040f9c067a8eca1f5a2eeac9ad604973527a356f3fa3aeff0e4d83\
+            0c21708cff382b7f4643c07b105c2eaec2cead93a917d825601e63c8f21f6abd9abc22c93c2bed6f235954b25048bb1a");
+    }
+}
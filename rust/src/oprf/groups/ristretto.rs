@@ -10,11 +10,12 @@
 //! ```
 
 use curve25519_dalek::ristretto::{RistrettoPoint, CompressedRistretto};
-use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::constants::{RISTRETTO_BASEPOINT_POINT,RISTRETTO_BASEPOINT_TABLE};
 use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
 
-use super::PrimeOrderGroup;
-use super::super::super::utils::{rand_bytes,copy_into};
+use super::{PrimeOrderGroup,GroupElement,MSM_CHUNK};
+use super::super::super::utils::{copy_into,expand_message_xmd,par_chunks,par_map};
 use hkdf_sha512::Hkdf;
 use super::super::super::errors::err_deserialization;
 
@@ -51,6 +52,9 @@ impl PrimeOrderGroup<RistrettoPoint,Sha512> {
             byte_length: RISTRETTO_BYTE_LENGTH,
             hash: || ristretto_hash(),
             deserialize: |buf: &[u8]| {
+                if buf.len() != RISTRETTO_BYTE_LENGTH {
+                    return Err(err_deserialization());
+                }
                 let mut compressed = CompressedRistretto([0u8; RISTRETTO_BYTE_LENGTH]);
                 compressed.0.copy_from_slice(&buf[..RISTRETTO_BYTE_LENGTH]);
                 match compressed.decompress() {
@@ -61,12 +65,28 @@ impl PrimeOrderGroup<RistrettoPoint,Sha512> {
             encode_to_group: |buf: &[u8]| {
                 RistrettoPoint::hash_from_bytes::<Sha512>(buf)
             },
+            // hash_to_ristretto255 of RFC 9380
+            hash_to_group: |buf: &[u8], dst: &[u8]| {
+                let mut wide = [0u8; 64];
+                wide.copy_from_slice(&expand_message_xmd(buf, dst, 64));
+                RistrettoPoint::from_uniform_bytes(&wide)
+            },
             is_valid: |_: &RistrettoPoint| true,
             is_equal: |p1: &RistrettoPoint, p2: &RistrettoPoint| p1 == p2,
             add: |p1: &RistrettoPoint, p2: &RistrettoPoint| p1 + p2,
             scalar_mult: |p: &RistrettoPoint, r: &[u8]| {
                 p * ristretto_scalar_from_slice(r)
             },
+            // the basepoint table of curve25519-dalek is precomputed statically
+            base_mult: |r: &[u8]| &ristretto_scalar_from_slice(r) * &RISTRETTO_BASEPOINT_TABLE,
+            batch_scalar_mult: |ps: &[RistrettoPoint], rs: &[Vec<u8>]| {
+                par_map(ps.len(), |i| ps[i] * ristretto_scalar_from_slice(&rs[i]))
+            },
+            batch_mult_by: |ps: &[RistrettoPoint], r: &[u8]| {
+                let sc = ristretto_scalar_from_slice(r);
+                par_map(ps.len(), |i| ps[i] * sc)
+            },
+            multi_scalar_mult: |ps: &[RistrettoPoint], rs: &[Vec<u8>]| ristretto_multi_scalar_mult(ps, rs),
             inverse_mult: |p: &RistrettoPoint, r: &[u8]| {
                 let inv_sc = ristretto_scalar_from_slice(r).invert();
                 p * inv_sc
@@ -82,117 +102,64 @@ impl PrimeOrderGroup<RistrettoPoint,Sha512> {
                 ristretto_sample_uniform_bytes(out);
             },
             reduce_scalar: |sc: &[u8], _: bool| sc.to_vec(), // ristretto scalars are reduced automatically
-            // DLEQ functions
-            dleq_generate: |key: &[u8], pub_key: &RistrettoPoint, input: &RistrettoPoint, eval: &RistrettoPoint| -> [Vec<u8>; 2] {
-                ristretto_dleq_gen(key, pub_key, input, eval)
-            },
-            dleq_verify: |pub_key: &RistrettoPoint, input: &RistrettoPoint, eval: &RistrettoPoint, proof: &[Vec<u8>; 2]| {
-                ristretto_dleq_vrf(pub_key, input, eval, proof)
+            is_valid_scalar: |sc: &[u8]| ristretto_is_valid_scalar(sc),
+            mul_scalars: |a: &[u8], b: &[u8]| {
+                (ristretto_scalar_from_slice(a) * ristretto_scalar_from_slice(b)).as_bytes().to_vec()
             },
-            batch_dleq_generate: |key: &[u8], pub_key: &RistrettoPoint, inputs: &[RistrettoPoint], evals: &[RistrettoPoint]| -> [Vec<u8>; 2] {
-                let [comp_m, comp_z] = ristretto_batch_compute_composities(pub_key, inputs, evals);
-                ristretto_dleq_gen(key, pub_key, &comp_m, &comp_z)
+            invert_scalar: |sc: &[u8]| ristretto_scalar_from_slice(sc).invert().as_bytes().to_vec(),
+            sub_scalars: |a: &[u8], b: &[u8]| {
+                (ristretto_scalar_from_slice(a) - ristretto_scalar_from_slice(b)).as_bytes().to_vec()
             },
-            batch_dleq_verify: |pub_key: &RistrettoPoint, inputs: &[RistrettoPoint], evals: &[RistrettoPoint], proof: &[Vec<u8>; 2]| {
-                let [comp_m, comp_z] = ristretto_batch_compute_composities(pub_key, inputs, evals);
-                ristretto_dleq_vrf(pub_key, &comp_m, &comp_z, proof)
+            hash_to_scalar: |msg: &[u8], dst: &[u8]| {
+                let mut wide = [0u8; 64];
+                wide.copy_from_slice(&expand_message_xmd(msg, dst, 64));
+                Scalar::from_bytes_mod_order_wide(&wide).as_bytes().to_vec()
             },
-            // DLEQ functions for testing
-            fixed_dleq_generate: |key: &[u8], pub_key: &RistrettoPoint, input: &RistrettoPoint, eval: &RistrettoPoint, fixed_scalar: &[u8]| -> [Vec<u8>; 2] {
-                ristretto_fixed_dleq_gen(key, pub_key, input, eval, fixed_scalar)
+            // legacy DLEQ functions
+            // the challenge is the full SHA-512 output, of which only the
+            // first 32 bytes are used as a scalar
+            legacy_challenge_length: 64,
+            legacy_dleq_challenge: |pts: &[&RistrettoPoint]| {
+                let mut c: Vec<u8> = Vec::new();
+                ristretto_dleq_hash(pts, &mut c);
+                c
             },
-            fixed_batch_dleq_generate: |key: &[u8], pub_key: &RistrettoPoint, inputs: &[RistrettoPoint], evals: &[RistrettoPoint], fixed_scalar: &[u8]| -> [Vec<u8>; 2] {
-                let [comp_m, comp_z] = ristretto_batch_compute_composities(pub_key, inputs, evals);
-                ristretto_fixed_dleq_gen(key, pub_key, &comp_m, &comp_z, fixed_scalar)
+            legacy_dleq_coefficients: |pts: &[&RistrettoPoint], n: usize| {
+                let mut seed: Vec<u8> = Vec::new();
+                ristretto_dleq_hash(pts, &mut seed);
+                ristretto_coefficients(&seed, n)
             },
         }
     }
 }
 
-// generates a DLEQ proof object for RistrettoPoint objects
-fn ristretto_dleq_gen(key: &[u8], pub_key: &RistrettoPoint, input: &RistrettoPoint, eval: &RistrettoPoint) -> [Vec<u8>; 2] {
-    let mut out: Vec<u8> = Vec::new();
-    ristretto_sample_uniform_bytes(&mut out);
-    ristretto_fixed_dleq_gen(key, pub_key, input, eval, &out)
-}
-// generates a DLEQ proof object for RistrettoPoint objects with a fixed scalar
-fn ristretto_fixed_dleq_gen(key: &[u8], pub_key: &RistrettoPoint, input: &RistrettoPoint, eval: &RistrettoPoint, fixed_scalar: &[u8]) -> [Vec<u8>; 2] {
-    let t = ristretto_scalar_from_slice(fixed_scalar);
-    let a = RISTRETTO_BASEPOINT_POINT * t;
-    let b = input * t;
-    let mut c: Vec<u8> = Vec::new();
-    ristretto_dleq_hash(&[pub_key, input, eval, &a, &b], &mut c);
-    let c_sc = ristretto_scalar_from_slice(&c);
-    let s_sc = t - (c_sc * ristretto_scalar_from_slice(key));
-    [c, s_sc.as_bytes().to_vec()]
-}
+impl GroupElement for RistrettoPoint {
+    type Hash = Sha512;
 
-// verifies a DLEQ proof object
-fn ristretto_dleq_vrf(pub_key: &RistrettoPoint, input: &RistrettoPoint, eval: &RistrettoPoint, proof: &[Vec<u8>; 2]) -> bool {
-    let g = RISTRETTO_BASEPOINT_POINT;
-    let c_proof = &proof[0];
-    let s_proof = &proof[1];
-    let c_sc = ristretto_scalar_from_slice(c_proof);
-    let s_sc = ristretto_scalar_from_slice(s_proof);
-    let s_g = g * s_sc;
-    let c_pk = pub_key * c_sc;
-    let a = s_g + c_pk;
-    let s_m = input * s_sc;
-    let c_z = eval * c_sc;
-    let b = s_m + c_z;
-    let mut c_vrf: Vec<u8> = Vec::new();
-    ristretto_dleq_hash(&[pub_key, input, eval, &a, &b], &mut c_vrf);
-    c_proof == &c_vrf
+    fn group() -> PrimeOrderGroup<RistrettoPoint,Sha512> {
+        PrimeOrderGroup::ristretto_255()
+    }
 }
 
-fn ristretto_batch_compute_composities(pub_key: &RistrettoPoint, inputs: &[RistrettoPoint], evals: &[RistrettoPoint]) -> [RistrettoPoint; 2] {
-    assert_eq!(inputs.len(), evals.len());
-    let mut seed: Vec<u8> = Vec::new();
-    ristretto_batch_dleq_seed(pub_key, inputs, evals, &mut seed);
-    ristretto_composites(&seed, inputs, evals)
+// computes Σ rs[i]*ps[i], summing the results for each chunk of the inputs
+fn ristretto_multi_scalar_mult(ps: &[RistrettoPoint], rs: &[Vec<u8>]) -> RistrettoPoint {
+    let chunks = par_chunks(ps.len(), MSM_CHUNK);
+    par_map(chunks.len(), |i| {
+        let scalars = rs[chunks[i].clone()].iter().map(|r| ristretto_scalar_from_slice(r));
+        RistrettoPoint::vartime_multiscalar_mul(scalars, &ps[chunks[i].clone()])
+    }).iter().sum()
 }
 
-// computes composite ristretto255 points that are used in batch DLEQ proofs
-// TODO: add these to the impl of some utility struct?
-fn ristretto_composites(seed: &[u8], inputs: &[RistrettoPoint], evals: &[RistrettoPoint]) -> [RistrettoPoint; 2] {
-    // init these with dummy values
-    let mut comp_m: RistrettoPoint = RISTRETTO_BASEPOINT_POINT;
-    let mut comp_z: RistrettoPoint = RISTRETTO_BASEPOINT_POINT;
-    for i in 0..inputs.len() {
-        let m_i = inputs[i];
-        let z_i = evals[i];
+// derives the coefficients that are used to construct the composite points in
+// batch DLEQ proofs from the seed
+pub(super) fn ristretto_coefficients(seed: &[u8], n: usize) -> Vec<Vec<u8>> {
+    (0..n).map(|i| {
         let mut i_vec = Vec::new();
         i_vec.write_u32::<LittleEndian>(i as u32).unwrap();
         let mut buf: Vec<u8> = Vec::new();
         Hkdf{}.extract(seed, &i_vec, &mut buf);
-        let d_i = ristretto_scalar_from_slice(&buf);
-        let dm_i = m_i * d_i;
-        let dz_i = z_i * d_i;
-
-        match i {
-            0 => {
-                // should always overwrite dummy values
-                comp_m = dm_i;
-                comp_z = dz_i;
-            }
-            _ => {
-                comp_m = comp_m + dm_i;
-                comp_z = comp_z + dz_i;
-            }
-        };
-    }
-    [comp_m, comp_z]
-}
-
-// generates a seed for deriving coefficients that are used to construct the
-// composite `RistrettoPoint` objects used in batch DLEQ proofs, moves the result
-// into the provided output buffer
-fn ristretto_batch_dleq_seed(y: &RistrettoPoint, m: &[RistrettoPoint], z: &[RistrettoPoint], out: &mut Vec<u8>) {
-    let mut inputs: Vec<&RistrettoPoint> = Vec::new();
-    inputs.push(y);
-    inputs.extend(m);
-    inputs.extend(z);
-    ristretto_dleq_hash(&inputs, out)
+        ristretto_scalar_from_slice(&buf).as_bytes().to_vec()
+    }).collect()
 }
 
 // hash inputs points for DLEQ proofs, moves the result into the provided output
@@ -221,9 +188,24 @@ fn ristretto_hash() -> Sha512 {
     Sha512::new()
 }
 
-// moves RISTRETTO_BYTE_LENGTH uniformly sampled bytes into the provided output buffer
+// moves the canonical encoding of a uniformly sampled scalar into the provided
+// output buffer. Most strings of 32 random bytes are not reduced, and would be
+// refused by the strict scalar checks when keys and blinds are deserialized
+// (see the `encoding` and `state` modules).
 fn ristretto_sample_uniform_bytes(out: &mut Vec<u8>) {
-    rand_bytes(RISTRETTO_BYTE_LENGTH, out)
+    let mut rng = OsRng;
+    copy_into(Scalar::random(&mut rng).as_bytes(), out)
+}
+
+// returns true if the bytes are the canonical encoding of a non-zero scalar
+fn ristretto_is_valid_scalar(x: &[u8]) -> bool {
+    if x.len() != RISTRETTO_BYTE_LENGTH {
+        return false;
+    }
+    match Scalar::from_canonical_bytes(ristretto_convert_slice_to_fixed(x)) {
+        Some(sc) => sc != Scalar::zero(),
+        None => false,
+    }
 }
 
 // converts a slice into an array of size RISTRETTO_BYTE_LENGTH
@@ -241,8 +223,9 @@ fn ristretto_scalar_from_slice(x: &[u8]) -> Scalar {
 
 #[cfg(test)]
 mod tests {
-    use super::{PrimeOrderGroup,ristretto_scalar_from_slice,ristretto_convert_slice_to_fixed};
+    use super::{PrimeOrderGroup,ristretto_scalar_from_slice,ristretto_convert_slice_to_fixed,RISTRETTO_BYTE_LENGTH};
     use super::err_deserialization;
+    use crate::oprf::dleq::{self,Domain};
 
     #[test]
     fn ristretto_serialization() {
@@ -290,6 +273,89 @@ mod tests {
         assert_eq!((pog.is_equal)(&add_p, &mult_p), true);
     }
 
+    #[test]
+    fn ristretto_base_mult() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut one = vec![0; RISTRETTO_BYTE_LENGTH];
+        one[0] = 1;
+        let mut scalars = vec![(pog.sub_scalars)(&[0; RISTRETTO_BYTE_LENGTH], &one), one];
+        for _ in 0..5 {
+            let mut r: Vec<u8> = Vec::new();
+            (pog.uniform_bytes)(&mut r);
+            scalars.push(r);
+        }
+        for r in &scalars {
+            let expected = (pog.scalar_mult)(&pog.generator, r);
+            assert!((pog.is_equal)(&(pog.base_mult)(r), &expected));
+        }
+    }
+
+    #[test]
+    fn ristretto_batch_invert_scalars() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        assert!(pog.batch_invert_scalars(&[]).unwrap().is_empty());
+        for &n in &[1, 2, 10] {
+            let mut scalars = Vec::new();
+            for _ in 0..n {
+                let mut r: Vec<u8> = Vec::new();
+                (pog.uniform_bytes)(&mut r);
+                scalars.push(r);
+            }
+            let invs = pog.batch_invert_scalars(&scalars).unwrap();
+            assert_eq!(invs.len(), n);
+            for i in 0..n {
+                assert_eq!(invs[i], (pog.invert_scalar)(&scalars[i]));
+            }
+            // zero has no inverse
+            scalars[n/2] = vec![0; 32];
+            assert!(pog.batch_invert_scalars(&scalars).is_err());
+        }
+    }
+
+    #[test]
+    fn ristretto_batch_scalar_mult() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut ps = Vec::new();
+        let mut rs = Vec::new();
+        for _ in 0..10 {
+            let mut r: Vec<u8> = Vec::new();
+            (pog.uniform_bytes)(&mut r);
+            ps.push((pog.random_element)());
+            rs.push(r);
+        }
+        let batch = (pog.batch_scalar_mult)(&ps, &rs);
+        assert_eq!(batch.len(), ps.len());
+        for i in 0..ps.len() {
+            assert!((pog.is_equal)(&batch[i], &(pog.scalar_mult)(&ps[i], &rs[i])));
+        }
+        let batch = (pog.batch_mult_by)(&ps, &rs[0]);
+        assert_eq!(batch.len(), ps.len());
+        for i in 0..ps.len() {
+            assert!((pog.is_equal)(&batch[i], &(pog.scalar_mult)(&ps[i], &rs[0])));
+        }
+    }
+
+    #[test]
+    fn ristretto_multi_scalar_mult() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        // covers both the small and large batch algorithms
+        for &n in &[1, 2, 70, 300] {
+            let mut ps = Vec::new();
+            let mut rs = Vec::new();
+            for _ in 0..n {
+                let mut r: Vec<u8> = Vec::new();
+                (pog.uniform_bytes)(&mut r);
+                ps.push((pog.random_element)());
+                rs.push(r);
+            }
+            let mut expected = (pog.scalar_mult)(&ps[0], &rs[0]);
+            for i in 1..n {
+                expected = (pog.add)(&expected, &(pog.scalar_mult)(&ps[i], &rs[i]));
+            }
+            assert!((pog.is_equal)(&(pog.multi_scalar_mult)(&ps, &rs), &expected));
+        }
+    }
+
     #[test]
     fn ristretto_encode_to_group() {
         let pog = PrimeOrderGroup::ristretto_255();
@@ -319,6 +385,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ristretto_valid_scalar() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut r: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r);
+        assert!((pog.is_valid_scalar)(&r));
+        assert!(!(pog.is_valid_scalar)(&vec![0; pog.byte_length]));
+        assert!(!(pog.is_valid_scalar)(&vec![0xff; pog.byte_length]));
+        assert!(!(pog.is_valid_scalar)(&r[1..]));
+    }
+
+    #[test]
+    fn ristretto_uniform_bytes_are_canonical() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut r: Vec<u8> = Vec::new();
+        for _ in 0..64 {
+            (pog.uniform_bytes)(&mut r);
+            assert_eq!(r.len(), RISTRETTO_BYTE_LENGTH);
+            assert!((pog.is_valid_scalar)(&r));
+        }
+    }
+
+    #[test]
+    fn ristretto_scalar_arithmetic() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut r: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut r);
+        let inv = (pog.invert_scalar)(&r);
+        assert!((pog.is_valid_scalar)(&inv));
+        let mut one = vec![0; pog.byte_length];
+        one[0] = 1;
+        assert_eq!((pog.mul_scalars)(&r, &inv), one);
+        // (r*s)*g == s*(r*g)
+        let mut s: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut s);
+        let p = (pog.scalar_mult)(&pog.generator, &(pog.mul_scalars)(&r, &s));
+        let q = (pog.scalar_mult)(&(pog.scalar_mult)(&pog.generator, &r), &s);
+        assert_eq!(p, q);
+    }
+
+    #[test]
+    fn ristretto_err_deser_length() {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut ser: Vec<u8> = Vec::new();
+        (pog.serialize)(&(pog.random_element)(), true, &mut ser);
+        assert!((pog.deserialize)(&ser[1..]).is_err());
+        ser.push(0);
+        assert!((pog.deserialize)(&ser).is_err());
+    }
+
     #[test]
     fn ristretto_inverse_mult() {
         let pog = PrimeOrderGroup::ristretto_255();
@@ -343,11 +459,11 @@ mod tests {
         let z = (pog.scalar_mult)(&m, &key);
 
         // generate proof
-        let proof = (pog.dleq_generate)(&key, &pub_key, &m, &z);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::prove(&pog, &Domain::Legacy, &key, &pog.generator, &pub_key, &m, &z).unwrap();
+        assert_eq!(proof.to_bytes().len(), pog.legacy_challenge_length + pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.dleq_verify)(&pub_key, &m, &z, &proof), true);
+        assert_eq!(dleq::verify(&pog, &Domain::Legacy, &pog.generator, &pub_key, &m, &z, &proof), true);
     }
 
     #[test]
@@ -368,11 +484,11 @@ mod tests {
         }
 
         // generate proof
-        let proof = (pog.batch_dleq_generate)(&key, &pub_key, &inputs, &evals);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::batch_prove(&pog, &Domain::Legacy, &key, &pog.generator, &pub_key, &inputs, &evals).unwrap();
+        assert_eq!(proof.to_bytes().len(), pog.legacy_challenge_length + pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.batch_dleq_verify)(&pub_key, &inputs, &evals, &proof), true);
+        assert_eq!(dleq::batch_verify(&pog, &Domain::Legacy, &pog.generator, &pub_key, &inputs, &evals, &proof), true);
     }
 
     #[test]
@@ -391,18 +507,18 @@ mod tests {
         let z_2 = (pog.scalar_mult)(&m, &key_2);
 
         // generate proof
-        let proof = (pog.dleq_generate)(&key_1, &pub_key_1, &m, &z_2);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::prove(&pog, &Domain::Legacy, &key_1, &pog.generator, &pub_key_1, &m, &z_2).unwrap();
+        assert_eq!(proof.to_bytes().len(), pog.legacy_challenge_length + pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.dleq_verify)(&pub_key_1, &m, &z_2, &proof), false);
+        assert_eq!(dleq::verify(&pog, &Domain::Legacy, &pog.generator, &pub_key_1, &m, &z_2, &proof), false);
 
         // generate proof
-        let proof = (pog.dleq_generate)(&key_1, &pub_key_2, &m, &z_1);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::prove(&pog, &Domain::Legacy, &key_1, &pog.generator, &pub_key_2, &m, &z_1).unwrap();
+        assert_eq!(proof.to_bytes().len(), pog.legacy_challenge_length + pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.dleq_verify)(&pub_key_2, &m, &z_1, &proof), false);
+        assert_eq!(dleq::verify(&pog, &Domain::Legacy, &pog.generator, &pub_key_2, &m, &z_1, &proof), false);
     }
 
     #[test]
@@ -428,10 +544,10 @@ mod tests {
         evals[2] = (pog.scalar_mult)(&inputs[2], &bad_key);
 
         // generate proof
-        let proof = (pog.batch_dleq_generate)(&key, &pub_key, &inputs, &evals);
-        assert_eq!(proof.len(), 2);
+        let proof = dleq::batch_prove(&pog, &Domain::Legacy, &key, &pog.generator, &pub_key, &inputs, &evals).unwrap();
+        assert_eq!(proof.to_bytes().len(), pog.legacy_challenge_length + pog.byte_length);
 
         // verify proof
-        assert_eq!((pog.batch_dleq_verify)(&pub_key, &inputs, &evals, &proof), false);
+        assert_eq!(dleq::batch_verify(&pog, &Domain::Legacy, &pog.generator, &pub_key, &inputs, &evals, &proof), false);
     }
 }
\ No newline at end of file
@@ -0,0 +1,373 @@
+//! The `weierstrass` module implements the arithmetic of the prime-order short
+//! Weierstrass curves `y^2 = x^3 - 3*x + b` over fields with `p = 3 mod 4`
+//! (e.g. NIST P-256 and P-384), together with the simplified SWU map of
+//! [RFC 9380](https://www.rfc-editor.org/rfc/rfc9380.html).
+//!
+//! The arithmetic is implemented over `BigUint`, and does not run in constant
+//! time.
+
+use num::{BigUint,One,Zero};
+use digest::{BlockInput,Digest};
+
+use super::MSM_CHUNK;
+use super::msm::multiscalar_mul;
+use super::super::super::utils::{expand_message_xmd_with,par_chunks,par_map};
+
+/// A point in affine coordinates, where `None` is the identity
+pub(crate) type Affine = Option<(BigUint, BigUint)>;
+
+/// A point in Jacobian coordinates `(X, Y, Z)`, which represents the affine
+/// point `(X/Z^2, Y/Z^3)`, or the identity if `Z = 0`
+#[derive(Clone)]
+pub(crate) struct Jacobian {
+    x: BigUint,
+    y: BigUint,
+    z: BigUint,
+}
+
+/// The parameters of a curve
+pub(crate) struct Curve {
+    /// The order of the base field
+    pub(crate) p: BigUint,
+    /// The constant `b` of the curve equation
+    b: BigUint,
+    /// The order of the group
+    pub(crate) n: BigUint,
+    /// The fixed generator of the group
+    pub(crate) g: (BigUint, BigUint),
+    // the constant Z of the simplified SWU map
+    z: BigUint,
+    /// The byte length of field elements and scalars
+    pub(crate) byte_length: usize,
+}
+
+impl Curve {
+    /// Constructs the curve from the (big-endian) hex encodings of its
+    /// parameters, where the SWU constant is `-z_neg`
+    pub(crate) fn new(p: &str, b: &str, n: &str, gx: &str, gy: &str, z_neg: u32) -> Self {
+        let p = from_hex(p);
+        Curve{
+            z: &p - BigUint::from(z_neg),
+            byte_length: (p.bits() + 7) / 8,
+            p: p,
+            b: from_hex(b),
+            n: from_hex(n),
+            g: (from_hex(gx), from_hex(gy)),
+        }
+    }
+
+    // the constant a = -3 of the curve equation
+    fn a(&self) -> BigUint {
+        &self.p - BigUint::from(3u8)
+    }
+
+    fn add_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + b) % &self.p
+    }
+
+    fn sub_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + &self.p - (b % &self.p)) % &self.p
+    }
+
+    fn mul_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a * b) % &self.p
+    }
+
+    fn neg_mod(&self, a: &BigUint) -> BigUint {
+        (&self.p - (a % &self.p)) % &self.p
+    }
+
+    // returns a^-1 (or 0 if a = 0)
+    fn inv_mod(&self, a: &BigUint) -> BigUint {
+        a.modpow(&(&self.p - BigUint::from(2u8)), &self.p)
+    }
+
+    // returns a square root of a, if a is square
+    fn sqrt_mod(&self, a: &BigUint) -> Option<BigUint> {
+        let r = a.modpow(&((&self.p + BigUint::one()) >> 2), &self.p);
+        match self.mul_mod(&r, &r) == a % &self.p {
+            true => Some(r),
+            false => None,
+        }
+    }
+
+    // returns x^3 - 3*x + b
+    fn rhs(&self, x: &BigUint) -> BigUint {
+        let x3 = self.mul_mod(&self.mul_mod(x, x), x);
+        self.add_mod(&self.sub_mod(&x3, &(x * BigUint::from(3u8))), &self.b)
+    }
+
+    /// Returns true if `(x, y)` is a point of the curve
+    pub(crate) fn is_on_curve(&self, x: &BigUint, y: &BigUint) -> bool {
+        x < &self.p && y < &self.p && self.mul_mod(y, y) == self.rhs(x)
+    }
+
+    /// Converts a point to Jacobian coordinates
+    pub(crate) fn to_jacobian(&self, p: &Affine) -> Jacobian {
+        match p {
+            Some((x, y)) => Jacobian{ x: x.clone(), y: y.clone(), z: BigUint::one() },
+            None => self.identity(),
+        }
+    }
+
+    /// Converts a point to affine coordinates
+    pub(crate) fn to_affine(&self, p: &Jacobian) -> Affine {
+        if p.z.is_zero() {
+            return None;
+        }
+        let z_inv = self.inv_mod(&p.z);
+        let z_inv2 = self.mul_mod(&z_inv, &z_inv);
+        Some((self.mul_mod(&p.x, &z_inv2), self.mul_mod(&p.y, &self.mul_mod(&z_inv2, &z_inv))))
+    }
+
+    /// Returns the identity in Jacobian coordinates
+    pub(crate) fn identity(&self) -> Jacobian {
+        Jacobian{ x: BigUint::one(), y: BigUint::one(), z: BigUint::zero() }
+    }
+
+    /// Doubles a point (dbl-2001-b)
+    pub(crate) fn double(&self, p: &Jacobian) -> Jacobian {
+        if p.z.is_zero() || p.y.is_zero() {
+            return self.identity();
+        }
+        let delta = self.mul_mod(&p.z, &p.z);
+        let gamma = self.mul_mod(&p.y, &p.y);
+        let beta = self.mul_mod(&p.x, &gamma);
+        let alpha = self.mul_mod(&BigUint::from(3u8), &self.mul_mod(&self.sub_mod(&p.x, &delta), &self.add_mod(&p.x, &delta)));
+        let x = self.sub_mod(&self.mul_mod(&alpha, &alpha), &(&beta * BigUint::from(8u8)));
+        let yz = self.add_mod(&p.y, &p.z);
+        let z = self.sub_mod(&self.mul_mod(&yz, &yz), &(&gamma + &delta));
+        let gamma2 = self.mul_mod(&gamma, &gamma);
+        let y = self.sub_mod(&self.mul_mod(&alpha, &self.sub_mod(&(&beta * BigUint::from(4u8)), &x)), &(gamma2 * BigUint::from(8u8)));
+        Jacobian{ x: x, y: y, z: z }
+    }
+
+    /// Adds two (possibly equal) points (add-2007-bl)
+    pub(crate) fn add(&self, p1: &Jacobian, p2: &Jacobian) -> Jacobian {
+        if p1.z.is_zero() {
+            return p2.clone();
+        }
+        if p2.z.is_zero() {
+            return p1.clone();
+        }
+        let z1z1 = self.mul_mod(&p1.z, &p1.z);
+        let z2z2 = self.mul_mod(&p2.z, &p2.z);
+        let u1 = self.mul_mod(&p1.x, &z2z2);
+        let u2 = self.mul_mod(&p2.x, &z1z1);
+        let s1 = self.mul_mod(&self.mul_mod(&p1.y, &p2.z), &z2z2);
+        let s2 = self.mul_mod(&self.mul_mod(&p2.y, &p1.z), &z1z1);
+        let h = self.sub_mod(&u2, &u1);
+        let r = self.mul_mod(&BigUint::from(2u8), &self.sub_mod(&s2, &s1));
+        if h.is_zero() {
+            return match r.is_zero() {
+                true => self.double(p1),
+                false => self.identity(),
+            };
+        }
+        let h2 = self.mul_mod(&BigUint::from(2u8), &h);
+        let i = self.mul_mod(&h2, &h2);
+        let j = self.mul_mod(&h, &i);
+        let v = self.mul_mod(&u1, &i);
+        let x = self.sub_mod(&self.mul_mod(&r, &r), &(&j + &v + &v));
+        let y = self.sub_mod(&self.mul_mod(&r, &self.sub_mod(&v, &x)), &(self.mul_mod(&s1, &j) * BigUint::from(2u8)));
+        let z12 = self.add_mod(&p1.z, &p2.z);
+        let z = self.mul_mod(&self.sub_mod(&self.mul_mod(&z12, &z12), &(&z1z1 + &z2z2)), &h);
+        Jacobian{ x: x, y: y, z: z }
+    }
+
+    /// Computes `r*p` for a big-endian scalar `r` of any length
+    pub(crate) fn mul(&self, p: &Jacobian, r: &[u8]) -> Jacobian {
+        let mut acc = self.identity();
+        for byte in r {
+            for i in (0..8).rev() {
+                acc = self.double(&acc);
+                if (byte >> i) & 1 == 1 {
+                    acc = self.add(&acc, p);
+                }
+            }
+        }
+        acc
+    }
+
+    /// Computes `Σ rs[i]*ps[i]` for big-endian scalars `rs[i]`, summing the
+    /// results for each chunk of the inputs over Jacobian coordinates
+    pub(crate) fn multi_mul(&self, ps: &[Affine], rs: &[Vec<u8>]) -> Affine {
+        let jac: Vec<_> = ps.iter().map(|p| self.to_jacobian(p)).collect();
+        // the generic implementation expects little-endian scalars
+        let le: Vec<Vec<u8>> = rs.iter().map(|r| r.iter().rev().cloned().collect()).collect();
+        let chunks = par_chunks(ps.len(), MSM_CHUNK);
+        let sums = par_map(chunks.len(), |i| {
+            multiscalar_mul(&jac[chunks[i].clone()], &le[chunks[i].clone()], |p1, p2| self.add(p1, p2))
+        });
+        match sums.into_iter().flatten().reduce(|p1, p2| self.add(&p1, &p2)) {
+            Some(p) => self.to_affine(&p),
+            None => None,
+        }
+    }
+
+    /// Returns the SEC1 encoding of a point, which is a single zero byte for
+    /// the identity
+    pub(crate) fn encode(&self, p: &Affine, compress: bool) -> Vec<u8> {
+        let (x, y) = match p {
+            Some(c) => c,
+            None => return vec![0],
+        };
+        let mut out = Vec::with_capacity(1 + 2*self.byte_length);
+        match compress {
+            true => out.push(0x02 | sgn0(y)),
+            false => out.push(0x04),
+        }
+        out.extend_from_slice(&self.pad(x));
+        if !compress {
+            out.extend_from_slice(&self.pad(y));
+        }
+        out
+    }
+
+    /// Decodes the compressed or uncompressed SEC1 encoding of a point other
+    /// than the identity, rejecting non-canonical encodings
+    pub(crate) fn decode(&self, buf: &[u8]) -> Affine {
+        let len = self.byte_length;
+        if buf.len() == 1 + len && (buf[0] == 0x02 || buf[0] == 0x03) {
+            let x = BigUint::from_bytes_be(&buf[1..]);
+            if x >= self.p {
+                return None;
+            }
+            let mut y = self.sqrt_mod(&self.rhs(&x))?;
+            if sgn0(&y) != buf[0] & 1 {
+                y = self.neg_mod(&y);
+            }
+            return Some((x, y));
+        }
+        if buf.len() == 1 + 2*len && buf[0] == 0x04 {
+            let x = BigUint::from_bytes_be(&buf[1..1+len]);
+            let y = BigUint::from_bytes_be(&buf[1+len..]);
+            if self.is_on_curve(&x, &y) {
+                return Some((x, y));
+            }
+        }
+        None
+    }
+
+    /// Maps the field element `u` to the curve with the simplified SWU map,
+    /// as in `map_to_curve_simple_swu` of RFC 9380
+    pub(crate) fn map_to_curve(&self, u: &BigUint) -> (BigUint, BigUint) {
+        let a = self.a();
+        let u2 = self.mul_mod(u, u);
+        let zu2 = self.mul_mod(&self.z, &u2);
+        let tv1 = self.inv_mod(&self.add_mod(&self.mul_mod(&zu2, &zu2), &zu2));
+        let x1 = match tv1.is_zero() {
+            true => self.mul_mod(&self.b, &self.inv_mod(&self.mul_mod(&self.z, &a))),
+            false => self.mul_mod(&self.mul_mod(&self.neg_mod(&self.b), &self.inv_mod(&a)), &self.add_mod(&BigUint::one(), &tv1)),
+        };
+        let (x, mut y) = match self.sqrt_mod(&self.rhs(&x1)) {
+            Some(y) => (x1, y),
+            None => {
+                let x2 = self.mul_mod(&zu2, &x1);
+                let y = self.sqrt_mod(&self.rhs(&x2)).expect("one of gx1 and gx2 is square");
+                (x2, y)
+            },
+        };
+        if sgn0(u) != sgn0(&y) {
+            y = self.neg_mod(&y);
+        }
+        (x, y)
+    }
+
+    /// Encodes `msg` to the curve (`encode_to_curve` of RFC 9380), with
+    /// expand_message_xmd over the hash function `H`. The cofactor is one, so
+    /// the result is in the group.
+    pub(crate) fn encode_to_curve<H>(&self, msg: &[u8], dst: &[u8]) -> Affine
+            where H: Digest + BlockInput {
+        let u = self.hash_to_field::<H>(msg, dst, 1);
+        Some(self.map_to_curve(&u[0]))
+    }
+
+    /// Hashes `msg` to the curve (`hash_to_curve` of RFC 9380), with
+    /// expand_message_xmd over the hash function `H`
+    pub(crate) fn hash_to_curve<H>(&self, msg: &[u8], dst: &[u8]) -> Affine
+            where H: Digest + BlockInput {
+        let u = self.hash_to_field::<H>(msg, dst, 2);
+        let q0 = self.to_jacobian(&Some(self.map_to_curve(&u[0])));
+        let q1 = self.to_jacobian(&Some(self.map_to_curve(&u[1])));
+        self.to_affine(&self.add(&q0, &q1))
+    }
+
+    // hash_to_field of RFC 9380
+    fn hash_to_field<H>(&self, msg: &[u8], dst: &[u8], count: usize) -> Vec<BigUint>
+            where H: Digest + BlockInput {
+        let l = self.hash_length();
+        let uniform = expand_message_xmd_with::<H>(msg, dst, count*l);
+        uniform.chunks(l).map(|c| BigUint::from_bytes_be(c) % &self.p).collect()
+    }
+
+    // the length L of the uniform bytes for each field element or scalar,
+    // for a security level of half the bit length of the field (128 bits for
+    // P-256, 192 bits for P-384)
+    fn hash_length(&self) -> usize {
+        self.byte_length + self.byte_length/2
+    }
+
+    /// Left-pads a (big-endian) integer that is smaller than the field or
+    /// group order to `byte_length` bytes
+    pub(crate) fn pad(&self, x: &BigUint) -> Vec<u8> {
+        let bytes = x.to_bytes_be();
+        let mut out = vec![0; self.byte_length - bytes.len()];
+        out.extend_from_slice(&bytes);
+        out
+    }
+
+    /// Reduces a big-endian scalar modulo the group order, and negates it if
+    /// `pve` is false
+    pub(crate) fn reduce_scalar(&self, sc: &[u8], pve: bool) -> Vec<u8> {
+        let red = BigUint::from_bytes_be(sc) % &self.n;
+        match pve {
+            true => self.pad(&red),
+            false => self.pad(&((&self.n - red) % &self.n)),
+        }
+    }
+
+    /// Returns true if the bytes are the (big-endian) encoding of a non-zero
+    /// scalar that is smaller than the group order
+    pub(crate) fn is_valid_scalar(&self, sc: &[u8]) -> bool {
+        let x = BigUint::from_bytes_be(sc);
+        sc.len() == self.byte_length && !x.is_zero() && x < self.n
+    }
+
+    /// Multiplies two scalars modulo the group order
+    pub(crate) fn mul_scalars(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
+        self.pad(&((BigUint::from_bytes_be(a) * BigUint::from_bytes_be(b)) % &self.n))
+    }
+
+    /// Subtracts the second scalar from the first modulo the group order
+    pub(crate) fn sub_scalars(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
+        let n = &self.n;
+        self.pad(&((BigUint::from_bytes_be(a) % n + n - BigUint::from_bytes_be(b) % n) % n))
+    }
+
+    /// Inverts a non-zero scalar modulo the (prime) group order
+    pub(crate) fn invert_scalar(&self, sc: &[u8]) -> Vec<u8> {
+        let exp = &self.n - BigUint::from(2u8);
+        self.pad(&BigUint::from_bytes_be(sc).modpow(&exp, &self.n))
+    }
+
+    /// Maps `msg` to a scalar with hash_to_field of RFC 9380 over the group
+    /// order
+    pub(crate) fn hash_to_scalar<H>(&self, msg: &[u8], dst: &[u8]) -> Vec<u8>
+            where H: Digest + BlockInput {
+        let wide = expand_message_xmd_with::<H>(msg, dst, self.hash_length());
+        self.pad(&(BigUint::from_bytes_be(&wide) % &self.n))
+    }
+}
+
+// the parity of a field element
+fn sgn0(x: &BigUint) -> u8 {
+    match x.is_zero() {
+        true => 0,
+        false => x.to_bytes_le()[0] & 1,
+    }
+}
+
+fn from_hex(s: &str) -> BigUint {
+    BigUint::from_bytes_be(&hex::decode(s).unwrap())
+}
@@ -1,5 +1,7 @@
 //! The `oprf` module provides access to the (V)OPRF API specified in
-//! [draft-irtf-cfrg-voprf](https://datatracker.ietf.org/doc/draft-irtf-cfrg-voprf/).
+//! [draft-irtf-cfrg-voprf](https://datatracker.ietf.org/doc/draft-irtf-cfrg-voprf/),
+//! and in [RFC 9497](https://www.rfc-editor.org/rfc/rfc9497.html) for the
+//! ciphersuites that follow it (see `ciphersuite::Spec`).
 //!
 //! # Example
 //!
@@ -28,10 +30,11 @@
 //! let input_vec = cli.blind(&vec![x]);
 //!
 //! // server evaluates PRF on single input
-//! let eval = srv.eval(&vec![input_vec[0].elem]);
+//! let eval = srv.eval(&vec![input_vec[0].elem]).unwrap();
 //! assert_eq!(eval.elems.len(), 1);
 //! if let Some(d) = &eval.proof {
-//!     assert_eq!(d.len(), 2)
+//!     // the challenge of (default) V1 proofs is a full scalar
+//!     assert_eq!(d.to_bytes().len(), 2*pog.byte_length)
 //! } else {
 //!     panic!("a proof should have been provided")
 //! }
@@ -54,16 +57,32 @@
 
 pub mod ciphersuite;
 pub mod groups;
-
+pub mod dleq;
+pub mod any;
+pub mod encoding;
+pub mod proof;
+pub mod state;
+pub mod consistency;
+pub mod update;
+
+use proof::Proof;
 use groups::PrimeOrderGroup;
-use ciphersuite::Ciphersuite;
+use ciphersuite::{Ciphersuite,Spec};
+use consistency::KeyConsistency;
 
 use hmac::Mac;
+use sha2::{Sha256,Digest};
+use subtle::ConstantTimeEq;
 
+use std::convert::TryFrom;
 use std::io::Error;
-use super::errors::{err_internal,err_public_key_not_found,err_proof_not_found,err_proof_verification};
+use std::collections::HashSet;
+use std::sync::Arc;
+use super::utils::shuffle;
+use super::errors::{err_deserialization,err_internal,err_finalization,err_evaluation,err_public_key_not_found,err_proof_not_found,err_proof_verification,err_shuffled_verifiable};
 
 const OPRF_DST: &'static str = "oprf_derive_output";
+const RFC9497_FINALIZE_DST: &[u8] = b"Finalize";
 
 /// The `SecretKey` struct provides a wrapper around a number of bytes of
 /// varying length.
@@ -90,7 +109,7 @@ impl SecretKey {
     /// the scaalr value of the secret key.
     pub fn pub_key<T,H>(&self, pog: &PrimeOrderGroup<T,H>) -> PublicKey<T>
             where T: Clone {
-        PublicKey((pog.scalar_mult)(&pog.generator, &self.0))
+        PublicKey((pog.base_mult)(&self.0))
     }
 }
 
@@ -107,10 +126,22 @@ impl<T> PublicKey<T> {
         hex::encode(out)
     }
 
-    /// constructs an instance of `PublicKey` from hex input
-    pub fn from_hex<H>(hex_str: String, pog: &PrimeOrderGroup<T,H>) -> Self {
-        let buf = hex::decode(hex_str).unwrap();
-        PublicKey((pog.deserialize)(&buf).unwrap())
+    /// outputs the SHA-256 fingerprint of the (compressed) public key
+    pub fn fingerprint<H>(&self, pog: &PrimeOrderGroup<T,H>) -> Vec<u8> {
+        let mut out = Vec::new();
+        (pog.serialize)(&self.0, true, &mut out);
+        Sha256::digest(&out).to_vec()
+    }
+
+    /// constructs an instance of `PublicKey` from hex input, returns an error
+    /// if the input is not the encoding of a valid group element
+    pub fn from_hex<H>(hex_str: String, pog: &PrimeOrderGroup<T,H>) -> Result<Self, Error> {
+        let buf = hex::decode(hex_str).map_err(|_| err_deserialization())?;
+        let elem = (pog.deserialize)(&buf)?;
+        if !(pog.is_valid)(&elem) {
+            return Err(err_deserialization());
+        }
+        Ok(PublicKey(elem))
     }
 }
 
@@ -124,6 +155,9 @@ impl<T> PublicKey<T> {
 /// * `elem`: A group element equal to `h1(data)*r` where `r` is the scalar
 /// respresentation of the blinding factor used
 /// * `blind`: The bytes corresponding to the blinding factor that is used
+///
+/// The inverse of the blinding factor is computed when the input is blinded,
+/// and used for unblinding.
 #[derive(Clone)]
 pub struct Input<T> {
     /// The initial bytes that characterise the client input
@@ -134,7 +168,9 @@ pub struct Input<T> {
     pub elem: T,
     /// The value that is used to blind the input by the client, to ensure that
     /// the server does not learn their input
-    pub blind: Vec<u8>
+    pub blind: Vec<u8>,
+    // the inverse of `blind`
+    pub(crate) inv_blind: Vec<u8>,
 }
 
 /// The struct used for wrapping the data generated in the server response.
@@ -155,7 +191,7 @@ pub struct Evaluation<T>{
     pub elems: Vec<T>,
     /// Optional proof (for verifiability in VOPRF) for ensuring that the server
     /// evaluates the PRF with a committed key
-    pub proof: Option<[Vec<u8>; 2]>
+    pub proof: Option<Proof<T>>
 }
 
 /// Defines a struct for (V)OPRF protocol participants. Corresponds to a
@@ -168,6 +204,8 @@ pub struct Participant<T,H,K>
     /// The type of key associated with the `Participant`, either `SecretKey` or
     /// `PublicKey`.
     pub key: K,
+    // the key consistency checker of a client
+    consistency: Option<Arc<KeyConsistency>>,
 }
 
 /// The `Server<T,H>` type wraps an instance of Participant where `K =
@@ -190,7 +228,7 @@ pub struct Participant<T,H,K>
 ///
 /// // evaluate (V)OPRF on a group element (computes k*m)
 /// let m = ciph.h1(b"some_input_data");
-/// let _ = srv.eval(&vec![m]);
+/// let _ = srv.eval(&vec![m]).unwrap();
 /// ```
 pub type Server<T,H> = Participant<T,H,SecretKey>;
 
@@ -207,38 +245,38 @@ impl<T,H> Server<T,H>
         Server{
             ciph: ciph,
             key: SecretKey::new(pog),
+            consistency: None,
         }
     }
 
     /// Corresponds to the (V)OPRF_Eval algorithm in draft-irtf-cfrg-voprf.
-    /// Evaluates the server-side PRF portion of the (V)OPRF of the protocol
+    /// Evaluates the server-side PRF portion of the (V)OPRF of the protocol.
+    /// Returns an error if the DLEQ proof cannot be generated, e.g. because
+    /// the context of the ciphersuite is too long.
     ///
     /// # Arguments
     ///
     /// `input_elems`: A slice of group elements (type `T`)
-    pub fn eval(&self, input_elems: &[T]) -> Evaluation<T> {
-        let mut eval_elems = Vec::new();
+    pub fn eval(&self, input_elems: &[T]) -> Result<Evaluation<T>, Error> {
         let ciph = &self.ciph;
         let pog = &ciph.pog;
         let key = &self.key;
 
         // recover secret key value
         let sk = &key.0;
-        for m in input_elems {
-            eval_elems.push((pog.scalar_mult)(m, sk));
-        }
+        let eval_elems = (pog.batch_mult_by)(input_elems, sk);
 
         // generate proof if necessary
         let mut proof = None;
         if ciph.verifiable {
             let pk = key.pub_key(pog).0;
-            proof = Some(self.proof_generation(&sk, &pk, input_elems, &eval_elems));
+            proof = Some(self.proof_generation(&sk, &pk, input_elems, &eval_elems)?);
         }
 
-        return Evaluation{
+        Ok(Evaluation{
             elems: eval_elems,
             proof: proof,
-        };
+        })
     }
 
     /// Evaluates the server-side PRF portion of the (V)OPRF of the protocol
@@ -247,7 +285,7 @@ impl<T,H> Server<T,H>
     /// # Arguments
     ///
     /// `input_elems`: A slice of group elements (type `T`)
-    pub fn fixed_eval(&self, input_elems: &[T], fixed_scalar: &[u8]) -> Evaluation<T> {
+    pub fn fixed_eval(&self, input_elems: &[T], fixed_scalar: &[u8]) -> Result<Evaluation<T>, Error> {
         let mut eval_elems = Vec::new();
         let ciph = &self.ciph;
         let pog = &ciph.pog;
@@ -263,13 +301,42 @@ impl<T,H> Server<T,H>
         let mut proof = None;
         if ciph.verifiable {
             let pk = key.pub_key(pog).0;
-            proof = Some(self.fixed_proof_generation(&sk, &pk, input_elems, &eval_elems, fixed_scalar));
+            proof = Some(self.fixed_proof_generation(&sk, &pk, input_elems, &eval_elems, fixed_scalar)?);
         }
 
-        return Evaluation{
+        Ok(Evaluation{
             elems: eval_elems,
             proof: proof,
-        };
+        })
+    }
+
+    /// Evaluates the server-side PRF portion of the OPRF like `eval`, and
+    /// returns the evaluated elements in a uniformly random order, so that they
+    /// cannot be matched with the input elements. Used for computing the
+    /// cardinality of a set intersection (see the `psi` module).
+    ///
+    /// Returns an error for verifiable ciphersuites: a batched DLEQ proof
+    /// depends on the order of the elements, and a proof over their sums does
+    /// not bind the individual elements (`Z1+δ` and `Z2-δ` have the same sum as
+    /// `Z1` and `Z2`), so no proof is produced for shuffled evaluations.
+    ///
+    /// # Arguments
+    ///
+    /// `input_elems`: A non-empty slice of group elements (type `T`)
+    pub fn eval_shuffled(&self, input_elems: &[T]) -> Result<Evaluation<T>, Error> {
+        let ciph = &self.ciph;
+        if ciph.verifiable {
+            return Err(err_shuffled_verifiable());
+        }
+        let pog = &ciph.pog;
+        let sk = &self.key.0;
+        let mut eval_elems: Vec<T> = input_elems.iter().map(|m| (pog.scalar_mult)(m, sk)).collect();
+        shuffle(&mut eval_elems);
+
+        Ok(Evaluation{
+            elems: eval_elems,
+            proof: None,
+        })
     }
 
     /// set_key allows specifying a new key for the server
@@ -278,19 +345,66 @@ impl<T,H> Server<T,H>
     }
 
     /// generates the DLEQ proof object
-    fn proof_generation(&self, sk: &[u8], pk: &T, input_elems: &[T], eval_elems: &[T]) -> [Vec<u8>; 2] {
-        match input_elems.len() > 1 {
-            true => (self.ciph.pog.batch_dleq_generate)(&sk, &pk, &input_elems, &eval_elems),
-            false => (self.ciph.pog.dleq_generate)(&sk, &pk, &input_elems[0], &eval_elems[0])
+    fn proof_generation(&self, sk: &[u8], pk: &T, input_elems: &[T], eval_elems: &[T]) -> Result<Proof<T>, Error> {
+        let pog = &self.ciph.pog;
+        let domain = self.ciph.dleq_domain()?;
+        // empty inputs are rejected by the batched proof, which RFC 9497 uses
+        // for single elements too
+        match input_elems.len() == 1 && self.ciph.spec == Spec::Draft02 {
+            true => dleq::prove(pog, &domain, sk, &pog.generator, pk, &input_elems[0], &eval_elems[0]),
+            false => dleq::batch_prove(pog, &domain, sk, &pog.generator, pk, input_elems, eval_elems)
         }
     }
 
     /// same as `proof_generation` except that it runs the algorithms with a
     /// fixed scalar, used for testing purposes.
-    fn fixed_proof_generation(&self, sk: &[u8], pk: &T, input_elems: &[T], eval_elems: &[T], fixed_scalar: &[u8]) -> [Vec<u8>; 2] {
-        match input_elems.len() > 1 {
-            true => (self.ciph.pog.fixed_batch_dleq_generate)(&sk, &pk, &input_elems, &eval_elems, fixed_scalar),
-            false => (self.ciph.pog.fixed_dleq_generate)(&sk, &pk, &input_elems[0], &eval_elems[0], fixed_scalar)
+    fn fixed_proof_generation(&self, sk: &[u8], pk: &T, input_elems: &[T], eval_elems: &[T], fixed_scalar: &[u8]) -> Result<Proof<T>, Error> {
+        let pog = &self.ciph.pog;
+        let domain = self.ciph.dleq_domain()?;
+        match input_elems.len() == 1 && self.ciph.spec == Spec::Draft02 {
+            true => dleq::prove_with_nonce(pog, &domain, sk, &pog.generator, pk, &input_elems[0], &eval_elems[0], fixed_scalar),
+            false => dleq::batch_prove_with_nonce(pog, &domain, sk, &pog.generator, pk, input_elems, eval_elems, fixed_scalar)
+        }
+    }
+}
+
+impl<T,H> Server<T,H>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T, H>: ciphersuite::Supported {
+    /// Evaluates the full (V)OPRF over `input` with the secret key, without
+    /// blinding. The output is the same as the output of `Client::finalize`
+    /// for `input` with auxiliary data `info`, after the evaluation has been
+    /// unblinded.
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: the bytes that the (V)OPRF is evaluated on
+    /// * `info`: arbitrary bytes used to evaluate finalization HMAC
+    pub fn full_evaluate(&self, input: &[u8], info: &[u8]) -> Result<Vec<u8>, Error> {
+        let ciph = &self.ciph;
+        let elem = (ciph.pog.scalar_mult)(&ciph.h1(input), &self.key.0);
+        finalize(ciph, input, &elem, info)
+    }
+
+    /// Evaluates the (V)OPRF over `input` like `full_evaluate`, except that the
+    /// output is derived from the evaluated element alone (it is finalized with
+    /// empty input bytes). The output is the same as the output of
+    /// `Client::finalize` with empty input bytes for the unblinded element, so
+    /// that it can be matched against evaluations that are not linked to the
+    /// inputs of the client (see `eval_shuffled`).
+    pub fn full_evaluate_element(&self, input: &[u8], info: &[u8]) -> Result<Vec<u8>, Error> {
+        let ciph = &self.ciph;
+        let elem = (ciph.pog.scalar_mult)(&ciph.h1(input), &self.key.0);
+        finalize(ciph, &[], &elem, info)
+    }
+
+    /// Checks that `output` is the (V)OPRF output for `input` and `info` (see
+    /// `full_evaluate`). The output is compared in constant time.
+    pub fn verify_output(&self, input: &[u8], info: &[u8], output: &[u8]) -> bool {
+        match self.full_evaluate(input, info) {
+            Ok(out) => out.ct_eq(output).into(),
+            Err(_) => false,
         }
     }
 }
@@ -326,9 +440,16 @@ impl<T,H> Client<T,H>
         Ok(Client{
             ciph: ciph,
             key: pub_key,
+            consistency: None,
         })
     }
 
+    /// Sets the checker that the public key is checked with before each
+    /// evaluation is unblinded (see the `consistency` module)
+    pub fn set_consistency(&mut self, checker: Arc<KeyConsistency>) {
+        self.consistency = Some(checker);
+    }
+
     /// Corresponds to the (V)OPRF_Blind algorithm in draft-irtf-cfrg-voprf.
     /// Computes a set of Input objects corresponding to blinded group elements
     /// derived from the input bytes
@@ -338,20 +459,70 @@ impl<T,H> Client<T,H>
     /// * `inputs`: A slice of byte vectors which the blinded group elements are
     ///   computed from
     pub fn blind(&self, inputs: &[Vec<u8>]) -> Vec<Input<T>> {
-        let mut blinded_inputs: Vec<Input<T>> = Vec::new();
+        let ciph = &self.ciph;
+        let pog = &ciph.pog;
+        let mut blinds = Vec::new();
+        let mut elems = Vec::new();
         for x in inputs {
-            let ciph = &self.ciph;
-            let pog = &ciph.pog;
-            let mut r: Vec<u8> = Vec::new();
-            (pog.uniform_bytes)(&mut r);
-            let p = self.blind_fixed(&x, &r);
-            blinded_inputs.push(Input{
-                data: x.to_vec(),
-                elem: p,
-                blind: r
-            });
+            blinds.push(sample_blind(pog));
+            elems.push(ciph.h1(x));
         }
-        blinded_inputs
+        let blinded_elems = (pog.batch_scalar_mult)(&elems, &blinds);
+        let inv_blinds = pog.batch_invert_scalars(&blinds).expect("blinds are non-zero");
+        inputs.iter().zip(blinded_elems).zip(blinds).zip(inv_blinds).map(|(((x, p), r), inv_r)| Input{
+            data: x.to_vec(),
+            elem: p,
+            blind: r,
+            inv_blind: inv_r,
+        }).collect()
+    }
+
+    /// Blinds all of the inputs with the same (random) blinding factor, so that
+    /// an evaluation of the blinded elements can be unblinded without knowing
+    /// which input each evaluated element corresponds to (see
+    /// `unblind_shuffled`).
+    ///
+    /// Duplicate inputs are only blinded once (keeping the first occurrence),
+    /// since equal inputs with a shared blind would produce equal blinded
+    /// elements, revealing to the server that they are equal.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs`: A slice of byte vectors which the blinded group elements are
+    ///   computed from
+    pub fn blind_shared(&self, inputs: &[Vec<u8>]) -> Vec<Input<T>> {
+        let pog = &self.ciph.pog;
+        let r = sample_blind(pog);
+        let inv_r = (pog.invert_scalar)(&r);
+        let mut seen = HashSet::new();
+        inputs.iter().filter(|x| seen.insert(x.to_vec())).map(|x| Input{
+            data: x.to_vec(),
+            elem: self.blind_fixed(x, &r),
+            blind: r.clone(),
+            inv_blind: inv_r.clone(),
+        }).collect()
+    }
+
+    /// Returns the input for `input` blinded with the fixed blind value, e.g.
+    /// for replaying test vectors. Returns an error if the blind is not the
+    /// canonical encoding of a non-zero scalar.
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: A slice of bytes which a blinded group element is computed
+    ///   from
+    /// * `blind`: The blind used to create the blinded group element
+    pub fn blind_with(&self, input: &[u8], blind: &[u8]) -> Result<Input<T>, Error> {
+        let pog = &self.ciph.pog;
+        if !(pog.is_valid_scalar)(blind) {
+            return Err(err_deserialization());
+        }
+        Ok(Input{
+            data: input.to_vec(),
+            elem: self.blind_fixed(input, blind),
+            blind: blind.to_vec(),
+            inv_blind: (pog.invert_scalar)(blind),
+        })
     }
 
     /// Corresponds to blinding a single point with a fixed blind value. Made
@@ -372,7 +543,8 @@ impl<T,H> Client<T,H>
     /// Corresponds to the (V)OPRF_Unblind algorithm in draft-irtf-cfrg-voprf.
     /// Unblinds the output of the server evaluation algorithm, in accordance
     /// with the client-specified inputs. If the client ciphersuite is for a
-    /// VOPRF< then it will also verify the server-generated DLEQ proof object.
+    /// VOPRF< then it will also verify the server-generated DLEQ proof object,
+    /// and check the consistency of the public key if a checker is set.
     ///
     /// # Arguments
     ///
@@ -383,25 +555,36 @@ impl<T,H> Client<T,H>
         let pog = &ciph.pog;
         let eval_elems = &eval.elems;
         // check that the number of inputs is the same as the number of outputs
-        assert_eq!(inputs.len(), eval_elems.len());
+        if inputs.is_empty() || inputs.len() != eval_elems.len() {
+            return Err(err_evaluation());
+        }
+        // refuse keys that are not consistent
+        let mut checked_key = None;
+        if let (true, Some(checker), Some(pk)) = (ciph.verifiable, &self.consistency, &self.key) {
+            let mut buf = Vec::new();
+            (pog.serialize)(&pk.0, true, &mut buf);
+            checker.check(&ciph.name, &buf)?;
+            checked_key = Some((checker, buf));
+        }
         // verify proof if necessary
         let mut proof_verification = Ok(false);
         if ciph.verifiable {
+            let domain = ciph.dleq_domain()?;
             // recover proof
             if let Some(d) = &eval.proof {
                 if let Some(pk) = &self.key {
                     // get input group elements and verify proof
                     let verify_evals = eval_elems;
-                    match inputs.len() > 1 {
+                    match inputs.len() > 1 || ciph.spec == Spec::Rfc9497 {
                         true => {
                             let mut input_elems = Vec::new();
                             for input in inputs {
                                 input_elems.push(input.elem.clone());
                             }
-                            proof_verification = Ok((pog.batch_dleq_verify)(&pk.0, &input_elems, &verify_evals, &d));
+                            proof_verification = Ok(dleq::batch_verify(pog, &domain, &pog.generator, &pk.0, &input_elems, &verify_evals, &d));
                         },
                         false => {
-                            proof_verification = Ok((pog.dleq_verify)(&pk.0, &inputs[0].elem, &verify_evals[0], &d));
+                            proof_verification = Ok(dleq::verify(pog, &domain, &pog.generator, &pk.0, &inputs[0].elem, &verify_evals[0], &d));
                         }
                     };
                 } else {
@@ -411,22 +594,48 @@ impl<T,H> Client<T,H>
                 proof_verification = Err(err_proof_not_found());
             }
         }
-        let mut outs: Vec<T> = Vec::new();
-        for i in 0..eval_elems.len() {
-            let elem = &eval_elems[i];
-            let blind = &inputs[i].blind;
-            outs.push((pog.inverse_mult)(elem, blind));
+        // the inverses of the blinds were computed when the inputs were blinded
+        let inv_blinds: Vec<Vec<u8>> = inputs.iter().map(|i| i.inv_blind.clone()).collect();
+        let outs = (pog.batch_scalar_mult)(eval_elems, &inv_blinds);
+
+        // a missing proof or public key fails verification, otherwise the
+        // server could evaluate without committing to its key
+        if ciph.verifiable && !proof_verification? {
+            return Err(err_proof_verification());
+        }
+        // only keys with a valid proof are recorded in the history
+        if let Some((checker, buf)) = checked_key {
+            checker.confirm(&ciph.name, &buf)?;
         }
+        Ok(outs)
+    }
 
+    /// Unblinds the output of `Server::eval_shuffled`, where the evaluated
+    /// elements are in a random order. The inputs must have been blinded with
+    /// `blind_shared`. The unblinded elements are returned in the order of the
+    /// evaluation, and are not linked to the inputs. Returns an error for
+    /// verifiable ciphersuites, since shuffled evaluations carry no proof (see
+    /// `Server::eval_shuffled`).
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs`: client-generated OPRF inputs with a shared blind
+    /// * `eval`: corresponding shuffled server evaluation over client inputs
+    pub fn unblind_shuffled(&self, inputs: &[Input<T>], eval: &Evaluation<T>) -> Result<Vec<T>, Error> {
+        let ciph = &self.ciph;
         if ciph.verifiable {
-            if let Ok(b) = proof_verification {
-                // if false, then the proof failed to verify
-                if !b {
-                    return Err(err_proof_verification());
-                }
-            }
+            return Err(err_shuffled_verifiable());
         }
-        Ok(outs)
+        let pog = &ciph.pog;
+        let eval_elems = &eval.elems;
+        if inputs.is_empty() || inputs.len() != eval_elems.len() {
+            return Err(err_finalization());
+        }
+        let blind = &inputs[0].blind;
+        if inputs.iter().any(|i| &i.blind != blind) {
+            return Err(err_finalization());
+        }
+        Ok((pog.batch_mult_by)(eval_elems, &inputs[0].inv_blind))
     }
 
     /// Corresponds to the (V)OPRF_Finalize algorithm in draft-irtf-cfrg-voprf.
@@ -440,29 +649,75 @@ impl<T,H> Client<T,H>
     /// * `elem`: unblinded group element recovered from (V)OPRF_Unblind
     ///   algorithm.
     /// * `aux`: arbitrary bytes used to evaluate finalization HMAC.
+    ///
+    /// For ciphersuites that follow RFC 9497, the output is the hash of the
+    /// input bytes and the unblinded element (as in `Finalize` of RFC 9497),
+    /// and `aux` must be empty.
     pub fn finalize(&self, input_data: &[u8], elem: &T, aux: &[u8]) -> Result<Vec<u8>, Error> {
-        let ciph = &self.ciph;
-        let pog = &ciph.pog;
+        finalize(&self.ciph, input_data, elem, aux)
+    }
+}
 
-        // derive shared key
-        match ciph.h2(&String::from(OPRF_DST).as_bytes()) {
-            Ok(mut mac) => {
-                mac.input(input_data);
-                let mut ser: Vec<u8> = Vec::new();
-                (pog.serialize)(&elem, true, &mut ser);
-                mac.input(&ser);
-                let dk = mac.result().code().to_vec();
-
-                // derive output
-                match ciph.h2(&dk) {
-                    Ok(mut inner_mac) => {
-                        inner_mac.input(&aux);
-                        Ok(inner_mac.result().code().to_vec())
-                    },
-                    Err(_) => Err(err_internal())
-                }
-            },
-            Err(_) => Err(err_internal())
+// computes the (V)OPRF output from the input bytes and the unblinded group
+// element, shared by `Client::finalize` and `Server::full_evaluate`
+fn finalize<T,H>(ciph: &Ciphersuite<T,H>, input_data: &[u8], elem: &T, aux: &[u8]) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T, H>: ciphersuite::Supported {
+    let pog = &ciph.pog;
+    if ciph.spec == Spec::Rfc9497 {
+        return finalize_rfc9497(ciph, input_data, elem, aux);
+    }
+
+    // derive shared key
+    match ciph.h2(&String::from(OPRF_DST).as_bytes()) {
+        Ok(mut mac) => {
+            mac.input(input_data);
+            let mut ser: Vec<u8> = Vec::new();
+            (pog.serialize)(&elem, true, &mut ser);
+            mac.input(&ser);
+            let dk = mac.result().code().to_vec();
+
+            // derive output
+            match ciph.h2(&dk) {
+                Ok(mut inner_mac) => {
+                    inner_mac.input(&aux);
+                    Ok(inner_mac.result().code().to_vec())
+                },
+                Err(_) => Err(err_internal())
+            }
+        },
+        Err(_) => Err(err_internal())
+    }
+}
+
+// computes `Hash(len(input) || input || len(elem) || elem || "Finalize")`, the
+// output of RFC 9497, which has no auxiliary data
+fn finalize_rfc9497<T,H>(ciph: &Ciphersuite<T,H>, input_data: &[u8], elem: &T, aux: &[u8]) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone {
+    let input_len = u16::try_from(input_data.len()).map_err(|_| err_finalization())?;
+    if !aux.is_empty() {
+        return Err(err_finalization());
+    }
+    let mut ser: Vec<u8> = Vec::new();
+    (ciph.pog.serialize)(elem, true, &mut ser);
+    let mut hash_input = Vec::new();
+    hash_input.extend_from_slice(&input_len.to_be_bytes());
+    hash_input.extend_from_slice(input_data);
+    hash_input.extend_from_slice(&(ser.len() as u16).to_be_bytes());
+    hash_input.extend_from_slice(&ser);
+    hash_input.extend_from_slice(RFC9497_FINALIZE_DST);
+    Ok((ciph.digest)(&hash_input))
+}
+
+// samples a blind, resampling in the (negligible) event that it is zero so that
+// it can always be inverted
+fn sample_blind<T,H>(pog: &PrimeOrderGroup<T,H>) -> Vec<u8> {
+    let mut r: Vec<u8> = Vec::new();
+    loop {
+        (pog.uniform_bytes)(&mut r);
+        if (pog.is_valid_scalar)(&r) {
+            return r;
         }
     }
 }
@@ -470,11 +725,13 @@ impl<T,H> Client<T,H>
 #[cfg(test)]
 mod tests {
     use super::groups::PrimeOrderGroup;
-    use super::{Client,Server,Ciphersuite,Input,Evaluation};
-    use super::ciphersuite::Supported;
+    use super::{Client,Server,Ciphersuite,Input,Evaluation,dleq};
+    use super::ciphersuite::{Supported,ProofVersion};
     use curve25519_dalek::ristretto::RistrettoPoint;
     use super::groups::p384::NistPoint;
+    use super::groups::p384_sha384::P384Point;
     use sha2::Sha512;
+    use rand_core::OsRng;
 
     #[test]
     fn end_to_end_oprf_ristretto() {
@@ -591,7 +848,7 @@ mod tests {
         let input_vec = cli.blind(&vec![x]);
 
         // evaluate PRF on single input
-        let eval = srv.eval(&vec![input_vec[0].elem.clone()]);
+        let eval = srv.eval(&vec![input_vec[0].elem.clone()]).unwrap();
         assert_eq!(eval.elems.len(), 1);
         if let Some(_) = eval.proof {
             panic!("no proof should have been provided")
@@ -620,7 +877,7 @@ mod tests {
         for input in &input_vec {
             input_elems.push(input.elem.clone());
         }
-        let eval = srv.eval(&input_elems);
+        let eval = srv.eval(&input_elems).unwrap();
         assert_eq!(eval.elems.len(), 5);
         if let Some(_) = eval.proof {
             panic!("no proof should have been provided")
@@ -641,10 +898,10 @@ mod tests {
         let input_vec = cli.blind(&vec![x]);
 
         // evaluate PRF on single input
-        let eval = srv.eval(&vec![input_vec[0].elem.clone()]);
+        let eval = srv.eval(&vec![input_vec[0].elem.clone()]).unwrap();
         assert_eq!(eval.elems.len(), 1);
         if let Some(d) = &eval.proof {
-            assert_eq!(d.len(), 2)
+            assert_eq!(d.to_bytes().len(), 2*pog.byte_length)
         } else {
             panic!("a proof should have been provided")
         }
@@ -672,10 +929,10 @@ mod tests {
         for input in &input_vec {
             input_elems.push(input.elem.clone());
         }
-        let eval = srv.eval(&input_elems);
+        let eval = srv.eval(&input_elems).unwrap();
         assert_eq!(eval.elems.len(), 5);
         if let Some(d) = eval.proof.clone() {
-            assert_eq!(d.len(), 2)
+            assert_eq!(d.to_bytes().len(), 2*pog.byte_length)
         } else {
             panic!("a proof should have been provided")
         }
@@ -692,17 +949,16 @@ mod tests {
         // unblind server evaluation
         match cli.unblind(&input_vec, eval) {
             Ok(u) => {
-                let sk = srv.key.0;
                 for i in 0..input_vec.len() {
                     let input_data = &input_vec[i].data;
-                    finalization_check(cli, &sk, ciph, input_data, &u[i]);
+                    finalization_check(cli, &srv, ciph, input_data, &u[i]);
                 }
             },
             Err(e) => panic!(e)
         }
     }
 
-    fn finalization_check<T,H>(cli: &Client<T,H>, sk: &[u8], ciph: &Ciphersuite<T,H>, input_data: &[u8], evals: &T)
+    fn finalization_check<T,H>(cli: &Client<T,H>, srv: &Server<T,H>, ciph: &Ciphersuite<T,H>, input_data: &[u8], evals: &T)
             where Input<T>: Clone, Evaluation<T>: Clone, T: Clone, H: Clone
             + digest::BlockInput + digest::FixedOutput + digest::Input
             + digest::Reset + std::default::Default,
@@ -716,7 +972,7 @@ mod tests {
 
         // check output with server (without blinding)
         let ge = ciph.h1(&input_data);
-        let chk_eval = (ciph.pog.scalar_mult)(&ge, &sk);
+        let chk_eval = (ciph.pog.scalar_mult)(&ge, &srv.key.0);
         let chk_out = match cli.finalize(&input_data, &chk_eval, aux) {
             Ok(o) => o,
             Err(e) => panic!(e)
@@ -724,5 +980,310 @@ mod tests {
 
         // check that the outputs are consistent
         assert_eq!(out, chk_out);
+
+        // check that the server computes the same output directly
+        assert_eq!(srv.full_evaluate(&input_data, aux).unwrap(), out);
+        assert!(srv.verify_output(&input_data, aux, &out));
+    }
+
+    #[test]
+    fn verify_output_ristretto() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        verify_output(pog)
+    }
+
+    #[test]
+    fn verify_output_p384() {
+        let pog = PrimeOrderGroup::<NistPoint,Sha512>::p384();
+        verify_output(pog)
+    }
+
+    #[test]
+    fn proofs_bound_to_context() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true).with_proof_version(ProofVersion::V1);
+        let srv = Server::setup(ciph.clone().with_context(b"deployment_a"));
+        let pk = srv.key.pub_key(&pog);
+        let data = [b"input_0".to_vec(), b"input_1".to_vec()];
+        for n in 1..3 {
+            let check = |cli_ciph: Ciphersuite<RistrettoPoint,Sha512>, srv: &Server<RistrettoPoint,Sha512>| {
+                let cli = Client::setup(cli_ciph, Some(pk.clone())).unwrap();
+                let inputs = cli.blind(&data[..n]);
+                let elems: Vec<RistrettoPoint> = inputs.iter().map(|i| i.elem).collect();
+                cli.unblind(&inputs, &srv.eval(&elems).unwrap()).is_ok()
+            };
+            assert!(check(ciph.clone().with_context(b"deployment_a"), &srv));
+            assert!(!check(ciph.clone().with_context(b"deployment_b"), &srv));
+            assert!(!check(ciph.clone(), &srv));
+
+            // legacy proofs are only accepted by legacy clients
+            let mut legacy = srv.clone();
+            legacy.ciph = ciph.clone().with_proof_version(ProofVersion::Legacy);
+            assert!(check(ciph.clone().with_proof_version(ProofVersion::Legacy), &legacy));
+            assert!(!check(ciph.clone().with_context(b"deployment_a"), &legacy));
+            assert!(!check(ciph.clone().with_context(b"deployment_a").with_proof_version(ProofVersion::Legacy), &srv));
+        }
+    }
+
+    #[test]
+    fn shuffled_eval_ristretto() {
+        shuffled_eval(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn shuffled_eval_p384() {
+        shuffled_eval(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    fn shuffled_eval<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T, H>: Supported {
+        let ciph = Ciphersuite::new(pog.clone(), false);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, None).unwrap();
+        let data: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 8]).collect();
+        let inputs = cli.blind_shared(&data);
+        let elems: Vec<T> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let mut eval = srv.eval_shuffled(&elems).unwrap();
+        assert!(eval.proof.is_none());
+
+        // the unblinded elements are the evaluations of the inputs, in some order
+        let expected: Vec<Vec<u8>> = data.iter().map(|x| srv.full_evaluate_element(x, b"info").unwrap()).collect();
+        let mut outs: Vec<Vec<u8>> = cli.unblind_shuffled(&inputs, &eval).unwrap().iter()
+            .map(|u| cli.finalize(&[], u, b"info").unwrap()).collect();
+        outs.sort();
+        let mut sorted = expected.clone();
+        sorted.sort();
+        assert_eq!(outs, sorted);
+
+        // inputs with distinct blinds, or a missing evaluation, are rejected
+        assert!(cli.unblind_shuffled(&cli.blind(&data), &eval).is_err());
+        eval.elems.pop();
+        assert!(cli.unblind_shuffled(&inputs, &eval).is_err());
+    }
+
+    #[test]
+    fn shuffled_eval_refuses_verifiable() {
+        // a DLEQ proof over the sums of the elements does not bind the
+        // individual elements: offsetting two evaluated elements by δ and -δ
+        // preserves the sum, so such a proof would accept a forged evaluation
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let pk = srv.key.pub_key(&pog);
+        let cli = Client::setup(ciph.clone(), Some(pk.clone())).unwrap();
+        let data: Vec<Vec<u8>> = (0..2u8).map(|i| vec![i; 8]).collect();
+        let inputs = cli.blind(&data);
+        let elems: Vec<RistrettoPoint> = inputs.iter().map(|i| i.elem).collect();
+        let evals: Vec<RistrettoPoint> = elems.iter().map(|m| (pog.scalar_mult)(m, &srv.key.0)).collect();
+        let delta = RistrettoPoint::random(&mut OsRng);
+        let forged = vec![evals[0] + delta, evals[1] - delta];
+        assert_ne!(forged, evals);
+        let m: RistrettoPoint = elems.iter().sum();
+        let z: RistrettoPoint = evals.iter().sum();
+        let z_forged: RistrettoPoint = forged.iter().sum();
+        let domain = ciph.dleq_domain().unwrap();
+        let proof = dleq::prove(&pog, &domain, &srv.key.0, &pog.generator, &pk.0, &m, &z).unwrap();
+        assert!(dleq::verify(&pog, &domain, &pog.generator, &pk.0, &m, &z_forged, &proof));
+
+        // so shuffled evaluations are refused for verifiable ciphersuites
+        assert!(srv.eval_shuffled(&elems).is_err());
+        let eval = Evaluation{ elems: forged, proof: Some(proof) };
+        assert!(cli.unblind_shuffled(&inputs, &eval).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn blind_shared_dedups_inputs() {
+        // equal inputs with a shared blind would have equal blinded elements
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let cli = Client::setup(Ciphersuite::new(pog, false), None).unwrap();
+        let data = vec![vec![1; 8], vec![2; 8], vec![1; 8], vec![3; 8], vec![2; 8]];
+        let inputs = cli.blind_shared(&data);
+        let kept: Vec<Vec<u8>> = inputs.iter().map(|i| i.data.clone()).collect();
+        assert_eq!(kept, vec![vec![1; 8], vec![2; 8], vec![3; 8]]);
+        for (i, a) in inputs.iter().enumerate() {
+            for b in &inputs[i+1..] {
+                assert_ne!(a.elem, b.elem);
+            }
+        }
+    }
+
+    #[test]
+    fn shuffled_eval_order_is_uniform() {
+        // the position of each evaluated element is independent of the
+        // position of its input
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), false);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, None).unwrap();
+        let data: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 8]).collect();
+        let inputs = cli.blind_shared(&data);
+        let elems: Vec<RistrettoPoint> = inputs.iter().map(|i| i.elem).collect();
+        let evals: Vec<RistrettoPoint> = elems.iter().map(|m| (pog.scalar_mult)(m, &srv.key.0)).collect();
+        let mut counts = [[0; 4]; 4];
+        for _ in 0..400 {
+            let eval = srv.eval_shuffled(&elems).unwrap();
+            for (pos, z) in eval.elems.iter().enumerate() {
+                let idx = evals.iter().position(|e| e == z).unwrap();
+                counts[idx][pos] += 1;
+            }
+        }
+        // each position is expected 100 times
+        for row in counts.iter() {
+            for c in row.iter() {
+                assert!(*c > 50 && *c < 150, "{:?}", counts);
+            }
+        }
+    }
+
+    fn verify_output<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T, H>: Supported {
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        let input = b"some_input_data".to_vec();
+        let inputs = cli.blind(&[input.clone()]);
+        let eval = srv.eval(&[inputs[0].elem.clone()]).unwrap();
+        let u = cli.unblind(&inputs, &eval).unwrap();
+        let out = cli.finalize(&input, &u[0], b"info").unwrap();
+        assert_eq!(srv.full_evaluate(&input, b"info").unwrap(), out);
+        assert!(srv.verify_output(&input, b"info", &out));
+
+        // outputs for other inputs, info, keys or of the wrong length fail
+        assert!(!srv.verify_output(b"other_input_data", b"info", &out));
+        assert!(!srv.verify_output(&input, b"other_info", &out));
+        assert!(!srv.verify_output(&input, b"info", &out[1..]));
+        let mut modified = out.clone();
+        modified[0] ^= 1;
+        assert!(!srv.verify_output(&input, b"info", &modified));
+        let other = Server::setup(Ciphersuite::new(pog, true));
+        assert!(!other.verify_output(&input, b"info", &out));
+    }
+
+    #[test]
+    fn batch_operations_ristretto() {
+        batch_operations(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn batch_operations_p384() {
+        batch_operations(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    // the batched (and possibly parallel) operations agree with the operations
+    // on each element
+    fn batch_operations<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T, H>: Supported {
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        let data: Vec<Vec<u8>> = (0..100u8).map(|i| vec![i; 8]).collect();
+        let inputs = cli.blind(&data);
+        let elems: Vec<T> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let eval = srv.eval(&elems).unwrap();
+        let outs = cli.unblind(&inputs, &eval).unwrap();
+        for i in 0..data.len() {
+            assert_eq!(inputs[i].inv_blind, (pog.invert_scalar)(&inputs[i].blind));
+            assert!((pog.is_equal)(&inputs[i].elem, &cli.blind_fixed(&data[i], &inputs[i].blind)));
+            assert!((pog.is_equal)(&eval.elems[i], &(pog.scalar_mult)(&elems[i], &srv.key.0)));
+            assert!((pog.is_equal)(&outs[i], &(pog.inverse_mult)(&eval.elems[i], &inputs[i].blind)));
+        }
+    }
+
+    #[test]
+    fn blind_with_fixed_blinds() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        let input = cli.blind(&[b"some_input_data".to_vec()]).remove(0);
+        let fixed = cli.blind_with(&input.data, &input.blind).unwrap();
+        assert!((pog.is_equal)(&fixed.elem, &input.elem));
+        assert_eq!(fixed.inv_blind, input.inv_blind);
+        let eval = srv.eval(&[fixed.elem.clone()]).unwrap();
+        assert!((pog.is_equal)(&cli.unblind(&[fixed], &eval).unwrap()[0], &cli.unblind(&[input.clone()], &eval).unwrap()[0]));
+
+        // blinds that cannot be inverted are rejected
+        assert!(cli.blind_with(&input.data, &[0; 32]).is_err());
+        assert!(cli.blind_with(&input.data, &[0xff; 32]).is_err());
+    }
+
+    #[test]
+    fn unblind_requires_proof() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        let inputs = cli.blind(&[b"some_input_data".to_vec()]);
+        let mut eval = srv.eval(&[inputs[0].elem.clone()]).unwrap();
+        eval.proof = None;
+        assert!(cli.unblind(&inputs, &eval).is_err());
+    }
+
+    #[test]
+    fn unblind_mismatched_evaluation() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        let inputs = cli.blind(&[b"first".to_vec(), b"second".to_vec()]);
+        let mut eval = srv.eval(&[inputs[0].elem.clone()]).unwrap();
+        assert!(cli.unblind(&inputs, &eval).is_err());
+
+        // empty evaluations are rejected rather than indexed
+        eval.elems.clear();
+        assert!(cli.unblind(&[], &eval).is_err());
+    }
+
+    // test vector 1 of appendix A.4.2 of RFC 9497 (VOPRF mode), where skSm is
+    // the output of DeriveKeyPair for the seed and info of the appendix, and a
+    // batch of two inputs checked against an independent implementation
+    #[test]
+    fn rfc9497_p384_sha384_vectors() {
+        let pog = PrimeOrderGroup::p384_sha384();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        assert_eq!(ciph.context_string(), b"OPRFV1-\x01-P384-SHA384");
+        let mut srv = Server::setup(ciph.clone());
+        srv.set_key(hex::decode("051646b9e6e7a71ae27c1e1d0b87b4381db6d3595eeeb1adb41579adbf992f4278f9016eafc944edaa2b43183581779d").unwrap());
+        let pk = srv.key.pub_key(&pog);
+        assert_eq!(pk.as_hex(&pog), "031d689686c611991b55f1a1d8f4305ccd6cb719446f660a30db61b7aa87b46acf59b7c0d4a9077b3da21c25dd482229a0");
+        let cli = Client::setup(ciph, Some(pk)).unwrap();
+        let ser = |p: &P384Point| {
+            let mut out = Vec::new();
+            (pog.serialize)(p, true, &mut out);
+            hex::encode(out)
+        };
+        let nonce = hex::decode("803d955f0e073a04aa5d92b3fb739f56f9db001266677f62c095021db018cd8cbb55941d4073698ce45c405d1348b7b1").unwrap();
+        let blinds = [
+            hex::decode("504650f53df8f16f6861633388936ea23338fa65ec36e0290022b48eb562889d89dbfa691d1cde91517fa222ed7ad364").unwrap(),
+            hex::decode("ae2fdfe9ea4b3bcd3ec7d0d8eeb8a6f2a7fd4e89cbc03f45b3e2e4d8b5c2c6a8b5c1f84c4d3a0f1e2d7c6b5a4938271f").unwrap(),
+        ];
+
+        let input = cli.blind_with(&[0x00], &blinds[0]).unwrap();
+        assert_eq!(ser(&input.elem), "02d338c05cbecb82de13d6700f09cb61190543a7b7e2c6cd4fca56887e564ea82653b27fdad383995ea6d02cf26d0e24d9");
+        let eval = srv.fixed_eval(&[input.elem.clone()], &nonce).unwrap();
+        assert_eq!(ser(&eval.elems[0]), "02a7bba589b3e8672aa19e8fd258de2e6aae20101c8d761246de97a6b5ee9cf105febce4327a326255a3c604f63f600ef6");
+        assert_eq!(hex::encode(eval.proof.as_ref().unwrap().to_bytes()), "bfc6cf3859127f5fe25548859856d6b7fa1c7459f0ba5712a806fc091a3000c42d8ba34ff45f32a52e40533efd2a03bc\
+            87f3bf4f9f58028297ccb9ccb18ae7182bcd1ef239df77e3be65ef147f3acf8bc9cbfc5524b702263414f043e3b7ca2e");
+        let outs = cli.unblind(&[input.clone()], &eval).unwrap();
+        let out = cli.finalize(&input.data, &outs[0], &[]).unwrap();
+        assert_eq!(hex::encode(&out), "3333230886b562ffb8329a8be08fea8025755372817ec969d114d1203d026b4a622beab60220bf19078bca35a529b35c");
+        assert_eq!(srv.full_evaluate(&input.data, &[]).unwrap(), out);
+        // there is no auxiliary data in RFC 9497
+        assert!(cli.finalize(&input.data, &outs[0], b"aux").is_err());
+
+        let inputs = vec![input, cli.blind_with(&[0x5a; 17], &blinds[1]).unwrap()];
+        let elems: Vec<P384Point> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let eval = srv.fixed_eval(&elems, &nonce).unwrap();
+        assert_eq!(ser(&eval.elems[1]), "027efce10f7e92bd6445774d0606a40a2ce89b271fc7443b6a92e5be96ff3c420e253c4a85b8f371e14ec11db4d3973e0f");
+        assert_eq!(hex::encode(eval.proof.as_ref().unwrap().to_bytes()), "e7fbc010c6476f190957739620f9f8d7ac7fe8f99a987d13843767b55326fe9b441036d52c0f5ab49b4b7159ed9dd5c9\
+            de3b752480cef27b9874fc1349204e73c53f2fa16e40af1253311d054689faf111a1be4deeeb2d9cae6889de0cd7b1d2");
+        let outs = cli.unblind(&inputs, &eval).unwrap();
+        assert_eq!(hex::encode(cli.finalize(&inputs[1].data, &outs[1], &[]).unwrap()), "b91c70ea3d4d62ba922eb8a7d03809a441e1c3c7af915cbc2226f485213e895942cd0f8580e6d99f82221e66c40d274f");
+    }
+}
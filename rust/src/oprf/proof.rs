@@ -0,0 +1,282 @@
+//! The proof module describes the `Proof` struct, which holds the DLEQ proof
+//! values `(c, s)` that are sent by a VOPRF server alongside its evaluations.
+//!
+//! A proof over a group with scalars of length `n` bytes has the canonical
+//! encoding `c||s`, where `c` and `s` are both encoded as scalars of exactly
+//! `n` bytes (in the byte-order of the group). Parsing is strict: a proof is
+//! rejected if it has the wrong length, or if either value is not reduced
+//! modulo the order of the group.
+//!
+//! The only exception are the legacy proofs of groups where the challenge of
+//! draft-irtf-cfrg-voprf-02 is the raw hash output (of
+//! `legacy_challenge_length` bytes, e.g. 64 bytes for ristretto255), which
+//! are kept byte-identical. In this case, `c` is the hash output, and the
+//! encoding is `legacy_challenge_length + n` bytes long.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::dleq::{self,Domain};
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::oprf::proof::Proof;
+//!
+//! let pog = PrimeOrderGroup::ristretto_255();
+//! let mut key: Vec<u8> = Vec::new();
+//! (pog.uniform_bytes)(&mut key);
+//! let pub_key = (pog.scalar_mult)(&pog.generator, &key);
+//! let m = (pog.random_element)();
+//! let z = (pog.scalar_mult)(&m, &key);
+//!
+//! let g = &pog.generator;
+//! let proof = dleq::prove(&pog, &Domain::Legacy, &key, g, &pub_key, &m, &z).unwrap();
+//! let parsed = Proof::from_bytes(&pog, &proof.to_bytes()).unwrap();
+//! assert!(dleq::verify(&pog, &Domain::Legacy, g, &pub_key, &m, &z, &parsed));
+//! ```
+use std::io::Error;
+use std::marker::PhantomData;
+
+use super::groups::PrimeOrderGroup;
+use crate::errors::err_deserialization;
+
+/// The `Proof` struct holds a DLEQ proof for the group with elements of type
+/// `T`. Both proof values are guaranteed to be canonical scalars.
+pub struct Proof<T> {
+    c: Vec<u8>,
+    s: Vec<u8>,
+    group: PhantomData<T>,
+}
+
+impl<T> Proof<T> {
+    /// Constructs a proof from the values `c` and `s`, returns an error if
+    /// either of them is not a canonical scalar for the group (or `c` is not
+    /// a legacy challenge)
+    pub fn new<H>(pog: &PrimeOrderGroup<T,H>, c: Vec<u8>, s: Vec<u8>) -> Result<Self, Error> {
+        if !(is_canonical(pog, &c) || is_legacy_challenge(pog, &c)) || !is_canonical(pog, &s) {
+            return Err(err_deserialization());
+        }
+        Ok(Proof::from_scalars(c, s))
+    }
+
+    /// Parses a proof from its canonical `c||s` encoding
+    pub fn from_bytes<H>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<Self, Error> {
+        if !Proof::<T>::is_valid_length(pog, buf.len()) {
+            return Err(err_deserialization());
+        }
+        let (c, s) = buf.split_at(buf.len() - pog.byte_length);
+        Proof::new(pog, c.to_vec(), s.to_vec())
+    }
+
+    /// Returns `true` if `len` is the length of an encoded proof for the
+    /// group, i.e. `2*n` bytes, or `legacy_challenge_length + n` bytes
+    pub fn is_valid_length<H>(pog: &PrimeOrderGroup<T,H>, len: usize) -> bool {
+        len == 2*pog.byte_length || len == pog.legacy_challenge_length + pog.byte_length
+    }
+
+    /// Returns the canonical `c||s` encoding of the proof
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.c.clone();
+        out.extend_from_slice(&self.s);
+        out
+    }
+
+    /// The challenge value `c`
+    pub fn c(&self) -> &[u8] {
+        &self.c
+    }
+
+    /// The response value `s`
+    pub fn s(&self) -> &[u8] {
+        &self.s
+    }
+
+    // constructs a proof from values that are known to be canonical scalars,
+    // as output by the group when generating proofs
+    pub(crate) fn from_scalars(c: Vec<u8>, s: Vec<u8>) -> Self {
+        Proof{
+            c: c,
+            s: s,
+            group: PhantomData,
+        }
+    }
+
+    // converts the proof into a proof for a different element type, used when
+    // handling proofs in serialized form
+    pub(crate) fn cast<U>(self) -> Proof<U> {
+        Proof::from_scalars(self.c, self.s)
+    }
+}
+
+impl<T> Clone for Proof<T> {
+    fn clone(&self) -> Self {
+        Proof::from_scalars(self.c.clone(), self.s.clone())
+    }
+}
+
+impl<T> PartialEq for Proof<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.c == other.c && self.s == other.s
+    }
+}
+
+// returns true if `x` is a legacy challenge that is not a scalar, for groups
+// where the challenge is the raw hash output
+fn is_legacy_challenge<T,H>(pog: &PrimeOrderGroup<T,H>, x: &[u8]) -> bool {
+    pog.legacy_challenge_length != pog.byte_length && x.len() == pog.legacy_challenge_length
+}
+
+// returns true if `x` is the canonical encoding of a (possibly zero) scalar
+fn is_canonical<T,H>(pog: &PrimeOrderGroup<T,H>, x: &[u8]) -> bool {
+    if x.len() != pog.byte_length {
+        return false;
+    }
+    x.iter().all(|b| *b == 0) || (pog.is_valid_scalar)(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Proof;
+    use crate::oprf::dleq::{self,Domain};
+    use crate::oprf::groups::PrimeOrderGroup;
+    use crate::oprf::groups::p384::NistPoint;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use curve25519_dalek::scalar::Scalar;
+    use sha2::Sha512;
+
+    fn setup() -> (PrimeOrderGroup<RistrettoPoint,Sha512>, RistrettoPoint, RistrettoPoint, RistrettoPoint, Proof<RistrettoPoint>) {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let mut key: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut key);
+        let pub_key = (pog.scalar_mult)(&pog.generator, &key);
+        let m = (pog.random_element)();
+        let z = (pog.scalar_mult)(&m, &key);
+        let proof = dleq::prove(&pog, &Domain::Legacy, &key, &pog.generator, &pub_key, &m, &z).unwrap();
+        (pog, pub_key, m, z, proof)
+    }
+
+    fn labelled(pog: &PrimeOrderGroup<RistrettoPoint,Sha512>, m: &RistrettoPoint) -> (Vec<u8>, Proof<RistrettoPoint>) {
+        let mut key: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut key);
+        let pub_key = (pog.base_mult)(&key);
+        let z = (pog.scalar_mult)(m, &key);
+        let proof = dleq::prove(pog, &Domain::new(b"test_label", b"").unwrap(), &key, &pog.generator, &pub_key, m, &z).unwrap();
+        (key, proof)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (pog, pub_key, m, z, proof) = setup();
+        // legacy ristretto255 challenges are the 64-byte hash output
+        let buf = proof.to_bytes();
+        assert_eq!(buf.len(), pog.legacy_challenge_length + pog.byte_length);
+        assert_eq!(&buf[..64], proof.c());
+        assert_eq!(&buf[64..], proof.s());
+        let parsed = Proof::from_bytes(&pog, &buf).unwrap();
+        assert!(parsed == proof);
+        assert!(dleq::verify(&pog, &Domain::Legacy, &pog.generator, &pub_key, &m, &z, &parsed));
+
+        let (key, proof) = labelled(&pog, &m);
+        assert!(dleq::verify(&pog, &Domain::new(b"test_label", b"").unwrap(), &pog.generator, &(pog.base_mult)(&key), &m, &(pog.scalar_mult)(&m, &key), &proof));
+        let buf = proof.to_bytes();
+        assert_eq!(buf.len(), 2*pog.byte_length);
+        assert!(Proof::from_bytes(&pog, &buf).unwrap() == proof);
+    }
+
+    #[test]
+    fn reject_wrong_lengths() {
+        let (pog, _, _, _, proof) = setup();
+        let buf = proof.to_bytes();
+        assert!(Proof::from_bytes(&pog, &buf[1..]).is_err());
+        assert!(Proof::from_bytes(&pog, &buf[..pog.byte_length]).is_err());
+        let mut long = buf.clone();
+        long.push(0);
+        assert!(Proof::from_bytes(&pog, &long).is_err());
+        assert!(Proof::from_bytes(&pog, &[]).is_err());
+        assert!(Proof::new(&pog, proof.c()[1..].to_vec(), proof.s().to_vec()).is_err());
+        let mut s = proof.s().to_vec();
+        s.push(0);
+        assert!(Proof::new(&pog, proof.c().to_vec(), s).is_err());
+    }
+
+    #[test]
+    fn reject_out_of_range() {
+        let (pog, _, _, _, proof) = setup();
+        // the (little-endian) order of the group is not reduced
+        let order: Vec<u8> = vec![
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58,
+            0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+        ];
+        assert_eq!(Scalar::from_bytes_mod_order(order_array(&order)), Scalar::zero());
+        assert!(Proof::new(&pog, order.clone(), proof.s().to_vec()).is_err());
+        assert!(Proof::new(&pog, proof.c().to_vec(), order).is_err());
+        let mut buf = proof.to_bytes();
+        let n = buf.len();
+        for b in buf[n-pog.byte_length..].iter_mut() {
+            *b = 0xff;
+        }
+        assert!(Proof::from_bytes(&pog, &buf).is_err());
+        // challenges of other lengths are rejected
+        assert!(Proof::new(&pog, vec![0xff; 48], proof.s().to_vec()).is_err());
+    }
+
+    fn order_array(x: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out.copy_from_slice(x);
+        out
+    }
+
+    #[test]
+    fn reject_swapped() {
+        let (pog, _, m, _, proof) = setup();
+        // the legacy challenge is not a scalar
+        assert!(Proof::new(&pog, proof.s().to_vec(), proof.c().to_vec()).is_err());
+        let (key, proof) = labelled(&pog, &m);
+        let swapped = Proof::new(&pog, proof.s().to_vec(), proof.c().to_vec()).unwrap();
+        let domain = Domain::new(b"test_label", b"").unwrap();
+        assert!(!dleq::verify(&pog, &domain, &pog.generator, &(pog.base_mult)(&key), &m, &(pog.scalar_mult)(&m, &key), &swapped));
+    }
+
+    // flips a bit in the last byte of `x`, which keeps scalars canonical
+    fn tamper(x: &[u8]) -> Vec<u8> {
+        let mut out = x.to_vec();
+        let n = out.len();
+        out[n-1] ^= 1;
+        out
+    }
+
+    fn reject_tampered<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone {
+        let mut key: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut key);
+        let mut other: Vec<u8> = Vec::new();
+        (pog.uniform_bytes)(&mut other);
+        let g = pog.generator.clone();
+        let pub_key = (pog.base_mult)(&key);
+        let m = (pog.random_element)();
+        let z = (pog.scalar_mult)(&m, &key);
+        for domain in &[Domain::Legacy, Domain::new(b"test_label", b"").unwrap()] {
+            let proof = dleq::prove(&pog, domain, &key, &g, &pub_key, &m, &z).unwrap();
+            assert!(dleq::verify(&pog, domain, &g, &pub_key, &m, &z, &proof));
+            let bad_c = Proof::from_scalars(tamper(proof.c()), proof.s().to_vec());
+            assert!(!dleq::verify(&pog, domain, &g, &pub_key, &m, &z, &bad_c));
+            let bad_s = Proof::from_scalars(proof.c().to_vec(), tamper(proof.s()));
+            assert!(!dleq::verify(&pog, domain, &g, &pub_key, &m, &z, &bad_s));
+            // proofs do not verify under other keys
+            let other_key = (pog.base_mult)(&other);
+            assert!(!dleq::verify(&pog, domain, &g, &other_key, &m, &z, &proof));
+            let other_proof = dleq::prove(&pog, domain, &other, &g, &other_key, &m, &z).unwrap();
+            assert!(!dleq::verify(&pog, domain, &g, &other_key, &m, &z, &other_proof));
+        }
+    }
+
+    #[test]
+    fn reject_tampered_ristretto() {
+        reject_tampered(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn reject_tampered_p384() {
+        reject_tampered(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+}
@@ -0,0 +1,422 @@
+//! The state module describes the `BlindState` struct, which allows a client
+//! to store the inputs that it has blinded, and to restore them in a later
+//! process for unblinding and finalizing the server evaluation.
+//!
+//! A `BlindState` records the name, DLEQ proof version and context of the
+//! ciphersuite, the fingerprint of the server public key (see
+//! `PublicKey::fingerprint`), and the data, blinded group elements and blinds
+//! of each input. The state may optionally be
+//! protected with an HMAC-SHA512 tag under a local key, in which case it is
+//! rejected if it has been modified.
+//!
+//! The state is opaque, and has the following versioned encoding (all lengths
+//! are big-endian u32 values):
+//!
+//! ```text
+//! version (1) || flags (1) || len || ciphersuite || proof_version (1)
+//!     || len || context || len || fingerprint || count
+//!     || (len || data || len || elem || len || blind)* || [tag (64)]
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,Client};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::oprf::state::BlindState;
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use sha2::Sha512;
+//!
+//! let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let srv = Server::setup(ciph.clone());
+//! let pk = srv.key.pub_key(&pog);
+//! let local_key = b"some local key";
+//!
+//! // blind the inputs and store the client state
+//! let cli = Client::setup(ciph, Some(pk.clone())).unwrap();
+//! let inputs = cli.blind(&[b"some_input_data".to_vec()]);
+//! let stored = BlindState::new(&cli, &inputs, Some(local_key)).to_bytes();
+//!
+//! // ... in a later process, restore the client and its inputs
+//! let state = BlindState::from_bytes(&stored).unwrap();
+//! let (cli, inputs) = state.restore::<RistrettoPoint,Sha512>(Some(pk), Some(local_key)).unwrap();
+//! let eval = srv.eval(&[inputs[0].elem]).unwrap();
+//! let outs = cli.unblind(&inputs, &eval).unwrap();
+//! let _ = cli.finalize(&inputs[0].data, &outs[0], b"aux").unwrap();
+//! ```
+use std::io::{Cursor,Error,Read};
+
+use byteorder::{BigEndian,ReadBytesExt,WriteBytesExt};
+use hmac::{Hmac,Mac};
+use sha2::Sha512;
+
+use super::{Client,Input,PublicKey};
+use super::ciphersuite::{Ciphersuite,ProofVersion,Supported};
+use super::groups::PrimeOrderGroup;
+use crate::errors::{err_deserialization,err_invalid_state,err_state_integrity};
+
+/// The current version of the `BlindState` encoding
+pub const BLIND_STATE_VERSION: u8 = 2;
+
+const FLAG_TAGGED: u8 = 1;
+const TAG_LENGTH: usize = 64;
+
+/// The `BlindState` struct holds the client state that is required for
+/// processing the server response to a set of blinded inputs
+#[derive(Clone, PartialEq)]
+pub struct BlindState {
+    ciphersuite: String,
+    proof_version: ProofVersion,
+    context: Vec<u8>,
+    fingerprint: Vec<u8>,
+    inputs: Vec<[Vec<u8>; 3]>,
+    tag: Option<Vec<u8>>,
+}
+
+impl BlindState {
+    /// Records the state of the client for the blinded `inputs`. If `mac_key`
+    /// is set, then the state is protected with an HMAC tag under the key.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli`: the client that blinded the inputs
+    /// * `inputs`: the blinded inputs
+    /// * `mac_key`: an optional local key for integrity protection
+    pub fn new<T,H>(cli: &Client<T,H>, inputs: &[Input<T>], mac_key: Option<&[u8]>) -> Self
+            where T: Clone, H: Clone {
+        let pog = &cli.ciph.pog;
+        let fingerprint = match &cli.key {
+            Some(pk) => pk.fingerprint(pog),
+            None => Vec::new(),
+        };
+        let mut stored = Vec::new();
+        for inp in inputs {
+            let mut elem = Vec::new();
+            (pog.serialize)(&inp.elem, true, &mut elem);
+            stored.push([inp.data.clone(), elem, inp.blind.clone()]);
+        }
+        let mut state = BlindState{
+            ciphersuite: cli.ciph.name.clone(),
+            proof_version: cli.ciph.proof_version,
+            context: cli.ciph.context.clone(),
+            fingerprint: fingerprint,
+            inputs: stored,
+            tag: None,
+        };
+        if let Some(k) = mac_key {
+            let mut mac = hmac(k);
+            mac.input(&state.body(true));
+            state.tag = Some(mac.result().code().to_vec());
+        }
+        state
+    }
+
+    /// Restores the client and its blinded inputs, with the ciphersuite, proof
+    /// version and context that the state was created with. The public key
+    /// must match the fingerprint of the key that the state was created with,
+    /// and the HMAC tag is checked if the state was created with a `mac_key`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pub_key`: the public key of the server
+    /// * `mac_key`: the local key used for integrity protection
+    pub fn restore<T,H>(&self, pub_key: Option<PublicKey<T>>, mac_key: Option<&[u8]>) -> Result<(Client<T,H>, Vec<Input<T>>), Error>
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        self.check_tag(mac_key)?;
+        let ciph = match Ciphersuite::<T,H>::from_name(&self.ciphersuite) {
+            Ok(c) => c.with_proof_version(self.proof_version).with_context(&self.context),
+            Err(_) => return Err(err_invalid_state()),
+        };
+        let pog = &ciph.pog;
+        let fingerprint = match &pub_key {
+            Some(pk) => pk.fingerprint(pog),
+            None => Vec::new(),
+        };
+        if fingerprint != self.fingerprint {
+            return Err(err_invalid_state());
+        }
+
+        let mut elems = Vec::new();
+        for [_, elem, blind] in &self.inputs {
+            let elem = (pog.deserialize)(elem)?;
+            if !(pog.is_valid)(&elem) || !(pog.is_valid_scalar)(blind) {
+                return Err(err_deserialization());
+            }
+            elems.push(elem);
+        }
+        let blinds: Vec<Vec<u8>> = self.inputs.iter().map(|[_, _, blind]| blind.clone()).collect();
+        let inv_blinds = pog.batch_invert_scalars(&blinds)?;
+        let inputs = self.inputs.iter().zip(elems).zip(inv_blinds).map(|(([data, _, blind], elem), inv_blind)| Input{
+            data: data.clone(),
+            elem: elem,
+            blind: blind.clone(),
+            inv_blind: inv_blind,
+        }).collect();
+        let cli = Client::setup(ciph, pub_key)?;
+        Ok((cli, inputs))
+    }
+
+    /// The name of the ciphersuite that the state was created with
+    pub fn ciphersuite(&self) -> &str {
+        &self.ciphersuite
+    }
+
+    /// The version of the DLEQ proofs of the ciphersuite
+    pub fn proof_version(&self) -> ProofVersion {
+        self.proof_version
+    }
+
+    /// The context that the DLEQ proofs of the ciphersuite are bound to
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    /// The fingerprint of the public key that the state was created with
+    /// (empty if the client had no public key)
+    pub fn fingerprint(&self) -> &[u8] {
+        &self.fingerprint
+    }
+
+    /// Returns true if the state is protected with an HMAC tag
+    pub fn is_tagged(&self) -> bool {
+        self.tag.is_some()
+    }
+
+    /// Returns the versioned encoding of the state
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.body(self.tag.is_some());
+        if let Some(tag) = &self.tag {
+            out.extend_from_slice(tag);
+        }
+        out
+    }
+
+    /// Parses a state from its versioned encoding
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        match parse(buf) {
+            Some(state) => Ok(state),
+            None => Err(err_deserialization()),
+        }
+    }
+
+    // the encoding of the state without the tag
+    fn body(&self, tagged: bool) -> Vec<u8> {
+        let mut out = vec![BLIND_STATE_VERSION];
+        out.push(if tagged { FLAG_TAGGED } else { 0 });
+        write_bytes(&mut out, self.ciphersuite.as_bytes());
+        out.push(self.proof_version.id());
+        write_bytes(&mut out, &self.context);
+        write_bytes(&mut out, &self.fingerprint);
+        out.write_u32::<BigEndian>(self.inputs.len() as u32).unwrap();
+        for inp in &self.inputs {
+            for x in inp {
+                write_bytes(&mut out, x);
+            }
+        }
+        out
+    }
+
+    // checks the tag of the state, which must be present if and only if a key
+    // is provided
+    fn check_tag(&self, mac_key: Option<&[u8]>) -> Result<(), Error> {
+        match (mac_key, &self.tag) {
+            (Some(k), Some(tag)) => {
+                let mut mac = hmac(k);
+                mac.input(&self.body(true));
+                match mac.verify(tag) {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(err_state_integrity()),
+                }
+            },
+            (None, None) => Ok(()),
+            _ => Err(err_state_integrity()),
+        }
+    }
+}
+
+fn hmac(key: &[u8]) -> Hmac<Sha512> {
+    // HMAC accepts keys of any length
+    Hmac::<Sha512>::new_varkey(key).unwrap()
+}
+
+fn write_bytes(out: &mut Vec<u8>, x: &[u8]) {
+    out.write_u32::<BigEndian>(x.len() as u32).unwrap();
+    out.extend_from_slice(x);
+}
+
+fn read_bytes(cur: &mut Cursor<&[u8]>) -> Option<Vec<u8>> {
+    let len = cur.read_u32::<BigEndian>().ok()? as usize;
+    let remaining = cur.get_ref().len() - cur.position() as usize;
+    if len > remaining {
+        return None;
+    }
+    let mut out = vec![0; len];
+    cur.read_exact(&mut out).ok()?;
+    Some(out)
+}
+
+fn parse(buf: &[u8]) -> Option<BlindState> {
+    let mut cur = Cursor::new(buf);
+    if cur.read_u8().ok()? != BLIND_STATE_VERSION {
+        return None;
+    }
+    let tagged = match cur.read_u8().ok()? {
+        0 => false,
+        FLAG_TAGGED => true,
+        _ => return None,
+    };
+    let ciphersuite = String::from_utf8(read_bytes(&mut cur)?).ok()?;
+    let proof_version = ProofVersion::from_id(cur.read_u8().ok()?).ok()?;
+    let context = read_bytes(&mut cur)?;
+    let fingerprint = read_bytes(&mut cur)?;
+    let count = cur.read_u32::<BigEndian>().ok()?;
+    let mut inputs = Vec::new();
+    for _ in 0..count {
+        inputs.push([read_bytes(&mut cur)?, read_bytes(&mut cur)?, read_bytes(&mut cur)?]);
+    }
+    let rest = &buf[cur.position() as usize..];
+    let tag = match tagged {
+        true if rest.len() == TAG_LENGTH => Some(rest.to_vec()),
+        false if rest.is_empty() => None,
+        _ => return None,
+    };
+    Some(BlindState{
+        ciphersuite: ciphersuite,
+        proof_version: proof_version,
+        context: context,
+        fingerprint: fingerprint,
+        inputs: inputs,
+        tag: tag,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlindState,BLIND_STATE_VERSION};
+    use crate::oprf::{Server,Client,Input};
+    use crate::oprf::ciphersuite::{Ciphersuite,ProofVersion};
+    use crate::oprf::groups::PrimeOrderGroup;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+
+    const MAC_KEY: &[u8] = b"local state key";
+
+    fn setup(verifiable: bool) -> (Server<RistrettoPoint,Sha512>, Client<RistrettoPoint,Sha512>, Vec<Input<RistrettoPoint>>) {
+        let pog = PrimeOrderGroup::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), verifiable);
+        let srv = Server::setup(ciph.clone());
+        let pk = match verifiable {
+            true => Some(srv.key.pub_key(&pog)),
+            false => None,
+        };
+        let cli = Client::setup(ciph, pk).unwrap();
+        let inputs = cli.blind(&[vec![1; 32], vec![2; 32]]);
+        (srv, cli, inputs)
+    }
+
+    fn check_restored(srv: &Server<RistrettoPoint,Sha512>, cli: &Client<RistrettoPoint,Sha512>,
+            inputs: &[Input<RistrettoPoint>], restored: (Client<RistrettoPoint,Sha512>, Vec<Input<RistrettoPoint>>)) {
+        let (res_cli, res_inputs) = restored;
+        assert_eq!(res_cli.ciph.name, cli.ciph.name);
+        let eval = srv.eval(&res_inputs.iter().map(|i| i.elem).collect::<Vec<_>>()).unwrap();
+        let outs = res_cli.unblind(&res_inputs, &eval).unwrap();
+        let expected = cli.unblind(inputs, &eval).unwrap();
+        assert_eq!(outs, expected);
+        for i in 0..inputs.len() {
+            assert_eq!(res_inputs[i].data, inputs[i].data);
+            assert_eq!(res_cli.finalize(&res_inputs[i].data, &outs[i], b"aux").unwrap(),
+                cli.finalize(&inputs[i].data, &expected[i], b"aux").unwrap());
+        }
+    }
+
+    #[test]
+    fn restore_voprf() {
+        let (srv, cli, inputs) = setup(true);
+        let stored = BlindState::new(&cli, &inputs, Some(MAC_KEY)).to_bytes();
+        assert_eq!(stored[0], BLIND_STATE_VERSION);
+        let state = BlindState::from_bytes(&stored).unwrap();
+        assert!(state.is_tagged());
+        assert_eq!(state.ciphersuite(), cli.ciph.name);
+        let restored = state.restore(cli.key.clone(), Some(MAC_KEY)).unwrap();
+        check_restored(&srv, &cli, &inputs, restored);
+    }
+
+    #[test]
+    fn restore_oprf_untagged() {
+        let (srv, cli, inputs) = setup(false);
+        let state = BlindState::from_bytes(&BlindState::new(&cli, &inputs, None).to_bytes()).unwrap();
+        assert!(!state.is_tagged());
+        assert!(state.fingerprint().is_empty());
+        let restored = state.restore(None, None).unwrap();
+        check_restored(&srv, &cli, &inputs, restored);
+    }
+
+    #[test]
+    fn restore_context_and_proof_version() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true)
+            .with_proof_version(ProofVersion::V1)
+            .with_context(b"deployment");
+        let srv = Server::setup(ciph.clone());
+        let pk = srv.key.pub_key(&pog);
+        let cli = Client::setup(ciph, Some(pk.clone())).unwrap();
+        let inputs = cli.blind(&[vec![1; 32], vec![2; 32]]);
+        let state = BlindState::from_bytes(&BlindState::new(&cli, &inputs, Some(MAC_KEY)).to_bytes()).unwrap();
+        assert_eq!(state.proof_version(), ProofVersion::V1);
+        assert_eq!(state.context(), b"deployment");
+        let restored = state.restore::<RistrettoPoint,Sha512>(Some(pk), Some(MAC_KEY)).unwrap();
+        assert_eq!(restored.0.ciph.proof_version, ProofVersion::V1);
+        assert_eq!(restored.0.ciph.context, b"deployment".to_vec());
+        // the proofs of the server are only accepted with the same context
+        check_restored(&srv, &cli, &inputs, restored);
+    }
+
+    #[test]
+    fn reject_tampered_state() {
+        let (_, cli, inputs) = setup(true);
+        let stored = BlindState::new(&cli, &inputs, Some(MAC_KEY)).to_bytes();
+        // flip a bit in the last blind
+        let mut tampered = stored.clone();
+        let idx = tampered.len() - 65;
+        tampered[idx] ^= 1;
+        let state = BlindState::from_bytes(&tampered).unwrap();
+        assert!(state.restore::<RistrettoPoint,Sha512>(cli.key.clone(), Some(MAC_KEY)).is_err());
+        // wrong key
+        let state = BlindState::from_bytes(&stored).unwrap();
+        assert!(state.restore::<RistrettoPoint,Sha512>(cli.key.clone(), Some(b"other key")).is_err());
+        // missing key, or stripped tag
+        assert!(state.restore::<RistrettoPoint,Sha512>(cli.key.clone(), None).is_err());
+        let mut stripped = stored[..stored.len()-64].to_vec();
+        stripped[1] = 0;
+        let state = BlindState::from_bytes(&stripped).unwrap();
+        assert!(state.restore::<RistrettoPoint,Sha512>(cli.key.clone(), Some(MAC_KEY)).is_err());
+    }
+
+    #[test]
+    fn reject_mismatched_key() {
+        let (_, cli, inputs) = setup(true);
+        let state = BlindState::new(&cli, &inputs, None);
+        let other = Server::setup(cli.ciph.clone());
+        let other_pk = other.key.pub_key(&cli.ciph.pog);
+        assert!(state.restore::<RistrettoPoint,Sha512>(Some(other_pk), None).is_err());
+        assert!(state.restore::<RistrettoPoint,Sha512>(None, None).is_err());
+    }
+
+    #[test]
+    fn reject_malformed_state() {
+        let (_, cli, inputs) = setup(true);
+        let stored = BlindState::new(&cli, &inputs, Some(MAC_KEY)).to_bytes();
+        let mut wrong_version = stored.clone();
+        wrong_version[0] = BLIND_STATE_VERSION + 1;
+        assert!(BlindState::from_bytes(&wrong_version).is_err());
+        assert!(BlindState::from_bytes(&stored[..stored.len()-1]).is_err());
+        let mut long = stored.clone();
+        long.push(0);
+        assert!(BlindState::from_bytes(&long).is_err());
+        assert!(BlindState::from_bytes(&[]).is_err());
+    }
+}
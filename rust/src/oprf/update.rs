@@ -0,0 +1,252 @@
+//! The update module provides the key-updatable mode of the (V)OPRF. When the
+//! server rotates its secret key from `k` to `k'`, it publishes an
+//! `UpdateToken` holding the scalar `Δ = k'/k`. Holders of unblinded group
+//! elements `k*H(x)` (the output of `Client::unblind`) can then move them to
+//! the new key by computing `Δ*(k*H(x)) = k'*H(x)`, without talking to the
+//! server again. Note that finalized (V)OPRF outputs cannot be updated, so
+//! updatable applications must store the unblinded elements instead.
+//!
+//! The token also holds the old and new public keys `Y` and `Y'`. Since `Δ`
+//! is public, the new public key is checked against the token directly, by
+//! checking that `Y' == Δ*Y`.
+//!
+//! The encoding of the token is `Δ || Y || Y'`.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,Client};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::oprf::update::UpdateToken;
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use sha2::Sha512;
+//!
+//! let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let mut srv = Server::setup(ciph.clone());
+//! let mut cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+//!
+//! // client stores the unblinded element for its input
+//! let inputs = cli.blind(&[b"some_input_data".to_vec()]);
+//! let eval = srv.eval(&[inputs[0].elem]).unwrap();
+//! let stored = cli.unblind(&inputs, &eval).unwrap();
+//!
+//! // server rotates its key, and publishes the update token
+//! let token = srv.rotate_key();
+//! let token = UpdateToken::from_bytes(&pog, &token.to_bytes(&pog)).unwrap();
+//!
+//! // client verifies the token, and updates its key and stored element
+//! cli.apply_update(&token).unwrap();
+//! let updated = token.update(&pog, &stored);
+//! let fresh = srv.eval(&[(pog.encode_to_group)(b"some_input_data")]).unwrap();
+//! assert_eq!(updated[0], fresh.elems[0]);
+//! ```
+use std::io::Error;
+
+use super::{Server,Client,SecretKey,PublicKey};
+use super::ciphersuite::Supported;
+use super::groups::PrimeOrderGroup;
+use crate::errors::{err_deserialization,err_public_key_not_found,err_update_token};
+
+/// The `UpdateToken` struct is published by the server when it rotates its
+/// secret key, and moves unblinded elements from the old key to the new key
+#[derive(Clone)]
+pub struct UpdateToken<T> {
+    delta: Vec<u8>,
+    old_pub_key: T,
+    new_pub_key: T,
+}
+
+impl<T> UpdateToken<T>
+        where T: Clone {
+    /// The scalar `Δ = k'/k`
+    pub fn delta(&self) -> &[u8] {
+        &self.delta
+    }
+
+    /// The public key of the old secret key
+    pub fn old_pub_key(&self) -> PublicKey<T> {
+        PublicKey(self.old_pub_key.clone())
+    }
+
+    /// The public key of the new secret key
+    pub fn new_pub_key(&self) -> PublicKey<T> {
+        PublicKey(self.new_pub_key.clone())
+    }
+
+    /// Returns `true` if `Δ` is a valid scalar, and the new public key is the
+    /// old public key multiplied by `Δ`
+    pub fn verify<H>(&self, pog: &PrimeOrderGroup<T,H>) -> bool {
+        (pog.is_valid_scalar)(&self.delta)
+            && (pog.is_equal)(&(pog.scalar_mult)(&self.old_pub_key, &self.delta), &self.new_pub_key)
+    }
+
+    /// Moves the unblinded `elems` (see `Client::unblind`) that were evaluated
+    /// with the old key to the new key
+    pub fn update<H>(&self, pog: &PrimeOrderGroup<T,H>, elems: &[T]) -> Vec<T> {
+        elems.iter().map(|e| (pog.scalar_mult)(e, &self.delta)).collect()
+    }
+
+    /// Returns the encoding of the token
+    pub fn to_bytes<H>(&self, pog: &PrimeOrderGroup<T,H>) -> Vec<u8> {
+        let mut out = self.delta.clone();
+        for elem in &[&self.old_pub_key, &self.new_pub_key] {
+            let mut buf = Vec::new();
+            (pog.serialize)(elem, true, &mut buf);
+            out.extend_from_slice(&buf);
+        }
+        out
+    }
+
+    /// Parses a token for the group `pog` from its encoding. The token is not
+    /// verified.
+    pub fn from_bytes<H>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<Self, Error> {
+        let mut ser = Vec::new();
+        (pog.serialize)(&pog.generator, true, &mut ser);
+        let ne = ser.len();
+        let ns = pog.byte_length;
+        if buf.len() != ns + 2*ne {
+            return Err(err_deserialization());
+        }
+        let mut elems = Vec::new();
+        for i in 0..2 {
+            let elem = (pog.deserialize)(&buf[ns+i*ne..ns+(i+1)*ne])?;
+            if !(pog.is_valid)(&elem) {
+                return Err(err_deserialization());
+            }
+            elems.push(elem);
+        }
+        let new_pub_key = elems.pop().unwrap();
+        let old_pub_key = elems.pop().unwrap();
+        Ok(UpdateToken{
+            delta: buf[..ns].to_vec(),
+            old_pub_key: old_pub_key,
+            new_pub_key: new_pub_key,
+        })
+    }
+}
+
+impl<T,H> Server<T,H>
+        where T: Clone, H: Clone {
+    /// Returns the token that updates elements from the current key of the
+    /// server to `new_key`
+    pub fn update_token(&self, new_key: &SecretKey) -> UpdateToken<T> {
+        let pog = &self.ciph.pog;
+        let delta = (pog.mul_scalars)(&new_key.0, &(pog.invert_scalar)(&self.key.0));
+        UpdateToken{
+            delta: delta,
+            old_pub_key: self.key.pub_key(pog).0,
+            new_pub_key: new_key.pub_key(pog).0,
+        }
+    }
+
+    /// Rotates the secret key of the server to a new random key, and returns
+    /// the update token for the rotation
+    pub fn rotate_key(&mut self) -> UpdateToken<T> {
+        let new_key = SecretKey::new(&self.ciph.pog);
+        let token = self.update_token(&new_key);
+        self.key = new_key;
+        token
+    }
+}
+
+impl<T,H> Client<T,H>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    /// Verifies `token` against the public key of the client, and replaces the
+    /// public key with the new public key of the token. Returns an error if
+    /// the client has no public key, or the token is invalid or does not
+    /// start from the public key of the client.
+    pub fn apply_update(&mut self, token: &UpdateToken<T>) -> Result<(), Error> {
+        let pog = &self.ciph.pog;
+        let current = match &self.key {
+            Some(pk) => pk,
+            None => return Err(err_public_key_not_found()),
+        };
+        if !(pog.is_equal)(&current.0, &token.old_pub_key) || !token.verify(pog) {
+            return Err(err_update_token());
+        }
+        self.key = Some(token.new_pub_key());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::oprf::groups::p384::NistPoint;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+
+    fn update_elements<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let mut srv = Server::setup(ciph.clone());
+        let mut cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+        let data = vec![b"input_0".to_vec(), b"input_1".to_vec()];
+        let inputs = cli.blind(&data);
+        let elems: Vec<T> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let stored = cli.unblind(&inputs, &srv.eval(&elems).unwrap()).unwrap();
+
+        let token = srv.rotate_key();
+        assert!(token.verify(&pog));
+        cli.apply_update(&token).unwrap();
+        let updated = token.update(&pog, &stored);
+
+        // the updated elements match fresh evaluations under the new key
+        let inputs = cli.blind(&data);
+        let elems: Vec<T> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let fresh = cli.unblind(&inputs, &srv.eval(&elems).unwrap()).unwrap();
+        for i in 0..data.len() {
+            assert!((pog.is_equal)(&updated[i], &fresh[i]));
+            assert_eq!(cli.finalize(&data[i], &updated[i], b"aux").unwrap(),
+                cli.finalize(&data[i], &fresh[i], b"aux").unwrap());
+        }
+
+        // the token cannot be applied twice
+        assert!(cli.apply_update(&token).is_err());
+    }
+
+    #[test]
+    fn update_elements_ristretto() {
+        update_elements(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn update_elements_p384() {
+        update_elements(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    #[test]
+    fn reject_invalid_tokens() {
+        let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let mut srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph.clone(), Some(srv.key.pub_key(&pog))).unwrap();
+        let token = srv.rotate_key();
+        let buf = token.to_bytes(&pog);
+        assert!(UpdateToken::from_bytes(&pog, &buf).unwrap().verify(&pog));
+        assert!(UpdateToken::from_bytes(&pog, &buf[1..]).is_err());
+
+        // a new public key that does not match the token is rejected
+        let mut bad = token.clone();
+        bad.new_pub_key = (pog.random_element)();
+        assert!(!bad.verify(&pog));
+        assert!(cli.clone().apply_update(&bad).is_err());
+        // a token whose scalar does not move the old public key to the new
+        // public key is rejected
+        let mut bad = token.clone();
+        bad.delta = (pog.mul_scalars)(&token.delta, &token.delta);
+        assert!(!bad.verify(&pog));
+
+        // a token for another key is rejected
+        let other = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, Some(other.key.pub_key(&pog))).unwrap();
+        assert!(cli.clone().apply_update(&token).is_err());
+    }
+}
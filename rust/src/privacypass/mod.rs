@@ -0,0 +1,686 @@
+//! The privacypass module implements the token layer of [Privacy
+//! Pass](https://www.rfc-editor.org/rfc/rfc9578) for VOPRF tokens, on top of
+//! `oprf::Client` and `oprf::Server`.
+//!
+//! Tokens are issued under the token type `TOKEN_TYPE_VOPRF` (`0x0001`) of
+//! RFC 9578, which is defined for the VOPRF(P-384, SHA-384) ciphersuite of
+//! RFC 9497, i.e. `VOPRF-P384-SHA384` over `PrimeOrderGroup::p384_sha384`
+//! (see `TOKEN_CIPHERSUITE`). The token authenticator is the output of
+//! `Client::finalize` for that ciphersuite, so the tokens are interoperable
+//! with other implementations of RFC 9578. Issuers and clients cannot be
+//! created for the other ciphersuites of this crate, which follow
+//! draft-irtf-cfrg-voprf-02.
+//!
+//! The protocol runs as follows:
+//!
+//! 1. The origin sends a `TokenChallenge` to the client.
+//! 2. The client constructs a `TokenRequest` for the challenge with
+//!    `TokenClient::request`, and sends it to the issuer.
+//! 3. The issuer evaluates the VOPRF with `Issuer::issue`, and returns a
+//!    `TokenResponse`.
+//! 4. The client verifies the response and finalizes the `Token` with
+//!    `TokenClient::finalize`.
+//! 5. The token is redeemed with the issuer (or an origin that holds the
+//!    issuer key), which recomputes the PRF with `Issuer::verify`. With
+//!    `Issuer::redeem`, the token is also recorded in a `SpendStore` (see the
+//!    `store` module), so that it is accepted at most once.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::Server;
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::privacypass::{Issuer,TokenClient,TokenChallenge,TokenRequest,TokenResponse,Token};
+//! use voprf_rs::privacypass::store::MemoryStore;
+//!
+//! let pog = PrimeOrderGroup::p384_sha384();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let issuer = Issuer::new(Server::setup(ciph.clone())).unwrap();
+//! let cli = TokenClient::new(ciph, issuer.pub_key()).unwrap();
+//!
+//! // client requests a token for the challenge of the origin
+//! let challenge = TokenChallenge::new("issuer.example", &[], &["origin.example"]).unwrap();
+//! let (req, state) = cli.request(&challenge);
+//!
+//! // issuer evaluates the request
+//! let req = TokenRequest::from_bytes(&pog, &req.to_bytes()).unwrap();
+//! let resp = issuer.issue(&req).unwrap();
+//!
+//! // client finalizes the token, which is then redeemed
+//! let resp = TokenResponse::from_bytes(&pog, &resp.to_bytes()).unwrap();
+//! let token = cli.finalize(&state, &resp).unwrap();
+//! let token = Token::from_bytes(&token.to_bytes()).unwrap();
+//! assert!(issuer.verify(&token));
+//!
+//! // a token can only be redeemed once
+//! let store = MemoryStore::new();
+//! assert!(issuer.redeem(&token, &store).unwrap());
+//! assert!(!issuer.redeem(&token, &store).unwrap());
+//! ```
+pub mod store;
+
+use std::io::{Cursor,Error,Read};
+
+use byteorder::{BigEndian,ReadBytesExt,WriteBytesExt};
+use sha2::{Sha256,Digest};
+
+use crate::oprf::{Server,Client,Input,Evaluation,PublicKey};
+use crate::oprf::ciphersuite::{Ciphersuite,Supported,Spec};
+use crate::oprf::groups::PrimeOrderGroup;
+use crate::oprf::proof::Proof;
+use crate::errors::{err_deserialization,err_unsupported_ciphersuite,err_unsupported_token_type,err_token_key_id,err_token_verification};
+use store::SpendStore;
+use crate::utils::rand_bytes;
+
+/// The token type of RFC 9578 for VOPRF(P-384, SHA-384) tokens
+pub const TOKEN_TYPE_VOPRF: u16 = 0x0001;
+/// The name of the ciphersuite that tokens of type `TOKEN_TYPE_VOPRF` are
+/// issued with
+pub const TOKEN_CIPHERSUITE: &str = "VOPRF-P384-SHA384";
+/// The length of the token nonce
+pub const NONCE_LENGTH: usize = 32;
+/// The content type of HTTP requests that carry a `TokenRequest`
+pub const REQUEST_CONTENT_TYPE: &str = "application/private-token-request";
+/// The content type of HTTP responses that carry a `TokenResponse`
+pub const RESPONSE_CONTENT_TYPE: &str = "application/private-token-response";
+
+// length of SHA-256 digests (challenge digest and token key ID)
+const DIGEST_LENGTH: usize = 32;
+// length of the token authenticator (Nk), i.e. the output of SHA-384
+const AUTHENTICATOR_LENGTH: usize = 48;
+
+/// The `TokenChallenge` struct is sent by an origin to request a token
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenChallenge {
+    /// The type of token that is requested
+    pub token_type: u16,
+    /// The name of the issuer that the token is requested from
+    pub issuer_name: Vec<u8>,
+    /// Either empty, or 32 bytes that bind the token to a redemption context
+    pub redemption_context: Vec<u8>,
+    /// A comma-separated list of the origins that the token can be redeemed
+    /// with (empty if it can be redeemed with any origin)
+    pub origin_info: Vec<u8>,
+}
+
+impl TokenChallenge {
+    /// Constructs a challenge for a VOPRF token, returns an error if the
+    /// issuer name is empty, or the redemption context is not empty or 32
+    /// bytes long.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer_name`: the name of the issuer
+    /// * `redemption_context`: the (possibly empty) redemption context
+    /// * `origins`: the origins that the token can be redeemed with
+    pub fn new(issuer_name: &str, redemption_context: &[u8], origins: &[&str]) -> Result<Self, Error> {
+        let challenge = TokenChallenge{
+            token_type: TOKEN_TYPE_VOPRF,
+            issuer_name: issuer_name.as_bytes().to_vec(),
+            redemption_context: redemption_context.to_vec(),
+            origin_info: origins.join(",").into_bytes(),
+        };
+        match challenge.is_valid() {
+            true => Ok(challenge),
+            false => Err(err_deserialization()),
+        }
+    }
+
+    /// Returns the encoding of the challenge
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.write_u16::<BigEndian>(self.token_type).unwrap();
+        out.write_u16::<BigEndian>(self.issuer_name.len() as u16).unwrap();
+        out.extend_from_slice(&self.issuer_name);
+        out.push(self.redemption_context.len() as u8);
+        out.extend_from_slice(&self.redemption_context);
+        out.write_u16::<BigEndian>(self.origin_info.len() as u16).unwrap();
+        out.extend_from_slice(&self.origin_info);
+        out
+    }
+
+    /// Parses a challenge from its encoding
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut cur = Cursor::new(buf);
+        let parsed = (|| {
+            let token_type = cur.read_u16::<BigEndian>().ok()?;
+            let issuer_len = cur.read_u16::<BigEndian>().ok()? as usize;
+            let issuer_name = read_exact(&mut cur, issuer_len)?;
+            let context_len = cur.read_u8().ok()? as usize;
+            let redemption_context = read_exact(&mut cur, context_len)?;
+            let origin_len = cur.read_u16::<BigEndian>().ok()? as usize;
+            let origin_info = read_exact(&mut cur, origin_len)?;
+            Some(TokenChallenge{
+                token_type: token_type,
+                issuer_name: issuer_name,
+                redemption_context: redemption_context,
+                origin_info: origin_info,
+            })
+        })();
+        match parsed {
+            Some(c) if c.is_valid() && cur.position() as usize == buf.len() => Ok(c),
+            _ => Err(err_deserialization()),
+        }
+    }
+
+    /// Returns the SHA-256 digest of the encoded challenge
+    pub fn digest(&self) -> Vec<u8> {
+        Sha256::digest(&self.to_bytes()).to_vec()
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.issuer_name.is_empty()
+            && self.issuer_name.len() <= u16::max_value() as usize
+            && (self.redemption_context.is_empty() || self.redemption_context.len() == 32)
+            && self.origin_info.len() <= u16::max_value() as usize
+    }
+}
+
+/// The `TokenRequest` struct is sent by the client to the issuer, and holds
+/// a blinded token input
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenRequest {
+    /// The type of token that is requested
+    pub token_type: u16,
+    /// The last byte of the ID of the issuer key
+    pub truncated_token_key_id: u8,
+    /// The (serialized) blinded group element
+    pub blinded_msg: Vec<u8>,
+}
+
+impl TokenRequest {
+    /// Returns the encoding of the request
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.write_u16::<BigEndian>(self.token_type).unwrap();
+        out.push(self.truncated_token_key_id);
+        out.extend_from_slice(&self.blinded_msg);
+        out
+    }
+
+    /// Parses a request for the group `pog` from its encoding
+    pub fn from_bytes<T,H>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() != 3 + element_length(pog) {
+            return Err(err_deserialization());
+        }
+        let token_type = (&buf[..2]).read_u16::<BigEndian>()?;
+        if token_type != TOKEN_TYPE_VOPRF {
+            return Err(err_unsupported_token_type());
+        }
+        Ok(TokenRequest{
+            token_type: token_type,
+            truncated_token_key_id: buf[2],
+            blinded_msg: buf[3..].to_vec(),
+        })
+    }
+}
+
+/// The `TokenResponse` struct is returned by the issuer, and holds the
+/// evaluated element and DLEQ proof
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenResponse {
+    /// The (serialized) evaluated group element
+    pub evaluate_msg: Vec<u8>,
+    /// The canonical encoding of the DLEQ proof (see `Proof::to_bytes`)
+    pub evaluate_proof: Vec<u8>,
+}
+
+impl TokenResponse {
+    /// Returns the encoding of the response
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.evaluate_msg.clone();
+        out.extend_from_slice(&self.evaluate_proof);
+        out
+    }
+
+    /// Parses a response for the group `pog` from its encoding
+    pub fn from_bytes<T,H>(pog: &PrimeOrderGroup<T,H>, buf: &[u8]) -> Result<Self, Error> {
+        // the proof is made up of two scalars
+        let ne = element_length(pog);
+        if buf.len() != ne + 2*pog.byte_length {
+            return Err(err_deserialization());
+        }
+        Ok(TokenResponse{
+            evaluate_msg: buf[..ne].to_vec(),
+            evaluate_proof: buf[ne..].to_vec(),
+        })
+    }
+}
+
+/// The `Token` struct is the finalized token that is redeemed by the client
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    /// The type of the token
+    pub token_type: u16,
+    /// The random nonce chosen by the client
+    pub nonce: Vec<u8>,
+    /// The SHA-256 digest of the challenge that the token was issued for
+    pub challenge_digest: Vec<u8>,
+    /// The ID of the issuer key
+    pub token_key_id: Vec<u8>,
+    /// The PRF output over the token input
+    pub authenticator: Vec<u8>,
+}
+
+impl Token {
+    /// Returns the encoding of the token
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.token_input();
+        out.extend_from_slice(&self.authenticator);
+        out
+    }
+
+    /// Parses a token from its encoding
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() != 2 + NONCE_LENGTH + 2*DIGEST_LENGTH + AUTHENTICATOR_LENGTH {
+            return Err(err_deserialization());
+        }
+        let token_type = (&buf[..2]).read_u16::<BigEndian>()?;
+        if token_type != TOKEN_TYPE_VOPRF {
+            return Err(err_unsupported_token_type());
+        }
+        let (nonce, rest) = buf[2..].split_at(NONCE_LENGTH);
+        let (challenge_digest, rest) = rest.split_at(DIGEST_LENGTH);
+        let (token_key_id, authenticator) = rest.split_at(DIGEST_LENGTH);
+        Ok(Token{
+            token_type: token_type,
+            nonce: nonce.to_vec(),
+            challenge_digest: challenge_digest.to_vec(),
+            token_key_id: token_key_id.to_vec(),
+            authenticator: authenticator.to_vec(),
+        })
+    }
+
+    /// Returns the input that the PRF is evaluated on:
+    /// `token_type || nonce || challenge_digest || token_key_id`
+    pub fn token_input(&self) -> Vec<u8> {
+        token_input(&self.nonce, &self.challenge_digest, &self.token_key_id)
+    }
+}
+
+/// Returns the ID of the issuer public key, i.e. the SHA-256 digest of the
+/// (compressed) key
+pub fn token_key_id<T,H>(pog: &PrimeOrderGroup<T,H>, pub_key: &PublicKey<T>) -> Vec<u8> {
+    pub_key.fingerprint(pog)
+}
+
+/// Returns whether tokens of type `TOKEN_TYPE_VOPRF` can be issued with the
+/// ciphersuite `ciph`
+pub fn is_token_ciphersuite<T,H>(ciph: &Ciphersuite<T,H>) -> bool
+        where PrimeOrderGroup<T,H>: Clone {
+    ciph.spec == Spec::Rfc9497 && ciph.name == TOKEN_CIPHERSUITE
+}
+
+/// The `Issuer` struct issues tokens with the key of a verifiable `Server`,
+/// and verifies the tokens that are redeemed.
+pub struct Issuer<T,H>
+        where T: Clone, H: Clone {
+    srv: Server<T,H>,
+    key_id: Vec<u8>,
+}
+
+impl<T,H> Issuer<T,H>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    /// Creates an issuer from a server, returns an error if the ciphersuite
+    /// of the server is not `TOKEN_CIPHERSUITE`
+    pub fn new(srv: Server<T,H>) -> Result<Self, Error> {
+        if !is_token_ciphersuite(&srv.ciph) {
+            return Err(err_unsupported_ciphersuite());
+        }
+        let pog = &srv.ciph.pog;
+        let key_id = token_key_id(pog, &srv.key.pub_key(pog));
+        Ok(Issuer{
+            srv: srv,
+            key_id: key_id,
+        })
+    }
+
+    /// The public key that tokens are issued under
+    pub fn pub_key(&self) -> PublicKey<T> {
+        self.srv.key.pub_key(&self.srv.ciph.pog)
+    }
+
+    /// The ID of the public key that tokens are issued under
+    pub fn token_key_id(&self) -> &[u8] {
+        &self.key_id
+    }
+
+    /// Evaluates the VOPRF on the blinded element in a token request
+    pub fn issue(&self, req: &TokenRequest) -> Result<TokenResponse, Error> {
+        issue(&self.srv, &self.key_id, req)
+    }
+
+    /// Verifies a redeemed token by recomputing the PRF over the token input
+    /// with the secret key. The authenticator is compared in constant time.
+    pub fn verify(&self, token: &Token) -> bool {
+        verify(&self.srv, &self.key_id, token)
+    }
+
+    /// Verifies a redeemed token and marks it as spent in `store`. Returns
+    /// `false` if the token has already been spent, and an error if it is
+    /// invalid.
+    pub fn redeem(&self, token: &Token, store: &dyn SpendStore) -> Result<bool, Error> {
+        redeem(&self.srv, &self.key_id, token, store)
+    }
+}
+
+/// The `TokenState` struct holds the client state for a token request, that
+/// is required for finalizing the token
+#[derive(Clone)]
+pub struct TokenState<T> {
+    nonce: Vec<u8>,
+    challenge_digest: Vec<u8>,
+    input: Input<T>,
+}
+
+/// The `TokenClient` struct requests and finalizes tokens for the public key
+/// of an issuer
+pub struct TokenClient<T,H>
+        where T: Clone, H: Clone {
+    cli: Client<T,H>,
+    key_id: Vec<u8>,
+}
+
+impl<T,H> TokenClient<T,H>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    /// Creates a client for the issuer public key `pub_key`, returns an error
+    /// if the ciphersuite is not `TOKEN_CIPHERSUITE`
+    pub fn new(ciph: Ciphersuite<T,H>, pub_key: PublicKey<T>) -> Result<Self, Error> {
+        if !is_token_ciphersuite(&ciph) {
+            return Err(err_unsupported_ciphersuite());
+        }
+        let key_id = token_key_id(&ciph.pog, &pub_key);
+        Ok(TokenClient{
+            cli: Client::setup(ciph, Some(pub_key))?,
+            key_id: key_id,
+        })
+    }
+
+    /// The ciphersuite used by the client
+    pub fn ciph(&self) -> &Ciphersuite<T,H> {
+        &self.cli.ciph
+    }
+
+    /// Constructs a token request for `challenge`, along with the state that
+    /// is required for finalizing the token
+    pub fn request(&self, challenge: &TokenChallenge) -> (TokenRequest, TokenState<T>) {
+        let mut nonce = Vec::new();
+        rand_bytes(NONCE_LENGTH, &mut nonce);
+        let challenge_digest = challenge.digest();
+        let x = token_input(&nonce, &challenge_digest, &self.key_id);
+        let input = self.cli.blind(&[x]).remove(0);
+        let mut blinded_msg = Vec::new();
+        (self.cli.ciph.pog.serialize)(&input.elem, true, &mut blinded_msg);
+        let req = TokenRequest{
+            token_type: TOKEN_TYPE_VOPRF,
+            truncated_token_key_id: self.key_id[DIGEST_LENGTH-1],
+            blinded_msg: blinded_msg,
+        };
+        let state = TokenState{
+            nonce: nonce,
+            challenge_digest: challenge_digest,
+            input: input,
+        };
+        (req, state)
+    }
+
+    /// Verifies the DLEQ proof in the issuer response, and finalizes the
+    /// token
+    pub fn finalize(&self, state: &TokenState<T>, resp: &TokenResponse) -> Result<Token, Error> {
+        let pog = &self.cli.ciph.pog;
+        let elem = (pog.deserialize)(&resp.evaluate_msg)?;
+        let eval = Evaluation{
+            elems: vec![elem],
+            proof: Some(Proof::from_bytes(pog, &resp.evaluate_proof)?),
+        };
+        let inputs = [state.input.clone()];
+        let outs = self.cli.unblind(&inputs, &eval)?;
+        let authenticator = self.cli.finalize(&state.input.data, &outs[0], &[])?;
+        Ok(Token{
+            token_type: TOKEN_TYPE_VOPRF,
+            nonce: state.nonce.clone(),
+            challenge_digest: state.challenge_digest.clone(),
+            token_key_id: self.key_id.clone(),
+            authenticator: authenticator,
+        })
+    }
+}
+
+/// Evaluates the VOPRF on the blinded element in a token request for the
+/// server with the key ID `key_id`
+pub(crate) fn issue<T,H>(srv: &Server<T,H>, key_id: &[u8], req: &TokenRequest) -> Result<TokenResponse, Error>
+        where T: Clone, H: Clone {
+    if !is_token_ciphersuite(&srv.ciph) {
+        return Err(err_unsupported_ciphersuite());
+    }
+    if req.token_type != TOKEN_TYPE_VOPRF {
+        return Err(err_unsupported_token_type());
+    }
+    if req.truncated_token_key_id != key_id[DIGEST_LENGTH-1] {
+        return Err(err_token_key_id());
+    }
+    let pog = &srv.ciph.pog;
+    let elem = (pog.deserialize)(&req.blinded_msg)?;
+    if !(pog.is_valid)(&elem) {
+        return Err(err_deserialization());
+    }
+    let ev = srv.eval(&[elem])?;
+    let proof = match ev.proof {
+        Some(p) => p,
+        None => return Err(err_unsupported_ciphersuite()),
+    };
+    let mut evaluate_msg = Vec::new();
+    (pog.serialize)(&ev.elems[0], true, &mut evaluate_msg);
+    Ok(TokenResponse{
+        evaluate_msg: evaluate_msg,
+        evaluate_proof: proof.to_bytes(),
+    })
+}
+
+/// Verifies a redeemed token for the server with the key ID `key_id`
+pub(crate) fn verify<T,H>(srv: &Server<T,H>, key_id: &[u8], token: &Token) -> bool
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    if !is_token_ciphersuite(&srv.ciph) || token.token_type != TOKEN_TYPE_VOPRF || token.token_key_id != key_id {
+        return false;
+    }
+    srv.verify_output(&token.token_input(), &[], &token.authenticator)
+}
+
+/// Verifies a redeemed token for the server with the key ID `key_id`, and
+/// marks it as spent in `store`
+pub(crate) fn redeem<T,H>(srv: &Server<T,H>, key_id: &[u8], token: &Token, store: &dyn SpendStore) -> Result<bool, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    if !verify(srv, key_id, token) {
+        return Err(err_token_verification());
+    }
+    // the token is only recorded once it has been verified, and the store
+    // checks and records it atomically
+    store.spend(&token.to_bytes())
+}
+
+fn token_input(nonce: &[u8], challenge_digest: &[u8], key_id: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.write_u16::<BigEndian>(TOKEN_TYPE_VOPRF).unwrap();
+    out.extend_from_slice(nonce);
+    out.extend_from_slice(challenge_digest);
+    out.extend_from_slice(key_id);
+    out
+}
+
+// the length of a serialized (compressed) group element
+fn element_length<T,H>(pog: &PrimeOrderGroup<T,H>) -> usize {
+    let mut buf = Vec::new();
+    (pog.serialize)(&pog.generator, true, &mut buf);
+    buf.len()
+}
+
+fn read_exact(cur: &mut Cursor<&[u8]>, len: usize) -> Option<Vec<u8>> {
+    let mut out = vec![0; len];
+    cur.read_exact(&mut out).ok()?;
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::groups::p384_sha384::P384Point;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::{Sha384,Sha512};
+
+    fn token_ciphersuite() -> Ciphersuite<P384Point,Sha384> {
+        Ciphersuite::new(PrimeOrderGroup::p384_sha384(), true)
+    }
+
+    #[test]
+    fn issue_and_redeem() {
+        let ciph = token_ciphersuite();
+        let pog = ciph.pog.clone();
+        let issuer = Issuer::new(Server::setup(ciph.clone())).unwrap();
+        let cli = TokenClient::new(ciph.clone(), issuer.pub_key()).unwrap();
+        let challenge = TokenChallenge::new("issuer.example", &[7; 32], &["a.example", "b.example"]).unwrap();
+
+        let (req, state) = cli.request(&challenge);
+        let req_bytes = req.to_bytes();
+        assert_eq!(req_bytes.len(), 3 + 49);
+        assert_eq!(&req_bytes[..3], &[0x00, 0x01, issuer.token_key_id()[31]]);
+        let resp = issuer.issue(&TokenRequest::from_bytes(&pog, &req_bytes).unwrap()).unwrap();
+        let resp_bytes = resp.to_bytes();
+        assert_eq!(resp_bytes.len(), 49 + 2*48);
+        let token = cli.finalize(&state, &TokenResponse::from_bytes(&pog, &resp_bytes).unwrap()).unwrap();
+        assert_eq!(token.challenge_digest, challenge.digest());
+        assert_eq!(token.token_key_id, issuer.token_key_id());
+        let token_bytes = token.to_bytes();
+        assert_eq!(token_bytes.len(), 2 + 32 + 32 + 32 + 48);
+        let parsed = Token::from_bytes(&token_bytes).unwrap();
+        assert_eq!(parsed, token);
+        assert!(issuer.verify(&parsed));
+        assert!(Token::from_bytes(&token_bytes[..token_bytes.len()-1]).is_err());
+
+        // the authenticator is the RFC 9497 output over the token input
+        let srv = Server::setup(ciph.clone());
+        let key_id = token_key_id(&pog, &srv.key.pub_key(&pog));
+        let mut input = vec![0x00, 0x01];
+        input.extend_from_slice(&token.nonce);
+        input.extend_from_slice(&token.challenge_digest);
+        input.extend_from_slice(&key_id);
+        let mut expected = token.clone();
+        expected.token_key_id = key_id;
+        expected.authenticator = srv.full_evaluate(&input, &[]).unwrap();
+        assert_eq!(expected.token_input(), input);
+        assert!(Issuer::new(srv).unwrap().verify(&expected));
+
+        // modified tokens are rejected
+        let mut bad = token.clone();
+        bad.nonce[0] ^= 1;
+        assert!(!issuer.verify(&bad));
+        let mut bad = token.clone();
+        bad.authenticator[0] ^= 1;
+        assert!(!issuer.verify(&bad));
+        let mut bad = token.clone();
+        bad.authenticator.pop();
+        assert!(!issuer.verify(&bad));
+
+        // tokens of other issuers are rejected
+        let other = Issuer::new(Server::setup(ciph)).unwrap();
+        assert!(!other.verify(&token));
+    }
+
+    #[test]
+    fn token_ciphersuites() {
+        let ciph = token_ciphersuite();
+        assert_eq!(ciph.name, TOKEN_CIPHERSUITE);
+        assert!(is_token_ciphersuite(&ciph));
+        // Ne = 49, Ns = 48
+        assert_eq!(element_length(&ciph.pog), 49);
+        assert_eq!(ciph.pog.byte_length, 48);
+
+        // the OPRF mode and the draft-02 ciphersuites cannot be used for
+        // issuance
+        let oprf = Ciphersuite::new(PrimeOrderGroup::p384_sha384(), false);
+        assert!(Issuer::new(Server::setup(oprf.clone())).is_err());
+        let pub_key = Server::setup(ciph).key.pub_key(&oprf.pog);
+        assert!(TokenClient::new(oprf, pub_key).is_err());
+        let draft = Ciphersuite::new(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        assert!(!is_token_ciphersuite(&draft));
+        let srv = Server::setup(draft.clone());
+        let pub_key = srv.key.pub_key(&draft.pog);
+        assert!(Issuer::new(srv).is_err());
+        assert!(TokenClient::new(draft, pub_key).is_err());
+    }
+
+    #[test]
+    fn challenge_encoding() {
+        let challenge = TokenChallenge::new("issuer.example", &[], &[]).unwrap();
+        let mut expected = vec![0x00, 0x01, 0x00, 14];
+        expected.extend_from_slice(b"issuer.example");
+        expected.extend_from_slice(&[0x00, 0x00, 0x00]);
+        assert_eq!(challenge.to_bytes(), expected);
+        assert_eq!(TokenChallenge::from_bytes(&expected).unwrap(), challenge);
+        assert_eq!(challenge.digest().len(), 32);
+
+        assert!(TokenChallenge::new("", &[], &[]).is_err());
+        assert!(TokenChallenge::new("issuer.example", &[1; 16], &[]).is_err());
+        assert!(TokenChallenge::from_bytes(&expected[..expected.len()-1]).is_err());
+        let mut long = expected.clone();
+        long.push(0);
+        assert!(TokenChallenge::from_bytes(&long).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_requests() {
+        let ciph = token_ciphersuite();
+        let pog = ciph.pog.clone();
+        let issuer = Issuer::new(Server::setup(ciph.clone())).unwrap();
+        let cli = TokenClient::new(ciph.clone(), issuer.pub_key()).unwrap();
+        let challenge = TokenChallenge::new("issuer.example", &[], &[]).unwrap();
+        let (req, state) = cli.request(&challenge);
+        let buf = req.to_bytes();
+
+        assert!(TokenRequest::from_bytes(&pog, &buf[1..]).is_err());
+        let mut wrong_type = buf.clone();
+        wrong_type[1] = 0x02;
+        assert!(TokenRequest::from_bytes(&pog, &wrong_type).is_err());
+        let mut wrong_key = req.clone();
+        wrong_key.truncated_token_key_id ^= 1;
+        assert!(issuer.issue(&wrong_key).is_err());
+        let mut invalid_elem = req.clone();
+        invalid_elem.blinded_msg = vec![0xff; 49];
+        assert!(issuer.issue(&invalid_elem).is_err());
+
+        // responses are verified against the issuer key
+        let other = Issuer::new(Server::setup(ciph)).unwrap();
+        let mut other_req = req.clone();
+        other_req.truncated_token_key_id = other.token_key_id()[31];
+        let resp = other.issue(&other_req).unwrap();
+        assert!(cli.finalize(&state, &resp).is_err());
+        assert!(TokenResponse::from_bytes(&pog, &resp.to_bytes()[1..]).is_err());
+    }
+
+    #[test]
+    fn reject_double_spend() {
+        let ciph = token_ciphersuite();
+        let issuer = Issuer::new(Server::setup(ciph.clone())).unwrap();
+        let cli = TokenClient::new(ciph, issuer.pub_key()).unwrap();
+        let challenge = TokenChallenge::new("issuer.example", &[], &[]).unwrap();
+        let (req, state) = cli.request(&challenge);
+        let token = cli.finalize(&state, &issuer.issue(&req).unwrap()).unwrap();
+
+        let store = store::MemoryStore::new();
+        let mut forged = token.clone();
+        forged.authenticator[0] ^= 1;
+        assert!(issuer.redeem(&forged, &store).is_err());
+        assert!(issuer.redeem(&token, &store).unwrap());
+        assert!(!issuer.redeem(&token, &store).unwrap());
+        // invalid tokens are not recorded
+        assert!(!store.is_spent(&forged.to_bytes()).unwrap());
+    }
+}
@@ -0,0 +1,550 @@
+//! The store module provides the `SpendStore` trait, which records the tokens
+//! that have been redeemed so that a token is accepted at most once, along
+//! with in-memory (`MemoryStore`) and file-backed (`FileStore`) backends.
+//!
+//! Each key epoch (identified by the token key ID) has its own store, and the
+//! stores of all epochs are held by `EpochStores`. When keys rotate, the
+//! stores of the epochs that have expired are purged.
+//!
+//! The `FileStore` keeps the SHA-256 digests of spent tokens in a sorted index
+//! file (`spent.idx`), and appends newly spent digests to a log file
+//! (`spent.log`). The log is merged into the index once it grows beyond a
+//! fixed number of entries. If appending to the log fails, the partial entry
+//! is truncated, so that later entries are appended at an entry boundary. If
+//! the log cannot be truncated either, the store refuses to spend any further
+//! tokens.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::privacypass::store::{SpendStore,MemoryStore};
+//!
+//! let store = MemoryStore::new();
+//! assert!(store.spend(b"token").unwrap());
+//! assert!(!store.spend(b"token").unwrap());
+//! ```
+use std::collections::{HashMap,HashSet};
+use std::fs::{self,File,OpenOptions};
+use std::io::{Error,ErrorKind,Read,Write};
+use std::path::{Path,PathBuf};
+use std::sync::{Arc,Mutex};
+
+use sha2::{Sha256,Digest};
+
+use crate::errors::err_internal;
+
+const INDEX_FILE: &str = "spent.idx";
+const LOG_FILE: &str = "spent.log";
+const ENTRY_LENGTH: usize = 32;
+/// The number of log entries after which the log is merged into the index
+pub const COMPACTION_THRESHOLD: usize = 1024;
+
+/// The `SpendStore` trait is implemented by stores that record spent tokens.
+/// Spending is atomic: if several threads spend the same token concurrently,
+/// then exactly one of them succeeds.
+pub trait SpendStore: Send + Sync {
+    /// Marks `token` as spent. Returns `true` if it had not been spent
+    /// before, and `false` otherwise.
+    fn spend(&self, token: &[u8]) -> Result<bool, Error>;
+    /// Returns `true` if `token` has been spent
+    fn is_spent(&self, token: &[u8]) -> Result<bool, Error>;
+    /// Removes all records of spent tokens
+    fn purge(&self) -> Result<(), Error>;
+}
+
+/// The `MemoryStore` struct records spent tokens in memory
+pub struct MemoryStore {
+    spent: Mutex<HashSet<Vec<u8>>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        MemoryStore{
+            spent: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpendStore for MemoryStore {
+    fn spend(&self, token: &[u8]) -> Result<bool, Error> {
+        Ok(lock(&self.spent)?.insert(token.to_vec()))
+    }
+
+    fn is_spent(&self, token: &[u8]) -> Result<bool, Error> {
+        Ok(lock(&self.spent)?.contains(token))
+    }
+
+    fn purge(&self) -> Result<(), Error> {
+        lock(&self.spent)?.clear();
+        Ok(())
+    }
+}
+
+/// The `FileStore` struct records spent tokens in a directory, as an
+/// append-only log with a compacting index
+pub struct FileStore {
+    dir: PathBuf,
+    state: Mutex<FileState>,
+}
+
+struct FileState<L = File> {
+    spent: HashSet<[u8; ENTRY_LENGTH]>,
+    log: L,
+    log_entries: usize,
+    // set if a failed write could not be truncated, so that the log may end
+    // with a partial entry
+    poisoned: bool,
+}
+
+// the log that spent entries are appended to
+trait Log: Write {
+    fn set_len(&mut self, len: u64) -> Result<(), Error>;
+    fn sync_data(&mut self) -> Result<(), Error>;
+}
+
+impl Log for File {
+    fn set_len(&mut self, len: u64) -> Result<(), Error> {
+        File::set_len(self, len)
+    }
+
+    fn sync_data(&mut self) -> Result<(), Error> {
+        File::sync_data(self)
+    }
+}
+
+impl<L: Log> FileState<L> {
+    // persists `entry` in the log and records it as spent
+    fn append(&mut self, entry: &[u8; ENTRY_LENGTH]) -> Result<(), Error> {
+        if self.poisoned {
+            return Err(err_internal());
+        }
+        if let Err(e) = self.log.write_all(entry).and_then(|_| self.log.sync_data()) {
+            // drop the partial entry, so that later entries are appended at
+            // an entry boundary
+            let len = (self.log_entries*ENTRY_LENGTH) as u64;
+            if self.log.set_len(len).and_then(|_| self.log.sync_data()).is_err() {
+                self.poisoned = true;
+            }
+            return Err(e);
+        }
+        self.spent.insert(*entry);
+        self.log_entries += 1;
+        Ok(())
+    }
+}
+
+impl FileStore {
+    /// Opens the store in `dir` (which is created if necessary), and loads the
+    /// tokens that have been spent
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut spent = HashSet::new();
+        // the index is replaced atomically, so a truncated index is corrupt
+        let (_, torn) = read_entries(&dir.join(INDEX_FILE), &mut spent)?;
+        if torn {
+            return Err(Error::new(ErrorKind::InvalidData, "Truncated spent token index"));
+        }
+        let (log_entries, torn) = read_entries(&dir.join(LOG_FILE), &mut spent)?;
+        let log = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE))?;
+        if torn {
+            // drop the partial entry of an interrupted write, so that later
+            // entries are appended at an entry boundary
+            log.set_len((log_entries*ENTRY_LENGTH) as u64)?;
+            log.sync_all()?;
+        }
+        Ok(FileStore{
+            dir: dir,
+            state: Mutex::new(FileState{
+                spent: spent,
+                log: log,
+                log_entries: log_entries,
+                poisoned: false,
+            }),
+        })
+    }
+
+    /// Merges the log into the index
+    pub fn compact(&self) -> Result<(), Error> {
+        let mut state = lock(&self.state)?;
+        self.compact_locked(&mut state)
+    }
+
+    fn compact_locked(&self, state: &mut FileState) -> Result<(), Error> {
+        let mut entries: Vec<&[u8; ENTRY_LENGTH]> = state.spent.iter().collect();
+        entries.sort();
+        let tmp = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        let mut f = File::create(&tmp)?;
+        for e in entries {
+            f.write_all(e)?;
+        }
+        f.sync_all()?;
+        fs::rename(&tmp, self.dir.join(INDEX_FILE))?;
+        state.log.set_len(0)?;
+        state.log.sync_all()?;
+        state.log_entries = 0;
+        state.poisoned = false;
+        Ok(())
+    }
+}
+
+impl SpendStore for FileStore {
+    fn spend(&self, token: &[u8]) -> Result<bool, Error> {
+        let entry = entry(token);
+        let mut state = lock(&self.state)?;
+        if state.spent.contains(&entry) {
+            return Ok(false);
+        }
+        // the token is only accepted once it has been persisted
+        state.append(&entry)?;
+        if state.log_entries >= COMPACTION_THRESHOLD {
+            self.compact_locked(&mut state)?;
+        }
+        Ok(true)
+    }
+
+    fn is_spent(&self, token: &[u8]) -> Result<bool, Error> {
+        Ok(lock(&self.state)?.spent.contains(&entry(token)))
+    }
+
+    fn purge(&self) -> Result<(), Error> {
+        let mut state = lock(&self.state)?;
+        state.spent.clear();
+        state.log.set_len(0)?;
+        state.log_entries = 0;
+        state.poisoned = false;
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+// opens the store for an epoch
+type Opener = Box<dyn Fn(&[u8]) -> Result<Arc<dyn SpendStore>, Error> + Send + Sync>;
+
+/// The `EpochStores` struct holds a `SpendStore` for each key epoch, where
+/// epochs are identified by the token key ID
+pub struct EpochStores {
+    stores: Mutex<HashMap<Vec<u8>, Arc<dyn SpendStore>>>,
+    open: Opener,
+}
+
+impl EpochStores {
+    /// Holds the spent tokens of each epoch in memory
+    pub fn in_memory() -> Self {
+        EpochStores{
+            stores: Mutex::new(HashMap::new()),
+            open: Box::new(|_| Ok(Arc::new(MemoryStore::new()))),
+        }
+    }
+
+    /// Holds the spent tokens of each epoch in a `FileStore`, in the
+    /// subdirectory of `dir` named after the hex-encoded key ID. The stores of
+    /// existing epochs are loaded, so that they are purged on rotation.
+    pub fn in_dir<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let open_dir = dir.clone();
+        let epochs = EpochStores{
+            stores: Mutex::new(HashMap::new()),
+            open: Box::new(move |epoch| {
+                Ok(Arc::new(FileStore::open(open_dir.join(hex::encode(epoch)))?))
+            }),
+        };
+        for entry in fs::read_dir(&dir)? {
+            let name = entry?.file_name();
+            if let Some(epoch) = name.to_str().and_then(|n| hex::decode(n).ok()) {
+                epochs.store(&epoch)?;
+            }
+        }
+        Ok(epochs)
+    }
+
+    /// Marks `token` as spent in the store for `epoch` (see
+    /// `SpendStore::spend`)
+    pub fn spend(&self, epoch: &[u8], token: &[u8]) -> Result<bool, Error> {
+        self.store(epoch)?.spend(token)
+    }
+
+    /// Returns `true` if `token` has been spent in `epoch`
+    pub fn is_spent(&self, epoch: &[u8], token: &[u8]) -> Result<bool, Error> {
+        self.store(epoch)?.is_spent(token)
+    }
+
+    /// Returns the epochs that have a store, in sorted order
+    pub fn epochs(&self) -> Result<Vec<Vec<u8>>, Error> {
+        let mut epochs: Vec<Vec<u8>> = lock(&self.stores)?.keys().cloned().collect();
+        epochs.sort();
+        Ok(epochs)
+    }
+
+    /// Purges and removes the stores of all epochs that are not `active`
+    pub fn rotate(&self, active: &[Vec<u8>]) -> Result<(), Error> {
+        let mut stores = lock(&self.stores)?;
+        let expired: Vec<Vec<u8>> = stores.keys()
+            .filter(|e| !active.contains(e))
+            .cloned()
+            .collect();
+        for epoch in expired {
+            if let Some(store) = stores.remove(&epoch) {
+                store.purge()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the store for `epoch`, which is opened if necessary
+    pub fn store(&self, epoch: &[u8]) -> Result<Arc<dyn SpendStore>, Error> {
+        let mut stores = lock(&self.stores)?;
+        if let Some(store) = stores.get(epoch) {
+            return Ok(store.clone());
+        }
+        let store = (self.open)(epoch)?;
+        stores.insert(epoch.to_vec(), store.clone());
+        Ok(store)
+    }
+}
+
+// the entry that is recorded for a spent token
+fn entry(token: &[u8]) -> [u8; ENTRY_LENGTH] {
+    let mut out = [0; ENTRY_LENGTH];
+    out.copy_from_slice(&Sha256::digest(token));
+    out
+}
+
+// reads the entries in `path` into `spent` and returns the number of entries,
+// along with whether the file ends with a truncated entry (from an interrupted
+// write), which is not read
+fn read_entries(path: &Path, spent: &mut HashSet<[u8; ENTRY_LENGTH]>) -> Result<(usize, bool), Error> {
+    let mut buf = Vec::new();
+    match File::open(path) {
+        Ok(mut f) => f.read_to_end(&mut buf)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((0, false)),
+        Err(e) => return Err(e),
+    };
+    let chunks = buf.chunks_exact(ENTRY_LENGTH);
+    let torn = !chunks.remainder().is_empty();
+    let mut n = 0;
+    for chunk in chunks {
+        let mut e = [0; ENTRY_LENGTH];
+        e.copy_from_slice(chunk);
+        spent.insert(e);
+        n += 1;
+    }
+    Ok((n, torn))
+}
+
+fn lock<X>(m: &Mutex<X>) -> Result<std::sync::MutexGuard<'_, X>, Error> {
+    m.lock().map_err(|_| err_internal())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("voprf-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // a log that fails once it holds `capacity` bytes
+    struct FailingLog {
+        buf: Vec<u8>,
+        capacity: usize,
+        fail_truncate: bool,
+    }
+
+    impl Write for FailingLog {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            let n = buf.len().min(self.capacity - self.buf.len());
+            self.buf.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    impl Log for FailingLog {
+        fn set_len(&mut self, len: u64) -> Result<(), Error> {
+            if self.fail_truncate {
+                return Err(Error::new(ErrorKind::PermissionDenied, "truncation failed"));
+            }
+            self.buf.truncate(len as usize);
+            Ok(())
+        }
+
+        fn sync_data(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn check_store(store: &dyn SpendStore) {
+        assert!(!store.is_spent(b"alpha").unwrap());
+        assert!(store.spend(b"alpha").unwrap());
+        assert!(store.is_spent(b"alpha").unwrap());
+        assert!(!store.spend(b"alpha").unwrap());
+        assert!(store.spend(b"beta").unwrap());
+        store.purge().unwrap();
+        assert!(!store.is_spent(b"alpha").unwrap());
+    }
+
+    #[test]
+    fn memory_store() {
+        check_store(&MemoryStore::new());
+    }
+
+    #[test]
+    fn file_store() {
+        let dir = temp_dir("file-store");
+        check_store(&FileStore::open(&dir).unwrap());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn file_store_persists() {
+        let dir = temp_dir("file-store-persists");
+        let store = FileStore::open(&dir).unwrap();
+        for i in 0..10u8 {
+            assert!(store.spend(&[i]).unwrap());
+        }
+        store.compact().unwrap();
+        assert!(store.spend(b"after compaction").unwrap());
+        drop(store);
+
+        // simulate an interrupted write at the end of the log
+        let mut log = OpenOptions::new().append(true).open(dir.join(LOG_FILE)).unwrap();
+        log.write_all(&[1, 2, 3]).unwrap();
+        drop(log);
+
+        let store = FileStore::open(&dir).unwrap();
+        for i in 0..10u8 {
+            assert!(!store.spend(&[i]).unwrap());
+        }
+        assert!(!store.spend(b"after compaction").unwrap());
+        assert!(store.spend(b"new").unwrap());
+        store.purge().unwrap();
+    }
+
+    #[test]
+    fn file_store_reopens_after_torn_write() {
+        let dir = temp_dir("file-store-torn");
+        let store = FileStore::open(&dir).unwrap();
+        assert!(store.spend(b"first").unwrap());
+        drop(store);
+        let mut log = OpenOptions::new().append(true).open(dir.join(LOG_FILE)).unwrap();
+        log.write_all(&[1, 2, 3]).unwrap();
+        drop(log);
+
+        // the partial entry is dropped, so entries spent after reopening are
+        // aligned and survive another reopen
+        let store = FileStore::open(&dir).unwrap();
+        assert_eq!(fs::metadata(dir.join(LOG_FILE)).unwrap().len() as usize, ENTRY_LENGTH);
+        assert!(store.spend(b"second").unwrap());
+        drop(store);
+        let store = FileStore::open(&dir).unwrap();
+        assert!(!store.spend(b"first").unwrap());
+        assert!(!store.spend(b"second").unwrap());
+        store.purge().unwrap();
+
+        // a truncated index is not expected, and is refused
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(INDEX_FILE), &[0; ENTRY_LENGTH+1]).unwrap();
+        assert!(FileStore::open(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_store_truncates_failed_writes() {
+        let mut state = FileState{
+            spent: HashSet::new(),
+            log: FailingLog{ buf: Vec::new(), capacity: ENTRY_LENGTH + 5, fail_truncate: false },
+            log_entries: 0,
+            poisoned: false,
+        };
+        state.append(&entry(b"first")).unwrap();
+        // the torn entry is dropped, and the token is not recorded
+        assert!(state.append(&entry(b"second")).is_err());
+        assert_eq!(state.log.buf, entry(b"first"));
+        assert!(!state.spent.contains(&entry(b"second")));
+        state.log.capacity = 2*ENTRY_LENGTH;
+        state.append(&entry(b"second")).unwrap();
+        assert_eq!(state.log.buf, [entry(b"first"), entry(b"second")].concat());
+        assert_eq!(state.log_entries, 2);
+
+        // if the torn entry cannot be dropped, no further tokens are spent
+        state.log.capacity += 5;
+        state.log.fail_truncate = true;
+        assert!(state.append(&entry(b"third")).is_err());
+        assert!(state.poisoned);
+        state.log.capacity = 4*ENTRY_LENGTH;
+        state.log.fail_truncate = false;
+        assert!(state.append(&entry(b"fourth")).is_err());
+        assert_eq!(state.log_entries, 2);
+        assert!(!state.spent.contains(&entry(b"fourth")));
+    }
+
+    #[test]
+    fn file_store_compacts() {
+        let dir = temp_dir("file-store-compacts");
+        let store = FileStore::open(&dir).unwrap();
+        for i in 0..COMPACTION_THRESHOLD+1 {
+            assert!(store.spend(&(i as u32).to_be_bytes()).unwrap());
+        }
+        let idx_len = fs::metadata(dir.join(INDEX_FILE)).unwrap().len() as usize;
+        let log_len = fs::metadata(dir.join(LOG_FILE)).unwrap().len() as usize;
+        assert_eq!(idx_len, COMPACTION_THRESHOLD*ENTRY_LENGTH);
+        assert_eq!(log_len, ENTRY_LENGTH);
+        store.purge().unwrap();
+    }
+
+    #[test]
+    fn concurrent_spend() {
+        let dir = temp_dir("concurrent-spend");
+        let stores: Vec<Arc<dyn SpendStore>> = vec![Arc::new(MemoryStore::new()), Arc::new(FileStore::open(&dir).unwrap())];
+        for store in stores {
+            let handles: Vec<_> = (0..8).map(|_| {
+                let store = store.clone();
+                thread::spawn(move || store.spend(b"token").unwrap())
+            }).collect();
+            let accepted = handles.into_iter().map(|h| h.join().unwrap()).filter(|b| *b).count();
+            assert_eq!(accepted, 1);
+            store.purge().unwrap();
+        }
+    }
+
+    #[test]
+    fn epoch_rotation() {
+        let dir = temp_dir("epochs");
+        let epochs = EpochStores::in_dir(&dir).unwrap();
+        assert!(epochs.spend(b"epoch-1", b"token").unwrap());
+        assert!(epochs.spend(b"epoch-2", b"token").unwrap());
+        assert!(!epochs.spend(b"epoch-1", b"token").unwrap());
+        drop(epochs);
+
+        // existing epochs are loaded, and purged on rotation
+        let epochs = EpochStores::in_dir(&dir).unwrap();
+        assert_eq!(epochs.epochs().unwrap(), vec![b"epoch-1".to_vec(), b"epoch-2".to_vec()]);
+        epochs.rotate(&[b"epoch-2".to_vec()]).unwrap();
+        assert_eq!(epochs.epochs().unwrap(), vec![b"epoch-2".to_vec()]);
+        assert!(!dir.join(hex::encode(b"epoch-1")).exists());
+        assert!(epochs.is_spent(b"epoch-2", b"token").unwrap());
+
+        let mem = EpochStores::in_memory();
+        assert!(mem.spend(b"epoch-1", b"token").unwrap());
+        mem.rotate(&[]).unwrap();
+        assert!(mem.epochs().unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
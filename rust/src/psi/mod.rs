@@ -0,0 +1,372 @@
+//! The psi module implements private set intersection (PSI) on top of the
+//! (V)OPRF, e.g. for contact discovery between two parties.
+//!
+//! The server evaluates the full (V)OPRF over each item of its own set with
+//! `Server::full_evaluate`, and publishes the outputs as a `ServerSet`. The
+//! client blinds the items of its set with `Client::blind`, has the server
+//! evaluate them, and unblinds and finalizes the evaluations. The items of the
+//! client whose outputs appear in the `ServerSet` are in the intersection. The
+//! client learns nothing about the other items of the server, and the server
+//! learns nothing about the items of the client.
+//!
+//! In the cardinality-only mode, the client learns the size of the
+//! intersection but not which items are in it. The server publishes a
+//! `ServerSet` built with `ServerSet::build_cardinality`, where the outputs are
+//! derived from the evaluated elements alone. The client blinds its items with
+//! a shared blind (`Client::blind_shared`), the server evaluates them with
+//! `Server::eval_shuffled` and returns them in a random order, and the client
+//! counts the outputs that appear in the set with `cardinality`. Shuffled
+//! evaluations carry no proof, so this mode requires an OPRF (non-verifiable)
+//! ciphersuite. Note that this only hides the correspondence within a batch: a client that evaluates
+//! its items one at a time (or alongside dummy items) still learns which items
+//! are in the intersection, so the server should bound the number of
+//! evaluations of each client.
+//!
+//! The `ServerSet` is a sorted table of the outputs truncated to
+//! `truncation` bytes, with the following encoding:
+//!
+//! ```text
+//! truncation (1) || count (u32, big-endian) || entries (count * truncation)
+//! ```
+//!
+//! The probability that an item of the client is wrongly found in the
+//! intersection is roughly `n * 2^(-8 * truncation)` for a server set of `n`
+//! items.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,Client};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::psi::{ServerSet,intersect,DEFAULT_TRUNCATION};
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use sha2::Sha512;
+//!
+//! let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let srv = Server::setup(ciph.clone());
+//! let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+//!
+//! // server publishes the outputs for its set
+//! let srv_items = vec![b"alice".to_vec(), b"bob".to_vec()];
+//! let set = ServerSet::build(&srv, &srv_items, DEFAULT_TRUNCATION).unwrap();
+//! let set = ServerSet::from_bytes(&set.to_bytes()).unwrap();
+//!
+//! // client obliviously evaluates its set and intersects
+//! let inputs = cli.blind(&[b"bob".to_vec(), b"carol".to_vec()]);
+//! let elems: Vec<_> = inputs.iter().map(|i| i.elem).collect();
+//! let eval = srv.eval(&elems).unwrap();
+//! assert_eq!(intersect(&cli, &inputs, &eval, &set).unwrap(), vec![b"bob".to_vec()]);
+//! ```
+use std::fs;
+use std::io::Error;
+
+use byteorder::{BigEndian,ReadBytesExt,WriteBytesExt};
+
+use crate::oprf::{Server,Client,Input,Evaluation};
+use crate::oprf::ciphersuite::Supported;
+use crate::oprf::groups::PrimeOrderGroup;
+use crate::errors::{err_deserialization,err_finalization,err_shuffled_verifiable};
+
+/// The auxiliary data that PSI outputs are finalized with
+pub const PSI_INFO: &[u8] = b"voprf_psi";
+/// The auxiliary data that outputs are finalized with in the cardinality-only
+/// mode
+pub const CARDINALITY_INFO: &[u8] = b"voprf_psi_cardinality";
+/// The default number of bytes that outputs are truncated to
+pub const DEFAULT_TRUNCATION: usize = 12;
+/// The minimum number of bytes that outputs may be truncated to
+pub const MIN_TRUNCATION: usize = 4;
+
+/// The `ServerSet` struct holds the truncated outputs for the items of the
+/// server, which are published to clients
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerSet {
+    truncation: usize,
+    entries: Vec<Vec<u8>>,
+}
+
+impl ServerSet {
+    /// Evaluates the outputs for `items` with the key of `srv`, and truncates
+    /// them to `truncation` bytes
+    pub fn build<T,H>(srv: &Server<T,H>, items: &[Vec<u8>], truncation: usize) -> Result<Self, Error>
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let mut outputs = Vec::new();
+        for item in items {
+            outputs.push(srv.full_evaluate(item, PSI_INFO)?);
+        }
+        ServerSet::from_outputs(&outputs, truncation)
+    }
+
+    /// Evaluates the outputs for `items` for the cardinality-only mode, where
+    /// the outputs are derived from the evaluated elements alone (see
+    /// `Server::full_evaluate_element`). Returns an error for verifiable
+    /// ciphersuites, which do not support shuffled evaluations.
+    pub fn build_cardinality<T,H>(srv: &Server<T,H>, items: &[Vec<u8>], truncation: usize) -> Result<Self, Error>
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        if srv.ciph.verifiable {
+            return Err(err_shuffled_verifiable());
+        }
+        let mut outputs = Vec::new();
+        for item in items {
+            outputs.push(srv.full_evaluate_element(item, CARDINALITY_INFO)?);
+        }
+        ServerSet::from_outputs(&outputs, truncation)
+    }
+
+    /// Creates the set from finalized outputs, truncated to `truncation` bytes
+    pub fn from_outputs(outputs: &[Vec<u8>], truncation: usize) -> Result<Self, Error> {
+        if truncation < MIN_TRUNCATION || truncation > u8::MAX as usize {
+            return Err(err_deserialization());
+        }
+        let mut entries = Vec::new();
+        for out in outputs {
+            if out.len() < truncation {
+                return Err(err_deserialization());
+            }
+            entries.push(out[..truncation].to_vec());
+        }
+        entries.sort();
+        entries.dedup();
+        Ok(ServerSet{
+            truncation: truncation,
+            entries: entries,
+        })
+    }
+
+    /// The number of bytes that outputs are truncated to
+    pub fn truncation(&self) -> usize {
+        self.truncation
+    }
+
+    /// The number of entries in the set
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the set has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns `true` if the (untruncated) output is in the set
+    pub fn contains(&self, output: &[u8]) -> bool {
+        output.len() >= self.truncation
+            && self.entries.binary_search_by(|e| e.as_slice().cmp(&output[..self.truncation])).is_ok()
+    }
+
+    /// Returns the encoding of the set
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![self.truncation as u8];
+        out.write_u32::<BigEndian>(self.entries.len() as u32).unwrap();
+        for e in &self.entries {
+            out.extend_from_slice(e);
+        }
+        out
+    }
+
+    /// Parses a set from its encoding, the entries must be sorted and unique
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() < 5 {
+            return Err(err_deserialization());
+        }
+        let truncation = buf[0] as usize;
+        let count = (&buf[1..5]).read_u32::<BigEndian>()? as usize;
+        if truncation < MIN_TRUNCATION || count.checked_mul(truncation) != Some(buf.len() - 5) {
+            return Err(err_deserialization());
+        }
+        let entries: Vec<Vec<u8>> = buf[5..].chunks(truncation).map(|c| c.to_vec()).collect();
+        if entries.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(err_deserialization());
+        }
+        Ok(ServerSet{
+            truncation: truncation,
+            entries: entries,
+        })
+    }
+}
+
+/// Unblinds and finalizes the server evaluation of the blinded `inputs` of the
+/// client, and returns the data of the inputs whose outputs are in `set`
+pub fn intersect<T,H>(cli: &Client<T,H>, inputs: &[Input<T>], eval: &Evaluation<T>, set: &ServerSet) -> Result<Vec<Vec<u8>>, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    if inputs.len() != eval.elems.len() {
+        return Err(err_finalization());
+    }
+    let unblinded = cli.unblind(inputs, eval)?;
+    let mut out = Vec::new();
+    for (input, elem) in inputs.iter().zip(unblinded.iter()) {
+        if set.contains(&cli.finalize(&input.data, elem, PSI_INFO)?) {
+            out.push(input.data.clone());
+        }
+    }
+    Ok(out)
+}
+
+/// Unblinds the shuffled server evaluation (see `Server::eval_shuffled`) of
+/// the `inputs` of the client, which must be blinded with
+/// `Client::blind_shared`, and returns the number of outputs in the set built
+/// with `ServerSet::build_cardinality`
+pub fn cardinality<T,H>(cli: &Client<T,H>, inputs: &[Input<T>], eval: &Evaluation<T>, set: &ServerSet) -> Result<usize, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    let mut count = 0;
+    for elem in cli.unblind_shuffled(inputs, eval)? {
+        if set.contains(&cli.finalize(&[], &elem, CARDINALITY_INFO)?) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Reads the items of a set from a file, with one item on each (non-empty)
+/// line, where surrounding whitespace is ignored
+pub fn read_items(path: &str) -> Result<Vec<Vec<u8>>, Error> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| l.as_bytes().to_vec())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::oprf::groups::p384::NistPoint;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use sha2::Sha512;
+
+    fn items(names: &[&str]) -> Vec<Vec<u8>> {
+        names.iter().map(|n| n.as_bytes().to_vec()).collect()
+    }
+
+    fn psi<T,H>(pog: PrimeOrderGroup<T,H>, verifiable: bool)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let ciph = Ciphersuite::new(pog.clone(), verifiable);
+        let srv = Server::setup(ciph.clone());
+        let pk = match verifiable {
+            true => Some(srv.key.pub_key(&pog)),
+            false => None,
+        };
+        let cli = Client::setup(ciph.clone(), pk).unwrap();
+        let set = ServerSet::build(&srv, &items(&["alice", "bob", "dave", "bob"]), DEFAULT_TRUNCATION).unwrap();
+        assert_eq!(set.len(), 3);
+
+        let inputs = cli.blind(&items(&["carol", "dave", "bob", "erin"]));
+        let elems: Vec<T> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let eval = srv.eval(&elems).unwrap();
+        assert_eq!(intersect(&cli, &inputs, &eval, &set).unwrap(), items(&["dave", "bob"]));
+
+        // the set of another server key does not intersect
+        let other = Server::setup(ciph);
+        let other_set = ServerSet::build(&other, &items(&["dave", "bob"]), DEFAULT_TRUNCATION).unwrap();
+        assert!(intersect(&cli, &inputs, &eval, &other_set).unwrap().is_empty());
+    }
+
+    #[test]
+    fn psi_ristretto() {
+        psi(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), true);
+        psi(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255(), false);
+    }
+
+    #[test]
+    fn psi_p384() {
+        psi(PrimeOrderGroup::<NistPoint,Sha512>::p384(), true);
+    }
+
+    fn psi_cardinality<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let ciph = Ciphersuite::new(pog, false);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph, None).unwrap();
+        let set = ServerSet::build_cardinality(&srv, &items(&["alice", "bob", "dave"]), DEFAULT_TRUNCATION).unwrap();
+        let verifiable = Server::setup(Ciphersuite::new(srv.ciph.pog.clone(), true));
+        assert!(ServerSet::build_cardinality(&verifiable, &items(&["alice"]), DEFAULT_TRUNCATION).is_err());
+
+        let inputs = cli.blind_shared(&items(&["carol", "dave", "bob", "erin"]));
+        let elems: Vec<T> = inputs.iter().map(|i| i.elem.clone()).collect();
+        let mut eval = srv.eval_shuffled(&elems).unwrap();
+        assert_eq!(cardinality(&cli, &inputs, &eval, &set).unwrap(), 2);
+
+        // the count does not depend on the order of the evaluation, which is
+        // all that could link the outputs to the inputs
+        for _ in 0..eval.elems.len() {
+            eval.elems.rotate_left(1);
+            assert_eq!(cardinality(&cli, &inputs, &eval, &set).unwrap(), 2);
+        }
+        // the outputs of the unblinded elements are the same for any input
+        // order, so they cannot be attributed to an input
+        let outs = |inputs: &[Input<T>], eval: &Evaluation<T>| {
+            let mut outs: Vec<Vec<u8>> = cli.unblind_shuffled(inputs, eval).unwrap().iter()
+                .map(|u| cli.finalize(&[], u, CARDINALITY_INFO).unwrap()).collect();
+            outs.sort();
+            outs
+        };
+        let mut reversed = inputs.clone();
+        reversed.reverse();
+        let rev_elems: Vec<T> = reversed.iter().map(|i| i.elem.clone()).collect();
+        assert_eq!(outs(&inputs, &eval), outs(&reversed, &srv.eval_shuffled(&rev_elems).unwrap()));
+
+        // the intersection set does not match cardinality outputs
+        let psi_set = ServerSet::build(&srv, &items(&["alice", "bob", "dave"]), DEFAULT_TRUNCATION).unwrap();
+        assert_eq!(cardinality(&cli, &inputs, &eval, &psi_set).unwrap(), 0);
+    }
+
+    #[test]
+    fn psi_cardinality_ristretto() {
+        psi_cardinality(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn psi_cardinality_p384() {
+        psi_cardinality(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    #[test]
+    fn set_encoding() {
+        let outputs: Vec<Vec<u8>> = (0..10u8).rev().map(|i| vec![i; 64]).collect();
+        let set = ServerSet::from_outputs(&outputs, 8).unwrap();
+        let buf = set.to_bytes();
+        assert_eq!(buf.len(), 5 + 10*8);
+        assert_eq!(ServerSet::from_bytes(&buf).unwrap(), set);
+        assert!(set.contains(&[3; 64]));
+        assert!(!set.contains(&[11; 64]));
+        assert!(!set.contains(&[3; 7]));
+
+        // reject malformed encodings
+        assert!(ServerSet::from_bytes(&buf[..buf.len()-1]).is_err());
+        assert!(ServerSet::from_bytes(&buf[..4]).is_err());
+        let mut unsorted = buf.clone();
+        unsorted[5..13].copy_from_slice(&[9; 8]);
+        assert!(ServerSet::from_bytes(&unsorted).is_err());
+        let mut short = buf;
+        short[0] = 2;
+        assert!(ServerSet::from_bytes(&short).is_err());
+        let huge = [255, 255, 255, 255, 255];
+        assert!(ServerSet::from_bytes(&huge).is_err());
+        assert!(ServerSet::from_outputs(&outputs, 2).is_err());
+        assert!(ServerSet::from_outputs(&outputs, 65).is_err());
+    }
+
+    #[test]
+    fn read_items_from_file() {
+        let path = std::env::temp_dir().join(format!("voprf-rs-psi-items-{}", std::process::id()));
+        fs::write(&path, "alice\n  bob \n\ncarol").unwrap();
+        assert_eq!(read_items(path.to_str().unwrap()).unwrap(), items(&["alice", "bob", "carol"]));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,440 @@
+//! The pwhash module implements password hardening with a (V)OPRF server, in
+//! the style of [Pythia](https://eprint.iacr.org/2015/644), so that a stolen
+//! database of password hashes cannot be cracked offline without the key of
+//! the server.
+//!
+//! To harden a password, the login backend derives `x = KDF(password, salt)`
+//! (see `kdf`), blinds `x`, and has the (V)OPRF server evaluate it. The
+//! finalized (V)OPRF output is then passed through a memory-hard function (see
+//! `mhf`), and the result is stored in a `PasswordRecord`, along with the
+//! salt, the cost parameters and the ID of the server key. The server never
+//! learns the password or the hash.
+//!
+//! Records can only be verified with the key that they were hardened with.
+//! When the key of the server is rotated, `PasswordRecord::needs_rehardening`
+//! identifies the records of the old key. These are re-hardened on the next
+//! login, by verifying the password with a client for the old key, and
+//! hardening it again with a client for the new key (see
+//! `PasswordRecord::reharden`, and `http::client::reharden_password` for a
+//! remote server). The server has to keep evaluating with the old key until
+//! all records are re-hardened.
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::oprf::{Server,Client};
+//! use voprf_rs::oprf::ciphersuite::Ciphersuite;
+//! use voprf_rs::oprf::groups::PrimeOrderGroup;
+//! use voprf_rs::pwhash::{Params,PasswordRecord,blind_password,harden,key_id};
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use sha2::Sha512;
+//!
+//! let pog = PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255();
+//! let ciph = Ciphersuite::new(pog.clone(), true);
+//! let srv = Server::setup(ciph.clone());
+//! let cli = Client::setup(ciph, Some(srv.key.pub_key(&pog))).unwrap();
+//! let params = Params{ space_cost: 64, time_cost: 1 };
+//!
+//! // enroll a password
+//! let salt = b"some random salt";
+//! let input = blind_password(&cli, b"hunter2", salt);
+//! let eval = srv.eval(&[input.elem]).unwrap();
+//! let hash = harden(&cli, &input, &eval, salt, &params).unwrap();
+//! let record = PasswordRecord::new(&cli, salt, params, hash).unwrap();
+//!
+//! // verify the password
+//! let input = blind_password(&cli, b"hunter2", &record.salt);
+//! let eval = srv.eval(&[input.elem]).unwrap();
+//! let hash = harden(&cli, &input, &eval, &record.salt, &record.params).unwrap();
+//! assert!(record.matches(&hash));
+//! assert!(!record.needs_rehardening(&key_id(&cli).unwrap()));
+//! ```
+use std::convert::TryFrom;
+use std::io::{Cursor,Error,Read};
+
+use byteorder::{BigEndian,ReadBytesExt,WriteBytesExt};
+use hmac::{Hmac,Mac};
+use sha2::{Sha256,Sha512,Digest};
+use subtle::ConstantTimeEq;
+
+use crate::oprf::{Client,Input,Evaluation};
+use crate::oprf::ciphersuite::Supported;
+use crate::oprf::groups::PrimeOrderGroup;
+use crate::errors::{err_deserialization,err_serialization,err_evaluation,err_public_key_not_found,err_pwhash_params,err_record_key_id};
+
+/// The auxiliary data that hardened outputs are finalized with
+pub const PWHASH_INFO: &[u8] = b"voprf_pwhash";
+/// The length of the salts generated for new records
+pub const SALT_LENGTH: usize = 16;
+/// The current version of the `PasswordRecord` encoding
+pub const RECORD_VERSION: u8 = 1;
+/// The maximum space cost (in 32-byte blocks) accepted for the memory-hard
+/// function, i.e. 128 MiB
+pub const MAX_SPACE_COST: u32 = 1 << 22;
+/// The maximum time cost (in rounds) accepted for the memory-hard function
+pub const MAX_TIME_COST: u32 = 16;
+
+// the number of pseudorandom blocks mixed into each block in every round of
+// the memory-hard function
+const MHF_DELTA: u64 = 3;
+const BLOCK_LENGTH: usize = 32;
+
+/// The `Params` struct holds the cost parameters of the memory-hard function
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    /// The number of 32-byte blocks of memory that are used
+    pub space_cost: u32,
+    /// The number of rounds of mixing over the memory
+    pub time_cost: u32,
+}
+
+impl Default for Params {
+    /// 16 MiB of memory and 3 rounds
+    fn default() -> Self {
+        Params{
+            space_cost: 1 << 19,
+            time_cost: 3,
+        }
+    }
+}
+
+impl Params {
+    fn is_valid(&self) -> bool {
+        self.space_cost > 0 && self.space_cost <= MAX_SPACE_COST
+            && self.time_cost > 0 && self.time_cost <= MAX_TIME_COST
+    }
+}
+
+/// Derives the (V)OPRF input for `password` and `salt`, using HMAC-SHA512
+/// keyed with the salt
+pub fn kdf(password: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha512>::new_varkey(salt).unwrap();
+    mac.input(password);
+    mac.result().code().to_vec()
+}
+
+/// Computes the memory-hard function of `input` and `salt` over SHA-256, with
+/// the cost parameters in `params`. It follows the structure of Algorithm 1 of
+/// [Balloon hashing](https://eprint.iacr.org/2016/027), but encodes the
+/// counter and the block indices as 8-byte big-endian integers, which the
+/// paper leaves open. Its outputs therefore differ from those of other
+/// Balloon implementations, and are only meant to be verified by this crate.
+pub fn mhf(input: &[u8], salt: &[u8], params: &Params) -> Result<Vec<u8>, Error> {
+    if !params.is_valid() {
+        return Err(err_pwhash_params());
+    }
+    let space = params.space_cost as usize;
+    let mut cnt: u64 = 0;
+    let mut buf = vec![[0u8; BLOCK_LENGTH]; space];
+    // expand the input into the buffer
+    buf[0] = mhf_hash(&mut cnt, &[input, salt]);
+    for m in 1..space {
+        buf[m] = mhf_hash(&mut cnt, &[&buf[m-1]]);
+    }
+    // mix each block with the previous block, and with pseudorandom blocks
+    // that only depend on the salt
+    for t in 0..params.time_cost as u64 {
+        for m in 0..space {
+            let prev = buf[(m + space - 1) % space];
+            buf[m] = mhf_hash(&mut cnt, &[&prev, &buf[m]]);
+            for i in 0..MHF_DELTA {
+                let mut idx = Vec::new();
+                idx.write_u64::<BigEndian>(t).unwrap();
+                idx.write_u64::<BigEndian>(m as u64).unwrap();
+                idx.write_u64::<BigEndian>(i).unwrap();
+                let other = mhf_hash(&mut cnt, &[salt, &idx]);
+                let other = (&other[..8]).read_u64::<BigEndian>().unwrap() % space as u64;
+                buf[m] = mhf_hash(&mut cnt, &[&buf[m], &buf[other as usize]]);
+            }
+        }
+    }
+    Ok(buf[space-1].to_vec())
+}
+
+// hashes the concatenation of the counter and `parts`, and increments the
+// counter
+fn mhf_hash(cnt: &mut u64, parts: &[&[u8]]) -> [u8; BLOCK_LENGTH] {
+    let mut hash = Sha256::new();
+    let mut ctr = Vec::new();
+    ctr.write_u64::<BigEndian>(*cnt).unwrap();
+    hash.input(&ctr);
+    for part in parts {
+        hash.input(part);
+    }
+    *cnt += 1;
+    let mut out = [0u8; BLOCK_LENGTH];
+    out.copy_from_slice(&hash.result());
+    out
+}
+
+/// Returns the ID of the public key of `cli`, which records are bound to.
+/// Returns an error if the client has no public key.
+pub fn key_id<T,H>(cli: &Client<T,H>) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone {
+    match &cli.key {
+        Some(pk) => Ok(pk.fingerprint(&cli.ciph.pog)),
+        None => Err(err_public_key_not_found()),
+    }
+}
+
+/// Blinds the (V)OPRF input for `password` and `salt`
+pub fn blind_password<T,H>(cli: &Client<T,H>, password: &[u8], salt: &[u8]) -> Input<T>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    cli.blind(&[kdf(password, salt)]).remove(0)
+}
+
+/// Unblinds and finalizes the server evaluation of the blinded password in
+/// `input`, and computes the hardened hash with the memory-hard function
+pub fn harden<T,H>(cli: &Client<T,H>, input: &Input<T>, eval: &Evaluation<T>, salt: &[u8], params: &Params) -> Result<Vec<u8>, Error>
+        where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+        + digest::Input + digest::Reset + std::default::Default,
+        PrimeOrderGroup<T,H>: Supported {
+    if eval.elems.len() != 1 {
+        return Err(err_evaluation());
+    }
+    let unblinded = cli.unblind(std::slice::from_ref(input), eval)?;
+    let out = cli.finalize(&input.data, &unblinded[0], PWHASH_INFO)?;
+    mhf(&out, salt, params)
+}
+
+/// The `PasswordRecord` struct is stored by the login backend for each
+/// enrolled password
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordRecord {
+    /// The name of the ciphersuite of the server
+    pub ciphersuite: String,
+    /// The ID of the server key that the password was hardened with
+    pub key_id: Vec<u8>,
+    /// The salt of the password
+    pub salt: Vec<u8>,
+    /// The cost parameters of the memory-hard function
+    pub params: Params,
+    /// The hardened hash
+    pub hash: Vec<u8>,
+}
+
+impl PasswordRecord {
+    /// Creates a record for the hardened `hash`, bound to the ciphersuite and
+    /// public key of `cli`
+    pub fn new<T,H>(cli: &Client<T,H>, salt: &[u8], params: Params, hash: Vec<u8>) -> Result<Self, Error>
+            where T: Clone, H: Clone {
+        Ok(PasswordRecord{
+            ciphersuite: cli.ciph.name.clone(),
+            key_id: key_id(cli)?,
+            salt: salt.to_vec(),
+            params: params,
+            hash: hash,
+        })
+    }
+
+    /// Returns `true` if `hash` is the hardened hash in the record, the
+    /// comparison runs in constant time
+    pub fn matches(&self, hash: &[u8]) -> bool {
+        self.hash.ct_eq(hash).into()
+    }
+
+    /// Returns `true` if the record was hardened with a key other than
+    /// `key_id`, and should be re-hardened on the next login
+    pub fn needs_rehardening(&self, key_id: &[u8]) -> bool {
+        self.key_id != key_id
+    }
+
+    /// Re-hardens the record after a key rotation. `old_hash` is the password
+    /// hardened with the key of the record, and `new_hash` is the password
+    /// hardened under `salt` with the new key of `cli`, using the cost
+    /// parameters of the record. Returns the record for the new key if
+    /// `old_hash` matches, and `None` otherwise. Returns an error if `cli` has
+    /// the key of the record.
+    pub fn reharden<T,H>(&self, cli: &Client<T,H>, old_hash: &[u8], salt: &[u8], new_hash: Vec<u8>) -> Result<Option<Self>, Error>
+            where T: Clone, H: Clone {
+        if !self.needs_rehardening(&key_id(cli)?) {
+            return Err(err_record_key_id());
+        }
+        if !self.matches(old_hash) {
+            return Ok(None);
+        }
+        Ok(Some(PasswordRecord::new(cli, salt, self.params, new_hash)?))
+    }
+
+    /// Returns the encoding of the record (all lengths are big-endian u16
+    /// values):
+    ///
+    /// ```text
+    /// version (1) || len || ciphersuite || len || key_id || space_cost (4)
+    ///     || time_cost (4) || len || salt || hash
+    /// ```
+    ///
+    /// Returns an error if a field is too long for its length.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![RECORD_VERSION];
+        write_bytes(&mut out, self.ciphersuite.as_bytes())?;
+        write_bytes(&mut out, &self.key_id)?;
+        out.write_u32::<BigEndian>(self.params.space_cost).unwrap();
+        out.write_u32::<BigEndian>(self.params.time_cost).unwrap();
+        write_bytes(&mut out, &self.salt)?;
+        out.extend_from_slice(&self.hash);
+        Ok(out)
+    }
+
+    /// Parses a record from its encoding
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut cur = Cursor::new(buf);
+        let parsed = (|| {
+            if cur.read_u8().ok()? != RECORD_VERSION {
+                return None;
+            }
+            let ciphersuite = String::from_utf8(read_bytes(&mut cur)?).ok()?;
+            let key_id = read_bytes(&mut cur)?;
+            let params = Params{
+                space_cost: cur.read_u32::<BigEndian>().ok()?,
+                time_cost: cur.read_u32::<BigEndian>().ok()?,
+            };
+            let salt = read_bytes(&mut cur)?;
+            let mut hash = Vec::new();
+            cur.read_to_end(&mut hash).ok()?;
+            Some(PasswordRecord{
+                ciphersuite: ciphersuite,
+                key_id: key_id,
+                salt: salt,
+                params: params,
+                hash: hash,
+            })
+        })();
+        match parsed {
+            Some(r) if r.params.is_valid() && r.hash.len() == BLOCK_LENGTH => Ok(r),
+            _ => Err(err_deserialization()),
+        }
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, x: &[u8]) -> Result<(), Error> {
+    let len = u16::try_from(x.len()).map_err(|_| err_serialization())?;
+    out.write_u16::<BigEndian>(len).unwrap();
+    out.extend_from_slice(x);
+    Ok(())
+}
+
+fn read_bytes(cur: &mut Cursor<&[u8]>) -> Option<Vec<u8>> {
+    let len = cur.read_u16::<BigEndian>().ok()? as usize;
+    let mut out = vec![0; len];
+    cur.read_exact(&mut out).ok()?;
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf::Server;
+    use crate::oprf::ciphersuite::Ciphersuite;
+    use crate::oprf::groups::p384::NistPoint;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+
+    const PARAMS: Params = Params{ space_cost: 64, time_cost: 2 };
+
+    fn hardened<T,H>(srv: &Server<T,H>, cli: &Client<T,H>, password: &[u8], salt: &[u8]) -> Vec<u8>
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let input = blind_password(cli, password, salt);
+        let eval = srv.eval(std::slice::from_ref(&input.elem)).unwrap();
+        harden(cli, &input, &eval, salt, &PARAMS).unwrap()
+    }
+
+    fn enroll_and_verify<T,H>(pog: PrimeOrderGroup<T,H>)
+            where T: Clone, H: Clone + digest::BlockInput + digest::FixedOutput
+            + digest::Input + digest::Reset + std::default::Default,
+            PrimeOrderGroup<T,H>: Supported {
+        let ciph = Ciphersuite::new(pog.clone(), true);
+        let srv = Server::setup(ciph.clone());
+        let cli = Client::setup(ciph.clone(), Some(srv.key.pub_key(&pog))).unwrap();
+        let salt = b"0123456789abcdef";
+        let hash = hardened(&srv, &cli, b"hunter2", salt);
+        let record = PasswordRecord::new(&cli, salt, PARAMS, hash).unwrap();
+        assert_eq!(record.ciphersuite, ciph.name);
+
+        assert!(record.matches(&hardened(&srv, &cli, b"hunter2", salt)));
+        assert!(!record.matches(&hardened(&srv, &cli, b"hunter3", salt)));
+        assert!(!record.matches(&hardened(&srv, &cli, b"hunter2", b"fedcba9876543210")));
+
+        // the hash depends on the key of the server
+        let other = Server::setup(ciph.clone());
+        let other_cli = Client::setup(ciph, Some(other.key.pub_key(&pog))).unwrap();
+        assert!(!record.matches(&hardened(&other, &other_cli, b"hunter2", salt)));
+        assert!(!record.needs_rehardening(&key_id(&cli).unwrap()));
+        assert!(record.needs_rehardening(&key_id(&other_cli).unwrap()));
+
+        // re-hardening with the new key after a rotation
+        let new_salt = b"fedcba9876543210";
+        let new_hash = hardened(&other, &other_cli, b"hunter2", new_salt);
+        let old_hash = hardened(&srv, &cli, b"hunter2", salt);
+        let rehardened = record.reharden(&other_cli, &old_hash, new_salt, new_hash.clone()).unwrap().unwrap();
+        assert!(!rehardened.needs_rehardening(&key_id(&other_cli).unwrap()));
+        assert_eq!(rehardened.params, record.params);
+        assert!(rehardened.matches(&hardened(&other, &other_cli, b"hunter2", new_salt)));
+        let wrong_hash = hardened(&srv, &cli, b"hunter3", salt);
+        assert!(record.reharden(&other_cli, &wrong_hash, new_salt, new_hash.clone()).unwrap().is_none());
+        assert!(record.reharden(&cli, &old_hash, new_salt, new_hash).is_err());
+    }
+
+    #[test]
+    fn enroll_and_verify_ristretto() {
+        enroll_and_verify(PrimeOrderGroup::<RistrettoPoint,Sha512>::ristretto_255());
+    }
+
+    #[test]
+    fn enroll_and_verify_p384() {
+        enroll_and_verify(PrimeOrderGroup::<NistPoint,Sha512>::p384());
+    }
+
+    #[test]
+    fn mhf_costs() {
+        let out = mhf(b"input", b"salt", &PARAMS).unwrap();
+        assert_eq!(out.len(), BLOCK_LENGTH);
+        // pins the encodings of the function, which stored records depend on
+        assert_eq!(hex::encode(&out), "203bf93c5580e9b13cf32eb408024add52adf19cf769575c429a7603bea9901d");
+        assert_eq!(mhf(b"input", b"salt", &PARAMS).unwrap(), out);
+        assert_ne!(mhf(b"input", b"salt2", &PARAMS).unwrap(), out);
+        assert_ne!(mhf(b"input2", b"salt", &PARAMS).unwrap(), out);
+        assert_ne!(mhf(b"input", b"salt", &Params{ space_cost: 65, time_cost: 2 }).unwrap(), out);
+        assert_ne!(mhf(b"input", b"salt", &Params{ space_cost: 64, time_cost: 3 }).unwrap(), out);
+        assert!(mhf(b"input", b"salt", &Params{ space_cost: 0, time_cost: 1 }).is_err());
+        assert!(mhf(b"input", b"salt", &Params{ space_cost: 1, time_cost: 0 }).is_err());
+        assert!(mhf(b"input", b"salt", &Params{ space_cost: MAX_SPACE_COST + 1, time_cost: 1 }).is_err());
+        assert!(mhf(b"input", b"salt", &Params{ space_cost: 1, time_cost: MAX_TIME_COST + 1 }).is_err());
+    }
+
+    #[test]
+    fn record_encoding() {
+        let record = PasswordRecord{
+            ciphersuite: "VOPRF-ristretto255-HKDF-SHA512-ELL2-RO".to_string(),
+            key_id: vec![1; 32],
+            salt: vec![2; SALT_LENGTH],
+            params: PARAMS,
+            hash: vec![3; BLOCK_LENGTH],
+        };
+        let buf = record.to_bytes().unwrap();
+        assert_eq!(PasswordRecord::from_bytes(&buf).unwrap(), record);
+        assert!(PasswordRecord::from_bytes(&buf[..buf.len()-1]).is_err());
+        let mut bad = buf.clone();
+        bad[0] = RECORD_VERSION + 1;
+        assert!(PasswordRecord::from_bytes(&bad).is_err());
+        let mut bad = record.clone();
+        bad.params.space_cost = 0;
+        assert!(PasswordRecord::from_bytes(&bad.to_bytes().unwrap()).is_err());
+
+        // the cost parameters are capped
+        let mut bad = record.clone();
+        bad.params.space_cost = MAX_SPACE_COST + 1;
+        assert!(PasswordRecord::from_bytes(&bad.to_bytes().unwrap()).is_err());
+        let mut bad = record.clone();
+        bad.params.time_cost = u32::max_value();
+        assert!(PasswordRecord::from_bytes(&bad.to_bytes().unwrap()).is_err());
+
+        // fields that are too long are not encoded
+        let mut bad = record.clone();
+        bad.salt = vec![0; 1 << 16];
+        assert!(bad.to_bytes().is_err());
+    }
+}
@@ -1,8 +1,14 @@
 //! The `utils` module provides access to a small number of utility functions
 //! for processing data.
 
+use std::ops::Range;
+use std::time::{SystemTime,UNIX_EPOCH};
+
 use byteorder::{LittleEndian, WriteBytesExt};
 use rand_core::{RngCore, OsRng};
+use digest::BlockInput;
+use digest::generic_array::typenum::Unsigned;
+use sha2::{Digest, Sha512};
 
 /// Moves a user-specified number of uniformly sampled bytes into the provided
 /// output buffer
@@ -42,4 +48,126 @@ pub fn rand_bytes(byte_length: usize, out: &mut Vec<u8>) {
 pub fn copy_into(src: &[u8], dst: &mut Vec<u8>) {
     dst.clear();
     dst.extend_from_slice(src)
-}
\ No newline at end of file
+}
+
+/// Expands `msg` into `len` uniformly distributed bytes under the
+/// domain-separation tag `dst`, using expand_message_xmd of
+/// [RFC 9380](https://www.rfc-editor.org/rfc/rfc9380.html#section-5.3.1) with
+/// SHA-512. Panics if `len > 255*64` or `dst` is longer than 255 bytes.
+///
+/// # Examples
+///
+/// ```
+/// use voprf_rs::utils::expand_message_xmd;
+/// let out = expand_message_xmd(b"", b"QUUX-V01-CS02-with-expander-SHA512-256", 32);
+/// assert_eq!(hex::encode(out), "6b9a7312411d92f921c6f68ca0b6380730a1a4d982c507211a90964c394179ba");
+/// ```
+pub fn expand_message_xmd(msg: &[u8], dst: &[u8], len: usize) -> Vec<u8> {
+    expand_message_xmd_with::<Sha512>(msg, dst, len)
+}
+
+/// Same as `expand_message_xmd`, with the hash function `H` instead of
+/// SHA-512. Panics if `len` is more than 255 hash outputs, or `dst` is longer
+/// than 255 bytes.
+///
+/// # Examples
+///
+/// ```
+/// use voprf_rs::utils::expand_message_xmd_with;
+/// use sha2::Sha256;
+/// let out = expand_message_xmd_with::<Sha256>(b"", b"QUUX-V01-CS02-with-expander-SHA256-128", 32);
+/// assert_eq!(hex::encode(out), "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235");
+/// ```
+pub fn expand_message_xmd_with<H>(msg: &[u8], dst: &[u8], len: usize) -> Vec<u8>
+        where H: Digest + BlockInput {
+    let block_size = H::BlockSize::to_usize();
+    assert!(len <= 255*H::OutputSize::to_usize() && dst.len() <= 255);
+    let mut dst_prime = dst.to_vec();
+    dst_prime.push(dst.len() as u8);
+    let mut hash = H::new();
+    hash.input(vec![0u8; block_size]);
+    hash.input(msg);
+    hash.input([(len >> 8) as u8, len as u8, 0x00]);
+    hash.input(&dst_prime);
+    let b_0 = hash.result();
+    let mut out: Vec<u8> = Vec::new();
+    let mut b_i = vec![0u8; b_0.len()];
+    let mut i = 1;
+    while out.len() < len {
+        let mut hash = H::new();
+        let xored: Vec<u8> = b_0.iter().zip(&b_i).map(|(x, y)| x ^ y).collect();
+        hash.input(&xored);
+        hash.input([i as u8]);
+        hash.input(&dst_prime);
+        b_i = hash.result().to_vec();
+        out.extend_from_slice(&b_i);
+        i = i+1;
+    }
+    out.truncate(len);
+    out
+}
+
+/// Shuffles the provided slice in place with a uniformly random permutation
+/// (Fisher-Yates), sampled from the OS randomness source
+///
+/// # Examples
+///
+/// ```
+/// use voprf_rs::utils::shuffle;
+/// let mut v = vec![1, 2, 3, 4];
+/// shuffle(&mut v);
+/// v.sort();
+/// assert_eq!(v, vec![1, 2, 3, 4]);
+/// ```
+pub fn shuffle<X>(v: &mut [X]) {
+    let mut rng = OsRng;
+    for i in (1..v.len()).rev() {
+        let j = rand_below(&mut rng, (i + 1) as u32) as usize;
+        v.swap(i, j);
+    }
+}
+
+/// Returns the current time in seconds since the UNIX epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Returns `[f(0), .., f(n-1)]`. The calls are spread across the rayon thread
+/// pool if the `parallel` feature is enabled, and made in order otherwise.
+pub(crate) fn par_map<B,F>(n: usize, f: F) -> Vec<B>
+        where B: Send, F: Fn(usize) -> B + Send + Sync {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        (0..n).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..n).map(f).collect()
+    }
+}
+
+/// Splits `0..n` into consecutive ranges of at least `min` indices (except if
+/// `n < min`), with one range per thread if the `parallel` feature is
+/// enabled, and a single range otherwise.
+pub(crate) fn par_chunks(n: usize, min: usize) -> Vec<Range<usize>> {
+    #[cfg(feature = "parallel")]
+    let threads = rayon::current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    let threads = 1;
+    let chunks = std::cmp::max(1, std::cmp::min(threads, n/min));
+    let size = std::cmp::max(1, (n + chunks - 1)/chunks);
+    (0..n).step_by(size).map(|start| start..std::cmp::min(n, start + size)).collect()
+}
+
+// samples a uniformly random integer in [0, n), rejecting the samples that
+// would bias the reduction
+fn rand_below(rng: &mut OsRng, n: u32) -> u32 {
+    let zone = u32::MAX - u32::MAX % n;
+    loop {
+        let u = rng.next_u32();
+        if u < zone {
+            return u % n;
+        }
+    }
+}
@@ -0,0 +1,530 @@
+//! The vrf module implements the elliptic curve verifiable random functions
+//! (ECVRF) of [RFC 9381](https://www.rfc-editor.org/rfc/rfc9381.html) for the
+//! ciphersuites `ECVRF-P256-SHA256-SSWU`, `ECVRF-EDWARDS25519-SHA512-TAI` and
+//! `ECVRF-EDWARDS25519-SHA512-ELL2`.
+//!
+//! The owner of a secret key computes a proof `pi` for an input `alpha`, and
+//! anyone holding the public key can verify `pi` and derive the output `beta`
+//! from it. The proof is a DLEQ proof that `log_B(Y) == log_H(Gamma)`, where
+//! `Y` is the public key, `H` is `alpha` encoded to the curve, and `Gamma =
+//! x*H`. The suites run over the groups of `oprf::groups::p256` and the
+//! prime-order subgroup of edwards25519 (see `oprf::groups::edwards25519`),
+//! and compute the proofs with `oprf::dleq::prove_with_challenge`, with the
+//! challenge and nonce of RFC 9381. The proofs of `oprf::dleq` compute
+//! `s = t - c*k`, whereas RFC 9381 computes `s = k + c*x`, so the challenge of
+//! the DLEQ proof is the negated RFC 9381 challenge.
+//!
+//! Public keys and `Gamma` values must be in the prime-order subgroup. For
+//! edwards25519, this is stricter than RFC 9381, which only rejects public
+//! keys of small order, but all keys and proofs that are generated honestly
+//! are accepted.
+//!
+//! Secret keys of the edwards25519 suites are 32-byte seeds that are expanded
+//! as in [RFC 8032](https://tools.ietf.org/html/rfc8032), so that Ed25519 key
+//! pairs can be used as VRF key pairs. Secret keys of `ECVRF-P256-SHA256-SSWU`
+//! are 32-byte big-endian scalars, and the nonces are generated as in [RFC
+//! 6979](https://tools.ietf.org/html/rfc6979). The P-256 arithmetic does not
+//! run in constant time (see `oprf::groups::p256`).
+//!
+//! # Example
+//!
+//! ```
+//! use voprf_rs::vrf::Suite;
+//!
+//! for suite in &[Suite::P256Sha256Sswu, Suite::Edwards25519Sha512Ell2] {
+//!     let sk = suite.generate_key();
+//!     let pk = suite.public_key(&sk).unwrap();
+//!     let pi = suite.prove(&sk, b"some_input_data").unwrap();
+//!     let beta = suite.verify(&pk, b"some_input_data", &pi).unwrap();
+//!     assert_eq!(beta, suite.proof_to_hash(&pi).unwrap());
+//!     assert!(suite.verify(&pk, b"other_input_data", &pi).is_err());
+//! }
+//! ```
+use std::io::Error;
+
+use curve25519_dalek::edwards::EdwardsPoint;
+use digest::Digest;
+use hmac::{Hmac,Mac};
+use sha2::Sha256;
+
+use crate::oprf::dleq;
+use crate::oprf::groups::{PrimeOrderGroup,GroupElement};
+use crate::oprf::groups::edwards25519::{edwards_encode_to_curve,edwards_clear_cofactor};
+use crate::oprf::groups::p256::{P256Point,p256_encode_to_curve};
+use crate::oprf::proof::Proof;
+use crate::utils::rand_bytes;
+use crate::errors::{err_deserialization,err_vrf_verification};
+
+/// Length of secret keys
+pub const KEY_LENGTH: usize = 32;
+/// Length of the challenge in a proof
+pub const CHALLENGE_LENGTH: usize = 16;
+
+const ELL2_H2C_SUITE: &[u8] = b"edwards25519_XMD:SHA-512_ELL2_NU_";
+const SSWU_H2C_SUITE: &[u8] = b"P256_XMD:SHA-256_SSWU_NU_";
+// The largest counter tried by the try-and-increment encoding. The encoding
+// fails with probability about 2^-256 with this bound.
+const TAI_MAX_COUNTER: u16 = 255;
+
+/// The ECVRF ciphersuites of RFC 9381 supported by this module
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Suite {
+    /// ECVRF-P256-SHA256-SSWU, encoding to the curve with the simplified SWU
+    /// hash-to-curve suite of RFC 9380
+    P256Sha256Sswu,
+    /// ECVRF-EDWARDS25519-SHA512-TAI, encoding to the curve with
+    /// try-and-increment
+    Edwards25519Sha512Tai,
+    /// ECVRF-EDWARDS25519-SHA512-ELL2, encoding to the curve with the
+    /// Elligator 2 hash-to-curve suite of RFC 9380
+    Edwards25519Sha512Ell2,
+}
+
+impl Suite {
+    /// Returns the name of the ciphersuite
+    pub fn name(&self) -> &'static str {
+        match self {
+            Suite::P256Sha256Sswu => "ECVRF-P256-SHA256-SSWU",
+            Suite::Edwards25519Sha512Tai => "ECVRF-EDWARDS25519-SHA512-TAI",
+            Suite::Edwards25519Sha512Ell2 => "ECVRF-EDWARDS25519-SHA512-ELL2",
+        }
+    }
+
+    /// Returns the `suite_string` octet of the ciphersuite
+    pub fn suite_string(&self) -> u8 {
+        match self {
+            Suite::P256Sha256Sswu => 0x02,
+            Suite::Edwards25519Sha512Tai => 0x03,
+            Suite::Edwards25519Sha512Ell2 => 0x04,
+        }
+    }
+
+    /// Returns the length of public keys and encoded points
+    pub fn point_length(&self) -> usize {
+        match self {
+            Suite::P256Sha256Sswu => 33,
+            _ => 32,
+        }
+    }
+
+    /// Returns the length of proofs
+    pub fn proof_length(&self) -> usize {
+        self.point_length() + CHALLENGE_LENGTH + 32
+    }
+
+    /// Returns the length of VRF outputs
+    pub fn output_length(&self) -> usize {
+        match self {
+            Suite::P256Sha256Sswu => 32,
+            _ => 64,
+        }
+    }
+
+    /// Returns a new random secret key
+    pub fn generate_key(&self) -> Vec<u8> {
+        let mut sk = Vec::new();
+        match self {
+            Suite::P256Sha256Sswu => {
+                let pog = P256Point::group();
+                while !(pog.is_valid_scalar)(&sk) {
+                    (pog.uniform_bytes)(&mut sk);
+                }
+            },
+            _ => rand_bytes(KEY_LENGTH, &mut sk),
+        }
+        sk
+    }
+
+    /// Returns the public key of the secret key `sk`
+    pub fn public_key(&self, sk: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Suite::P256Sha256Sswu => public_key::<P256Point>(sk),
+            _ => public_key::<EdwardsPoint>(sk),
+        }
+    }
+
+    /// Returns the proof `pi` for the input `alpha` under the secret key `sk`
+    pub fn prove(&self, sk: &[u8], alpha: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Suite::P256Sha256Sswu => prove::<P256Point>(*self, sk, alpha),
+            _ => prove::<EdwardsPoint>(*self, sk, alpha),
+        }
+    }
+
+    /// Verifies the proof `pi` for the input `alpha` against the public key
+    /// `pk`, and returns the VRF output `beta` if the proof is valid
+    pub fn verify(&self, pk: &[u8], alpha: &[u8], pi: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Suite::P256Sha256Sswu => verify::<P256Point>(*self, pk, alpha, pi),
+            _ => verify::<EdwardsPoint>(*self, pk, alpha, pi),
+        }
+    }
+
+    /// Returns the VRF output `beta` of the proof `pi`. The proof is not
+    /// verified, so this should only be used on proofs that were created or
+    /// verified by the caller.
+    pub fn proof_to_hash(&self, pi: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Suite::P256Sha256Sswu => proof_to_hash::<P256Point>(*self, pi),
+            _ => proof_to_hash::<EdwardsPoint>(*self, pi),
+        }
+    }
+}
+
+/// The `Curve` trait describes the parts of RFC 9381 that differ between the
+/// groups of the ciphersuites
+trait Curve: GroupElement + Clone {
+    /// The cofactor of the curve, as a scalar
+    const COFACTOR: u8;
+
+    /// Expands the secret key `sk` into the secret scalar and the secret input
+    /// of the nonce generation
+    fn expand_secret_key(pog: &PrimeOrderGroup<Self,Self::Hash>, sk: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error>;
+
+    /// Generates the proof nonce from the secret nonce input and the encoding
+    /// of `H`
+    fn generate_nonce(pog: &PrimeOrderGroup<Self,Self::Hash>, secret: &[u8], h_string: &[u8]) -> Vec<u8>;
+
+    /// Encodes `alpha` to a point of the prime-order subgroup, using the
+    /// public key `pk` as salt
+    fn encode_to_curve(suite: Suite, pog: &PrimeOrderGroup<Self,Self::Hash>, pk: &[u8], alpha: &[u8]) -> Result<Self, Error>;
+
+    /// Returns the encoding of the truncated challenge from the encoding of
+    /// the full challenge scalar
+    fn truncate_challenge(c: &[u8]) -> &[u8];
+}
+
+impl Curve for P256Point {
+    const COFACTOR: u8 = 1;
+
+    fn expand_secret_key(pog: &PrimeOrderGroup<P256Point,Sha256>, sk: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        if !(pog.is_valid_scalar)(sk) {
+            return Err(err_deserialization());
+        }
+        Ok((sk.to_vec(), sk.to_vec()))
+    }
+
+    // the nonce generation of section 3.2 of RFC 6979, with the message
+    // `h_string`
+    fn generate_nonce(pog: &PrimeOrderGroup<P256Point,Sha256>, secret: &[u8], h_string: &[u8]) -> Vec<u8> {
+        let hmac = |key: &[u8], data: &[&[u8]]| {
+            let mut mac = Hmac::<Sha256>::new_varkey(key).unwrap();
+            for d in data {
+                mac.input(d);
+            }
+            mac.result().code().to_vec()
+        };
+        // the order and the hash output both have 256 bits, so bits2octets
+        // only reduces the hash
+        let h1 = (pog.reduce_scalar)(&Sha256::digest(h_string), true);
+        let mut v = vec![0x01; 32];
+        let mut k = vec![0x00; 32];
+        k = hmac(&k, &[&v, &[0x00], secret, &h1]);
+        v = hmac(&k, &[&v]);
+        k = hmac(&k, &[&v, &[0x01], secret, &h1]);
+        v = hmac(&k, &[&v]);
+        loop {
+            v = hmac(&k, &[&v]);
+            if (pog.is_valid_scalar)(&v) {
+                return v;
+            }
+            k = hmac(&k, &[&v, &[0x00]]);
+            v = hmac(&k, &[&v]);
+        }
+    }
+
+    fn encode_to_curve(suite: Suite, _: &PrimeOrderGroup<P256Point,Sha256>, pk: &[u8], alpha: &[u8]) -> Result<P256Point, Error> {
+        let mut msg = pk.to_vec();
+        msg.extend_from_slice(alpha);
+        Ok(p256_encode_to_curve(&msg, &h2c_dst(suite, SSWU_H2C_SUITE)))
+    }
+
+    // scalars are big-endian
+    fn truncate_challenge(c: &[u8]) -> &[u8] {
+        &c[c.len()-CHALLENGE_LENGTH..]
+    }
+}
+
+impl Curve for EdwardsPoint {
+    const COFACTOR: u8 = 8;
+
+    // expands the secret key as in RFC 8032
+    fn expand_secret_key(pog: &PrimeOrderGroup<EdwardsPoint,Self::Hash>, sk: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        if sk.len() != KEY_LENGTH {
+            return Err(err_deserialization());
+        }
+        let mut hash = (pog.hash)();
+        hash.input(sk);
+        let h = hash.result();
+        let mut x = h[..KEY_LENGTH].to_vec();
+        x[0] &= 248;
+        x[31] &= 127;
+        x[31] |= 64;
+        Ok(((pog.reduce_scalar)(&x, true), h[KEY_LENGTH..].to_vec()))
+    }
+
+    fn generate_nonce(pog: &PrimeOrderGroup<EdwardsPoint,Self::Hash>, secret: &[u8], h_string: &[u8]) -> Vec<u8> {
+        let mut hash = (pog.hash)();
+        hash.input(secret);
+        hash.input(h_string);
+        (pog.reduce_scalar)(&hash.result(), true)
+    }
+
+    fn encode_to_curve(suite: Suite, pog: &PrimeOrderGroup<EdwardsPoint,Self::Hash>, pk: &[u8], alpha: &[u8]) -> Result<EdwardsPoint, Error> {
+        if suite == Suite::Edwards25519Sha512Ell2 {
+            let mut msg = pk.to_vec();
+            msg.extend_from_slice(alpha);
+            return Ok(edwards_encode_to_curve(&msg, &h2c_dst(suite, ELL2_H2C_SUITE)));
+        }
+        // try-and-increment
+        for ctr in 0..=TAI_MAX_COUNTER {
+            let mut hash = (pog.hash)();
+            hash.input([suite.suite_string(), 0x01]);
+            hash.input(pk);
+            hash.input(alpha);
+            hash.input([ctr as u8, 0x00]);
+            if let Some(h) = edwards_clear_cofactor(&hash.result()[..KEY_LENGTH]) {
+                return Ok(h);
+            }
+        }
+        Err(err_vrf_verification())
+    }
+
+    // scalars are little-endian
+    fn truncate_challenge(c: &[u8]) -> &[u8] {
+        &c[..CHALLENGE_LENGTH]
+    }
+}
+
+fn public_key<T>(sk: &[u8]) -> Result<Vec<u8>, Error>
+        where T: Curve, T::Hash: Digest {
+    let pog = T::group();
+    let (x, _) = T::expand_secret_key(&pog, sk)?;
+    Ok(serialize(&pog, &(pog.base_mult)(&x)))
+}
+
+fn prove<T>(suite: Suite, sk: &[u8], alpha: &[u8]) -> Result<Vec<u8>, Error>
+        where T: Curve, T::Hash: Digest {
+    let pog = T::group();
+    let (x, secret) = T::expand_secret_key(&pog, sk)?;
+    let y = (pog.base_mult)(&x);
+    let h = T::encode_to_curve(suite, &pog, &serialize(&pog, &y), alpha)?;
+    let gamma = (pog.scalar_mult)(&h, &x);
+    let k = T::generate_nonce(&pog, &secret, &serialize(&pog, &h));
+    let proof = dleq::prove_with_challenge(&pog, &x, &pog.generator, &y, &h, &gamma, &k,
+        |pts| Ok(challenge(suite, &pog, pts)))?;
+    let c = (pog.reduce_scalar)(proof.c(), false);
+    let mut pi = serialize(&pog, &gamma);
+    pi.extend_from_slice(T::truncate_challenge(&c));
+    pi.extend_from_slice(proof.s());
+    Ok(pi)
+}
+
+fn verify<T>(suite: Suite, pk: &[u8], alpha: &[u8], pi: &[u8]) -> Result<Vec<u8>, Error>
+        where T: Curve, T::Hash: Digest {
+    let pog = T::group();
+    let y = match (pog.deserialize)(pk) {
+        Ok(y) if (pog.is_valid)(&y) => y,
+        _ => return Err(err_vrf_verification()),
+    };
+    let (gamma, proof) = decode_proof(suite, &pog, pi)?;
+    let h = T::encode_to_curve(suite, &pog, pk, alpha)?;
+    if !dleq::verify_with_challenge(&pog, &pog.generator, &y, &h, &gamma, &proof,
+            |pts| Ok(challenge(suite, &pog, pts))) {
+        return Err(err_vrf_verification());
+    }
+    Ok(gamma_to_hash(suite, &pog, &gamma))
+}
+
+fn proof_to_hash<T>(suite: Suite, pi: &[u8]) -> Result<Vec<u8>, Error>
+        where T: Curve, T::Hash: Digest {
+    let pog = T::group();
+    let (gamma, _) = decode_proof(suite, &pog, pi)?;
+    Ok(gamma_to_hash(suite, &pog, &gamma))
+}
+
+// the domain-separation tag of the RFC 9380 encodings
+fn h2c_dst(suite: Suite, h2c_suite: &[u8]) -> Vec<u8> {
+    let mut dst = b"ECVRF_".to_vec();
+    dst.extend_from_slice(h2c_suite);
+    dst.push(suite.suite_string());
+    dst
+}
+
+/// Derives the challenge of the DLEQ proof for the statement and commitments
+/// `[B, Y, H, Gamma, U, V]`, which is the negated truncated challenge of RFC
+/// 9381 over `(Y, H, Gamma, U, V)`
+fn challenge<T,H>(suite: Suite, pog: &PrimeOrderGroup<T,H>, pts: &[&T]) -> Vec<u8>
+        where H: Digest {
+    let mut hash = (pog.hash)();
+    hash.input([suite.suite_string(), 0x02]);
+    for p in &pts[1..] {
+        hash.input(serialize(pog, p));
+    }
+    hash.input([0x00]);
+    (pog.reduce_scalar)(&hash.result()[..CHALLENGE_LENGTH], false)
+}
+
+fn gamma_to_hash<T>(suite: Suite, pog: &PrimeOrderGroup<T,T::Hash>, gamma: &T) -> Vec<u8>
+        where T: Curve, T::Hash: Digest {
+    let cofactor = (pog.reduce_scalar)(&[T::COFACTOR], true);
+    let mut hash = (pog.hash)();
+    hash.input([suite.suite_string(), 0x03]);
+    hash.input(serialize(pog, &(pog.scalar_mult)(gamma, &cofactor)));
+    hash.input([0x00]);
+    hash.result().to_vec()
+}
+
+/// Decodes `pi` into `Gamma` and the DLEQ proof, rejecting non-canonical
+/// encodings
+fn decode_proof<T,H>(suite: Suite, pog: &PrimeOrderGroup<T,H>, pi: &[u8]) -> Result<(T, Proof<T>), Error> {
+    if pi.len() != suite.proof_length() {
+        return Err(err_deserialization());
+    }
+    let (gamma, rest) = pi.split_at(suite.point_length());
+    let (c, s) = rest.split_at(CHALLENGE_LENGTH);
+    let gamma = (pog.deserialize)(gamma)?;
+    let proof = Proof::new(pog, (pog.reduce_scalar)(c, false), s.to_vec())?;
+    Ok((gamma, proof))
+}
+
+fn serialize<T,H>(pog: &PrimeOrderGroup<T,H>, p: &T) -> Vec<u8> {
+    let mut out = Vec::new();
+    (pog.serialize)(p, true, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::traits::Identity;
+
+    // (suite, SK, PK, alpha, pi, beta) from appendix B of RFC 9381
+    const VECTORS: &[(Suite, &str, &str, &str, &str, &str)] = &[
+        (Suite::P256Sha256Sswu,
+            "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721",
+            "0360fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6",
+            "73616d706c65",
+            "0331d984ca8fece9cbb9a144c0d53df3c4c7a33080c1e02ddb1a96a365394c7888782fffde7b842c38c20c08de6ec6c2e7027a97000f2c9fa4425d5c03e639fb48fde58114d755985498d7eb234cf4aed9",
+            "21e66dc9747430f17ed9efeda054cf4a264b097b9e8956a1787526ed00dc664b"),
+        (Suite::P256Sha256Sswu,
+            "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721",
+            "0360fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6",
+            "74657374",
+            "03f814c0455d32dbc75ad3aea08c7e2db31748e12802db23640203aebf1fa8db2743aad348a3006dc1caad7da28687320740bf7dd78fe13c298867321ce3b36b79ec3093b7083ac5e4daf3465f9f43c627",
+            "8e7185d2b420e4f4681f44ce313a26d05613323837da09a69f00491a83ad25dd"),
+        (Suite::P256Sha256Sswu,
+            "2ca1411a41b17b24cc8c3b089cfd033f1920202a6c0de8abb97df1498d50d2c8",
+            "03596375e6ce57e0f20294fc46bdfcfd19a39f8161b58695b3ec5b3d16427c274d",
+            "4578616d706c65207573696e67204543445341206b65792066726f6d20417070656e646978204c2e342e32206f6620414e53492e58392d36322d32303035",
+            "039f8d9cdc162c89be2871cbcb1435144739431db7fab437ab7bc4e2651a9e99d5488405a11a6c7fc8defddd9e1573a563b7333aab4effe73ae9803274174c659269fd39b53e133dcd9e0d24f01288de9a",
+            "4fbadf33b42a5f42f23a6f89952d2e634a6e3810f15878b46ef1bb85a04fe95a"),
+        (Suite::Edwards25519Sha512Tai,
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805",
+            "90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae"),
+        (Suite::Edwards25519Sha512Tai,
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "f3141cd382dc42909d19ec5110469e4feae18300e94f304590abdced48aed5933bf0864a62558b3ed7f2fea45c92a465301b3bbf5e3e54ddf2d935be3b67926da3ef39226bbc355bdc9850112c8f4b02",
+            "eb4440665d3891d668e7e0fcaf587f1b4bd7fbfe99d0eb2211ccec90496310eb5e33821bc613efb94db5e5b54c70a848a0bef4553a41befc57663b56373a5031"),
+        (Suite::Edwards25519Sha512Ell2,
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "7d9c633ffeee27349264cf5c667579fc583b4bda63ab71d001f89c10003ab46f14adf9a3cd8b8412d9038531e865c341cafa73589b023d14311c331a9ad15ff2fb37831e00f0acaa6d73bc9997b06501",
+            "9d574bf9b8302ec0fc1e21c3ec5368269527b87b462ce36dab2d14ccf80c53cccf6758f058c5b1c856b116388152bbe509ee3b9ecfe63d93c3b4346c1fbc6c54"),
+        (Suite::Edwards25519Sha512Ell2,
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "47b327393ff2dd81336f8a2ef10339112401253b3c714eeda879f12c509072ef055b48372bb82efbdce8e10c8cb9a2f9d60e93908f93df1623ad78a86a028d6bc064dbfc75a6a57379ef855dc6733801",
+            "38561d6b77b71d30eb97a062168ae12b667ce5c28caccdf76bc88e093e4635987cd96814ce55b4689b3dd2947f80e59aac7b7675f8083865b46c89b2ce9cc735"),
+        (Suite::Edwards25519Sha512Ell2,
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "926e895d308f5e328e7aa159c06eddbe56d06846abf5d98c2512235eaa57fdce35b46edfc655bc828d44ad09d1150f31374e7ef73027e14760d42e77341fe05467bb286cc2c9d7fde29120a0b2320d04",
+            "121b7f9b9aaaa29099fc04a94ba52784d44eac976dd1a3cca458733be5cd090a7b5fbd148444f17f8daf1fb55cb04b1ae85a626e30a54b4b0f8abf4a43314a58"),
+    ];
+
+    #[test]
+    fn rfc_9381_vectors() {
+        for (suite, sk, pk, alpha, pi, beta) in VECTORS {
+            let sk = hex::decode(sk).unwrap();
+            let alpha = hex::decode(alpha).unwrap();
+            assert_eq!(hex::encode(suite.public_key(&sk).unwrap()), *pk, "{}", suite.name());
+            let proof = suite.prove(&sk, &alpha).unwrap();
+            assert_eq!(hex::encode(&proof), *pi, "{}", suite.name());
+            let out = suite.verify(&hex::decode(pk).unwrap(), &alpha, &proof).unwrap();
+            assert_eq!(hex::encode(&out), *beta, "{}", suite.name());
+            assert_eq!(suite.proof_to_hash(&proof).unwrap(), out);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_proofs() {
+        let suites = [Suite::P256Sha256Sswu, Suite::Edwards25519Sha512Tai, Suite::Edwards25519Sha512Ell2];
+        for (i, suite) in suites.iter().enumerate() {
+            let sk = suite.generate_key();
+            let pk = suite.public_key(&sk).unwrap();
+            assert_eq!(pk.len(), suite.point_length());
+            let pi = suite.prove(&sk, b"alpha").unwrap();
+            assert_eq!(pi.len(), suite.proof_length());
+            assert_eq!(suite.verify(&pk, b"alpha", &pi).unwrap().len(), suite.output_length());
+
+            // wrong input, key or suite
+            assert!(suite.verify(&pk, b"beta", &pi).is_err());
+            let other = suite.public_key(&suite.generate_key()).unwrap();
+            assert!(suite.verify(&other, b"alpha", &pi).is_err());
+            let other_suite = suites[(i + 1) % suites.len()];
+            assert!(other_suite.verify(&pk, b"alpha", &pi).is_err());
+
+            // tampered proofs
+            for j in &[0, suite.point_length(), suite.proof_length()-2] {
+                let mut bad = pi.clone();
+                bad[*j] ^= 1;
+                assert!(suite.verify(&pk, b"alpha", &bad).is_err());
+            }
+            assert!(suite.verify(&pk, b"alpha", &pi[1..]).is_err());
+            assert!(suite.proof_to_hash(&pi[1..]).is_err());
+            assert!(suite.prove(&sk[1..], b"alpha").is_err());
+        }
+    }
+
+    #[test]
+    fn reject_invalid_edwards_keys() {
+        for suite in &[Suite::Edwards25519Sha512Tai, Suite::Edwards25519Sha512Ell2] {
+            let sk = suite.generate_key();
+            let pk = suite.public_key(&sk).unwrap();
+            let pi = suite.prove(&sk, b"alpha").unwrap();
+
+            // small order public keys, and keys outside of the prime-order
+            // subgroup
+            let identity = EdwardsPoint::identity().compress().to_bytes();
+            assert!(suite.verify(&identity, b"alpha", &pi).is_err());
+            let pog = EdwardsPoint::group();
+            let y = (pog.deserialize)(&pk).unwrap();
+            let mixed = (y + curve25519_dalek::constants::EIGHT_TORSION[1]).compress().to_bytes();
+            assert!(suite.verify(&mixed, b"alpha", &pi).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_invalid_p256_keys() {
+        let suite = Suite::P256Sha256Sswu;
+        let sk = suite.generate_key();
+        let pi = suite.prove(&sk, b"alpha").unwrap();
+        // secret keys must be non-zero scalars
+        assert!(suite.public_key(&[0; KEY_LENGTH]).is_err());
+        assert!(suite.prove(&[0xff; KEY_LENGTH], b"alpha").is_err());
+        // the identity and points that are not on the curve
+        assert!(suite.verify(&[0], b"alpha", &pi).is_err());
+        let mut pk = suite.public_key(&sk).unwrap();
+        pk[0] = 0x04;
+        assert!(suite.verify(&pk, b"alpha", &pi).is_err());
+    }
+}